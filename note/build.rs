@@ -1,4 +1,9 @@
+use std::env;
+
 fn main() {
-    let res = winres::WindowsResource::new();
-    res.compile().unwrap();
+    // The resource is embedded only into Windows executable.
+    if env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("windows") {
+        let res = winres::WindowsResource::new();
+        res.compile().unwrap();
+    }
 }
@@ -0,0 +1,96 @@
+use crate::error::Error;
+use std::path::{Path, PathBuf};
+
+/// Command line arguments.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Args {
+    filename: Option<PathBuf>,
+    abort_on_discard: bool,
+}
+
+impl Args {
+    /// Parse the arguments without the program name.
+    /// A file path with spaces is given as a single argument.
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, Error> {
+        let mut parsed = Args::default();
+        let mut options = true;
+
+        for arg in args {
+            match arg.as_str() {
+                "--" if options => options = false,
+                "--abort-on-discard" if options => parsed.abort_on_discard = true,
+                // The process is blocked until the editor is closed in any case.
+                "--wait-for-close" if options => {}
+                _ if options && arg.starts_with("--") => {
+                    return Err(Error::Argument(format!("unknown option `{}`", arg)));
+                }
+                _ if parsed.filename.is_some() => {
+                    return Err(Error::Argument(format!("unexpected argument `{}`", arg)));
+                }
+                _ => parsed.filename = Some(PathBuf::from(arg)),
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    /// Returns whether discarding changes at exit is an abort.
+    pub fn abort_on_discard(&self) -> bool {
+        self.abort_on_discard
+    }
+
+    pub fn filename(&self) -> Option<&Path> {
+        self.filename.as_deref()
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Result<Args, Error> {
+        Args::parse(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn args_parse_none() {
+        let parsed = args(&[]).unwrap();
+
+        assert_eq!(None, parsed.filename());
+        assert!(!parsed.abort_on_discard());
+    }
+
+    #[test]
+    fn args_parse_filename_with_spaces() {
+        let parsed = args(&["C:\\my repo\\.git\\COMMIT_EDITMSG"]).unwrap();
+
+        assert_eq!(
+            Some(Path::new("C:\\my repo\\.git\\COMMIT_EDITMSG")),
+            parsed.filename()
+        );
+    }
+
+    #[test]
+    fn args_parse_options() {
+        let parsed = args(&["--wait-for-close", "--abort-on-discard", "a.txt"]).unwrap();
+
+        assert_eq!(Some(Path::new("a.txt")), parsed.filename());
+        assert!(parsed.abort_on_discard());
+    }
+
+    #[test]
+    fn args_parse_end_of_options() {
+        let parsed = args(&["--", "--abort-on-discard"]).unwrap();
+
+        assert_eq!(Some(Path::new("--abort-on-discard")), parsed.filename());
+        assert!(!parsed.abort_on_discard());
+    }
+
+    #[test]
+    fn args_parse_error() {
+        assert!(args(&["--unknown"]).is_err());
+        assert!(args(&["a.txt", "b.txt"]).is_err());
+    }
+}
@@ -1,16 +1,52 @@
+use crate::config::{AutoIndent, Comment, Formatter, LineEnding, Settings, WordChars};
 use crate::cursor::{AsCoordinates, Coordinates, Cursor};
 use crate::editor::SelectMode;
 use crate::error::Error;
+use crate::highlight::{self, Highlighter};
 use crate::history::{History, Operation};
+use crate::log_warn;
+use regex::Regex;
+use std::borrow::Cow;
+#[cfg(test)]
+use std::cell::Cell;
 use std::cmp::{max, min};
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
 use std::fs::File;
-use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::hash::{Hash, Hasher};
+use std::io::{self, ErrorKind, Read, Write};
 use std::iter;
+use std::mem;
 use std::ops::Range;
 use std::path::{Path, PathBuf};
+use std::str;
 use unicode_width::UnicodeWidthChar;
 
-const TAB_STOP: usize = 8;
+/// Tab stop of the rows not in a buffer, such as the bars and the prompt.
+pub const TAB_STOP: usize = 8;
+const INDENT_UNIT: &str = "    ";
+
+const BRACKETS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+const QUOTES: [char; 3] = ['"', '\'', '`'];
+
+const BINARY_PLACEHOLDER: char = '.';
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+const BINARY_SNIFF_SIZE: usize = 8192;
+
+/// Changed regions separated by this number of unchanged rows or less are merged.
+const CHANGE_MERGE_GAP: usize = 1;
+/// Rows between the first and the last change are all changed over this edit distance.
+const CHANGE_MAX_EDITS: usize = 500;
+/// Progress of checking brackets is reported every this number of rows.
+const BRACKET_PROGRESS_ROWS: usize = 10000;
+/// Number of the numbered marks, set and jumped by the digit keys.
+const MARKS: usize = 10;
+
+#[cfg(test)]
+thread_local! {
+    /// Count of `Row::width_range` calls in current thread.
+    pub static WIDTH_RANGE_CALLS: Cell<usize> = const { Cell::new(0) };
+}
 
 #[derive(Default)]
 pub struct Buffer {
@@ -18,32 +54,284 @@ pub struct Buffer {
     filename: Option<PathBuf>,
     cached: bool,
     updated: Vec<Range<usize>>,
-    history: History<(usize, usize)>,
-    pending: Option<(Vec<Row>, SelectMode)>,
+    history: History,
+    /// Copied rows, the mode, and whether the rows are pasted as lines.
+    pending: Option<(Vec<Row>, SelectMode, bool)>,
+    binary: bool,
+    read_only: bool,
+    /// Whether trailing spaces and tabs are left out of the written rows, kept in the buffer.
+    trim_trailing_whitespace: bool,
+    word_chars: WordChars,
+    comment: Comment,
+    auto_indent: AutoIndent,
+    formatter: Option<Formatter>,
+    settings: Settings,
+    observers: Vec<Box<dyn FnMut(Range<usize>)>>,
+    line_endings: LineEndings,
+    /// Line ending of the first line at loading or set explicitly, preferred to the settings.
+    line_ending: Option<LineEnding>,
+    /// Whether the loaded text does not end with line break, which is kept at saving.
+    no_final_newline: bool,
+    /// Whether the loaded text starts with BOM, which is kept at saving.
+    bom: bool,
+    /// Encoding of the loaded text, which is kept at saving.
+    encoding: Encoding,
+    /// Positions recorded in the numbered marks, following the inserted and deleted rows.
+    marks: [Option<(usize, usize)>; MARKS],
+    /// Number of lines with invalid sequences replaced at loading.
+    invalid_lines: usize,
+    /// Count of changes, to tell whether results cached for the content are stale.
+    revision: usize,
 }
 
 impl TryFrom<Option<&Path>> for Buffer {
     type Error = Error;
 
     fn try_from(value: Option<&Path>) -> Result<Self, Self::Error> {
+        Buffer::load(value, None)
+    }
+}
+
+impl Buffer {
+    /// Create from the file decoded by the encoding, or the detected one if `None`.
+    fn load(path: Option<&Path>, encoding: Option<Encoding>) -> Result<Self, Error> {
         let mut buffer = Buffer::default();
 
-        if let Some(path) = value {
-            let file = File::open(path)?;
-            let reader = BufReader::new(file);
-            for line in reader.lines() {
-                let chars = Row::from(line?);
-                buffer.rows.push(chars);
+        if let Some(path) = path {
+            match File::open(path) {
+                Ok(mut file) => {
+                    let mut bytes = vec![];
+                    file.read_to_end(&mut bytes)?;
+
+                    let (detected, bom) = Encoding::detect(&bytes);
+                    // BOM of another encoding is decoded as text.
+                    let bom = bom && encoding.map_or(true, |e| e == detected);
+                    if bom {
+                        bytes.drain(..detected.bom().len());
+                    }
+
+                    match encoding {
+                        None if detected == Encoding::Utf8 && is_binary(&bytes) => {
+                            buffer.load_binary(&bytes);
+                        }
+                        _ => {
+                            buffer = match encoding {
+                                Some(encoding) => Buffer::from_decoded(bytes, encoding),
+                                None => Buffer::from_encoded(bytes, detected),
+                            };
+                            // Saving would lose the original bytes replaced.
+                            buffer.read_only = 0 < buffer.invalid_lines;
+                        }
+                    }
+                    buffer.bom = bom;
+                }
+                // New file is created at first saving.
+                Err(e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => return Err(Error::from(e)),
             }
         }
 
-        buffer.filename = value.map(PathBuf::from);
+        buffer.filename = path.map(PathBuf::from);
+        // Undoing back to the loaded content makes the buffer unmodified.
+        buffer.history.mark_saved();
+
+        if let Some(extension) = path.and_then(|p| p.extension()).and_then(|e| e.to_str()) {
+            buffer.auto_indent = AutoIndent::from_extension(extension);
+            buffer.formatter = Formatter::from_extension(extension);
+        }
 
         Ok(buffer)
     }
-}
 
-impl Buffer {
+    /// Load the file again decoded by the encoding, or the detected one if `None`.
+    /// The changes and the settings are discarded, and the buffer is kept if loading fails.
+    pub fn reload(&mut self, encoding: Option<Encoding>) -> Result<(), Error> {
+        *self = Buffer::load(self.filename.as_deref(), encoding)?;
+        Ok(())
+    }
+
+    /// Create from the rows.
+    pub fn from_rows(rows: Vec<Row>) -> Self {
+        Buffer {
+            rows,
+            ..Buffer::default()
+        }
+    }
+
+    /// Create from the bytes split into rows in the same way as text.
+    /// Invalid UTF-8 sequences are replaced by U+FFFD, and the lines containing them are counted.
+    ///
+    /// ```
+    /// use note::buffer::{Buffer, LineEndingPolicy};
+    ///
+    /// let buf = Buffer::from_bytes(b"a\n\xffb\n".to_vec(), LineEndingPolicy::LineFeed);
+    ///
+    /// assert_eq!(Some("\u{FFFD}b".to_string()), buf.line_text(1));
+    /// assert_eq!(1, buf.invalid_lines());
+    /// ```
+    pub fn from_bytes(bytes: Vec<u8>, policy: LineEndingPolicy) -> Self {
+        match String::from_utf8(bytes) {
+            Ok(text) => Buffer::from_string(text, policy),
+            Err(e) => {
+                let bytes = e.into_bytes();
+                let invalid_lines = bytes
+                    .split(|&b| b == b'\n')
+                    .filter(|line| str::from_utf8(line).is_err())
+                    .count();
+                let text = String::from_utf8_lossy(&bytes).into_owned();
+                Buffer {
+                    invalid_lines,
+                    ..Buffer::from_string(text, policy)
+                }
+            }
+        }
+    }
+
+    /// Create from the bytes decoded by the encoding.
+    /// UTF-8 is decoded as Shift_JIS if it is invalid as UTF-8 but valid as Shift_JIS.
+    fn from_encoded(bytes: Vec<u8>, encoding: Encoding) -> Self {
+        let policy = LineEndingPolicy::LineFeed;
+        if encoding == Encoding::Utf8 {
+            if str::from_utf8(&bytes).is_ok() {
+                return Buffer::from_bytes(bytes, policy);
+            }
+
+            return match Encoding::ShiftJis.decode(&bytes) {
+                (text, false) => Buffer {
+                    encoding: Encoding::ShiftJis,
+                    ..Buffer::from_string(text, policy)
+                },
+                (_, true) => Buffer::from_bytes(bytes, policy),
+            };
+        }
+
+        Buffer::from_decoded(bytes, encoding)
+    }
+
+    /// Create from the bytes decoded by the encoding without falling back to another.
+    fn from_decoded(bytes: Vec<u8>, encoding: Encoding) -> Self {
+        let policy = LineEndingPolicy::LineFeed;
+        let (text, replaced) = encoding.decode(&bytes);
+        let invalid_lines = match replaced {
+            true => text.lines().filter(|l| l.contains('\u{FFFD}')).count(),
+            false => 0,
+        };
+        Buffer {
+            encoding,
+            invalid_lines,
+            ..Buffer::from_string(text, policy)
+        }
+    }
+
+    /// Create from the text split into rows in one pass.
+    /// The row after the last line break is kept only if it is not empty.
+    ///
+    /// ```
+    /// use note::buffer::{Buffer, LineEndingPolicy};
+    ///
+    /// let buf = Buffer::from_string("a\r\nb\rc\n".to_string(), LineEndingPolicy::Any);
+    ///
+    /// assert_eq!(3, buf.rows());
+    /// assert_eq!((1, 1, 1), buf.line_endings().counts());
+    /// ```
+    pub fn from_string(text: String, policy: LineEndingPolicy) -> Self {
+        let mut rows = vec![];
+        let mut line_endings = LineEndings::default();
+        let mut line_ending = None;
+        let mut no_final_newline = false;
+
+        let mut rest = text.as_str();
+        while !rest.is_empty() {
+            let found = match policy {
+                LineEndingPolicy::LineFeed => rest.find('\n'),
+                LineEndingPolicy::Any => rest.find(['\r', '\n']),
+            };
+
+            let index = match found {
+                Some(index) => index,
+                None => {
+                    rows.push(row_from_str(rest));
+                    no_final_newline = true;
+                    break;
+                }
+            };
+
+            let mut line = &rest[..index];
+            let (next, ending) = if rest[index..].starts_with("\r\n") {
+                line_endings.crlf += 1;
+                (index + 2, Some(LineEnding::Crlf))
+            } else if rest[index..].starts_with('\r') {
+                line_endings.cr += 1;
+                (index + 1, None)
+            } else if let Some(l) = line.strip_suffix('\r') {
+                line = l;
+                line_endings.crlf += 1;
+                (index + 1, Some(LineEnding::Crlf))
+            } else {
+                line_endings.lf += 1;
+                (index + 1, Some(LineEnding::Lf))
+            };
+
+            if rows.is_empty() {
+                line_ending = ending;
+            }
+
+            rows.push(row_from_str(line));
+            rest = &rest[next..];
+        }
+
+        Buffer {
+            line_endings,
+            line_ending,
+            no_final_newline,
+            ..Buffer::from_rows(rows)
+        }
+    }
+
+    /// Add the delta to the integer at the position, and returns the range of new integer.
+    /// The width is kept by leading zeros if the integer has them.
+    ///
+    /// ```
+    /// use note::buffer::Buffer;
+    ///
+    /// let mut buf = Buffer::default();
+    /// buf.insert_row(&(0, 0), &"x=007".chars().collect::<Vec<char>>());
+    ///
+    /// assert_eq!(Some(2..5), buf.add_number(&(3, 0), -1));
+    /// assert_eq!(Some("x=006".to_string()), buf.line_text(0));
+    /// ```
+    pub fn add_number<P: Coordinates>(&mut self, at: &P, delta: i64) -> Option<Range<usize>> {
+        let range = self.number_range_at(at)?;
+        let column = &self.rows[at.y()].column()[range.clone()];
+
+        let text = column.iter().collect::<String>();
+        let value = text.parse::<i64>().ok()?.checked_add(delta)?;
+
+        let digits = text.trim_start_matches('-');
+        let new = if 1 < digits.len() && digits.starts_with('0') {
+            let sign = if value < 0 { "-" } else { "" };
+            format!(
+                "{}{:0width$}",
+                sign,
+                value.unsigned_abs(),
+                width = digits.len()
+            )
+        } else {
+            value.to_string()
+        };
+
+        let new = new.chars().collect::<Vec<char>>();
+        let end = range.start + new.len();
+        self.replace(&(range.start, at.y()), range.len(), &new);
+        Some(range.start..end)
+    }
+
+    /// Register the function called with the range of changed rows whenever the buffer is edited.
+    /// The range may include the rows after the changed ones, such as by inserting a row.
+    pub fn add_observer<F: FnMut(Range<usize>) + 'static>(&mut self, observer: F) {
+        self.observers.push(Box::new(observer));
+    }
+
     pub fn append_row<P: Coordinates + AsCoordinates>(&mut self, at: &P, text: &[char]) {
         if let Some(cur) = self.append_row_bypass(at, text) {
             self.history
@@ -58,25 +346,123 @@ impl Buffer {
     ) -> Option<(usize, usize)> {
         if let Some(row) = self.rows.get_mut(at.y()) {
             self.cached = true;
-            self.updated.push(at.y()..at.y() + 1);
             let x = row.len();
             row.append(text);
+            self.mark_updated(at.y()..at.y() + 1);
             Some((x, at.y()))
         } else {
             None
         }
     }
 
+    pub fn auto_indent(&self) -> &AutoIndent {
+        &self.auto_indent
+    }
+
+    pub fn binary(&self) -> bool {
+        self.binary
+    }
+
     pub fn cached(&self) -> bool {
         self.cached
     }
 
+    /// Returns the character at the position.
+    ///
+    /// ```
+    /// use note::buffer::Buffer;
+    ///
+    /// let mut buf = Buffer::default();
+    /// buf.insert_row(&(0, 0), &['a', 'b']);
+    ///
+    /// assert_eq!(Some('b'), buf.char_at(&(1, 0)));
+    /// assert_eq!(None, buf.char_at(&(2, 0)));
+    /// assert_eq!(None, buf.char_at(&(0, 1)));
+    /// ```
+    pub fn char_at<P: Coordinates>(&self, at: &P) -> Option<char> {
+        self.rows.get(at.y())?.column().get(at.x()).cloned()
+    }
+
+    /// Returns the row ranges changed from the snapshot in order.
+    /// The range is empty at the position where rows are only deleted.
+    ///
+    /// ```
+    /// use note::buffer::Buffer;
+    ///
+    /// let mut buf = Buffer::default();
+    /// buf.insert_row(&(0, 0), &['a']);
+    /// buf.insert_row(&(0, 1), &['b']);
+    /// let snapshot = buf.snapshot();
+    ///
+    /// buf.insert_char(&(1, 1), 'c');
+    /// assert_eq!(vec![1..2], buf.changed_rows(&snapshot));
+    /// ```
+    pub fn changed_rows(&self, snapshot: &Snapshot) -> Vec<Range<usize>> {
+        snapshot.changed_rows(&self.snapshot().hashes)
+    }
+
     pub fn clear_updated(&mut self) {
         self.updated.clear();
     }
 
+    pub fn comment(&self) -> &Comment {
+        &self.comment
+    }
+
+    /// Returns the index of the first row different from the other buffer,
+    /// or `None` if all rows are equal.
+    ///
+    /// ```
+    /// use note::buffer::Buffer;
+    ///
+    /// let mut buf = Buffer::default();
+    /// buf.insert_row(&(0, 0), &['a']);
+    /// let mut other = Buffer::default();
+    /// other.insert_row(&(0, 0), &['a']);
+    /// assert_eq!(None, buf.content_eq(&other));
+    ///
+    /// other.insert_row(&(0, 1), &['b']);
+    /// assert_eq!(Some(1), buf.content_eq(&other));
+    /// ```
+    pub fn content_eq(&self, other: &Buffer) -> Option<usize> {
+        let differ = self
+            .rows
+            .iter()
+            .zip(other.rows.iter())
+            .position(|(a, b)| a.column() != b.column());
+        match differ {
+            Some(y) => Some(y),
+            None if self.rows() != other.rows() => Some(min(self.rows(), other.rows())),
+            None => None,
+        }
+    }
+
     pub fn copy_pending(&mut self, range: Range<&Cursor>, mode: SelectMode) {
-        self.pending = self.get_range(range, mode).map(|r| (r, mode));
+        let linewise = mode == SelectMode::Line;
+        self.pending = self.get_range(range, mode).map(|r| (r, mode, linewise));
+    }
+
+    /// Copy the row to pending as a line, or returns `false` if the row does not exist.
+    pub fn copy_line<P: Coordinates>(&mut self, at: &P) -> bool {
+        match self.rows.get(at.y()) {
+            Some(row) => {
+                let rows = vec![row.clone(), Row::default()];
+                self.pending = Some((rows, SelectMode::None, true));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Delete the row and copy it to pending as a line.
+    pub fn cut_line<P: Coordinates + AsCoordinates>(&mut self, at: &P) -> bool {
+        match self.delete_row(at) {
+            Some(row) => {
+                self.pending = Some((vec![row, Row::default()], SelectMode::None, true));
+                true
+            }
+            None => false,
+        }
     }
 
     pub fn delete_row<P: Coordinates + AsCoordinates>(&mut self, at: &P) -> Option<Row> {
@@ -93,7 +479,14 @@ impl Buffer {
     pub fn delete_row_bypass<P: Coordinates + AsCoordinates>(&mut self, at: &P) -> Option<Row> {
         if at.y() < self.rows() {
             self.cached = true;
-            self.updated.push(at.y()..self.rows());
+            for mark in self.marks.iter_mut() {
+                match *mark {
+                    Some((_, y)) if y == at.y() => *mark = None,
+                    Some((x, y)) if at.y() < y => *mark = Some((x, y - 1)),
+                    _ => {}
+                }
+            }
+            self.mark_updated(at.y()..self.rows());
             Some(self.rows.remove(at.y()))
         } else {
             None
@@ -114,7 +507,7 @@ impl Buffer {
             if 0 < at.x() && at.x() <= row.len() {
                 if let Some(ch) = row.remove(at.x() - 1) {
                     self.cached = true;
-                    self.updated.push(at.y()..at.y() + 1);
+                    self.mark_updated(at.y()..at.y() + 1);
                     return Some(ch);
                 }
             }
@@ -146,6 +539,7 @@ impl Buffer {
         let mut rs = match mode {
             SelectMode::None => self.delete_chars_none(start, end),
             SelectMode::Rectangle => self.delete_chars_rectangle(start, end),
+            SelectMode::Line => self.delete_chars_line(start, end),
         };
 
         if rs.is_empty() {
@@ -153,23 +547,189 @@ impl Buffer {
         } else {
             self.cached = true;
             rs.reverse();
-            self.pending = Some((rs.clone(), mode));
+            self.pending = Some((rs.clone(), mode, mode == SelectMode::Line));
             if rs.len() == 1 {
                 // in row
-                self.updated.push(start.y()..start.y() + 1);
+                self.mark_updated(start.y()..start.y() + 1);
             } else {
-                self.updated.push(start.y()..self.rows());
+                self.mark_updated(start.y()..self.rows());
             }
             Some(rs)
         }
     }
 
+    /// Returns the smallest quoted string or bracketed group containing the range.
+    /// The span includes the delimiters, and is larger than the range.
+    ///
+    /// ```
+    /// use note::buffer::Buffer;
+    ///
+    /// let mut buf = Buffer::default();
+    /// buf.insert_row(&(0, 0), &"f(\"a b\")".chars().collect::<Vec<char>>());
+    ///
+    /// assert_eq!(Some(((2, 0), (7, 0))), buf.enclosing_span((3, 0), (4, 0)));
+    /// assert_eq!(Some(((1, 0), (8, 0))), buf.enclosing_span((2, 0), (7, 0)));
+    /// assert_eq!(None, buf.enclosing_span((1, 0), (8, 0)));
+    /// ```
+    pub fn enclosing_span(
+        &self,
+        start: (usize, usize),
+        end: (usize, usize),
+    ) -> Option<((usize, usize), (usize, usize))> {
+        let quotes = self.enclosing_quotes(start, end);
+        let brackets = self.enclosing_brackets(start, end);
+        match (quotes, brackets) {
+            (Some(q), Some(b)) if (q.0 .1, q.0 .0) < (b.0 .1, b.0 .0) => Some(b),
+            (Some(q), _) => Some(q),
+            (_, b) => b,
+        }
+    }
+
+    pub fn filename(&self) -> Option<&Path> {
+        self.filename.as_deref()
+    }
+
+    pub fn formatter(&self) -> Option<&Formatter> {
+        self.formatter.as_ref()
+    }
+
     pub fn find_at<P: Coordinates>(&self, at: &P, keyword: &str) -> Option<(usize, usize)> {
+        self.find_at_case(at, keyword, false)
+    }
+
+    /// Returns the first match at or after the position ignoring case.
+    ///
+    /// ```
+    /// use note::buffer::Buffer;
+    ///
+    /// let mut buf = Buffer::default();
+    /// buf.insert_row(&(0, 0), &"İ Error".chars().collect::<Vec<char>>());
+    ///
+    /// assert_eq!(Some((2, 0)), buf.find_at_icase(&(0, 0), "ERROR"));
+    /// ```
+    pub fn find_at_icase<P: Coordinates>(&self, at: &P, keyword: &str) -> Option<(usize, usize)> {
+        self.find_at_case(at, keyword, true)
+    }
+
+    /// Returns the first match at or after the position in the rows with tabs expanded to spaces,
+    /// and its length in characters. The match may start in the middle of a tab,
+    /// and then the position is of the tab.
+    ///
+    /// ```
+    /// use note::buffer::Buffer;
+    ///
+    /// let mut buf = Buffer::default();
+    /// buf.insert_row(&(0, 0), &"a\tb".chars().collect::<Vec<char>>());
+    ///
+    /// assert_eq!(Some(((1, 0), 2)), buf.find_expanded_at(&(0, 0), "  b", false));
+    /// assert_eq!(None, buf.find_expanded_at(&(0, 0), "\tb", false));
+    /// ```
+    pub fn find_expanded_at<P: Coordinates>(
+        &self,
+        at: &P,
+        keyword: &str,
+        ignore_case: bool,
+    ) -> Option<((usize, usize), usize)> {
+        let keyword = fold_text(keyword.to_string(), ignore_case);
+        let keyword = keyword.chars().collect::<Vec<char>>();
+        if keyword.is_empty() {
+            return None;
+        }
+
+        let tab_width = self.tab_width();
+        let mut skip_x = at.x();
+        for (y, row) in self.rows.iter().enumerate().skip(at.y()) {
+            let found = expanded_matches(row, &keyword, ignore_case, tab_width);
+            if let Some(&(x, len)) = found.iter().find(|&&(x, _)| skip_x <= x) {
+                return Some(((x, y), len));
+            }
+
+            skip_x = 0;
+        }
+
+        None
+    }
+
+    /// Returns the positions of all matches in order, which do not overlap each other.
+    ///
+    /// ```
+    /// use note::buffer::Buffer;
+    ///
+    /// let mut buf = Buffer::default();
+    /// buf.insert_row(&(0, 0), &"aaa Aa".chars().collect::<Vec<char>>());
+    ///
+    /// assert_eq!(vec![(0, 0), (4, 0)], buf.find_all("aa", true));
+    /// ```
+    pub fn find_all(&self, keyword: &str, ignore_case: bool) -> Vec<(usize, usize)> {
+        let keyword = fold_text(keyword.to_string(), ignore_case);
+        if keyword.is_empty() {
+            return vec![];
+        }
+
+        let mut found = vec![];
+        for (y, row) in self.rows.iter().enumerate() {
+            let text = fold_text(row.to_string_at(0), ignore_case);
+            let (mut x, mut last) = (0, 0);
+            for (index, _) in text.match_indices(&keyword) {
+                // byte offset to character index.
+                x += text[last..index].chars().count();
+                last = index;
+                found.push((x, y));
+            }
+        }
+        found
+    }
+
+    fn find_at_case<P: Coordinates>(
+        &self,
+        at: &P,
+        keyword: &str,
+        ignore_case: bool,
+    ) -> Option<(usize, usize)> {
+        let keyword = fold_text(keyword.to_string(), ignore_case);
+        let mut skip_x = at.x();
+        for (y, c) in self.rows.iter().enumerate().skip(at.y()) {
+            let row = fold_text(c.to_string_at(skip_x), ignore_case);
+            if let Some(x) = row.find(&keyword) {
+                // byte offset to character index.
+                return Some((row[..x].chars().count() + skip_x, y));
+            }
+
+            skip_x = 0;
+        }
+
+        None
+    }
+
+    /// Returns the first match of the pattern at or after the position, and its length in characters.
+    /// Empty matches are skipped.
+    ///
+    /// ```
+    /// use note::buffer::Buffer;
+    /// use regex::Regex;
+    ///
+    /// let mut buf = Buffer::default();
+    /// buf.insert_row(&(0, 0), &"あa1 b22".chars().collect::<Vec<char>>());
+    /// let regex = Regex::new("[a-z][0-9]+").unwrap();
+    ///
+    /// assert_eq!(Some(((1, 0), 2)), buf.find_regex_at(&(0, 0), &regex));
+    /// assert_eq!(Some(((4, 0), 3)), buf.find_regex_at(&(2, 0), &regex));
+    /// ```
+    pub fn find_regex_at<P: Coordinates>(
+        &self,
+        at: &P,
+        regex: &Regex,
+    ) -> Option<((usize, usize), usize)> {
         let mut skip_x = at.x();
         for (y, c) in self.rows.iter().enumerate().skip(at.y()) {
-            let row = c.to_string_at(skip_x);
-            if let Some(x) = row.find(keyword) {
-                return Some((x + skip_x, y));
+            let row = c.to_string_at(0);
+            // Search in the whole row for anchors and word boundaries.
+            let found = regex
+                .find_iter(&row)
+                .find(|m| !m.is_empty() && skip_x <= row[..m.start()].chars().count());
+            if let Some(m) = found {
+                let x = row[..m.start()].chars().count();
+                return Some(((x, y), m.as_str().chars().count()));
             }
 
             skip_x = 0;
@@ -186,6 +746,7 @@ impl Buffer {
         match mode {
             SelectMode::None => self.get_range_none(range),
             SelectMode::Rectangle => self.get_range_rectangle(range),
+            SelectMode::Line => self.get_range_line(range),
         }
     }
 
@@ -199,8 +760,39 @@ impl Buffer {
 
     pub fn insert_row_bypass<P: Coordinates + AsCoordinates>(&mut self, at: &P, text: &[char]) {
         self.cached = true;
-        self.updated.push(at.y()..self.rows() + 1);
+        self.mark_updated(at.y()..self.rows() + 1);
         self.rows.insert(at.y(), Row::from(text));
+        for (_, y) in self.marks.iter_mut().flatten() {
+            if at.y() <= *y {
+                *y += 1;
+            }
+        }
+    }
+
+    /// Swap the rows at the positions, and returns `true` if both rows exist.
+    /// The swap is undone by swapping them again.
+    pub fn swap_rows<P: Coordinates + AsCoordinates>(&mut self, a: &P, b: &P) -> bool {
+        if !self.swap_rows_bypass(a, b) {
+            return false;
+        }
+
+        self.history.record(
+            a.as_coordinates(),
+            Operation::SwapRows(a.as_coordinates(), b.as_coordinates()),
+        );
+        true
+    }
+
+    pub fn swap_rows_bypass<P: Coordinates + AsCoordinates>(&mut self, a: &P, b: &P) -> bool {
+        let rows = self.rows();
+        if rows <= a.y() || rows <= b.y() || a.y() == b.y() {
+            return false;
+        }
+
+        self.cached = true;
+        self.mark_updated(min(a.y(), b.y())..max(a.y(), b.y()) + 1);
+        self.rows.swap(a.y(), b.y());
+        true
     }
 
     pub fn insert_char<P: Coordinates + AsCoordinates>(&mut self, at: &P, ch: char) {
@@ -220,8 +812,8 @@ impl Buffer {
         if let Some(row) = self.rows.get_mut(at.y()) {
             if at.x() <= row.len() {
                 self.cached = true;
-                self.updated.push(at.y()..at.y() + 1);
                 row.insert(at.x(), ch);
+                self.mark_updated(at.y()..at.y() + 1);
                 return Some((at.x(), at.y()));
             }
         }
@@ -255,17 +847,19 @@ impl Buffer {
         let end = match mode {
             SelectMode::None => self.insert_chars_none(at, rows),
             SelectMode::Rectangle => self.insert_chars_rectangle(at, rows),
+            SelectMode::Line => self.insert_chars_line(at, rows),
         };
 
         if let Some(end) = end {
-            if at.as_coordinates() == end {
+            // An empty row inserted as line ends at the position.
+            if at.as_coordinates() == end && mode != SelectMode::Line {
                 None
             } else {
                 if at.y() == end.y() {
                     // in row
-                    self.updated.push(at.y()..end.y() + 1);
+                    self.mark_updated(at.y()..end.y() + 1);
                 } else {
-                    self.updated.push(at.y()..self.rows());
+                    self.mark_updated(at.y()..self.rows());
                 }
                 Some(end)
             }
@@ -274,6 +868,38 @@ impl Buffer {
         }
     }
 
+    /// Redo the last undone change, and returns the position of it.
+    /// Nothing is redone after the buffer is edited since undo.
+    pub fn redo(&mut self) -> Option<(usize, usize)> {
+        if let Some((cur, op)) = self.history.redo() {
+            if let Some(inverse) = self.undo_operation(op) {
+                self.history.record_redone(cur, inverse);
+            }
+            self.cached = !self.history.at_saved();
+            Some(cur)
+        } else {
+            None
+        }
+    }
+
+    /// Record the operations in the function as a undo step.
+    pub fn record_group<R, F: FnOnce(&mut Buffer) -> R>(&mut self, f: F) -> R {
+        let start = self.history_mark();
+        let ret = f(self);
+        self.group_since(start);
+        ret
+    }
+
+    /// Returns the mark to merge the following operations by `group_since`.
+    pub fn history_mark(&self) -> usize {
+        self.history.len()
+    }
+
+    /// Merge the operations recorded after the mark into a undo step.
+    pub fn group_since(&mut self, mark: usize) {
+        self.history.group(mark);
+    }
+
     pub fn replace<P: Coordinates + AsCoordinates>(
         &mut self,
         at: &P,
@@ -299,7 +925,7 @@ impl Buffer {
         if let Some(row) = self.rows.get_mut(at.y()) {
             if let Some(removed) = row.replace(at.x(), length, text) {
                 self.cached = true;
-                self.updated.push(at.y()..at.y() + 1);
+                self.mark_updated(at.y()..at.y() + 1);
                 return Some(Row::from(removed));
             }
         }
@@ -307,8 +933,40 @@ impl Buffer {
         None
     }
 
+    /// Replace text from `start` to `end` with the rows in one undo step.
+    /// Returns the end position of the inserted text.
+    pub fn replace_range<P: Coordinates + AsCoordinates>(
+        &mut self,
+        start: &P,
+        end: &P,
+        rows: &[Row],
+    ) -> Option<(usize, usize)> {
+        // Deleted text is not copied to pending.
+        let pending = self.pending.clone();
+        let end = self.record_group(|buf| {
+            buf.delete_chars(start, end, SelectMode::None);
+            buf.insert_chars(start, rows, SelectMode::None)
+        });
+        self.pending = pending;
+        end
+    }
+
     pub fn rfind_at<P: Coordinates>(&self, at: &P, keyword: &str) -> Option<(usize, usize)> {
-        let rkeyword = keyword.chars().rev().collect::<String>();
+        self.rfind_at_case(at, keyword, false)
+    }
+
+    /// Returns the last match at or before the position ignoring case.
+    pub fn rfind_at_icase<P: Coordinates>(&self, at: &P, keyword: &str) -> Option<(usize, usize)> {
+        self.rfind_at_case(at, keyword, true)
+    }
+
+    fn rfind_at_case<P: Coordinates>(
+        &self,
+        at: &P,
+        keyword: &str,
+        ignore_case: bool,
+    ) -> Option<(usize, usize)> {
+        let rkeyword = fold_text(keyword.chars().rev().collect::<String>(), ignore_case);
         let mut skip_x = if at.y() < self.rows() {
             at.x()
         } else {
@@ -320,10 +978,12 @@ impl Buffer {
             } else {
                 skip_x + 1
             };
-            let row = c.rev_at(taken).to_string_at(0);
+            let row = fold_text(c.rev_at(taken).to_string_at(0), ignore_case);
 
             if let Some(x) = row.find(&rkeyword) {
-                return Some((row.len() - x - keyword.len(), y));
+                // byte offset to character index.
+                let end = row.chars().count() - row[..x].chars().count();
+                return Some((end - keyword.chars().count(), y));
             }
 
             skip_x = usize::MAX;
@@ -332,1514 +992,4958 @@ impl Buffer {
         None
     }
 
-    pub fn row_char_len<P: Coordinates>(&self, at: &P) -> usize {
-        self.rows.get(at.y()).map(|r| r.len()).unwrap_or_default()
-    }
+    /// Returns the last match at or before the position in the rows with tabs expanded to spaces,
+    /// and its length in characters.
+    pub fn rfind_expanded_at<P: Coordinates>(
+        &self,
+        at: &P,
+        keyword: &str,
+        ignore_case: bool,
+    ) -> Option<((usize, usize), usize)> {
+        let keyword = fold_text(keyword.to_string(), ignore_case);
+        let keyword = keyword.chars().collect::<Vec<char>>();
+        if keyword.is_empty() {
+            return None;
+        }
 
-    pub fn row_updated(&self, row: usize) -> bool {
-        self.updated.iter().any(|r| r.start <= row && row < r.end)
-    }
+        let tab_width = self.tab_width();
+        let mut last_x = if at.y() < self.rows() {
+            at.x()
+        } else {
+            usize::MAX
+        };
+        for (y, row) in self.rows.iter().enumerate().take(at.y() + 1).rev() {
+            let found = expanded_matches(row, &keyword, ignore_case, tab_width);
+            if let Some(&(x, len)) = found.iter().rev().find(|&&(x, _)| x <= last_x) {
+                return Some(((x, y), len));
+            }
 
-    pub fn rows(&self) -> usize {
-        self.rows.len()
+            last_x = usize::MAX;
+        }
+
+        None
     }
 
-    pub fn paste_pending<P: Coordinates + AsCoordinates>(
-        &mut self,
+    /// Returns the last match of the pattern starting at or before the position,
+    /// and its length in characters. Empty matches are skipped.
+    pub fn rfind_regex_at<P: Coordinates>(
+        &self,
         at: &P,
-    ) -> Option<(usize, usize)> {
-        if let Some((rows, mode)) = self.pending.clone() {
-            self.insert_chars(at, rows.as_slice(), mode)
+        regex: &Regex,
+    ) -> Option<((usize, usize), usize)> {
+        let mut skip_x = if at.y() < self.rows() {
+            at.x()
         } else {
-            None
+            usize::MAX
+        };
+        for (y, c) in self.rows.iter().enumerate().take(at.y() + 1).rev() {
+            let row = c.to_string_at(0);
+            let found = regex
+                .find_iter(&row)
+                .filter(|m| !m.is_empty() && row[..m.start()].chars().count() <= skip_x)
+                .last();
+            if let Some(m) = found {
+                let x = row[..m.start()].chars().count();
+                return Some(((x, y), m.as_str().chars().count()));
+            }
+
+            skip_x = usize::MAX;
         }
+
+        None
     }
 
-    pub fn pending(&self) -> Option<&[Row]> {
-        self.pending.as_ref().map(|p| p.0.as_slice())
+    pub fn read_only(&self) -> bool {
+        self.read_only
     }
 
-    pub fn save(&mut self) -> Result<(), Error> {
-        if let Some(path) = self.filename.clone() {
-            self.save_as(&path)?;
-        }
+    pub fn row_char_len<P: Coordinates>(&self, at: &P) -> usize {
+        self.rows.get(at.y()).map(|r| r.len()).unwrap_or_default()
+    }
 
-        Ok(())
+    pub fn row_updated(&self, row: usize) -> bool {
+        self.updated.iter().any(|r| r.start <= row && row < r.end)
     }
 
-    pub fn save_as(&mut self, path: &Path) -> Result<(), Error> {
-        let file = File::create(path)?;
-        let mut writer = BufWriter::new(file);
+    pub fn rows(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Returns the indent unit, or detects it from the first indented row.
+    pub fn indent_unit(&self) -> Vec<char> {
+        if let Some(unit) = self.auto_indent.unit() {
+            return unit.chars().collect();
+        }
 
+        let mut prev = 0;
         for row in &self.rows {
-            let buf = row.to_string_at(0);
-            writer.write_all(buf.as_bytes())?;
-            writer.write_all("\r\n".as_bytes())?;
+            if let Some(len) = row.indent_len() {
+                if prev < len {
+                    return row.column()[prev..len].to_vec();
+                }
+                prev = len;
+            }
         }
 
-        writer.flush()?;
+        INDENT_UNIT.chars().collect()
+    }
 
-        self.cached = false;
+    /// Returns the encoding detected at loading, which is kept at saving.
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
 
-        Ok(())
+    /// Returns whether BOM is written at saving.
+    /// This is `true` if the loaded text starts with it, or `write_bom`.
+    pub fn has_bom(&self) -> bool {
+        self.bom || self.settings.write_bom()
     }
 
-    pub fn set_filename(&mut self, filename: &Path) {
-        self.filename = Some(PathBuf::from(filename));
+    /// Returns whether the last row is terminated by line break at saving.
+    /// This is `false` if the loaded text does not end with it, unless `insert_final_newline`.
+    pub fn has_final_newline(&self) -> bool {
+        !self.no_final_newline || self.settings.insert_final_newline()
     }
 
-    pub fn shrink_row<P: Coordinates + AsCoordinates>(&mut self, at: &P) {
-        if let Some(row) = self.shrink_row_bypass(at) {
-            self.history.record(
-                at.as_coordinates(),
-                Operation::ShrinkRow(at.as_coordinates(), row),
-            );
-        }
+    /// Returns the number of lines whose invalid UTF-8 was replaced at loading.
+    pub fn invalid_lines(&self) -> usize {
+        self.invalid_lines
     }
 
-    pub fn shrink_row_bypass<P: Coordinates + AsCoordinates>(&mut self, at: &P) -> Option<Row> {
-        if let Some(row) = self.rows.get_mut(at.y()) {
-            self.cached = true;
-            let removed = row.split_off(at.x());
-            self.updated.push(at.y()..at.y() + 1);
-            self.pending = Some((vec![removed.clone()], SelectMode::None));
-            Some(removed)
-        } else {
-            None
-        }
+    /// Returns the number of changes so far, which differs whenever the content is changed.
+    pub fn revision(&self) -> usize {
+        self.revision
     }
 
-    pub fn split_row<P: Coordinates + AsCoordinates>(&mut self, at: &P) {
-        if let Some(cur) = self.split_row_bypass(at) {
-            self.history
-                .record(at.as_coordinates(), Operation::SplitRow(cur));
-        }
+    /// Returns the line ending written at saving.
+    /// The line ending of the loaded file is kept, otherwise it is of the settings.
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_ending.unwrap_or(self.settings.line_ending())
     }
 
-    pub fn split_row_bypass<P: Coordinates + AsCoordinates>(
-        &mut self,
-        at: &P,
-    ) -> Option<(usize, usize)> {
-        let row_len = self.rows();
-        if let Some(row) = self.rows.get_mut(at.y()) {
-            self.cached = true;
-            self.updated.push(at.y()..row_len + 1);
+    /// Returns the line breaks counted at loading.
+    pub fn line_endings(&self) -> &LineEndings {
+        &self.line_endings
+    }
 
-            let next = row.split_off(at.x());
+    /// Returns the text of the row.
+    ///
+    /// ```
+    /// use note::buffer::Buffer;
+    ///
+    /// let mut buf = Buffer::default();
+    /// buf.insert_row(&(0, 0), &['a', 'b']);
+    ///
+    /// assert_eq!(Some("ab".to_string()), buf.line_text(0));
+    /// assert_eq!(None, buf.line_text(1));
+    /// ```
+    pub fn line_text(&self, y: usize) -> Option<String> {
+        self.rows.get(y).map(|r| r.to_string_at(0))
+    }
 
-            let mut next_at = Cursor::default();
-            next_at.set(self, &(at.x(), at.y() + 1));
+    /// Returns the position in the numbered mark, or `None` if it is not set or its row is deleted.
+    pub fn mark(&self, slot: usize) -> Option<(usize, usize)> {
+        self.marks.get(slot).copied().flatten()
+    }
 
-            self.insert_row_bypass(&next_at, next.column());
+    /// Returns the position of the bracket paired with the bracket at the position.
+    pub fn matching_bracket<P: Coordinates>(&self, at: &P) -> Option<(usize, usize)> {
+        let ch = self.char_at(at)?;
 
-            Some(next_at.as_coordinates())
-        } else {
-            None
+        if let Some(&(open, close)) = BRACKETS.iter().find(|&&(o, _)| o == ch) {
+            let mut depth = 0;
+            for (pos, c) in self.chars_from((at.x() + 1, at.y())) {
+                if c == open {
+                    depth += 1;
+                } else if c == close {
+                    if depth == 0 {
+                        return Some(pos);
+                    }
+                    depth -= 1;
+                }
+            }
+        } else if let Some(&(open, close)) = BRACKETS.iter().find(|&&(_, c)| c == ch) {
+            let mut depth = 0;
+            for (pos, c) in self.chars_before((at.x(), at.y())) {
+                if c == close {
+                    depth += 1;
+                } else if c == open {
+                    if depth == 0 {
+                        return Some(pos);
+                    }
+                    depth -= 1;
+                }
+            }
         }
-    }
 
-    pub fn squash_row<P: Coordinates + AsCoordinates>(&mut self, at: &P) {
-        if let Some(cur) = self.squash_row_bypass(at) {
-            self.history
-                .record(at.as_coordinates(), Operation::SquashRow(cur));
-        }
+        None
     }
 
-    pub fn squash_row_bypass<P: Coordinates + AsCoordinates>(
-        &mut self,
-        at: &P,
-    ) -> Option<(usize, usize)> {
-        if 0 < at.y() {
-            if let Some(row) = self.delete_row_bypass(at) {
-                self.cached = true;
-                self.updated.push(at.y() - 1..self.rows());
-
-                let mut next_at = Cursor::default();
-                next_at.set(self, &(at.x(), at.y() - 1));
+    /// Returns the first bracket without the pair in the buffer, or `None` if balanced.
+    /// An unmatched closer is returned as soon as found, otherwise the first unclosed opener.
+    /// Brackets in quoted strings are ignored, and the quote is closed at the end of row.
+    /// The progress is called with the row index for large buffer.
+    ///
+    /// ```
+    /// use note::buffer::Buffer;
+    ///
+    /// let mut buf = Buffer::default();
+    /// buf.insert_row(&(0, 0), &"f(\")\", [1)".chars().collect::<Vec<char>>());
+    ///
+    /// assert_eq!(Some(((9, 0), ')')), buf.unmatched_bracket(|_| {}));
+    /// ```
+    pub fn unmatched_bracket<F: FnMut(usize)>(
+        &self,
+        mut progress: F,
+    ) -> Option<((usize, usize), char)> {
+        let mut openers: Vec<((usize, usize), char)> = vec![];
+
+        for (y, row) in self.rows.iter().enumerate() {
+            if 0 < y && y % BRACKET_PROGRESS_ROWS == 0 {
+                progress(y);
+            }
 
-                return self.append_row_bypass(&next_at, row.column());
+            let mut quote: Option<char> = None;
+            let mut escaped = false;
+            for (x, &ch) in row.column().iter().enumerate() {
+                match quote {
+                    Some(_) if escaped => escaped = false,
+                    Some(_) if ch == '\\' => escaped = true,
+                    Some(q) if ch == q => quote = None,
+                    Some(_) => {}
+                    None if QUOTES.contains(&ch) => quote = Some(ch),
+                    None if BRACKETS.iter().any(|&(o, _)| o == ch) => openers.push(((x, y), ch)),
+                    None => {
+                        if let Some(&(open, _)) = BRACKETS.iter().find(|&&(_, c)| c == ch) {
+                            match openers.pop() {
+                                Some((_, o)) if o == open => {}
+                                _ => return Some(((x, y), ch)),
+                            }
+                        }
+                    }
+                }
             }
         }
 
-        None
+        openers.first().copied()
     }
 
-    pub fn undo(&mut self) -> Option<(usize, usize)> {
-        if let Some(history) = self.history.rollback() {
-            self.cached = true;
-            let cord = match history {
-                (cur, Operation::Append(cord)) => {
-                    self.shrink_row_bypass(&cord);
-                    cur
-                }
-                (cur, Operation::DeleteChar(cord, ch)) => {
-                    self.insert_char_bypass(&(cord.0 - 1, cord.1), ch);
-                    cur
-                }
-                (cur, Operation::DeleteChars(cord, rows, mode)) => {
-                    self.insert_chars_bypass(&cord, rows.as_slice(), mode);
-                    cur
-                }
-                (cur, Operation::DeleteRow(cord, row)) => {
-                    self.insert_row_bypass(&cord, row.column());
-                    cur
-                }
-                (cur, Operation::InsertChar(cord)) => {
-                    self.delete_char_bypass(&(cord.0 + 1, cord.1));
-                    cur
-                }
-                (cur, Operation::InsertChars(cord, end, mode)) => {
-                    self.delete_chars_bypass(&cord, &end, mode);
-                    cur
-                }
-                (cur, Operation::InsertRow(cord)) => {
-                    self.delete_row_bypass(&cord);
-                    cur
-                }
-                (cur, Operation::Replace(cord, length, row)) => {
-                    self.replace_bypass(&cord, length, row.column());
-                    cur
-                }
-                (cur, Operation::ShrinkRow(cord, row)) => {
-                    self.append_row_bypass(&cord, row.column());
-                    cur
-                }
-                (cur, Operation::SplitRow(cord)) => {
-                    self.squash_row_bypass(&cord);
-                    cur
-                }
-                (cur, Operation::SquashRow(cord)) => {
-                    self.split_row_bypass(&cord);
-                    cur
-                }
-            };
-            Some(cord)
-        } else {
-            None
+    /// Returns the indentation of new line split at the position.
+    pub fn new_line_indent<P: Coordinates>(&self, at: &P) -> Vec<char> {
+        let row = match self.rows.get(at.y()) {
+            Some(row) => row,
+            None => return vec![],
+        };
+
+        let column = row.column();
+        let x = min(at.x(), column.len());
+        let len = row.indent_len().map_or(x, |len| min(len, x));
+        let mut indent = column[..len].to_vec();
+
+        let before = column[..x].iter().collect::<String>();
+        let after = column[x..].iter().collect::<String>();
+        let unit = self.indent_unit();
+        if self.auto_indent.opens(&before) {
+            indent.extend_from_slice(&unit);
+        }
+        if self.auto_indent.closes(&after) && indent.ends_with(&unit) {
+            indent.truncate(indent.len() - unit.len());
         }
+
+        indent
     }
 
-    pub fn updated(&self) -> bool {
-        !self.updated.is_empty()
+    /// Returns the next row whose indentation is less than or equal to the row.
+    /// Blank rows are skipped.
+    pub fn next_indent_row(&self, y: usize) -> Option<usize> {
+        let tab_width = self.tab_width();
+        let width = self.rows.get(y)?.indent_width(tab_width)?;
+        self.rows
+            .iter()
+            .enumerate()
+            .skip(y + 1)
+            .find(|(_, r)| r.indent_width(tab_width).is_some_and(|w| w <= width))
+            .map(|(idx, _)| idx)
     }
 
-    fn delete_chars_none<P: Coordinates + AsCoordinates>(
+    /// Reflow the paragraph at the position to the width in one undo step.
+    /// Returns the position on the same word in the reflowed paragraph,
+    /// or `None` if the paragraph is not changed.
+    pub fn reflow_paragraph<P: Coordinates>(
         &mut self,
-        start: &P,
-        end: &P,
-    ) -> Vec<Row> {
-        let mut rs = vec![];
+        at: &P,
+        width: usize,
+    ) -> Option<(usize, usize)> {
+        let range = self.paragraph_range(at.y())?;
+        let rows = &self.rows[range.clone()];
+        let prefix = common_prefix(rows);
+        let reflowed = reflow(rows, width, &prefix, self.tab_width());
+        if reflowed
+            .iter()
+            .map(Row::column)
+            .eq(rows.iter().map(Row::column))
+        {
+            return None;
+        }
 
-        if start.y() == end.y() {
-            if let Some(row) = self.rows.get_mut(start.y()) {
-                if start.x() < row.len() {
-                    if let Some(text) = row.remove_range(start.x()..end.x()) {
-                        rs.push(Row::from(text));
-                    }
-                }
+        // The word at or before the position, and the offset in it.
+        let (mut index, mut offset) = (0, 0);
+        for (n, (x, y, word)) in paragraph_words(rows, prefix.len()).enumerate() {
+            let y = range.start + y;
+            if (at.y(), at.x()) < (y, x) {
+                break;
             }
-        } else {
-            let mut last = Row::default();
-            for idx in (start.y()..end.y() + 1).rev() {
-                if let Some(row) = self.rows.get_mut(idx) {
-                    if idx == start.y() {
-                        if let Some(text) = row.remove_range(start.x()..row.len()) {
-                            rs.push(Row::from(text));
-                        }
+            index = n;
+            offset = if y == at.y() {
+                min(at.x() - x, word.len())
+            } else {
+                word.len()
+            };
+        }
 
-                        row.append(last.column());
-                    } else if idx == end.y() {
-                        if let Some(text) = row.remove_range(0..end.x()) {
-                            rs.push(Row::from(text));
-                        }
+        let located = paragraph_words(&reflowed, prefix.len())
+            .nth(index)
+            .map(|(x, y, _)| (x + offset, range.start + y));
 
-                        if let Some(r) = self.delete_row_bypass(&(0, idx)) {
-                            last = r;
-                        }
-                    } else if let Some(r) = self.delete_row_bypass(&(0, idx)) {
-                        rs.push(r);
-                    }
-                }
-            }
-        }
+        let last = range.end - 1;
+        let end = (self.rows[last].len(), last);
+        self.replace_range(&(0, range.start), &end, &reflowed);
 
-        rs
+        located.or(Some((0, range.start)))
     }
 
-    fn delete_chars_rectangle<P: Coordinates + AsCoordinates>(
-        &mut self,
-        start: &P,
-        end: &P,
-    ) -> Vec<Row> {
-        let mut rs = vec![];
+    /// Returns the rows of the paragraph delimited by blank rows, or `None` if the row is blank.
+    pub fn paragraph_range(&self, y: usize) -> Option<Range<usize>> {
+        self.rows.get(y)?.indent_len()?;
 
-        if let Some(rows) = self.rows.get_mut(start.y()..end.y() + 1) {
-            let startx = min(start.x(), end.x());
-            let endx = max(start.x(), end.x());
-            let length = end.x() - start.x();
+        let start = self.rows[..y]
+            .iter()
+            .rposition(|r| r.indent_len().is_none())
+            .map_or(0, |p| p + 1);
+        let end = self.rows[y..]
+            .iter()
+            .position(|r| r.indent_len().is_none())
+            .map_or(self.rows.len(), |p| p + y);
+        Some(start..end)
+    }
 
-            for row in rows.iter_mut().rev() {
-                if startx < row.len() {
-                    let endx = min(row.len(), endx);
-                    let post = length - (endx - startx);
-                    if let Some(mut r) = row.remove_range(startx..endx) {
-                        let spaces = iter::repeat(' ').take(post).collect::<Vec<char>>();
-                        r.extend_from_slice(&spaces);
-                        rs.push(Row::from(r));
-                    }
-                } else {
-                    let chars = iter::repeat(' ').take(length).collect::<Vec<char>>();
-                    rs.push(Row::from(chars));
-                }
+    /// Paste pending text at the position, or above the row if pending is lines.
+    pub fn paste_pending<P: Coordinates + AsCoordinates>(
+        &mut self,
+        at: &P,
+    ) -> Option<(usize, usize)> {
+        match self.pending.clone() {
+            Some((rows, SelectMode::Line, _)) => self
+                .insert_chars(&(0, at.y()), rows.as_slice(), SelectMode::Line)
+                .map(|_| (0, at.y() + rows.len())),
+            Some((rows, _, true)) if self.rows() <= at.y() => {
+                self.insert_row(&(0, at.y()), rows[0].column());
+                Some((0, at.y() + 1))
             }
+            Some((rows, mode, true)) => self.insert_chars(&(0, at.y()), rows.as_slice(), mode),
+            Some((rows, mode, false)) => self.insert_chars(at, rows.as_slice(), mode),
+            None => None,
         }
+    }
 
-        rs
+    pub fn pending(&self) -> Option<&[Row]> {
+        self.pending.as_ref().map(|p| p.0.as_slice())
     }
 
-    fn get_range_none(&self, range: Range<&Cursor>) -> Option<Vec<Row>> {
-        if let Some(rows) = self.rows.get(range.start.y()..range.end.y() + 1) {
-            let last_idx = rows.len() - 1;
-            let mut rs = vec![];
-            for (idx, row) in rows.iter().enumerate() {
-                let startx = if idx == 0 { range.start.x() } else { 0 };
-                let endx = if idx == last_idx {
-                    range.end.x()
-                } else {
-                    row.len()
-                };
-                let r = &row.column()[startx..endx];
-                rs.push(Row::from(r));
-            }
-            Some(rs)
+    /// Returns the pending rows joined with CRLF. Whole lines are also terminated by it.
+    pub fn pending_text(&self) -> Option<String> {
+        let rows = self.pending()?;
+        let mut texts = rows
+            .iter()
+            .map(|r| r.to_string_at(0))
+            .collect::<Vec<String>>();
+        if matches!(self.pending, Some((_, SelectMode::Line, _))) {
+            texts.push(String::new());
+        }
+        Some(texts.join("\r\n"))
+    }
+
+    /// Set the text split at `\r\n` and `\n` to pending to paste as characters.
+    ///
+    /// ```
+    /// use note::buffer::Buffer;
+    ///
+    /// let mut buf = Buffer::default();
+    /// buf.set_pending_text("a\r\nb\n");
+    ///
+    /// assert_eq!(3, buf.pending().unwrap().len());
+    /// assert_eq!(Some("a\r\nb\r\n".to_string()), buf.pending_text());
+    /// ```
+    pub fn set_pending_text(&mut self, text: &str) {
+        let rows = text
+            .split('\n')
+            .map(|line| row_from_str(line.strip_suffix('\r').unwrap_or(line)))
+            .collect();
+        self.pending = Some((rows, SelectMode::None, false));
+    }
+
+    /// Returns whether pending text is pasted as lines.
+    pub fn pending_linewise(&self) -> bool {
+        matches!(self.pending, Some((_, _, true)))
+    }
+
+    /// Returns the number of rows padded with spaces and the maximum spaces
+    /// when pending rectangle is pasted.
+    pub fn pending_padding<P: Coordinates>(&self, at: &P) -> Option<(usize, usize)> {
+        if let Some((rows, SelectMode::Rectangle, _)) = self.pending.as_ref() {
+            let paddings = (at.y()..at.y() + rows.len())
+                .map(|y| {
+                    at.x()
+                        .saturating_sub(self.rows.get(y).map_or(0, |r| r.len()))
+                })
+                .filter(|&space| space > 0)
+                .collect::<Vec<usize>>();
+            paddings.iter().max().map(|&max| (paddings.len(), max))
         } else {
             None
         }
     }
 
-    fn get_range_rectangle(&self, range: Range<&Cursor>) -> Option<Vec<Row>> {
-        if let Some(rows) = self.rows.get(range.start.y()..range.end.y() + 1) {
-            let start = min(range.start.x(), range.end.x());
-            let end = max(range.start.x(), range.end.x());
-            let length = end - start;
+    /// Returns the number of rows and characters of pending text.
+    pub fn pending_size(&self) -> Option<(usize, usize)> {
+        let rows = self.pending()?;
+        Some((rows.len(), rows.iter().map(|r| r.len()).sum()))
+    }
 
-            let mut rs = vec![];
-            for row in rows {
-                if start < row.len() {
-                    let startx = start;
-                    let endx = min(row.len(), end);
-                    let post = length - (endx - startx);
-                    let mut chars = row.column()[startx..endx].to_vec();
-                    let spaces = iter::repeat(' ').take(post).collect::<Vec<char>>();
-                    chars.extend_from_slice(&spaces);
-                    rs.push(Row::from(chars));
-                } else {
-                    let chars = iter::repeat(' ').take(length).collect::<Vec<char>>();
-                    rs.push(Row::from(chars));
-                }
-            }
-            Some(rs)
-        } else {
-            None
+    pub fn save(&mut self) -> Result<(), Error> {
+        if let Some(path) = self.filename.clone() {
+            self.save_as(&path)?;
         }
-    }
 
-    fn insert_chars_none<P: Coordinates + AsCoordinates>(
-        &mut self,
-        at: &P,
-        rows: &[Row],
-    ) -> Option<(usize, usize)> {
-        let mut end = at.as_coordinates();
-        let mut rest = Row::default();
+        Ok(())
+    }
 
-        // first row
-        if let (Some(row), Some(first)) = (self.rows.get_mut(at.y()), rows.first()) {
-            if at.x() <= row.len() {
-                self.cached = true;
-                if 1 < rows.len() {
-                    rest = row.split_off(at.x());
-                    row.append(first.column());
-                } else {
-                    row.insert_slice(at.x(), first.column());
-                }
-                end = (at.x() + first.len(), at.y());
-            }
-        } else {
-            return None;
+    pub fn save_as(&mut self, path: &Path) -> Result<(), Error> {
+        if self.read_only {
+            let e = io::Error::new(ErrorKind::PermissionDenied, "buffer is read-only");
+            return Err(Error::from(e));
         }
 
-        if 1 < rows.len() {
-            // first row + 1 .. last row - 1
-            if let Some(middles) = rows.get(1..rows.len() - 1) {
-                self.cached = true;
-                for (idx, middle) in middles.iter().enumerate() {
-                    let y = at.y() + idx + 1;
-                    self.insert_row_bypass(&(0, y), middle.column());
-                    end = (middle.len(), y);
-                }
-            }
+        // Encode the whole text first, not to truncate the file by an unencodable character.
+        let mut bytes = vec![];
+        self.write_to(&mut bytes)?;
+        File::create(path)?.write_all(&bytes)?;
 
-            // last row
-            if let Some(last) = rows.last() {
-                self.cached = true;
-                let y = at.y() + rows.len() - 1;
-                self.insert_row_bypass(&(0, y), last.column());
-                self.append_row_bypass(&(0, y), rest.column());
-                end = (last.len(), y);
-            }
-        }
+        self.cached = false;
+        self.history.mark_saved();
 
-        Some(end)
+        Ok(())
     }
 
-    fn insert_chars_rectangle<P: Coordinates + AsCoordinates>(
-        &mut self,
-        at: &P,
-        rows: &[Row],
-    ) -> Option<(usize, usize)> {
-        let mut end = at.as_coordinates();
+    /// Write the rows terminated by the line ending, without trailing spaces and tabs if trimmed.
+    /// The last row is not terminated if the loaded text is not, unless `insert_final_newline`.
+    /// Unlike saving, the buffer is kept modified.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        if self.has_bom() {
+            writer.write_all(self.encoding.bom())?;
+        }
 
-        for (idx, row) in rows.iter().enumerate() {
-            if let Some(r) = self.rows.get_mut(idx + at.y()) {
-                if r.len() < at.x() {
-                    let space = at.x() - r.len();
-                    let mut chars = iter::repeat(' ').take(space).collect::<Vec<char>>();
-                    chars.extend_from_slice(row.column());
-                    r.append(&chars);
-                } else {
-                    r.insert_slice(at.x(), row.column());
-                }
-            } else {
-                let mut chars = iter::repeat(' ').take(at.x()).collect::<Vec<char>>();
-                chars.extend_from_slice(row.column());
-                self.insert_row_bypass(&(0, idx + at.y()), chars.as_slice());
+        let line_ending = self.encoding.encode(self.line_ending().as_str())?;
+        let breaks = self.line_breaks();
+        for (y, row) in self.rows.iter().enumerate() {
+            let buf = String::from_iter(self.written_column(row));
+            writer.write_all(&self.encoding.encode(&buf)?)?;
+            if y < breaks {
+                writer.write_all(&line_ending)?;
             }
-
-            end = (at.x() + row.len(), idx + at.y());
         }
 
-        Some(end)
+        writer.flush()?;
+
+        Ok(())
     }
-}
 
-// -----------------------------------------------------------------------------------------------
+    /// Replace all rows in one undo step.
+    /// The position is restored at undo, and should be clamped by the caller.
+    pub fn set_all_rows<P: Coordinates + AsCoordinates>(&mut self, at: &P, rows: Vec<Row>) {
+        let previous = self.set_all_rows_bypass(rows);
+        self.history
+            .record(at.as_coordinates(), Operation::SetAllRows(previous));
+    }
 
-#[derive(Clone, Default)]
-pub struct Row {
-    column: Vec<char>,
-}
+    /// Returns the previous rows.
+    pub fn set_all_rows_bypass(&mut self, rows: Vec<Row>) -> Vec<Row> {
+        let previous = mem::replace(&mut self.rows, rows);
+        self.cached = true;
+        self.mark_updated(0..max(previous.len(), self.rows.len()));
+        previous
+    }
 
-impl From<Vec<char>> for Row {
-    fn from(value: Vec<char>) -> Self {
-        Row { column: value }
+    pub fn set_auto_indent(&mut self, auto_indent: AutoIndent) {
+        self.auto_indent = auto_indent;
     }
-}
 
-impl From<&[char]> for Row {
-    fn from(value: &[char]) -> Self {
-        Row {
-            column: value.to_vec(),
+    pub fn set_comment(&mut self, comment: Comment) {
+        self.comment = comment;
+    }
+
+    /// Returns the syntax highlighter by the configured comment prefixes,
+    /// or by the extension of the filename if none is configured.
+    pub fn highlighter(&self) -> &dyn Highlighter {
+        match self.settings.comment_highlight() {
+            comment if comment.is_empty() => highlight::by_path(self.filename.as_deref()),
+            comment => comment,
         }
     }
-}
 
-impl From<String> for Row {
-    fn from(value: String) -> Self {
-        Row {
-            column: value.chars().collect(),
+    pub fn set_filename(&mut self, filename: &Path) {
+        self.filename = Some(PathBuf::from(filename));
+    }
+
+    pub fn set_formatter(&mut self, formatter: Option<Formatter>) {
+        self.formatter = formatter;
+    }
+
+    /// Set whether BOM is written at saving.
+    pub fn set_bom(&mut self, bom: bool) {
+        self.bom = bom;
+    }
+
+    /// Set whether the last row is terminated by line break at saving.
+    pub fn set_final_newline(&mut self, final_newline: bool) {
+        self.no_final_newline = !final_newline;
+    }
+
+    /// Set the line ending written at saving instead of the loaded one.
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        self.line_ending = Some(line_ending);
+    }
+
+    /// Record the position in the numbered mark from 0 to 9.
+    pub fn set_mark<P: AsCoordinates>(&mut self, slot: usize, at: &P) {
+        if let Some(mark) = self.marks.get_mut(slot) {
+            *mark = Some(at.as_coordinates());
         }
     }
-}
 
-impl From<&str> for Row {
-    fn from(value: &str) -> Self {
-        Row {
-            column: value.chars().collect(),
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    /// Set whether trailing whitespace is left out at saving, without changing the rows.
+    pub fn set_trim_trailing_whitespace(&mut self, trim: bool) {
+        self.trim_trailing_whitespace = trim;
+    }
+
+    /// Set the settings for the file type, and the comment prefix, the tab stop,
+    /// trimming at saving and the word characters by them.
+    pub fn set_settings(&mut self, settings: Settings) {
+        self.comment.set_prefix(settings.comment_prefix());
+        self.trim_trailing_whitespace = settings.trim_trailing_on_save();
+        self.word_chars = settings.word_chars().clone();
+        self.updated.push(0..self.rows());
+        self.settings = settings;
+    }
+
+    /// Set the tab stop, and rows are redrawn with it. Zero is ignored.
+    pub fn set_tab_width(&mut self, width: usize) {
+        let mut settings = self.settings.clone();
+        if settings.set("tab_width", &width.to_string()) {
+            self.set_settings(settings);
         }
     }
-}
 
-impl Row {
-    pub fn append(&mut self, other: &[char]) {
-        self.column.extend_from_slice(other)
+    /// Returns the hashes of rows to compare later.
+    pub fn snapshot(&self) -> Snapshot {
+        let hashes = self
+            .rows
+            .iter()
+            .map(|row| {
+                let mut hasher = DefaultHasher::new();
+                row.column.hash(&mut hasher);
+                hasher.finish()
+            })
+            .collect();
+        Snapshot { hashes }
     }
 
-    pub fn clear(&mut self) {
-        self.column.clear();
+    pub fn set_word_chars(&mut self, word_chars: WordChars) {
+        self.word_chars = word_chars;
     }
 
-    pub fn column(&self) -> &[char] {
-        &self.column
+    pub fn settings(&self) -> &Settings {
+        &self.settings
     }
 
-    pub fn insert(&mut self, index: usize, element: char) {
-        if index <= self.column.len() {
-            self.column.insert(index, element);
+    pub fn shrink_row<P: Coordinates + AsCoordinates>(&mut self, at: &P) {
+        if let Some(row) = self.shrink_row_bypass(at) {
+            self.history.record(
+                at.as_coordinates(),
+                Operation::ShrinkRow(at.as_coordinates(), row),
+            );
         }
     }
 
-    pub fn insert_slice(&mut self, index: usize, other: &[char]) {
-        if index <= self.column.len() {
-            let removed = self.column.split_off(index);
-            self.column.extend_from_slice(other);
-            self.column.extend(removed);
+    pub fn shrink_row_bypass<P: Coordinates + AsCoordinates>(&mut self, at: &P) -> Option<Row> {
+        if let Some(row) = self.rows.get_mut(at.y()) {
+            self.cached = true;
+            let removed = row.split_off(at.x());
+            self.mark_updated(at.y()..at.y() + 1);
+            self.pending = Some((vec![removed.clone()], SelectMode::None, false));
+            Some(removed)
+        } else {
+            None
         }
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.column.is_empty()
+    /// Convert leading spaces of the rows to tabs by the tab width in one undo step.
+    /// Spaces less than the tab width are kept after the tabs.
+    pub fn spaces_to_tabs(&mut self, rows: Range<usize>) -> bool {
+        self.replace_indents(rows, |width, tab_width| {
+            let mut indent = vec!['\t'; width / tab_width];
+            indent.extend(iter::repeat(' ').take(width % tab_width));
+            indent
+        })
     }
 
-    pub fn last_char_width(&self) -> usize {
-        match self.column.last() {
-            Some(&ch) => char_width(ch),
-            _ => 0,
+    pub fn split_row<P: Coordinates + AsCoordinates>(&mut self, at: &P) {
+        if let Some(cur) = self.split_row_bypass(at) {
+            self.history
+                .record(at.as_coordinates(), Operation::SplitRow(cur));
         }
     }
 
-    pub fn len(&self) -> usize {
-        self.column.len()
+    pub fn split_row_bypass<P: Coordinates + AsCoordinates>(
+        &mut self,
+        at: &P,
+    ) -> Option<(usize, usize)> {
+        let row_len = self.rows();
+        if let Some(row) = self.rows.get_mut(at.y()) {
+            self.cached = true;
+            let next = row.split_off(at.x());
+            self.mark_updated(at.y()..row_len + 1);
+
+            let mut next_at = Cursor::default();
+            next_at.set(self, &(at.x(), at.y() + 1));
+
+            self.insert_row_bypass(&next_at, next.column());
+            self.move_marks(at.as_coordinates(), (0, at.y() + 1));
+
+            Some(next_at.as_coordinates())
+        } else {
+            None
+        }
     }
 
-    pub fn replace(&mut self, index: usize, length: usize, other: &[char]) -> Option<Vec<char>> {
-        let stop = index + length;
-        if let Some(removed) = self.remove_range(index..stop) {
-            self.insert_slice(index, other);
-            Some(removed)
+    pub fn squash_row<P: Coordinates + AsCoordinates>(&mut self, at: &P) {
+        if let Some(cur) = self.squash_row_bypass(at) {
+            self.history
+                .record(at.as_coordinates(), Operation::SquashRow(cur));
+        }
+    }
+
+    pub fn squash_row_bypass<P: Coordinates + AsCoordinates>(
+        &mut self,
+        at: &P,
+    ) -> Option<(usize, usize)> {
+        if 0 < at.y() {
+            if at.y() < self.rows() {
+                // The marks on the row are kept after the end of the previous row.
+                let x = self.rows[at.y() - 1].len();
+                self.move_marks((0, at.y()), (x, at.y() - 1));
+            }
+
+            if let Some(row) = self.delete_row_bypass(at) {
+                self.cached = true;
+                self.mark_updated(at.y() - 1..self.rows());
+
+                let mut next_at = Cursor::default();
+                next_at.set(self, &(at.x(), at.y() - 1));
+
+                return self.append_row_bypass(&next_at, row.column());
+            }
+        }
+
+        None
+    }
+
+    /// Sort the rows stably by the text from the start column, up to the length if given.
+    /// Returns `false` if the rows are already sorted.
+    pub fn sort_rows(&mut self, rows: Range<usize>, start: usize, length: Option<usize>) -> bool {
+        let rows = rows.start..min(rows.end, self.rows());
+        let end = length.map_or(usize::MAX, |l| start.saturating_add(l));
+
+        let mut order = rows.clone().collect::<Vec<usize>>();
+        order.sort_by(|&a, &b| {
+            let a = self.rows[a].slice(start..end);
+            let b = self.rows[b].slice(start..end);
+            a.cmp(b)
+        });
+
+        if order.iter().copied().eq(rows.clone()) {
+            return false;
+        }
+
+        let sorted = order
+            .iter()
+            .map(|&y| self.rows[y].column().to_vec())
+            .collect::<Vec<Vec<char>>>();
+
+        self.record_group(|buffer| {
+            for ((y, &from), text) in rows.zip(&order).zip(sorted) {
+                if y != from {
+                    let length = buffer.rows[y].len();
+                    buffer.replace(&(0, y), length, &text);
+                }
+            }
+        });
+
+        true
+    }
+
+    /// Convert leading tabs of the rows to spaces by the tab width in one undo step.
+    /// Returns the tab stop the rows are rendered with.
+    pub fn tab_width(&self) -> usize {
+        self.settings.tab_width()
+    }
+
+    pub fn tabs_to_spaces(&mut self, rows: Range<usize>) -> bool {
+        self.replace_indents(rows, |width, _| vec![' '; width])
+    }
+
+    /// Comment out the rows, or uncomment if all of the rows are commented.
+    /// Blank rows are not changed.
+    pub fn toggle_comment(&mut self, rows: Range<usize>) -> bool {
+        let prefix = self.comment.prefix().chars().collect::<Vec<char>>();
+        let targets = (rows.start..min(rows.end, self.rows()))
+            .filter_map(|y| self.rows[y].indent_len().map(|x| (x, y)))
+            .collect::<Vec<(usize, usize)>>();
+
+        if prefix.is_empty() || targets.is_empty() {
+            return false;
+        }
+
+        let commented = targets
+            .iter()
+            .all(|&(x, y)| self.rows[y].column()[x..].starts_with(&prefix));
+
+        self.record_group(|buffer| {
+            for (x, y) in targets {
+                if commented {
+                    let mut length = prefix.len();
+                    if buffer.rows[y].column().get(x + length) == Some(&' ') {
+                        length += 1;
+                    }
+                    buffer.replace(&(x, y), length, &[]);
+                } else {
+                    let x = if buffer.comment.indented() { x } else { 0 };
+                    let mut text = prefix.clone();
+                    text.push(' ');
+                    buffer.replace(&(x, y), 0, &text);
+                }
+            }
+        });
+
+        true
+    }
+
+    /// Append text to the row, or returns error if the row does not exist.
+    pub fn try_append_row<P: Coordinates + AsCoordinates>(
+        &mut self,
+        at: &P,
+        text: &[char],
+    ) -> Result<(), Error> {
+        match self.append_row_bypass(at, text) {
+            Some(cur) => {
+                self.history
+                    .record(at.as_coordinates(), Operation::Append(cur));
+                Ok(())
+            }
+            None => Err(Error::OutOfRange(at.x(), at.y())),
+        }
+    }
+
+    /// Insert the character, or returns error if the position is out of range.
+    pub fn try_insert_char<P: Coordinates + AsCoordinates>(
+        &mut self,
+        at: &P,
+        ch: char,
+    ) -> Result<(), Error> {
+        match self.insert_char_bypass(at, ch) {
+            Some(_) => {
+                self.history.record(
+                    at.as_coordinates(),
+                    Operation::InsertChar(at.as_coordinates()),
+                );
+                Ok(())
+            }
+            None => Err(Error::OutOfRange(at.x(), at.y())),
+        }
+    }
+
+    pub fn undo(&mut self) -> Option<(usize, usize)> {
+        if let Some((cur, op)) = self.history.rollback() {
+            if let Some(inverse) = self.undo_operation(op) {
+                self.history.record_undone(cur, inverse);
+            }
+            self.cached = !self.history.at_saved();
+            Some(cur)
         } else {
             None
         }
     }
 
-    pub fn shrink_width(&mut self, min_width: usize) -> usize {
-        if self.width() <= min_width {
-            let width = self.width();
-            self.column.clear();
-            return width;
+    pub fn updated(&self) -> bool {
+        !self.updated.is_empty()
+    }
+
+    /// Load the saved file again, and returns the index of the first row not restored from it,
+    /// or `None` if the same rows and the line breaks of the setting are loaded.
+    /// The number of rows is returned if only the line breaks differ.
+    pub fn verify_saved(&self) -> Result<Option<usize>, Error> {
+        let saved = Buffer::try_from(self.filename.as_deref())?;
+        let differ = self
+            .rows
+            .iter()
+            .zip(saved.rows.iter())
+            .position(|(a, b)| self.written_column(a) != b.column());
+        match differ {
+            Some(y) => return Ok(Some(y)),
+            None if self.rows() != saved.rows() => return Ok(Some(min(self.rows(), saved.rows()))),
+            None => {}
         }
 
-        for index in 0..self.column.len() {
-            let width = self.width_range(0..index);
-            if min_width <= width {
-                self.column.drain(..index);
-                return width;
+        let mut expected = LineEndings::default();
+        match self.line_ending() {
+            LineEnding::Crlf => expected.crlf = self.line_breaks(),
+            LineEnding::Lf => expected.lf = self.line_breaks(),
+        }
+
+        if saved.line_endings != expected {
+            return Ok(Some(self.rows()));
+        }
+
+        Ok(None)
+    }
+
+    /// Returns the characters of the row written at saving.
+    fn written_column<'a>(&self, row: &'a Row) -> &'a [char] {
+        let column = row.column();
+        if !self.trim_trailing_whitespace {
+            return column;
+        }
+
+        let len = column.iter().rposition(|c| !matches!(c, ' ' | '\t'));
+        &column[..len.map_or(0, |x| x + 1)]
+    }
+
+    /// Returns the range of the integer at the position, including `-` sign.
+    /// The integer before the position is returned if the position is not in integer.
+    pub fn number_range_at<P: Coordinates>(&self, at: &P) -> Option<Range<usize>> {
+        let column = self.rows.get(at.y())?.column();
+
+        let x = if column.get(at.x()).is_some_and(|ch| ch.is_ascii_digit()) {
+            at.x()
+        } else if 0 < at.x() && column.get(at.x() - 1).is_some_and(|ch| ch.is_ascii_digit()) {
+            at.x() - 1
+        } else {
+            return None;
+        };
+
+        let mut start = column[..x]
+            .iter()
+            .rposition(|ch| !ch.is_ascii_digit())
+            .map_or(0, |p| p + 1);
+        let end = column[x..]
+            .iter()
+            .position(|ch| !ch.is_ascii_digit())
+            .map_or(column.len(), |p| p + x);
+
+        // `-` is sign if it is not a part of word like `a-1`.
+        if 0 < start
+            && column[start - 1] == '-'
+            && (start < 2 || !self.word_chars.contains(column[start - 2]))
+        {
+            start -= 1;
+        }
+
+        Some(start..end)
+    }
+
+    /// Returns the previous row whose indentation is less than or equal to the row.
+    /// Blank rows are skipped.
+    pub fn prev_indent_row(&self, y: usize) -> Option<usize> {
+        let tab_width = self.tab_width();
+        let width = self.rows.get(y)?.indent_width(tab_width)?;
+        self.rows
+            .iter()
+            .enumerate()
+            .take(y)
+            .rev()
+            .find(|(_, r)| r.indent_width(tab_width).is_some_and(|w| w <= width))
+            .map(|(idx, _)| idx)
+    }
+
+    /// Returns the range and the text of the word at the position.
+    /// The word before the position is returned if the position is not in word.
+    ///
+    /// ```
+    /// use note::buffer::Buffer;
+    ///
+    /// let mut buf = Buffer::default();
+    /// buf.insert_row(&(0, 0), &['a', 'b', ' ', 'c']);
+    ///
+    /// assert_eq!(Some((0..2, "ab".to_string())), buf.word_at(&(2, 0)));
+    /// assert_eq!(Some((3..4, "c".to_string())), buf.word_at(&(3, 0)));
+    /// ```
+    pub fn word_at<P: Coordinates>(&self, at: &P) -> Option<(Range<usize>, String)> {
+        let range = self.word_range_at(at)?;
+        let text = self.rows[at.y()].column()[range.clone()].iter().collect();
+        Some((range, text))
+    }
+
+    pub fn word_chars(&self) -> &WordChars {
+        &self.word_chars
+    }
+
+    /// Returns the range of word under the position.
+    /// The word is found at the previous character if the position is end of word.
+    pub fn word_range_at<P: Coordinates>(&self, at: &P) -> Option<Range<usize>> {
+        let row = self.rows.get(at.y())?;
+        let column = row.column();
+
+        let x = if column
+            .get(at.x())
+            .is_some_and(|&ch| self.word_chars.contains(ch))
+        {
+            at.x()
+        } else if 0 < at.x()
+            && column
+                .get(at.x() - 1)
+                .is_some_and(|&ch| self.word_chars.contains(ch))
+        {
+            at.x() - 1
+        } else {
+            return None;
+        };
+
+        let start = column[..x]
+            .iter()
+            .rposition(|&ch| !self.word_chars.contains(ch))
+            .map(|p| p + 1)
+            .unwrap_or(0);
+        let end = column[x..]
+            .iter()
+            .position(|&ch| !self.word_chars.contains(ch))
+            .map(|p| p + x)
+            .unwrap_or(column.len());
+        Some(start..end)
+    }
+
+    /// Returns whether the operation can be undone at the positions in the buffer.
+    fn can_undo(&self, op: &Operation) -> bool {
+        let in_row = |&(x, y): &(usize, usize)| self.rows.get(y).is_some_and(|r| x <= r.len());
+
+        match op {
+            Operation::Append(cord) | Operation::SquashRow(cord) => in_row(cord),
+            Operation::DeleteChar((x, y), _) => 0 < *x && in_row(&(x - 1, *y)),
+            Operation::DeleteChars(cord, _, SelectMode::None) => in_row(cord),
+            Operation::DeleteChars((_, y), _, SelectMode::Rectangle) => *y <= self.rows(),
+            Operation::DeleteRow((_, y), _) => *y <= self.rows(),
+            Operation::InsertChars(start, end, SelectMode::None) => {
+                (start.1, start.0) <= (end.1, end.0)
+            }
+            Operation::InsertChars(start, end, SelectMode::Rectangle) => {
+                start.0 <= end.0 && start.1 <= end.1
+            }
+            Operation::DeleteChars((_, y), _, SelectMode::Line) => *y <= self.rows(),
+            Operation::InsertChars(start, end, SelectMode::Line) => {
+                start.1 <= end.1 && end.1 < self.rows()
+            }
+            _ => true,
+        }
+    }
+
+    /// Undo the operation, and returns the operation to undo it in turn.
+    /// Applying the returned one redoes the original operation.
+    fn undo_operation(&mut self, op: Operation) -> Option<Operation> {
+        if !self.can_undo(&op) {
+            log_warn!(
+                "Undo is skipped at position out of buffer ({} rows).",
+                self.rows()
+            );
+            return None;
+        }
+
+        match op {
+            Operation::Append(cord) => self
+                .shrink_row_bypass(&cord)
+                .map(|row| Operation::ShrinkRow(cord, row)),
+            Operation::DeleteChar(cord, ch) => self
+                .insert_char_bypass(&(cord.0 - 1, cord.1), ch)
+                .map(Operation::InsertChar),
+            Operation::DeleteChars(cord, rows, mode) => self
+                .insert_chars_bypass(&cord, rows.as_slice(), mode)
+                .map(|end| Operation::InsertChars(cord, end, mode)),
+            Operation::DeleteRow(cord, row) => {
+                self.insert_row_bypass(&cord, row.column());
+                Some(Operation::InsertRow(cord))
+            }
+            Operation::Group(entries) => {
+                let inverses = entries
+                    .into_iter()
+                    .rev()
+                    .filter_map(|(cur, op)| self.undo_operation(op).map(|inv| (cur, inv)))
+                    .collect::<Vec<_>>();
+                Some(Operation::Group(inverses))
+            }
+            Operation::InsertChar(cord) => self
+                .delete_char_bypass(&(cord.0 + 1, cord.1))
+                .map(|ch| Operation::DeleteChar((cord.0 + 1, cord.1), ch)),
+            Operation::InsertChars(cord, end, mode) => self
+                .delete_chars_bypass(&cord, &end, mode)
+                .map(|rows| Operation::DeleteChars(cord, rows, mode)),
+            Operation::InsertRow(cord) => self
+                .delete_row_bypass(&cord)
+                .map(|row| Operation::DeleteRow(cord, row)),
+            Operation::Replace(cord, length, row) => self
+                .replace_bypass(&cord, length, row.column())
+                .map(|removed| Operation::Replace(cord, row.len(), removed)),
+            Operation::SetAllRows(rows) => {
+                Some(Operation::SetAllRows(self.set_all_rows_bypass(rows)))
+            }
+            Operation::ShrinkRow(cord, row) => self
+                .append_row_bypass(&cord, row.column())
+                .map(Operation::Append),
+            Operation::SplitRow(cord) => self.squash_row_bypass(&cord).map(Operation::SquashRow),
+            Operation::SquashRow(cord) => self.split_row_bypass(&cord).map(Operation::SplitRow),
+            Operation::SwapRows(a, b) => self
+                .swap_rows_bypass(&a, &b)
+                .then_some(Operation::SwapRows(a, b)),
+        }
+    }
+
+    /// Returns the characters before the position in reverse order.
+    fn chars_before(
+        &self,
+        (x0, y0): (usize, usize),
+    ) -> impl Iterator<Item = ((usize, usize), char)> + '_ {
+        self.rows
+            .iter()
+            .enumerate()
+            .take(y0.saturating_add(1))
+            .rev()
+            .flat_map(move |(y, row)| {
+                let len = if y == y0 {
+                    min(x0, row.len())
+                } else {
+                    row.len()
+                };
+                row.column()[..len]
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .map(move |(x, &ch)| ((x, y), ch))
+            })
+    }
+
+    /// Returns the characters from the position.
+    fn chars_from(
+        &self,
+        (x0, y0): (usize, usize),
+    ) -> impl Iterator<Item = ((usize, usize), char)> + '_ {
+        self.rows
+            .iter()
+            .enumerate()
+            .skip(y0)
+            .flat_map(move |(y, row)| {
+                let skip = if y == y0 { x0 } else { 0 };
+                row.column()
+                    .iter()
+                    .enumerate()
+                    .skip(skip)
+                    .map(move |(x, &ch)| ((x, y), ch))
+            })
+    }
+
+    /// Returns the innermost bracketed group containing the range.
+    /// The bracket at the start of the range is the opener of the group.
+    fn enclosing_brackets(
+        &self,
+        start: (usize, usize),
+        end: (usize, usize),
+    ) -> Option<((usize, usize), (usize, usize))> {
+        let mut depths = [0; BRACKETS.len()];
+        let first = self.char_at(&start).map(|ch| (start, ch));
+
+        for (pos, ch) in first.into_iter().chain(self.chars_before(start)) {
+            if let Some(k) = BRACKETS.iter().position(|&(_, c)| c == ch) {
+                if pos != start {
+                    depths[k] += 1;
+                }
+            } else if let Some(k) = BRACKETS.iter().position(|&(o, _)| o == ch) {
+                if 0 < depths[k] {
+                    depths[k] -= 1;
+                    continue;
+                }
+
+                if let Some((x, y)) = self.matching_bracket(&pos) {
+                    let span = (pos, (x + 1, y));
+                    if (end.1, end.0) <= (y, x + 1) && span != (start, end) {
+                        return Some(span);
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Returns the quoted string containing the range in a row.
+    /// Unclosed quote is ignored, and quote escaped by `\` does not close the string.
+    fn enclosing_quotes(
+        &self,
+        start: (usize, usize),
+        end: (usize, usize),
+    ) -> Option<((usize, usize), (usize, usize))> {
+        if start.1 != end.1 {
+            return None;
+        }
+
+        let y = start.1;
+        let mut open: Option<(usize, char)> = None;
+        let mut escaped = false;
+
+        for (x, &ch) in self.rows.get(y)?.column().iter().enumerate() {
+            match open {
+                Some(_) if escaped => escaped = false,
+                Some(_) if ch == '\\' => escaped = true,
+                Some((o, q)) if ch == q => {
+                    open = None;
+                    if o <= start.0 && end.0 <= x + 1 && (o, x + 1) != (start.0, end.0) {
+                        return Some(((o, y), (x + 1, y)));
+                    }
+                }
+                Some(_) => {}
+                None if QUOTES.contains(&ch) => open = Some((x, ch)),
+                None => {}
             }
         }
 
-        0
+        None
     }
 
-    pub fn slice_width(&self, range: Range<usize>) -> Row {
-        let mut render = self.render();
+    /// Returns the number of line breaks written at saving.
+    fn line_breaks(&self) -> usize {
+        if self.has_final_newline() {
+            self.rows()
+        } else {
+            self.rows().saturating_sub(1)
+        }
+    }
+
+    fn load_binary(&mut self, bytes: &[u8]) {
+        let text = String::from_utf8_lossy(bytes);
+        for line in text.lines() {
+            let chars = line
+                .chars()
+                .map(|ch| {
+                    if ch.is_control() && ch != '\t' {
+                        BINARY_PLACEHOLDER
+                    } else {
+                        ch
+                    }
+                })
+                .collect::<Vec<char>>();
+            self.rows.push(Row::from(chars));
+        }
+
+        self.binary = true;
+        self.read_only = true;
+    }
+
+    fn mark_updated(&mut self, rows: Range<usize>) {
+        for observer in self.observers.iter_mut() {
+            observer(rows.clone());
+        }
+        self.updated.push(rows);
+        self.revision += 1;
+    }
+
+    /// Move the marks at or after the position in its row to the position of the destination.
+    fn move_marks(&mut self, (x0, y0): (usize, usize), (x1, y1): (usize, usize)) {
+        for mark in self.marks.iter_mut().flatten() {
+            if mark.1 == y0 && x0 <= mark.0 {
+                *mark = (x1 + mark.0 - x0, y1);
+            }
+        }
+    }
+
+    /// Replace leading whitespace of the rows by the width in one undo step.
+    /// Blank rows are not changed.
+    fn replace_indents<F: Fn(usize, usize) -> Vec<char>>(
+        &mut self,
+        rows: Range<usize>,
+        indent: F,
+    ) -> bool {
+        let tab_width = self.tab_width();
+        let targets = (rows.start..min(rows.end, self.rows()))
+            .filter_map(|y| {
+                let row = &self.rows[y];
+                let len = row.indent_len()?;
+                let new = indent(row.width_range(0..len, tab_width), tab_width);
+                (row.column()[..len] != new[..]).then_some((y, len, new))
+            })
+            .collect::<Vec<(usize, usize, Vec<char>)>>();
+
+        if targets.is_empty() {
+            return false;
+        }
+
+        self.record_group(|buffer| {
+            for (y, len, new) in targets {
+                buffer.replace(&(0, y), len, &new);
+            }
+        });
+
+        true
+    }
+
+    fn delete_chars_none<P: Coordinates + AsCoordinates>(
+        &mut self,
+        start: &P,
+        end: &P,
+    ) -> Vec<Row> {
+        let mut rs = vec![];
+
+        if start.y() == end.y() {
+            if let Some(row) = self.rows.get_mut(start.y()) {
+                if start.x() < row.len() {
+                    if let Some(text) = row.remove_range(start.x()..end.x()) {
+                        rs.push(Row::from(text));
+                    }
+                }
+            }
+        } else {
+            let mut last = Row::default();
+            for idx in (start.y()..end.y() + 1).rev() {
+                if let Some(row) = self.rows.get_mut(idx) {
+                    if idx == start.y() {
+                        if let Some(text) = row.remove_range(start.x()..row.len()) {
+                            rs.push(Row::from(text));
+                        }
+
+                        row.append(last.column());
+                    } else if idx == end.y() {
+                        if let Some(text) = row.remove_range(0..end.x()) {
+                            rs.push(Row::from(text));
+                        }
+
+                        if let Some(r) = self.delete_row_bypass(&(0, idx)) {
+                            last = r;
+                        }
+                    } else if let Some(r) = self.delete_row_bypass(&(0, idx)) {
+                        rs.push(r);
+                    }
+                }
+            }
+        }
+
+        rs
+    }
+
+    /// Delete the whole rows from the start to the end, and returns them in reverse order.
+    fn delete_chars_line<P: Coordinates + AsCoordinates>(
+        &mut self,
+        start: &P,
+        end: &P,
+    ) -> Vec<Row> {
+        let end = min(end.y() + 1, self.rows());
+        (start.y()..end)
+            .rev()
+            .filter_map(|y| self.delete_row_bypass(&(0, y)))
+            .collect()
+    }
+
+    fn delete_chars_rectangle<P: Coordinates + AsCoordinates>(
+        &mut self,
+        start: &P,
+        end: &P,
+    ) -> Vec<Row> {
+        let mut rs = vec![];
+
+        if let Some(rows) = self.rows.get_mut(start.y()..end.y() + 1) {
+            let startx = min(start.x(), end.x());
+            let endx = max(start.x(), end.x());
+            let length = end.x() - start.x();
+
+            for row in rows.iter_mut().rev() {
+                if startx < row.len() {
+                    let endx = min(row.len(), endx);
+                    let post = length - (endx - startx);
+                    if let Some(mut r) = row.remove_range(startx..endx) {
+                        let spaces = iter::repeat(' ').take(post).collect::<Vec<char>>();
+                        r.extend_from_slice(&spaces);
+                        rs.push(Row::from(r));
+                    }
+                } else {
+                    let chars = iter::repeat(' ').take(length).collect::<Vec<char>>();
+                    rs.push(Row::from(chars));
+                }
+            }
+        }
+
+        rs
+    }
+
+    fn get_range_none(&self, range: Range<&Cursor>) -> Option<Vec<Row>> {
+        if let Some(rows) = self.rows.get(range.start.y()..range.end.y() + 1) {
+            let last_idx = rows.len() - 1;
+            let mut rs = vec![];
+            for (idx, row) in rows.iter().enumerate() {
+                let startx = if idx == 0 { range.start.x() } else { 0 };
+                let endx = if idx == last_idx {
+                    range.end.x()
+                } else {
+                    row.len()
+                };
+                let r = &row.column()[startx..endx];
+                rs.push(Row::from(r));
+            }
+            Some(rs)
+        } else {
+            None
+        }
+    }
+
+    fn get_range_line(&self, range: Range<&Cursor>) -> Option<Vec<Row>> {
+        let end = min(range.end.y() + 1, self.rows());
+        self.rows
+            .get(range.start.y()..end)
+            .filter(|rows| !rows.is_empty())
+            .map(|rows| rows.to_vec())
+    }
+
+    fn get_range_rectangle(&self, range: Range<&Cursor>) -> Option<Vec<Row>> {
+        if let Some(rows) = self.rows.get(range.start.y()..range.end.y() + 1) {
+            let start = min(range.start.x(), range.end.x());
+            let end = max(range.start.x(), range.end.x());
+            let length = end - start;
+
+            let mut rs = vec![];
+            for row in rows {
+                if start < row.len() {
+                    let startx = start;
+                    let endx = min(row.len(), end);
+                    let post = length - (endx - startx);
+                    let mut chars = row.column()[startx..endx].to_vec();
+                    let spaces = iter::repeat(' ').take(post).collect::<Vec<char>>();
+                    chars.extend_from_slice(&spaces);
+                    rs.push(Row::from(chars));
+                } else {
+                    let chars = iter::repeat(' ').take(length).collect::<Vec<char>>();
+                    rs.push(Row::from(chars));
+                }
+            }
+            Some(rs)
+        } else {
+            None
+        }
+    }
+
+    fn insert_chars_none<P: Coordinates + AsCoordinates>(
+        &mut self,
+        at: &P,
+        rows: &[Row],
+    ) -> Option<(usize, usize)> {
+        let mut end = at.as_coordinates();
+        let mut rest = Row::default();
+
+        // first row
+        if let (Some(row), Some(first)) = (self.rows.get_mut(at.y()), rows.first()) {
+            if at.x() <= row.len() {
+                self.cached = true;
+                if 1 < rows.len() {
+                    rest = row.split_off(at.x());
+                    row.append(first.column());
+                } else {
+                    row.insert_slice(at.x(), first.column());
+                }
+                end = (at.x() + first.len(), at.y());
+            }
+        } else {
+            return None;
+        }
+
+        if 1 < rows.len() {
+            // first row + 1 .. last row - 1
+            if let Some(middles) = rows.get(1..rows.len() - 1) {
+                self.cached = true;
+                for (idx, middle) in middles.iter().enumerate() {
+                    let y = at.y() + idx + 1;
+                    self.insert_row_bypass(&(0, y), middle.column());
+                    end = (middle.len(), y);
+                }
+            }
+
+            // last row
+            if let Some(last) = rows.last() {
+                self.cached = true;
+                let y = at.y() + rows.len() - 1;
+                self.insert_row_bypass(&(0, y), last.column());
+                self.append_row_bypass(&(0, y), rest.column());
+                end = (last.len(), y);
+            }
+        }
+
+        Some(end)
+    }
+
+    /// Insert the rows as whole rows above the row, and returns the end of the last one.
+    fn insert_chars_line<P: Coordinates + AsCoordinates>(
+        &mut self,
+        at: &P,
+        rows: &[Row],
+    ) -> Option<(usize, usize)> {
+        if self.rows() < at.y() || rows.is_empty() {
+            return None;
+        }
+
+        for (idx, row) in rows.iter().enumerate() {
+            self.insert_row_bypass(&(0, at.y() + idx), row.column());
+        }
+        rows.last()
+            .map(|last| (last.len(), at.y() + rows.len() - 1))
+    }
+
+    fn insert_chars_rectangle<P: Coordinates + AsCoordinates>(
+        &mut self,
+        at: &P,
+        rows: &[Row],
+    ) -> Option<(usize, usize)> {
+        let mut end = at.as_coordinates();
+
+        for (idx, row) in rows.iter().enumerate() {
+            if let Some(r) = self.rows.get_mut(idx + at.y()) {
+                if r.len() < at.x() {
+                    let space = at.x() - r.len();
+                    let mut chars = iter::repeat(' ').take(space).collect::<Vec<char>>();
+                    chars.extend_from_slice(row.column());
+                    r.append(&chars);
+                } else {
+                    r.insert_slice(at.x(), row.column());
+                }
+            } else {
+                let mut chars = iter::repeat(' ').take(at.x()).collect::<Vec<char>>();
+                chars.extend_from_slice(row.column());
+                self.insert_row_bypass(&(0, idx + at.y()), chars.as_slice());
+            }
+
+            end = (at.x() + row.len(), idx + at.y());
+        }
+
+        Some(end)
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+/// Encoding of the text in the file.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    ShiftJis,
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Encoding::Utf8 => write!(f, "UTF-8"),
+            Encoding::Utf16Le => write!(f, "UTF-16LE"),
+            Encoding::Utf16Be => write!(f, "UTF-16BE"),
+            Encoding::ShiftJis => write!(f, "Shift_JIS"),
+        }
+    }
+}
+
+impl Encoding {
+    /// Returns the encoding of the bytes by BOM or the pattern of NUL, and whether BOM is found.
+    /// Shift_JIS is not told from UTF-8 here, but tried if the bytes are invalid as UTF-8.
+    ///
+    /// ```
+    /// use note::buffer::Encoding;
+    ///
+    /// assert_eq!((Encoding::Utf16Le, true), Encoding::detect(b"\xFF\xFEa\0"));
+    /// assert_eq!((Encoding::Utf16Be, false), Encoding::detect(b"\0a\0b"));
+    /// assert_eq!((Encoding::Utf8, false), Encoding::detect(b"ab"));
+    /// ```
+    pub fn detect(bytes: &[u8]) -> (Encoding, bool) {
+        for encoding in [Encoding::Utf8, Encoding::Utf16Le, Encoding::Utf16Be] {
+            if bytes.starts_with(encoding.bom()) {
+                return (encoding, true);
+            }
+        }
+
+        let head = &bytes[..min(bytes.len(), BINARY_SNIFF_SIZE)];
+        if !is_utf16(head) {
+            return (Encoding::Utf8, false);
+        }
+
+        // The upper byte of ASCII is NUL, which is the second in little endian.
+        match head.iter().step_by(2).any(|&b| b == 0) {
+            true => (Encoding::Utf16Be, false),
+            false => (Encoding::Utf16Le, false),
+        }
+    }
+
+    /// Returns BOM of the encoding, which is empty if the encoding has no BOM.
+    pub fn bom(&self) -> &'static [u8] {
+        match self {
+            Encoding::Utf8 => UTF8_BOM,
+            Encoding::Utf16Le => b"\xFF\xFE",
+            Encoding::Utf16Be => b"\xFE\xFF",
+            Encoding::ShiftJis => b"",
+        }
+    }
+
+    /// Returns the text decoded without BOM, and whether invalid sequences are replaced.
+    pub fn decode(&self, bytes: &[u8]) -> (String, bool) {
+        let (text, replaced) = self.codec().decode_without_bom_handling(bytes);
+        (text.into_owned(), replaced)
+    }
+
+    /// Returns the bytes of the text encoded without BOM,
+    /// or an error if the text has a character not representable in the encoding.
+    pub fn encode<'a>(&self, text: &'a str) -> Result<Cow<'a, [u8]>, Error> {
+        let bytes = match self {
+            Encoding::Utf8 => Cow::Borrowed(text.as_bytes()),
+            // `encoding_rs` writes UTF-8 for UTF-16, so units are written here.
+            Encoding::Utf16Le => text.encode_utf16().flat_map(|u| u.to_le_bytes()).collect(),
+            Encoding::Utf16Be => text.encode_utf16().flat_map(|u| u.to_be_bytes()).collect(),
+            Encoding::ShiftJis => match self.codec().encode(text) {
+                (bytes, _, false) => bytes,
+                (_, _, true) => {
+                    let message = format!("text is not representable in {}", self);
+                    let e = io::Error::new(ErrorKind::InvalidData, message);
+                    return Err(Error::from(e));
+                }
+            },
+        };
+        Ok(bytes)
+    }
+
+    fn codec(&self) -> &'static encoding_rs::Encoding {
+        match self {
+            Encoding::Utf8 => encoding_rs::UTF_8,
+            Encoding::Utf16Le => encoding_rs::UTF_16LE,
+            Encoding::Utf16Be => encoding_rs::UTF_16BE,
+            Encoding::ShiftJis => encoding_rs::SHIFT_JIS,
+        }
+    }
+}
+
+/// Line breaks recognized at loading text.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LineEndingPolicy {
+    /// `\n` and `\r\n`. A lone `\r` is kept in the row.
+    #[default]
+    LineFeed,
+    /// `\n`, `\r\n` and a lone `\r`.
+    Any,
+}
+
+/// Number of each line break in loaded text.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LineEndings {
+    crlf: usize,
+    lf: usize,
+    cr: usize,
+}
+
+impl LineEndings {
+    /// Returns the numbers of `\r\n`, `\n` and `\r`.
+    pub fn counts(&self) -> (usize, usize, usize) {
+        (self.crlf, self.lf, self.cr)
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+#[derive(Clone, Default)]
+pub struct Row {
+    column: Vec<char>,
+}
+
+impl From<Vec<char>> for Row {
+    fn from(value: Vec<char>) -> Self {
+        Row { column: value }
+    }
+}
+
+impl From<&[char]> for Row {
+    fn from(value: &[char]) -> Self {
+        Row {
+            column: value.to_vec(),
+        }
+    }
+}
+
+impl From<String> for Row {
+    fn from(value: String) -> Self {
+        Row {
+            column: value.chars().collect(),
+        }
+    }
+}
+
+impl From<&str> for Row {
+    fn from(value: &str) -> Self {
+        Row {
+            column: value.chars().collect(),
+        }
+    }
+}
+
+impl Row {
+    pub fn append(&mut self, other: &[char]) {
+        self.column.extend_from_slice(other)
+    }
+
+    pub fn clear(&mut self) {
+        self.column.clear();
+    }
+
+    pub fn column(&self) -> &[char] {
+        &self.column
+    }
+
+    /// Returns the count of leading whitespace, or `None` if the row is blank.
+    pub fn indent_len(&self) -> Option<usize> {
+        self.column.iter().position(|ch| !ch.is_whitespace())
+    }
+
+    /// Returns the render width of leading whitespace, or `None` if the row is blank.
+    pub fn indent_width(&self, tab_width: usize) -> Option<usize> {
+        self.indent_len()
+            .map(|len| self.width_range(0..len, tab_width))
+    }
+
+    pub fn insert(&mut self, index: usize, element: char) {
+        if index <= self.column.len() {
+            self.column.insert(index, element);
+        }
+    }
+
+    pub fn insert_slice(&mut self, index: usize, other: &[char]) {
+        if index <= self.column.len() {
+            let removed = self.column.split_off(index);
+            self.column.extend_from_slice(other);
+            self.column.extend(removed);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.column.is_empty()
+    }
+
+    pub fn last_char_width(&self) -> usize {
+        match self.column.last() {
+            Some(&ch) => char_width(ch),
+            _ => 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.column.len()
+    }
+
+    pub fn replace(&mut self, index: usize, length: usize, other: &[char]) -> Option<Vec<char>> {
+        let stop = index + length;
+        if let Some(removed) = self.remove_range(index..stop) {
+            self.insert_slice(index, other);
+            Some(removed)
+        } else {
+            None
+        }
+    }
+
+    pub fn shrink_width(&mut self, min_width: usize, tab_width: usize) -> usize {
+        let mut width = 0;
+
+        for (index, &ch) in self.column.iter().enumerate() {
+            if min_width <= width {
+                self.column.drain(..index);
+                return width;
+            }
+            width += advance(width, ch, tab_width);
+        }
+
+        self.column.clear();
+        width
+    }
+
+    /// Returns the characters in the range clamped to the row.
+    pub fn slice(&self, range: Range<usize>) -> &[char] {
+        let end = min(range.end, self.column.len());
+        let start = min(range.start, end);
+        &self.column[start..end]
+    }
+
+    pub fn slice_width(&self, range: Range<usize>, tab_width: usize) -> Row {
+        let mut render = self.render(tab_width);
+
+        let removed = render.shrink_width(range.start, tab_width);
+        if range.start < removed {
+            for _ in 0..(removed - range.start) {
+                render.insert(0, ' ')
+            }
+        }
+
+        let width = render.truncate_width(range.end - range.start, tab_width);
+        for _ in width..(range.end - range.start) {
+            render.append(&[' '])
+        }
+
+        render
+    }
+
+    pub fn split_off(&mut self, at: usize) -> Row {
+        Row::from(self.column.split_off(at))
+    }
+
+    pub fn to_string_at(&self, at: usize) -> String {
+        self.column.iter().skip(at).collect()
+    }
+
+    pub fn truncate_width(&mut self, max_width: usize, tab_width: usize) -> usize {
+        let mut width = 0;
+
+        for (index, &ch) in self.column.iter().enumerate() {
+            let next = width + advance(width, ch, tab_width);
+            if max_width < next {
+                self.column.truncate(index);
+                break;
+            }
+            width = next;
+        }
+
+        width
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<char> {
+        if index < self.column.len() {
+            Some(self.column.remove(index))
+        } else {
+            None
+        }
+    }
+
+    pub fn remove_range(&mut self, range: Range<usize>) -> Option<Vec<char>> {
+        if range.end <= self.column.len() {
+            Some(self.column.drain(range).collect())
+        } else {
+            None
+        }
+    }
+
+    pub fn rev_at(&self, at: usize) -> Row {
+        let rev = self
+            .column
+            .iter()
+            .take(at)
+            .rev()
+            .cloned()
+            .collect::<Vec<char>>();
+        Row::from(rev)
+    }
+
+    pub fn width(&self, tab_width: usize) -> usize {
+        self.width_range(0..self.column.len(), tab_width)
+    }
+
+    pub fn width_range(&self, range: Range<usize>, tab_width: usize) -> usize {
+        #[cfg(test)]
+        WIDTH_RANGE_CALLS.with(|c| c.set(c.get() + 1));
+
+        let mut render = 0;
+
+        for &ch in &self.column[range] {
+            render += advance(render, ch, tab_width);
+        }
+
+        render
+    }
+
+    /// Returns the widths from start of row to each index in one pass.
+    pub fn widths_at(&self, a: usize, b: usize, tab_width: usize) -> (usize, usize) {
+        let (mut width_a, mut width_b) = (0, 0);
+        let mut render = 0;
+
+        for (index, &ch) in self.column.iter().enumerate().take(max(a, b)) {
+            render += advance(render, ch, tab_width);
+            if index < a {
+                width_a = render;
+            }
+            if index < b {
+                width_b = render;
+            }
+        }
+
+        (width_a, width_b)
+    }
+
+    fn render(&self, tab_width: usize) -> Row {
+        let mut render = Row::default();
+        let mut width = 0;
+
+        for &ch in &self.column {
+            let next = advance(width, ch, tab_width);
+            if ch == '\t' {
+                let spaces = iter::repeat(char::from(b' '))
+                    .take(next)
+                    .collect::<Vec<char>>();
+                render.column.extend_from_slice(&spaces);
+            } else {
+                render.column.push(ch)
+            }
+            width += next;
+        }
+
+        render
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+/// Hashes of rows at a point of time.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Snapshot {
+    hashes: Vec<u64>,
+}
+
+impl Snapshot {
+    fn changed_rows(&self, current: &[u64]) -> Vec<Range<usize>> {
+        let old = &self.hashes;
+
+        let prefix = old.iter().zip(current).take_while(|(a, b)| a == b).count();
+        let suffix = old[prefix..]
+            .iter()
+            .rev()
+            .zip(current[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        let a = &old[prefix..old.len() - suffix];
+        let b = &current[prefix..current.len() - suffix];
+        if a.is_empty() && b.is_empty() {
+            return vec![];
+        }
+
+        // Unchanged rows as the pairs of indexes, with sentinels at both ends.
+        let mut pairs = vec![(prefix, prefix)];
+        if let Some(common) = common_rows(a, b) {
+            pairs.extend(
+                common
+                    .iter()
+                    .map(|&(x, y)| (prefix + x + 1, prefix + y + 1)),
+            );
+        }
+        pairs.push((old.len() - suffix + 1, current.len() - suffix + 1));
+
+        let mut changes: Vec<Range<usize>> = vec![];
+        for pair in pairs.windows(2) {
+            let ((a0, b0), (a1, b1)) = (pair[0], pair[1]);
+            if a0 + 1 == a1 && b0 + 1 == b1 {
+                continue;
+            }
+
+            // Indexes are shifted by the sentinel.
+            let range = b0..b1 - 1;
+            match changes.last_mut() {
+                Some(last) if range.start <= last.end + CHANGE_MERGE_GAP => last.end = range.end,
+                _ => changes.push(range),
+            }
+        }
+        changes
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+/// Returns the pairs of indexes of common rows in order by Myers' difference algorithm,
+/// or `None` if the edit distance is over the limit.
+fn common_rows(a: &[u64], b: &[u64]) -> Option<Vec<(usize, usize)>> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let limit = min(a.len() + b.len(), CHANGE_MAX_EDITS) as isize;
+    let offset = limit + 1;
+    let mut v = vec![0isize; 2 * offset as usize + 1];
+    let mut trace = vec![];
+
+    for d in 0..=limit {
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            let index = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[index - 1] < v[index + 1]) {
+                v[index + 1]
+            } else {
+                v[index - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[index] = x;
+
+            if n <= x && m <= y {
+                return Some(backtrack(&trace, n, m, offset));
+            }
+        }
+    }
+
+    None
+}
+
+fn backtrack(trace: &[Vec<isize>], n: isize, m: isize, offset: isize) -> Vec<(usize, usize)> {
+    let mut common = vec![];
+    let (mut x, mut y) = (n, m);
+
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let index = (k + offset) as usize;
+        let prev_k = if k == -d || (k != d && v[index - 1] < v[index + 1]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = v[(prev_k + offset) as usize];
+        let prev_y = prev_x - prev_k;
+
+        while prev_x < x && prev_y < y {
+            x -= 1;
+            y -= 1;
+            common.push((x as usize, y as usize));
+        }
+
+        if 0 < d {
+            (x, y) = (prev_x, prev_y);
+        }
+    }
+
+    common.reverse();
+    common
+}
+
+/// Returns the rows reflowed to the width with the prefix on every row.
+/// The prefix of the rows is replaced, and the words are separated by a space.
+/// A word longer than the width is not broken.
+///
+/// ```
+/// use note::buffer::{reflow, Row};
+///
+/// let rows = [Row::from("> a  bb"), Row::from("> ccc dd ")];
+/// let reflowed = reflow(&rows, 7, &['>', ' '], 8);
+///
+/// assert_eq!("> a bb", reflowed[0].to_string_at(0));
+/// assert_eq!("> ccc", reflowed[1].to_string_at(0));
+/// assert_eq!("> dd", reflowed[2].to_string_at(0));
+/// ```
+pub fn reflow(rows: &[Row], width: usize, prefix: &[char], tab_width: usize) -> Vec<Row> {
+    let prefix_width = Row::from(prefix).width(tab_width);
+    let mut reflowed = vec![];
+    let mut line = prefix.to_vec();
+    let mut line_width = prefix_width;
+
+    for (_, _, word) in paragraph_words(rows, prefix.len()) {
+        let word_width = Row::from(word).width(tab_width);
+        if prefix.len() < line.len() {
+            if width < line_width + 1 + word_width {
+                reflowed.push(Row::from(mem::replace(&mut line, prefix.to_vec())));
+                line_width = prefix_width;
+            } else {
+                line.push(' ');
+                line_width += 1;
+            }
+        }
+        line.extend_from_slice(word);
+        line_width += word_width;
+    }
+
+    if prefix.len() < line.len() {
+        reflowed.push(Row::from(line));
+    }
+    reflowed
+}
+
+/// Returns the common leading indent and `>` quoting of the rows.
+fn common_prefix(rows: &[Row]) -> Vec<char> {
+    let mut prefix: Option<&[char]> = None;
+    for row in rows {
+        let column = row.column();
+        let lead = column
+            .iter()
+            .take_while(|&&ch| ch == ' ' || ch == '\t' || ch == '>')
+            .count();
+        let lead = &column[..lead];
+        prefix = Some(match prefix {
+            Some(p) => &p[..p.iter().zip(lead).take_while(|(a, b)| a == b).count()],
+            None => lead,
+        });
+    }
+    prefix.unwrap_or_default().to_vec()
+}
+
+/// Returns the words after the prefix of the rows with the positions.
+fn paragraph_words(
+    rows: &[Row],
+    prefix_len: usize,
+) -> impl Iterator<Item = (usize, usize, &[char])> {
+    rows.iter().enumerate().flat_map(move |(y, row)| {
+        let column = row.column();
+        let mut words = vec![];
+        let mut x = min(prefix_len, column.len());
+        while x < column.len() {
+            if column[x].is_whitespace() {
+                x += 1;
+                continue;
+            }
+            let start = x;
+            while x < column.len() && !column[x].is_whitespace() {
+                x += 1;
+            }
+            words.push((start, y, &column[start..x]));
+        }
+        words
+    })
+}
+
+/// Returns the closing character paired with the bracket or the quote.
+///
+/// ```
+/// use note::buffer::closing_pair;
+///
+/// assert_eq!(Some(')'), closing_pair('('));
+/// assert_eq!(Some('"'), closing_pair('"'));
+/// assert_eq!(None, closing_pair(')'));
+/// ```
+pub fn closing_pair(ch: char) -> Option<char> {
+    match BRACKETS.iter().find(|&&(o, _)| o == ch) {
+        Some(&(_, close)) => Some(close),
+        None if QUOTES.contains(&ch) => Some(ch),
+        None => None,
+    }
+}
+
+/// Returns the character in lower case if mapped to a character, otherwise as is.
+/// The position of characters is not changed by folding case with this.
+///
+/// ```
+/// use note::buffer::fold_case;
+///
+/// assert_eq!('a', fold_case('A'));
+/// assert_eq!('ä', fold_case('Ä'));
+/// assert_eq!('İ', fold_case('İ'));
+/// ```
+pub fn fold_case(ch: char) -> char {
+    let mut lower = ch.to_lowercase();
+    match (lower.next(), lower.next()) {
+        (Some(folded), None) => folded,
+        _ => ch,
+    }
+}
+
+/// Returns whether the content is likely binary data.
+/// The content is binary if the head contains NUL (except UTF-16 text)
+/// or many control characters.
+pub fn is_binary(bytes: &[u8]) -> bool {
+    let head = &bytes[..min(bytes.len(), BINARY_SNIFF_SIZE)];
+    if head.is_empty() || is_utf16(head) {
+        return false;
+    }
+
+    if head.contains(&0) {
+        return true;
+    }
+
+    let controls = head
+        .iter()
+        .filter(|&&b| (b < 0x20 && !b"\t\n\r\x0C\x1B".contains(&b)) || b == 0x7F)
+        .count();
+
+    // over 10% are control characters.
+    head.len() < controls * 10
+}
+
+/// Returns the width of the character rendered at the column.
+fn advance(render: usize, ch: char, tab_width: usize) -> usize {
+    if ch == '\t' {
+        tab_width - (render % tab_width)
+    } else {
+        char_width(ch)
+    }
+}
+
+/// Returns the index of the characters where the keyword starts in the row with tabs expanded,
+/// and the count of the characters matched. The index is of the tab if the keyword starts
+/// in the middle of it.
+fn expanded_matches(
+    row: &Row,
+    keyword: &[char],
+    ignore_case: bool,
+    tab_width: usize,
+) -> Vec<(usize, usize)> {
+    let mut text = vec![];
+    let mut origin = vec![];
+
+    let mut render = 0;
+    for (x, &ch) in row.column().iter().enumerate() {
+        let width = advance(render, ch, tab_width);
+        if ch == '\t' {
+            text.extend(iter::repeat(' ').take(width));
+            origin.extend(iter::repeat(x).take(width));
+        } else {
+            text.push(if ignore_case { fold_case(ch) } else { ch });
+            origin.push(x);
+        }
+        render += width;
+    }
+
+    let mut found = text
+        .windows(keyword.len())
+        .enumerate()
+        .filter(|(_, w)| *w == keyword)
+        .map(|(i, _)| (origin[i], origin[i + keyword.len() - 1] + 1 - origin[i]))
+        .collect::<Vec<(usize, usize)>>();
+    found.dedup_by_key(|(x, _)| *x);
+    found
+}
+
+fn fold_text(text: String, ignore_case: bool) -> String {
+    if ignore_case {
+        text.chars().map(fold_case).collect()
+    } else {
+        text
+    }
+}
+
+pub(crate) fn char_width(ch: char) -> usize {
+    ch.width_cjk().unwrap_or(1)
+}
+
+fn is_utf16(head: &[u8]) -> bool {
+    if head.starts_with(&[0xFF, 0xFE]) || head.starts_with(&[0xFE, 0xFF]) {
+        return true;
+    }
+
+    // ASCII text encoded by UTF-16 without BOM has NUL at either even or odd index.
+    let even = head.iter().step_by(2).filter(|&&b| b == 0).count();
+    let odd = head.iter().skip(1).step_by(2).filter(|&&b| b == 0).count();
+    let pairs = head.len() / 2;
+    0 < pairs && ((even == 0 && pairs <= odd * 2) || (odd == 0 && pairs <= even * 2))
+}
+
+/// Returns the row allocated at once for the text.
+fn row_from_str(text: &str) -> Row {
+    let mut column = Vec::with_capacity(text.len());
+    column.extend(text.chars());
+    Row::from(column)
+}
+
+// -----------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use std::cell::RefCell;
+    use std::env;
+    use std::fs;
+    use std::rc::Rc;
+
+    fn init_screen(buf: &mut Buffer) {
+        buf.cached = false;
+        buf.clear_updated();
+        buf.history.clear();
+    }
+
+    fn texts(buf: &Buffer) -> Vec<String> {
+        buf.rows.iter().map(|r| r.to_string_at(0)).collect()
+    }
+
+    #[test]
+    fn buffer_from_string_as_lines() {
+        use std::io::BufRead;
+
+        let fixtures = [
+            "",
+            "a",
+            "a\n",
+            "a\r\n",
+            "\n",
+            "\n\n",
+            "a\nb",
+            "a\r\nb\r\n",
+            "a\n\nb\n",
+            "a\rb\r",
+            "a\r\r\nb",
+            "\u{FEFF}あ\tい\r\n",
+        ];
+
+        for text in fixtures {
+            let lines = text
+                .as_bytes()
+                .lines()
+                .map(|l| l.unwrap())
+                .collect::<Vec<String>>();
+            let buf = Buffer::from_string(text.to_string(), LineEndingPolicy::LineFeed);
+
+            assert_eq!(lines, texts(&buf), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn buffer_from_string_any() {
+        let buf = Buffer::from_string("a\rb\r\n\rc\n".to_string(), LineEndingPolicy::Any);
+
+        assert_eq!(vec!["a", "b", "", "c"], texts(&buf));
+        assert_eq!((1, 1, 2), buf.line_endings().counts());
+    }
+
+    #[test]
+    fn buffer_from_string_line_endings() {
+        let buf = Buffer::from_string("a\r\nb\nc\r".to_string(), LineEndingPolicy::LineFeed);
+
+        assert_eq!(vec!["a", "b", "c\r"], texts(&buf));
+        assert_eq!((1, 1, 0), buf.line_endings().counts());
+    }
+
+    #[test]
+    fn buffer_from_bytes_invalid() {
+        let fixtures: [(&[u8], &[&str], usize); 4] = [
+            (b"a\nb\n", &["a", "b"], 0),
+            (b"a\n\xe3\x81b\nc\n", &["a", "\u{FFFD}b", "c"], 1),
+            (b"a\xff\n\xfe\r\nc", &["a\u{FFFD}", "\u{FFFD}", "c"], 2),
+            (b"a\nb\xe3\x81", &["a", "b\u{FFFD}"], 1),
+        ];
+        for (bytes, rows, invalid) in fixtures {
+            let buf = Buffer::from_bytes(bytes.to_vec(), LineEndingPolicy::LineFeed);
+
+            assert_eq!(rows, texts(&buf), "{:?}", bytes);
+            assert_eq!(invalid, buf.invalid_lines(), "{:?}", bytes);
+        }
+    }
+
+    #[test]
+    fn buffer_try_from_invalid_utf8() {
+        let path = env::temp_dir().join("note_buffer_try_from_invalid_utf8.txt");
+        fs::write(&path, b"a\n\xffb\n").unwrap();
+
+        let buf = Buffer::try_from(Some(path.as_path())).unwrap();
+
+        assert_eq!(2, buf.rows());
+        assert_eq!(1, buf.invalid_lines());
+        assert!(buf.read_only());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn buffer_from_rows() {
+        let buf = Buffer::from_rows(vec![Row::from("a"), Row::from("b")]);
+
+        assert_eq!(vec!["a", "b"], texts(&buf));
+        assert!(!buf.cached());
+    }
+
+    // Run with `cargo test --release -- --ignored --nocapture`.
+    // 40 MB loads about 1.5 times faster than splitting by `BufRead::lines`.
+    #[test]
+    #[ignore]
+    fn buffer_from_string_bench() {
+        use std::io::BufRead;
+        use std::time::Instant;
+
+        let text = "0123456789abcdefghijklmnopqrstuvwxyzあいうえお\r\n".repeat(800_000);
+
+        let start = Instant::now();
+        let rows = text
+            .as_bytes()
+            .lines()
+            .map(|l| Row::from(l.unwrap()))
+            .collect::<Vec<Row>>();
+        let lines = start.elapsed();
+
+        let start = Instant::now();
+        let buf = Buffer::from_string(text, LineEndingPolicy::LineFeed);
+        let from_string = start.elapsed();
+
+        assert_eq!(rows.len(), buf.rows());
+        println!("lines: {:?}, from_string: {:?}", lines, from_string);
+    }
+
+    #[test]
+    fn buffer_try_from_none() {
+        let buf = Buffer::try_from(None).unwrap();
+
+        assert_eq!(0, buf.rows());
+        assert!(buf.filename.is_none());
+        assert!(!buf.cached());
+    }
+
+    #[test]
+    fn buffer_try_from_notfound() {
+        let path = PathBuf::from("notfound.txt");
+
+        let buf = Buffer::try_from(Some(path.as_path())).unwrap();
+
+        assert_eq!(0, buf.rows());
+        assert_eq!(Some(path), buf.filename);
+        assert!(!buf.cached());
+    }
+
+    #[test]
+    fn buffer_try_from_error() {
+        let path = env::temp_dir();
+
+        let ret = Buffer::try_from(Some(path.as_path()));
+
+        assert!(ret.is_err());
+    }
+
+    #[test]
+    fn buffer_try_from_binary() {
+        let path = env::temp_dir().join("note_buffer_try_from_binary.bin");
+        fs::write(&path, b"a\0\0b\nc\x01d\n").unwrap();
+
+        let buf = Buffer::try_from(Some(path.as_path())).unwrap();
+
+        assert_eq!(2, buf.rows());
+        assert_eq!(&['a', '.', '.', 'b'], buf.rows[0].column());
+        assert_eq!(&['c', '.', 'd'], buf.rows[1].column());
+        assert!(buf.binary());
+        assert!(buf.read_only());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    fn observed(buf: &mut Buffer) -> Rc<RefCell<Vec<Range<usize>>>> {
+        let ranges = Rc::new(RefCell::new(vec![]));
+        let observed = ranges.clone();
+        buf.add_observer(move |rows| observed.borrow_mut().push(rows));
+        ranges
+    }
+
+    #[test]
+    fn buffer_add_observer_insert() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a']);
+        let ranges = observed(&mut buf);
+
+        buf.insert_char(&(1, 0), 'b');
+        buf.insert_row(&(0, 1), &['c']);
+
+        assert_eq!(vec![0..1, 1..2], *ranges.borrow());
+    }
+
+    #[test]
+    fn buffer_add_observer_delete() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'b']);
+        buf.insert_row(&(0, 1), &['c']);
+        buf.insert_row(&(0, 2), &['d']);
+        let ranges = observed(&mut buf);
+
+        buf.delete_char(&(1, 1));
+        buf.delete_row(&(0, 0));
+        buf.undo();
+
+        assert_eq!(vec![1..2, 0..3, 0..3], *ranges.borrow());
+    }
+
+    #[test]
+    fn buffer_append_row() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a']);
+        init_screen(&mut buf);
+
+        buf.append_row(&(0, 0), &['b']);
+
+        assert_eq!(&['a', 'b'], buf.rows[0].column());
+        assert!(buf.cached());
+        assert!(buf.updated());
+        assert_eq!(1, buf.history.len());
+    }
+
+    #[test]
+    fn buffer_append_row_yoverflow() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a']);
+        init_screen(&mut buf);
+
+        buf.append_row(&(0, 1), &['b']);
+
+        assert_eq!(&['a'], buf.rows[0].column());
+        assert!(!buf.cached());
+        assert!(!buf.updated());
+        assert_eq!(0, buf.history.len());
+    }
+
+    #[test]
+    fn buffer_copy_pending_none() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a']);
+        init_screen(&mut buf);
+
+        let s = Cursor::from((0, 0));
+        let e = Cursor::from((1, 0));
+        buf.copy_pending(&s..&e, SelectMode::None);
+
+        assert_eq!(&['a'], buf.pending.unwrap().0.first().unwrap().column());
+    }
+
+    #[test]
+    fn buffer_copy_pending_rectangle() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'b']);
+        buf.insert_row(&(0, 1), &['c', 'd']);
+        init_screen(&mut buf);
+
+        let s = Cursor::from((0, 0));
+        let e = Cursor::from((1, 1));
+        buf.copy_pending(&s..&e, SelectMode::Rectangle);
+
+        assert_eq!(&['a'], buf.pending.as_ref().unwrap().0[0].column());
+        assert_eq!(&['c'], buf.pending.as_ref().unwrap().0[1].column());
+    }
+
+    #[test]
+    fn buffer_pending_padding() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'b', 'c', 'd']);
+        buf.insert_row(&(0, 1), &['e']);
+        init_screen(&mut buf);
+
+        let s = Cursor::from((0, 0));
+        let e = Cursor::from((1, 1));
+        buf.copy_pending(&s..&e, SelectMode::Rectangle);
+
+        assert_eq!(None, buf.pending_padding(&(1, 0)));
+        assert_eq!(Some((1, 2)), buf.pending_padding(&(3, 0)));
+        assert_eq!(Some((2, 3)), buf.pending_padding(&(3, 1)));
+    }
+
+    #[test]
+    fn buffer_pending_padding_none_mode() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'b']);
+        init_screen(&mut buf);
+
+        let s = Cursor::from((0, 0));
+        let e = Cursor::from((1, 0));
+        buf.copy_pending(&s..&e, SelectMode::None);
+
+        assert_eq!(None, buf.pending_padding(&(5, 0)));
+    }
+
+    #[test]
+    fn buffer_copy_pending_yoverflow() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a']);
+        init_screen(&mut buf);
+
+        let s = Cursor::from((0, 1));
+        let e = Cursor::from((1, 1));
+        buf.copy_pending(&s..&e, SelectMode::None);
+
+        assert!(buf.pending.is_none());
+    }
+
+    #[test]
+    fn buffer_delete_row() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a']);
+        init_screen(&mut buf);
+
+        buf.delete_row(&(0, 0));
+
+        assert_eq!(0, buf.rows());
+        assert!(buf.cached());
+        assert!(buf.updated());
+        assert_eq!(1, buf.history.len());
+    }
+
+    #[test]
+    fn buffer_delete_row_yoverflow() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a']);
+        init_screen(&mut buf);
+
+        buf.delete_row(&(0, 1));
+
+        assert_eq!(1, buf.rows());
+        assert!(!buf.cached());
+        assert!(!buf.updated());
+        assert_eq!(0, buf.history.len());
+    }
+
+    #[test]
+    fn buffer_delete_char() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'b']);
+        init_screen(&mut buf);
+
+        buf.delete_char(&(1, 0));
+
+        assert_eq!(&['b'], buf.rows[0].column());
+        assert!(buf.cached());
+        assert!(buf.updated());
+        assert_eq!(1, buf.history.len());
+    }
+
+    #[test]
+    fn buffer_delete_char_xoverflow() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'b']);
+        init_screen(&mut buf);
+
+        buf.delete_char(&(3, 0));
+
+        assert_eq!(&['a', 'b'], buf.rows[0].column());
+        assert!(!buf.cached());
+        assert!(!buf.updated());
+        assert_eq!(0, buf.history.len());
+    }
+
+    #[test]
+    fn buffer_delete_char_yoverflow() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'b']);
+        init_screen(&mut buf);
+
+        buf.delete_char(&(1, 1));
+
+        assert_eq!(&['a', 'b'], buf.rows[0].column());
+        assert!(!buf.cached());
+        assert!(!buf.updated());
+        assert_eq!(0, buf.history.len());
+    }
+
+    #[test]
+    fn buffer_delete_chars_1row() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'b']);
+        init_screen(&mut buf);
+
+        buf.delete_chars(&(1, 0), &(2, 0), SelectMode::None);
+
+        assert_eq!(1, buf.rows.len());
+        assert_eq!(&['a'], buf.rows[0].column());
+        assert!(buf.cached());
+        assert!(buf.updated());
+        assert_eq!(1, buf.history.len());
+    }
+
+    #[test]
+    fn buffer_delete_chars_2row() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'b']);
+        buf.insert_row(&(0, 1), &['c', 'd']);
+        init_screen(&mut buf);
+
+        buf.delete_chars(&(1, 0), &(1, 1), SelectMode::None);
+
+        assert_eq!(1, buf.rows.len());
+        assert_eq!(&['a', 'd'], buf.rows[0].column());
+        assert!(buf.cached());
+        assert!(buf.updated());
+        assert_eq!(1, buf.history.len());
+    }
+
+    #[test]
+    fn buffer_delete_chars_2row_start() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'b']);
+        buf.insert_row(&(0, 1), &['c', 'd']);
+        init_screen(&mut buf);
+
+        buf.delete_chars(&(0, 0), &(0, 1), SelectMode::None);
+
+        assert_eq!(1, buf.rows.len());
+        assert_eq!(&['c', 'd'], buf.rows[0].column());
+        assert!(buf.cached());
+        assert!(buf.updated());
+        assert_eq!(1, buf.history.len());
+    }
+
+    #[test]
+    fn buffer_delete_chars_2row_end() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'b']);
+        buf.insert_row(&(0, 1), &['c', 'd']);
+        init_screen(&mut buf);
+
+        buf.delete_chars(&(2, 0), &(2, 1), SelectMode::None);
+
+        assert_eq!(1, buf.rows.len());
+        assert_eq!(&['a', 'b'], buf.rows[0].column());
+        assert!(buf.cached());
+        assert!(buf.updated());
+        assert_eq!(1, buf.history.len());
+    }
+
+    #[test]
+    fn buffer_delete_chars_2row_all() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'b']);
+        buf.insert_row(&(0, 1), &['c', 'd']);
+        init_screen(&mut buf);
+
+        buf.delete_chars(&(0, 0), &(2, 1), SelectMode::None);
+
+        assert_eq!(1, buf.rows.len());
+        assert!(buf.rows[0].is_empty());
+        assert!(buf.cached());
+        assert!(buf.updated());
+        assert_eq!(1, buf.history.len());
+    }
+
+    #[test]
+    fn buffer_delete_chars_3row_none() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'b']);
+        buf.insert_row(&(0, 1), &['c', 'd']);
+        buf.insert_row(&(0, 2), &['e', 'f']);
+        init_screen(&mut buf);
+
+        buf.delete_chars(&(1, 0), &(1, 2), SelectMode::None);
+
+        assert_eq!(1, buf.rows.len());
+        assert_eq!(&['a', 'f'], buf.rows[0].column());
+        assert!(buf.cached());
+        assert!(buf.updated());
+        assert_eq!(1, buf.history.len());
+    }
+
+    #[test]
+    fn buffer_delete_chars_3row_rectangle() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'b']);
+        buf.insert_row(&(0, 1), &['c', 'd']);
+        buf.insert_row(&(0, 2), &['e', 'f']);
+        init_screen(&mut buf);
+
+        buf.delete_chars(&(1, 0), &(2, 2), SelectMode::Rectangle);
+
+        assert_eq!(3, buf.rows.len());
+        assert_eq!(&['a'], buf.rows[0].column());
+        assert_eq!(&['c'], buf.rows[1].column());
+        assert_eq!(&['e'], buf.rows[2].column());
+        assert!(buf.cached());
+        assert!(buf.updated());
+        assert_eq!(1, buf.history.len());
+    }
+
+    #[test]
+    fn buffer_delete_chars_xoverflow() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'b']);
+        init_screen(&mut buf);
+
+        buf.delete_chars(&(3, 0), &(4, 0), SelectMode::None);
+
+        assert_eq!(1, buf.rows.len());
+        assert_eq!(&['a', 'b'], buf.rows[0].column());
+        assert!(!buf.cached());
+        assert!(!buf.updated());
+        assert_eq!(0, buf.history.len());
+    }
+
+    #[test]
+    fn buffer_delete_chars_yoverflow() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'b']);
+        init_screen(&mut buf);
+
+        buf.delete_chars(&(1, 1), &(2, 1), SelectMode::None);
+
+        assert_eq!(1, buf.rows.len());
+        assert_eq!(&['a', 'b'], buf.rows[0].column());
+        assert!(!buf.cached());
+        assert!(!buf.updated());
+        assert_eq!(0, buf.history.len());
+    }
+
+    #[test]
+    fn buffer_find_at_0() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'b', 'c']);
+        buf.insert_row(&(0, 1), &['a', 'b', 'c']);
+        buf.insert_row(&(0, 2), &['a', 'b', 'c']);
+        init_screen(&mut buf);
+
+        let at = buf.find_at(&(0, 0), "bc");
+
+        assert_eq!(Some((1, 0)), at);
+    }
+
+    #[test]
+    fn buffer_find_at_1() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'b', 'c']);
+        buf.insert_row(&(0, 1), &['a', 'b', 'c']);
+        buf.insert_row(&(0, 2), &['a', 'b', 'c']);
+        init_screen(&mut buf);
+
+        let at = buf.find_at(&(2, 1), "bc");
+
+        assert_eq!(Some((1, 2)), at);
+    }
+
+    fn changed(before: &[&str], after: &[&str]) -> Vec<Range<usize>> {
+        let snapshot = text_buffer(before).snapshot();
+        text_buffer(after).changed_rows(&snapshot)
+    }
+
+    #[test]
+    fn buffer_changed_rows_edit() {
+        assert_eq!(
+            Vec::<Range<usize>>::new(),
+            changed(&["a", "b"], &["a", "b"])
+        );
+        assert_eq!(vec![1..2], changed(&["a", "b", "c"], &["a", "x", "c"]));
+        assert_eq!(
+            vec![0..1, 4..5],
+            changed(&["a", "b", "c", "d", "e"], &["x", "b", "c", "d", "y"])
+        );
+    }
+
+    #[test]
+    fn buffer_changed_rows_insert() {
+        assert_eq!(vec![1..3], changed(&["a", "b"], &["a", "x", "y", "b"]));
+        assert_eq!(vec![2..3], changed(&["a", "b"], &["a", "b", "x"]));
+        assert_eq!(vec![0..2], changed(&[], &["a", "b"]));
+    }
+
+    #[test]
+    fn buffer_changed_rows_delete() {
+        assert_eq!(vec![1..1], changed(&["a", "b", "c"], &["a", "c"]));
+        assert_eq!(vec![0..0], changed(&["a", "b"], &["b"]));
+        assert_eq!(vec![1..1], changed(&["a", "b"], &["a"]));
+        assert_eq!(vec![0..0], changed(&["a"], &[]));
+    }
+
+    #[test]
+    fn buffer_changed_rows_merge() {
+        let before = ["a", "b", "c", "d", "e", "f"];
+
+        // one unchanged row between changes is merged.
+        assert_eq!(
+            vec![1..4],
+            changed(&before, &["a", "x", "c", "y", "e", "f"])
+        );
+        assert_eq!(
+            vec![0..1, 3..5],
+            changed(&before, &["x", "b", "c", "e", "y", "f"])
+        );
+    }
+
+    #[test]
+    fn buffer_changed_rows_many() {
+        let before = (0..2000).map(|i| i.to_string()).collect::<Vec<String>>();
+        let mut after = before.clone();
+        for i in (0..2000).step_by(3) {
+            after[i] = "x".to_string();
+        }
+        after.remove(1000);
+
+        let before = before.iter().map(|r| r.as_str()).collect::<Vec<&str>>();
+        let after = after.iter().map(|r| r.as_str()).collect::<Vec<&str>>();
+
+        // over the edit limit, all rows between the first and the last change.
+        assert_eq!(vec![0..1998], changed(&before, &after));
+    }
+
+    fn reflowed(rows: &[&str], width: usize, prefix: &str) -> Vec<String> {
+        let rows = rows.iter().map(|&r| Row::from(r)).collect::<Vec<Row>>();
+        let prefix = prefix.chars().collect::<Vec<char>>();
+        reflow(&rows, width, &prefix, TAB_STOP)
+            .iter()
+            .map(|r| r.to_string_at(0))
+            .collect()
+    }
+
+    #[test]
+    fn reflow_join_and_split() {
+        assert_eq!(
+            vec!["aa bb cc", "dd"],
+            reflowed(&["aa", "bb  cc ", " dd  "], 8, "")
+        );
+        assert_eq!(vec!["aaaaaaaaaa", "b"], reflowed(&["aaaaaaaaaa b"], 4, ""));
+        assert_eq!(Vec::<String>::new(), reflowed(&["  "], 4, ""));
+    }
+
+    #[test]
+    fn reflow_wide() {
+        assert_eq!(vec!["あい a", "うえ"], reflowed(&["あい a うえ"], 6, ""));
+    }
+
+    #[test]
+    fn reflow_prefix() {
+        assert_eq!(
+            vec!["  > a b", "  > c"],
+            reflowed(&["  > a", "  > b c"], 7, "  > ")
+        );
+    }
+
+    #[test]
+    fn buffer_reflow_paragraph() {
+        let mut buf = text_buffer(&["> aa bb", "> cc", "", "> dd"]);
+        init_screen(&mut buf);
+
+        let at = buf.reflow_paragraph(&(3, 1), 20);
+
+        assert_eq!(Some((9, 0)), at);
+        assert_eq!(vec!["> aa bb cc", "", "> dd"], texts(&buf));
+        assert_eq!(None, buf.reflow_paragraph(&(0, 0), 20));
+        assert_eq!(None, buf.reflow_paragraph(&(0, 1), 20));
+    }
+
+    #[test]
+    fn buffer_find_at_wide() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &"あい bc bc".chars().collect::<Vec<char>>());
+
+        assert_eq!(Some((3, 0)), buf.find_at(&(0, 0), "bc"));
+        assert_eq!(Some((6, 0)), buf.find_at(&(4, 0), "bc"));
+        assert_eq!(Some((6, 0)), buf.rfind_at(&(7, 0), "bc"));
+        assert_eq!(Some((3, 0)), buf.rfind_at(&(5, 0), "bc"));
+        assert_eq!(Some((1, 0)), buf.rfind_at(&(7, 0), "い"));
+    }
+
+    #[test]
+    fn buffer_find_at_notfound() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'b', 'c']);
+        buf.insert_row(&(0, 1), &['a', 'b', 'c']);
+        buf.insert_row(&(0, 2), &['a', 'b', 'c']);
+        init_screen(&mut buf);
+
+        let at = buf.find_at(&(2, 2), "bc");
+
+        assert_eq!(None, at);
+    }
+
+    #[test]
+    fn buffer_find_at_icase() {
+        let buf = text_buffer(&["error", "ÄB Error", "ERROR"]);
+
+        assert_eq!(Some((0, 0)), buf.find_at_icase(&(0, 0), "ERROR"));
+        assert_eq!(Some((3, 1)), buf.find_at_icase(&(1, 0), "eRRor"));
+        assert_eq!(Some((0, 1)), buf.find_at_icase(&(1, 0), "äb"));
+        assert_eq!(None, buf.find_at(&(1, 0), "ERRor"));
+        assert_eq!(Some((0, 2)), buf.rfind_at_icase(&(4, 2), "error"));
+        assert_eq!(Some((3, 1)), buf.rfind_at_icase(&(0, 2), "error"));
+        assert_eq!(Some((0, 1)), buf.rfind_at_icase(&(2, 1), "Äb"));
+    }
+
+    #[test]
+    fn buffer_find_at_icase_index() {
+        let buf = text_buffer(&["xあABC", "abc"]);
+
+        assert_eq!(Some((2, 0)), buf.find_at_icase(&(0, 0), "abc"));
+        assert_eq!(Some((0, 1)), buf.find_at(&(0, 0), "abc"));
+        assert_eq!(Some((2, 0)), buf.rfind_at_icase(&(0, 1), "abc"));
+        assert_eq!(None, buf.rfind_at(&(0, 1), "abc"));
+    }
+
+    #[test]
+    fn buffer_find_expanded_at() {
+        let buf = text_buffer(&["name\tvalue", "id      value", "\tVALUE", "あ\tvalue"]);
+
+        assert_eq!(
+            Some(((4, 0), 6)),
+            buf.find_expanded_at(&(0, 0), "    value", false)
+        );
+        assert_eq!(
+            Some(((4, 1), 9)),
+            buf.find_expanded_at(&(5, 0), "    value", false)
+        );
+        assert_eq!(
+            Some(((1, 3), 6)),
+            buf.find_expanded_at(&(5, 1), "    value", false)
+        );
+        assert_eq!(
+            Some(((0, 2), 6)),
+            buf.find_expanded_at(&(5, 1), "    value", true)
+        );
+        assert_eq!(
+            Some(((0, 3), 3)),
+            buf.find_expanded_at(&(0, 3), "あ      v", false)
+        );
+        assert_eq!(None, buf.find_expanded_at(&(0, 0), "\tvalue", false));
+        assert_eq!(None, buf.find_expanded_at(&(0, 0), "", false));
+    }
+
+    #[test]
+    fn buffer_rfind_expanded_at() {
+        let buf = text_buffer(&["name\tvalue", "id      value", "\tvalue"]);
+
+        assert_eq!(
+            Some(((0, 2), 6)),
+            buf.rfind_expanded_at(&(0, 3), "    value", false)
+        );
+        assert_eq!(
+            Some(((0, 2), 6)),
+            buf.rfind_expanded_at(&(3, 2), "    value", false)
+        );
+        assert_eq!(
+            Some(((4, 0), 6)),
+            buf.rfind_expanded_at(&(3, 1), "    value", false)
+        );
+        assert_eq!(None, buf.rfind_expanded_at(&(3, 0), "    value", false));
+    }
+
+    #[test]
+    fn buffer_find_regex_at_wide() {
+        let buf = text_buffer(&["あいう", "xいいy"]);
+        let regex = Regex::new("い+").unwrap();
+
+        assert_eq!(Some(((1, 0), 1)), buf.find_regex_at(&(0, 0), &regex));
+        assert_eq!(Some(((1, 1), 2)), buf.find_regex_at(&(2, 0), &regex));
+    }
+
+    #[test]
+    fn buffer_find_regex_at_anchor() {
+        let buf = text_buffer(&["abab", "ab"]);
+        let regex = Regex::new("^ab").unwrap();
+
+        assert_eq!(Some(((0, 1), 2)), buf.find_regex_at(&(1, 0), &regex));
+    }
+
+    #[test]
+    fn buffer_find_regex_at_empty_match() {
+        let buf = text_buffer(&["xx", "xa"]);
+        let regex = Regex::new("a*").unwrap();
+
+        assert_eq!(Some(((1, 1), 1)), buf.find_regex_at(&(0, 0), &regex));
+    }
+
+    #[test]
+    fn buffer_get() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a']);
+        init_screen(&mut buf);
+
+        let row = buf.get(0);
+
+        assert!(row.is_some());
+    }
+
+    #[test]
+    fn buffer_get_notfound() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a']);
+        init_screen(&mut buf);
+
+        let row = buf.get(1);
+
+        assert!(row.is_none());
+    }
+
+    #[test]
+    fn buffer_get_range_1row() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'b']);
+        init_screen(&mut buf);
+
+        let start = Cursor::from((1, 0));
+        let end = Cursor::from((2, 0));
+        let rows = buf.get_range(&start..&end, SelectMode::None);
+
+        let rows = rows.unwrap();
+        assert_eq!(1, rows.len());
+        assert_eq!(&['b'], rows[0].column());
+    }
+
+    #[test]
+    fn buffer_get_range_3row() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'b']);
+        buf.insert_row(&(0, 1), &['c', 'd']);
+        buf.insert_row(&(0, 2), &['e', 'f']);
+        init_screen(&mut buf);
+
+        let start = Cursor::from((1, 0));
+        let end = Cursor::from((1, 2));
+        let rows = buf.get_range(&start..&end, SelectMode::None);
+
+        let rows = rows.unwrap();
+        assert_eq!(3, rows.len());
+        assert_eq!(&['b'], rows[0].column());
+        assert_eq!(&['c', 'd'], rows[1].column());
+        assert_eq!(&['e'], rows[2].column());
+    }
+
+    #[test]
+    fn buffer_insert_row_0() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a']);
+        init_screen(&mut buf);
+
+        buf.insert_row(&(0, 0), &['b']);
+
+        assert_eq!(&['b'], buf.rows[0].column());
+        assert!(buf.cached());
+        assert!(buf.updated());
+        assert_eq!(1, buf.history.len());
+    }
+
+    #[test]
+    fn buffer_insert_row_1() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a']);
+        init_screen(&mut buf);
+
+        buf.insert_row(&(0, 1), &['b']);
+
+        assert_eq!(&['b'], buf.rows[1].column());
+        assert!(buf.cached());
+        assert!(buf.updated());
+        assert_eq!(1, buf.history.len());
+    }
+
+    #[test]
+    fn buffer_swap_rows() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a']);
+        buf.insert_row(&(0, 1), &['b']);
+        buf.insert_row(&(0, 2), &['c']);
+        init_screen(&mut buf);
+
+        assert!(buf.swap_rows(&(0, 1), &(0, 2)));
+
+        assert_eq!(&['a'], buf.rows[0].column());
+        assert_eq!(&['c'], buf.rows[1].column());
+        assert_eq!(&['b'], buf.rows[2].column());
+        assert!(buf.cached());
+        assert!(buf.updated());
+        assert_eq!(1, buf.history.len());
+    }
+
+    #[test]
+    fn buffer_swap_rows_out_of_buffer() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a']);
+        buf.insert_row(&(0, 1), &['b']);
+        init_screen(&mut buf);
+
+        assert!(!buf.swap_rows(&(0, 1), &(0, 2)));
+        assert!(!buf.swap_rows(&(0, 1), &(0, 1)));
+
+        assert_eq!(&['b'], buf.rows[1].column());
+        assert!(!buf.updated());
+        assert_eq!(0, buf.history.len());
+    }
+
+    #[test]
+    fn buffer_undo_swap_rows() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a']);
+        buf.insert_row(&(0, 1), &['b']);
+        buf.swap_rows(&(1, 1), &(1, 0));
+
+        assert_eq!(Some((1, 1)), buf.undo());
+        assert_eq!(&['a'], buf.rows[0].column());
+        assert_eq!(&['b'], buf.rows[1].column());
+
+        buf.redo();
+        assert_eq!(&['b'], buf.rows[0].column());
+        assert_eq!(&['a'], buf.rows[1].column());
+    }
+
+    #[test]
+    fn buffer_mark_insert_delete_row() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a']);
+        buf.insert_row(&(0, 1), &['b', 'c']);
+        buf.insert_row(&(0, 2), &['d']);
+        buf.set_mark(1, &(1, 1));
+        buf.set_mark(2, &(0, 2));
+        buf.set_mark(MARKS, &(0, 0));
+
+        buf.insert_row(&(0, 1), &['e']);
+        assert_eq!(Some((1, 2)), buf.mark(1));
+        assert_eq!(Some((0, 3)), buf.mark(2));
+
+        buf.delete_row(&(0, 2));
+        assert_eq!(None, buf.mark(1));
+        assert_eq!(Some((0, 2)), buf.mark(2));
+
+        buf.undo();
+        assert_eq!(None, buf.mark(1));
+        assert_eq!(None, buf.mark(0));
+        assert_eq!(None, buf.mark(MARKS));
+    }
+
+    #[test]
+    fn buffer_mark_split_squash_row() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a']);
+        buf.insert_row(&(0, 1), &['b', 'c', 'd']);
+        buf.set_mark(0, &(0, 1));
+        buf.set_mark(1, &(2, 1));
+
+        buf.split_row(&(1, 1));
+        assert_eq!(Some((0, 1)), buf.mark(0));
+        assert_eq!(Some((1, 2)), buf.mark(1));
+
+        buf.squash_row(&(0, 1));
+        assert_eq!(Some((1, 0)), buf.mark(0));
+        assert_eq!(Some((1, 1)), buf.mark(1));
+    }
+
+    #[test]
+    fn buffer_insert_char_0() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a']);
+        init_screen(&mut buf);
+
+        buf.insert_char(&(0, 0), 'b');
+
+        assert_eq!(&['b', 'a'], buf.rows[0].column());
+        assert!(buf.cached());
+        assert!(buf.updated());
+        assert_eq!(1, buf.history.len());
+    }
+
+    #[test]
+    fn buffer_insert_char_1() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a']);
+        init_screen(&mut buf);
+
+        buf.insert_char(&(1, 0), 'b');
+
+        assert_eq!(&['a', 'b'], buf.rows[0].column());
+        assert!(buf.cached());
+        assert!(buf.updated());
+        assert_eq!(1, buf.history.len());
+    }
+
+    #[test]
+    fn buffer_insert_char_xoverflow() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a']);
+        init_screen(&mut buf);
+
+        buf.insert_char(&(2, 0), 'b');
+
+        assert_eq!(&['a'], buf.rows[0].column());
+        assert!(!buf.cached());
+        assert!(!buf.updated());
+        assert_eq!(0, buf.history.len());
+    }
+
+    #[test]
+    fn buffer_insert_chars_1row_0() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a']);
+        init_screen(&mut buf);
+
+        buf.insert_chars(&(0, 0), &[Row::from("bc")], SelectMode::None);
+
+        assert_eq!(1, buf.rows.len());
+        assert_eq!(&['b', 'c', 'a'], buf.rows[0].column());
+        assert!(buf.cached());
+        assert!(buf.updated());
+        assert_eq!(1, buf.history.len());
+    }
+
+    #[test]
+    fn buffer_insert_chars_1row_1() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a']);
+        init_screen(&mut buf);
+
+        buf.insert_chars(&(1, 0), &[Row::from("bc")], SelectMode::None);
+
+        assert_eq!(1, buf.rows.len());
+        assert_eq!(&['a', 'b', 'c'], buf.rows[0].column());
+        assert!(buf.cached());
+        assert!(buf.updated());
+        assert_eq!(1, buf.history.len());
+    }
+
+    #[test]
+    fn buffer_insert_chars_2row_start() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a']);
+        init_screen(&mut buf);
+
+        buf.insert_chars(&(0, 0), &[Row::from("b"), Row::from("c")], SelectMode::None);
+
+        assert_eq!(2, buf.rows.len());
+        assert_eq!(&['b'], buf.rows[0].column());
+        assert_eq!(&['c', 'a'], buf.rows[1].column());
+        assert!(buf.cached());
+        assert!(buf.updated());
+        assert_eq!(1, buf.history.len());
+    }
+
+    #[test]
+    fn buffer_insert_chars_2row_start_empty() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a']);
+        init_screen(&mut buf);
+
+        buf.insert_chars(&(0, 0), &[Row::from(""), Row::from("c")], SelectMode::None);
+
+        assert_eq!(2, buf.rows.len());
+        assert!(buf.rows[0].is_empty());
+        assert_eq!(&['c', 'a'], buf.rows[1].column());
+        assert!(buf.cached());
+        assert!(buf.updated());
+        assert_eq!(1, buf.history.len());
+    }
+
+    #[test]
+    fn buffer_insert_chars_2row_end() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a']);
+        init_screen(&mut buf);
+
+        buf.insert_chars(&(1, 0), &[Row::from("b"), Row::from("c")], SelectMode::None);
+
+        assert_eq!(2, buf.rows.len());
+        assert_eq!(&['a', 'b'], buf.rows[0].column());
+        assert_eq!(&['c'], buf.rows[1].column());
+        assert!(buf.cached());
+        assert!(buf.updated());
+        assert_eq!(1, buf.history.len());
+    }
+
+    #[test]
+    fn buffer_insert_chars_2row_end_empty() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a']);
+        init_screen(&mut buf);
+
+        buf.insert_chars(&(1, 0), &[Row::from("b"), Row::from("")], SelectMode::None);
+
+        assert_eq!(2, buf.rows.len());
+        assert_eq!(&['a', 'b'], buf.rows[0].column());
+        assert!(buf.rows[1].is_empty());
+        assert!(buf.cached());
+        assert!(buf.updated());
+        assert_eq!(1, buf.history.len());
+    }
+
+    #[test]
+    fn buffer_insert_chars_3row_1() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'b']);
+        init_screen(&mut buf);
+
+        buf.insert_chars(
+            &(1, 0),
+            &[Row::from("c"), Row::from("d"), Row::from("e")],
+            SelectMode::None,
+        );
+
+        assert_eq!(3, buf.rows.len());
+        assert_eq!(&['a', 'c'], buf.rows[0].column());
+        assert_eq!(&['d'], buf.rows[1].column());
+        assert_eq!(&['e', 'b'], buf.rows[2].column());
+        assert!(buf.cached());
+        assert!(buf.updated());
+        assert_eq!(1, buf.history.len());
+    }
+
+    #[test]
+    fn buffer_insert_chars_3row_rectangle() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'b']);
+        init_screen(&mut buf);
+
+        buf.insert_chars(
+            &(1, 0),
+            &[Row::from("c"), Row::from("d"), Row::from("e")],
+            SelectMode::Rectangle,
+        );
+
+        assert_eq!(3, buf.rows.len());
+        assert_eq!(&['a', 'c', 'b'], buf.rows[0].column());
+        assert_eq!(&[' ', 'd'], buf.rows[1].column());
+        assert_eq!(&[' ', 'e'], buf.rows[2].column());
+        assert!(buf.cached());
+        assert!(buf.updated());
+        assert_eq!(1, buf.history.len());
+    }
+
+    #[test]
+    fn buffer_insert_chars_xoverflow() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a']);
+        init_screen(&mut buf);
+
+        buf.insert_chars(&(2, 0), &[Row::from("bc")], SelectMode::None);
+
+        assert_eq!(1, buf.rows.len());
+        assert_eq!(&['a'], buf.rows[0].column());
+        assert!(!buf.cached());
+        assert!(!buf.updated());
+        assert_eq!(0, buf.history.len());
+    }
+
+    #[test]
+    fn buffer_insert_char_yoverflow() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a']);
+        init_screen(&mut buf);
+
+        buf.insert_char(&(0, 1), 'b');
+
+        assert_eq!(1, buf.rows.len());
+        assert_eq!(&['a'], buf.rows[0].column());
+        assert!(!buf.cached());
+        assert!(!buf.updated());
+        assert_eq!(0, buf.history.len());
+    }
+
+    #[test]
+    fn buffer_replace() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'b', 'c']);
+        init_screen(&mut buf);
+
+        buf.replace(&(0, 0), 2, &['d']);
+
+        assert_eq!(&['d', 'c'], buf.rows[0].column());
+    }
+
+    #[test]
+    fn buffer_replace_yoverflow() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'b', 'c']);
+        init_screen(&mut buf);
+
+        buf.replace(&(0, 1), 2, &['d']);
+
+        assert_eq!(&['a', 'b', 'c'], buf.rows[0].column());
+    }
+
+    #[test]
+    fn buffer_replace_range() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'b', 'c']);
+        buf.insert_row(&(0, 1), &['d', 'e', 'f']);
+        buf.insert_row(&(0, 2), &['g', 'h', 'i']);
+        init_screen(&mut buf);
+
+        let end = buf.replace_range(&(1, 0), &(2, 1), &[Row::from("xy")]);
+
+        assert_eq!(Some((3, 0)), end);
+        assert_eq!(2, buf.rows());
+        assert_eq!(&['a', 'x', 'y', 'f'], buf.rows[0].column());
+        assert_eq!(&['g', 'h', 'i'], buf.rows[1].column());
+        assert!(buf.pending.is_none());
+
+        assert_eq!(Some((1, 0)), buf.undo());
+        assert_eq!(3, buf.rows());
+        assert_eq!(&['a', 'b', 'c'], buf.rows[0].column());
+        assert_eq!(&['d', 'e', 'f'], buf.rows[1].column());
+        assert_eq!(&['g', 'h', 'i'], buf.rows[2].column());
+        assert_eq!(0, buf.history.len());
+    }
+
+    #[test]
+    fn buffer_replace_range_multi_rows() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'b', 'c']);
+        buf.insert_row(&(0, 1), &['d', 'e', 'f']);
+        init_screen(&mut buf);
+
+        let end = buf.replace_range(&(1, 0), &(1, 1), &[Row::from("x"), Row::from("y")]);
+
+        assert_eq!(Some((1, 1)), end);
+        assert_eq!(2, buf.rows());
+        assert_eq!(&['a', 'x'], buf.rows[0].column());
+        assert_eq!(&['y', 'e', 'f'], buf.rows[1].column());
+    }
+
+    #[test]
+    fn buffer_rfind_at_0() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'b', 'c']);
+        buf.insert_row(&(0, 1), &['a', 'b', 'c']);
+        buf.insert_row(&(0, 2), &['a', 'b', 'c']);
+        init_screen(&mut buf);
+
+        let at = buf.rfind_at(&(0, 3), "bc");
+
+        assert_eq!(Some((1, 2)), at);
+    }
+
+    #[test]
+    fn buffer_rfind_at_1() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'b', 'c']);
+        buf.insert_row(&(0, 1), &['a', 'b', 'c']);
+        buf.insert_row(&(0, 2), &['a', 'b', 'c']);
+        init_screen(&mut buf);
+
+        let at = buf.rfind_at(&(1, 1), "bc");
+
+        assert_eq!(Some((1, 0)), at);
+    }
+
+    #[test]
+    fn buffer_rfind_at_notfound() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'b', 'c']);
+        buf.insert_row(&(0, 1), &['a', 'b', 'c']);
+        buf.insert_row(&(0, 2), &['a', 'b', 'c']);
+        init_screen(&mut buf);
+
+        let at = buf.rfind_at(&(1, 0), "bc");
+
+        assert_eq!(None, at);
+    }
+
+    #[test]
+    fn buffer_rfind_regex_at() {
+        let buf = text_buffer(&["ab1", "ab22 ab3"]);
+        let regex = Regex::new("b[0-9]+").unwrap();
+
+        assert_eq!(Some(((1, 1), 3)), buf.rfind_regex_at(&(5, 1), &regex));
+        assert_eq!(Some(((6, 1), 2)), buf.rfind_regex_at(&(6, 1), &regex));
+        assert_eq!(Some(((1, 0), 2)), buf.rfind_regex_at(&(0, 1), &regex));
+        assert_eq!(Some(((6, 1), 2)), buf.rfind_regex_at(&(0, 2), &regex));
+        assert_eq!(None, buf.rfind_regex_at(&(0, 0), &regex));
+    }
+
+    #[test]
+    fn buffer_row_char_len() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a']);
+        init_screen(&mut buf);
+
+        let len = buf.row_char_len(&(0, 0));
+
+        assert_eq!(1, len);
+    }
+
+    #[test]
+    fn buffer_row_char_len_yoverflow() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a']);
+        init_screen(&mut buf);
+
+        let len = buf.row_char_len(&(0, 1));
+
+        assert_eq!(0, len);
+    }
+
+    #[test]
+    fn buffer_rows() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a']);
+        init_screen(&mut buf);
+
+        let len = buf.rows();
+
+        assert_eq!(1, len);
+    }
+
+    #[test]
+    fn buffer_paste_pending() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a']);
+        buf.pending = Some((vec![Row::from("b")], SelectMode::None, false));
+        init_screen(&mut buf);
+
+        buf.paste_pending(&(0, 0));
+
+        assert_eq!(&['b', 'a'], buf.rows[0].column());
+        assert!(buf.cached());
+        assert!(buf.updated());
+        assert_eq!(1, buf.history.len());
+    }
+
+    #[test]
+    fn buffer_copy_line() {
+        let mut buf = text_buffer(&["a", "b"]);
+        init_screen(&mut buf);
+
+        assert!(buf.copy_line(&(1, 1)));
+        assert!(!buf.copy_line(&(0, 2)));
+
+        assert!(buf.pending_linewise());
+        let pending = buf.pending().unwrap();
+        assert_eq!(2, pending.len());
+        assert_eq!(&['b'], pending[0].column());
+        assert!(pending[1].is_empty());
+        assert!(!buf.cached());
+    }
+
+    #[test]
+    fn buffer_cut_line() {
+        let mut buf = text_buffer(&["a", "b"]);
+        init_screen(&mut buf);
+
+        assert!(buf.cut_line(&(0, 0)));
+
+        assert_eq!(1, buf.rows());
+        assert!(buf.pending_linewise());
+        assert_eq!(Some((0, 0)), buf.undo());
+        assert_eq!(Some("a".to_string()), buf.line_text(0));
+    }
+
+    #[test]
+    fn buffer_paste_pending_linewise() {
+        let mut buf = text_buffer(&["a", "b"]);
+        buf.copy_line(&(0, 1));
+        init_screen(&mut buf);
+
+        assert_eq!(Some((0, 1)), buf.paste_pending(&(1, 0)));
+        assert_eq!(Some("b".to_string()), buf.line_text(0));
+        assert_eq!(Some("a".to_string()), buf.line_text(1));
+
+        assert_eq!(Some((0, 4)), buf.paste_pending(&(0, 3)));
+        assert_eq!(4, buf.rows());
+        assert_eq!(Some("b".to_string()), buf.line_text(3));
+
+        buf.copy_pending(
+            &Cursor::from((0, 0))..&Cursor::from((1, 0)),
+            SelectMode::None,
+        );
+        assert!(!buf.pending_linewise());
+    }
+
+    #[test]
+    fn buffer_copy_paste_lines() {
+        let mut buf = text_buffer(&["ab", "cd", "ef"]);
+        init_screen(&mut buf);
+
+        buf.copy_pending(
+            &Cursor::from((2, 0))..&Cursor::from((0, 1)),
+            SelectMode::Line,
+        );
+        assert!(buf.pending_linewise());
+        assert_eq!(Some("ab\r\ncd\r\n".to_string()), buf.pending_text());
+
+        assert_eq!(Some((0, 4)), buf.paste_pending(&(1, 2)));
+        assert_eq!(vec!["ab", "cd", "ab", "cd", "ef"], texts(&buf));
+        assert_eq!(1, buf.history.len());
+
+        buf.undo();
+        assert_eq!(vec!["ab", "cd", "ef"], texts(&buf));
+
+        assert_eq!(Some((0, 5)), buf.paste_pending(&(0, 3)));
+        assert_eq!(vec!["ab", "cd", "ef", "ab", "cd"], texts(&buf));
+    }
+
+    #[test]
+    fn buffer_delete_chars_line() {
+        let mut buf = text_buffer(&["ab", "cd", "ef"]);
+        init_screen(&mut buf);
+
+        buf.delete_chars(&(1, 0), &(1, 1), SelectMode::Line);
+
+        assert_eq!(vec!["ef"], texts(&buf));
+        assert!(buf.pending_linewise());
+        assert_eq!(Some("ab\r\ncd\r\n".to_string()), buf.pending_text());
+
+        assert_eq!(Some((1, 0)), buf.undo());
+        assert_eq!(vec!["ab", "cd", "ef"], texts(&buf));
+
+        buf.redo();
+        assert_eq!(vec!["ef"], texts(&buf));
+    }
+
+    #[test]
+    fn buffer_save() {
+        let path = env::temp_dir().join("note_buffer_save.txt");
+        let mut buf = Buffer::default();
+        buf.set_filename(&path);
+        buf.insert_row(&(0, 0), &['a']);
+        buf.history.clear();
+
+        let ret = buf.save();
+
+        assert!(ret.is_ok());
+        assert!(!buf.cached());
+        assert!(buf.updated());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn buffer_undo_after_save() {
+        let path = env::temp_dir().join("note_buffer_undo_after_save.txt");
+        let mut buf = text_buffer(&["a"]);
+        buf.insert_char(&(1, 0), 'b');
+        buf.save_as(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        buf.undo();
+        assert!(buf.cached());
+
+        buf.redo();
+        assert!(!buf.cached());
+
+        buf.undo();
+        buf.insert_char(&(1, 0), 'c');
+        buf.undo();
+        assert!(buf.cached());
+    }
+
+    #[test]
+    fn buffer_undo_after_load() {
+        let path = env::temp_dir().join("note_buffer_undo_after_load.txt");
+        fs::write(&path, "a\n").unwrap();
+        let mut buf = Buffer::try_from(Some(path.as_path())).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        buf.insert_char(&(1, 0), 'b');
+        assert!(buf.cached());
+
+        buf.undo();
+        assert!(!buf.cached());
+    }
+
+    #[test]
+    fn buffer_save_read_only() {
+        let mut buf = Buffer::default();
+        buf.set_filename(&PathBuf::from("a.txt"));
+        buf.insert_row(&(0, 0), &['a']);
+        buf.set_read_only(true);
+
+        let ret = buf.save();
+
+        assert!(ret.is_err());
+        assert!(buf.cached());
+    }
+
+    #[test]
+    fn buffer_save_trim_trailing() {
+        let path = env::temp_dir().join("note_buffer_save_trim_trailing.txt");
+        let mut buf = text_buffer(&["a  ", "b\t", "c\u{3000}"]);
+        buf.set_settings(Config::parse("trim_trailing_on_save = true").resolve(None));
+
+        buf.save_as(&path).unwrap();
+
+        // Other whitespace such as the ideographic space is kept.
+        let expected = "a\r\nb\r\nc\u{3000}\r\n";
+        assert_eq!(expected, fs::read_to_string(&path).unwrap());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn buffer_save_trim_trailing_whitespace() {
+        let path = env::temp_dir().join("note_buffer_save_trim_trailing_whitespace.txt");
+        let mut buf = text_buffer(&["a \t", " \t ", "b"]);
+        buf.set_trim_trailing_whitespace(true);
+        buf.insert_char(&(0, 2), 'c');
+
+        buf.save_as(&path).unwrap();
+
+        assert_eq!(b"a\r\n\r\ncb\r\n", fs::read(&path).unwrap().as_slice());
+        assert_eq!("a \t", buf.rows[0].to_string_at(0));
+        assert_eq!(" \t ", buf.rows[1].to_string_at(0));
+        assert!(!buf.cached());
+
+        // Undo only reverts the typed character.
+        buf.undo();
+        assert_eq!("b", buf.rows[2].to_string_at(0));
+        assert_eq!("a \t", buf.rows[0].to_string_at(0));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn buffer_save_line_ending() {
+        let path = env::temp_dir().join("note_buffer_save_line_ending.txt");
+        let mut buf = text_buffer(&["a", "b"]);
+        buf.set_settings(Config::parse("line_ending = lf").resolve(None));
+
+        buf.save_as(&path).unwrap();
+
+        assert_eq!("a\nb\n", fs::read_to_string(&path).unwrap());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn buffer_write_to() {
+        let mut buf = text_buffer(&["a", "", "あ"]);
+        let mut bytes = Vec::new();
+
+        buf.write_to(&mut bytes).unwrap();
+
+        assert_eq!("a\r\n\r\nあ\r\n".as_bytes(), bytes.as_slice());
+        assert!(buf.cached());
+
+        buf.set_settings(Config::parse("line_ending = lf").resolve(None));
+        let mut bytes = Vec::new();
+        buf.write_to(&mut bytes).unwrap();
+        assert_eq!(b"a\n\n\xe3\x81\x82\n", bytes.as_slice());
+    }
+
+    #[test]
+    fn buffer_save_keep_line_ending() {
+        let path = env::temp_dir().join("note_buffer_save_keep_line_ending.txt");
+        fs::write(&path, "a\nb\r\nc\n").unwrap();
+        let mut buf = Buffer::try_from(Some(path.as_path())).unwrap();
+        assert_eq!(LineEnding::Lf, buf.line_ending());
+
+        buf.save().unwrap();
+        assert_eq!("a\nb\nc\n", fs::read_to_string(&path).unwrap());
+
+        buf.set_line_ending(LineEnding::Crlf);
+        buf.set_settings(Config::parse("line_ending = lf").resolve(None));
+        buf.save().unwrap();
+        assert_eq!("a\r\nb\r\nc\r\n", fs::read_to_string(&path).unwrap());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn buffer_save_round_trip() {
+        let path = env::temp_dir().join("note_buffer_save_round_trip.txt");
+        for text in ["a\nb\n", "a\nb", "a\r\n\r\nb", "a\nb\r\n", ""] {
+            fs::write(&path, text).unwrap();
+            let mut buf = Buffer::try_from(Some(path.as_path())).unwrap();
+
+            buf.save().unwrap();
 
-        let removed = render.shrink_width(range.start);
-        if range.start < removed {
-            for _ in 0..(removed - range.start) {
-                render.insert(0, ' ')
-            }
+            let expected = text.replace("b\r\n", "b\n");
+            assert_eq!(expected, fs::read_to_string(&path).unwrap());
+            assert_eq!(None, buf.verify_saved().unwrap());
         }
+        fs::remove_file(&path).unwrap();
+    }
 
-        let width = render.truncate_width(range.end - range.start);
-        for _ in width..(range.end - range.start) {
-            render.append(&[' '])
+    #[test]
+    fn buffer_save_bom() {
+        let path = env::temp_dir().join("note_buffer_save_bom.txt");
+        for (bytes, bom) in [(&b"\xEF\xBB\xBFa\r\n"[..], true), (b"a\r\n", false)] {
+            fs::write(&path, bytes).unwrap();
+            let mut buf = Buffer::try_from(Some(path.as_path())).unwrap();
+            assert_eq!(bom, buf.has_bom());
+            assert_eq!(Some("a".to_string()), buf.line_text(0));
+
+            buf.insert_char(&(0, 0), 'b');
+            buf.save().unwrap();
+
+            let expected = [if bom { UTF8_BOM } else { b"" }, b"ba\r\n"].concat();
+            assert_eq!(expected, fs::read(&path).unwrap());
+            assert_eq!(None, buf.verify_saved().unwrap());
         }
-
-        render
+        fs::remove_file(&path).unwrap();
     }
 
-    pub fn split_off(&mut self, at: usize) -> Row {
-        Row::from(self.column.split_off(at))
-    }
+    #[test]
+    fn buffer_save_shift_jis() {
+        let path = env::temp_dir().join("note_buffer_save_shift_jis.txt");
+        // "日本\r\nあ\r\n"
+        fs::write(&path, b"\x93\xFA\x96\x7B\r\n\x82\xA0\r\n").unwrap();
+        let mut buf = Buffer::try_from(Some(path.as_path())).unwrap();
+        assert_eq!(Encoding::ShiftJis, buf.encoding());
+        assert_eq!(Some("日本".to_string()), buf.line_text(0));
+        assert!(!buf.read_only());
 
-    pub fn to_string_at(&self, at: usize) -> String {
-        self.column.iter().skip(at).collect()
-    }
+        buf.insert_char(&(1, 1), 'a');
+        buf.save().unwrap();
 
-    pub fn truncate_width(&mut self, max_width: usize) -> usize {
-        for index in 0..self.column.len() {
-            if max_width < self.width_range(0..index + 1) {
-                self.column.truncate(index);
-                break;
-            }
-        }
+        let expected = b"\x93\xFA\x96\x7B\r\n\x82\xA0a\r\n";
+        assert_eq!(expected.as_slice(), fs::read(&path).unwrap());
+        assert_eq!(None, buf.verify_saved().unwrap());
 
-        self.width()
+        buf.insert_char(&(0, 0), '😀');
+        assert!(buf.save().is_err());
+        assert_eq!(expected.as_slice(), fs::read(&path).unwrap());
+        fs::remove_file(&path).unwrap();
     }
 
-    pub fn remove(&mut self, index: usize) -> Option<char> {
-        if index < self.column.len() {
-            Some(self.column.remove(index))
-        } else {
-            None
+    #[test]
+    fn buffer_save_utf16() {
+        let path = env::temp_dir().join("note_buffer_save_utf16.txt");
+        let le = |text: &str| text.encode_utf16().flat_map(|u| u.to_le_bytes()).collect();
+        let be = |text: &str| text.encode_utf16().flat_map(|u| u.to_be_bytes()).collect();
+        type Case = (fn(&str) -> Vec<u8>, &'static str, &'static str, Encoding);
+        let cases: [Case; 3] = [
+            (le, "\u{FEFF}あ\r\n", "\u{FEFF}😀あ\r\n", Encoding::Utf16Le),
+            (be, "\u{FEFF}あ\r\n", "\u{FEFF}😀あ\r\n", Encoding::Utf16Be),
+            (le, "ab\r\n", "éab\r\n", Encoding::Utf16Le),
+        ];
+        for (encode, text, saved, encoding) in cases {
+            fs::write(&path, encode(text)).unwrap();
+            let mut buf = Buffer::try_from(Some(path.as_path())).unwrap();
+            assert_eq!(encoding, buf.encoding(), "{:?}", text);
+            assert_eq!(text.starts_with('\u{FEFF}'), buf.has_bom());
+
+            let ch = saved.trim_start_matches('\u{FEFF}').chars().next().unwrap();
+            buf.insert_char(&(0, 0), ch);
+            buf.save().unwrap();
+
+            assert_eq!(encode(saved), fs::read(&path).unwrap());
+            assert_eq!(None, buf.verify_saved().unwrap());
         }
+        fs::remove_file(&path).unwrap();
     }
 
-    pub fn remove_range(&mut self, range: Range<usize>) -> Option<Vec<char>> {
-        if range.end <= self.column.len() {
-            Some(self.column.drain(range).collect())
-        } else {
-            None
-        }
-    }
+    #[test]
+    fn buffer_try_from_invalid_utf16() {
+        let path = env::temp_dir().join("note_buffer_try_from_invalid_utf16.txt");
+        // Unpaired surrogate at the second line.
+        fs::write(&path, b"\xFF\xFEa\0\n\0\x00\xD8\n\0").unwrap();
 
-    pub fn rev_at(&self, at: usize) -> Row {
-        let rev = self
-            .column
-            .iter()
-            .take(at)
-            .rev()
-            .cloned()
-            .collect::<Vec<char>>();
-        Row::from(rev)
+        let buf = Buffer::try_from(Some(path.as_path())).unwrap();
+
+        assert_eq!(Encoding::Utf16Le, buf.encoding());
+        assert_eq!(Some("\u{FFFD}".to_string()), buf.line_text(1));
+        assert_eq!(1, buf.invalid_lines());
+        assert!(buf.read_only());
+        fs::remove_file(&path).unwrap();
     }
 
-    pub fn width(&self) -> usize {
-        self.width_range(0..self.column.len())
+    #[test]
+    fn buffer_reload_encoding() {
+        let path = env::temp_dir().join("note_buffer_reload_encoding.txt");
+        // "日本\r\nあ\r\n"
+        fs::write(&path, b"\x93\xFA\x96\x7B\r\n\x82\xA0\r\n").unwrap();
+        let mut buf = Buffer::try_from(Some(path.as_path())).unwrap();
+
+        buf.reload(Some(Encoding::Utf8)).unwrap();
+        assert_eq!(Encoding::Utf8, buf.encoding());
+        assert_eq!(
+            Some("\u{FFFD}\u{FFFD}\u{FFFD}{".to_string()),
+            buf.line_text(0)
+        );
+        assert_eq!(2, buf.invalid_lines());
+        assert!(buf.read_only());
+
+        buf.reload(Some(Encoding::ShiftJis)).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(Encoding::ShiftJis, buf.encoding());
+        assert_eq!(vec!["日本", "あ"], texts(&buf));
+        assert_eq!(0, buf.invalid_lines());
+        assert!(!buf.read_only());
     }
 
-    pub fn width_range(&self, range: Range<usize>) -> usize {
-        let mut render = 0;
+    #[test]
+    fn buffer_save_write_bom() {
+        let mut buf = text_buffer(&["a"]);
+        buf.set_settings(Config::parse("write_bom = true").resolve(None));
+        let mut bytes = Vec::new();
 
-        for &ch in &self.column[range] {
-            if ch == '\t' {
-                render += TAB_STOP - (render % TAB_STOP);
+        buf.write_to(&mut bytes).unwrap();
+
+        assert_eq!(b"\xEF\xBB\xBFa\r\n", bytes.as_slice());
+    }
+
+    #[test]
+    fn buffer_final_newline() {
+        let path = env::temp_dir().join("note_buffer_final_newline.txt");
+        for (text, final_newline) in [("a\r\nb\r\n", true), ("a\r\nb", false)] {
+            fs::write(&path, text).unwrap();
+            let mut buf = Buffer::try_from(Some(path.as_path())).unwrap();
+            assert_eq!(final_newline, buf.has_final_newline());
+
+            buf.save().unwrap();
+            assert_eq!(text.as_bytes(), fs::read(&path).unwrap());
+
+            buf.set_final_newline(!final_newline);
+            buf.save().unwrap();
+            let toggled = if final_newline {
+                "a\r\nb"
             } else {
-                render += char_width(ch);
-            }
+                "a\r\nb\r\n"
+            };
+            assert_eq!(toggled.as_bytes(), fs::read(&path).unwrap());
         }
-
-        render
+        fs::remove_file(&path).unwrap();
     }
 
-    fn render(&self) -> Row {
-        let mut render = Row::default();
+    #[test]
+    fn buffer_save_insert_final_newline() {
+        let path = env::temp_dir().join("note_buffer_save_insert_final_newline.txt");
+        fs::write(&path, "a\nb").unwrap();
+        let mut buf = Buffer::try_from(Some(path.as_path())).unwrap();
+        buf.set_settings(Config::parse("insert_final_newline = true").resolve(None));
 
-        for &ch in &self.column {
-            if ch == '\t' {
-                let next_tab_stop = TAB_STOP - (render.width() % TAB_STOP);
-                let spaces = iter::repeat(char::from(b' '))
-                    .take(next_tab_stop)
-                    .collect::<Vec<char>>();
-                render.column.extend_from_slice(&spaces);
-            } else {
-                render.column.push(ch)
+        buf.save().unwrap();
+
+        assert_eq!("a\nb\n", fs::read_to_string(&path).unwrap());
+        assert_eq!(None, buf.verify_saved().unwrap());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn buffer_verify_saved_matrix() {
+        let path = env::temp_dir().join("note_buffer_verify_saved_matrix.txt");
+        let texts: [&[&str]; 4] = [&["a", "b"], &["", "あ😀", ""], &["\ta  ", " b\r c"], &[]];
+        for config in [
+            "line_ending = crlf",
+            "line_ending = lf",
+            "line_ending = crlf\ntrim_trailing_on_save = true",
+            "line_ending = lf\ntrim_trailing_on_save = true",
+        ] {
+            for rows in texts {
+                let mut buf = text_buffer(rows);
+                buf.set_settings(Config::parse(config).resolve(None));
+                buf.set_filename(&path);
+
+                buf.save().unwrap();
+
+                assert_eq!(None, buf.verify_saved().unwrap(), "{:?} {:?}", config, rows);
             }
         }
-
-        render
+        fs::remove_file(&path).unwrap();
     }
-}
 
-// -----------------------------------------------------------------------------------------------
+    #[test]
+    fn buffer_verify_saved_mismatch() {
+        let path = env::temp_dir().join("note_buffer_verify_saved_mismatch.txt");
+        let mut buf = text_buffer(&["a", "b\r", "c"]);
+        buf.set_settings(Config::parse("line_ending = lf").resolve(None));
+        buf.set_filename(&path);
 
-fn char_width(ch: char) -> usize {
-    ch.width_cjk().unwrap_or(1)
-}
+        buf.save().unwrap();
 
-// -----------------------------------------------------------------------------------------------
+        // The carriage return at the end of row is read as a line break.
+        assert_eq!(Some(1), buf.verify_saved().unwrap());
+        fs::remove_file(&path).unwrap();
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn buffer_content_eq() {
+        let buf = text_buffer(&["a", "b"]);
 
-    fn init_screen(buf: &mut Buffer) {
-        buf.cached = false;
-        buf.clear_updated();
-        buf.history.clear();
+        assert_eq!(None, buf.content_eq(&text_buffer(&["a", "b"])));
+        assert_eq!(Some(1), buf.content_eq(&text_buffer(&["a", "c"])));
+        assert_eq!(Some(1), buf.content_eq(&text_buffer(&["a"])));
+        assert_eq!(Some(0), Buffer::default().content_eq(&buf));
     }
 
     #[test]
-    fn buffer_append_row() {
+    fn buffer_save_none() {
         let mut buf = Buffer::default();
         buf.insert_row(&(0, 0), &['a']);
-        init_screen(&mut buf);
+        buf.history.clear();
 
-        buf.append_row(&(0, 0), &['b']);
+        let ret = buf.save();
 
-        assert_eq!(&['a', 'b'], buf.rows[0].column());
+        assert!(ret.is_ok());
         assert!(buf.cached());
         assert!(buf.updated());
-        assert_eq!(1, buf.history.len());
     }
 
     #[test]
-    fn buffer_append_row_yoverflow() {
+    fn buffer_shrink_row() {
         let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a']);
+        buf.insert_row(&(0, 0), &['a', 'b', 'c']);
         init_screen(&mut buf);
 
-        buf.append_row(&(0, 1), &['b']);
+        buf.shrink_row(&(1, 0));
 
+        assert_eq!(1, buf.rows());
         assert_eq!(&['a'], buf.rows[0].column());
-        assert!(!buf.cached());
-        assert!(!buf.updated());
-        assert_eq!(0, buf.history.len());
+        assert!(buf.cached());
+        assert!(buf.updated());
+        assert_eq!(1, buf.history.len());
     }
 
     #[test]
-    fn buffer_copy_pending_none() {
+    fn buffer_shrink_row_yoverflow() {
         let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a']);
+        buf.insert_row(&(0, 0), &['a', 'b', 'c']);
         init_screen(&mut buf);
 
-        let s = Cursor::from((0, 0));
-        let e = Cursor::from((1, 0));
-        buf.copy_pending(&s..&e, SelectMode::None);
+        buf.shrink_row(&(1, 1));
 
-        assert_eq!(&['a'], buf.pending.unwrap().0.first().unwrap().column());
+        assert_eq!(1, buf.rows());
+        assert_eq!(&['a', 'b', 'c'], buf.rows[0].column());
+        assert!(!buf.cached());
+        assert!(!buf.updated());
+        assert_eq!(0, buf.history.len());
     }
 
     #[test]
-    fn buffer_copy_pending_rectangle() {
+    fn buffer_split_row() {
         let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a', 'b']);
-        buf.insert_row(&(0, 1), &['c', 'd']);
+        buf.insert_row(&(0, 0), &['a', 'b', 'c']);
         init_screen(&mut buf);
 
-        let s = Cursor::from((0, 0));
-        let e = Cursor::from((1, 1));
-        buf.copy_pending(&s..&e, SelectMode::Rectangle);
+        buf.split_row(&(1, 0));
 
-        assert_eq!(&['a'], buf.pending.as_ref().unwrap().0[0].column());
-        assert_eq!(&['c'], buf.pending.as_ref().unwrap().0[1].column());
+        assert_eq!(2, buf.rows());
+        assert_eq!(&['a'], buf.rows[0].column());
+        assert_eq!(&['b', 'c'], buf.rows[1].column());
+        assert!(buf.cached());
+        assert!(buf.updated());
+        assert_eq!(1, buf.history.len());
     }
 
     #[test]
-    fn buffer_copy_pending_yoverflow() {
+    fn buffer_split_row_start() {
         let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a']);
+        buf.insert_row(&(0, 0), &['a', 'b', 'c']);
         init_screen(&mut buf);
 
-        let s = Cursor::from((0, 1));
-        let e = Cursor::from((1, 1));
-        buf.copy_pending(&s..&e, SelectMode::None);
+        buf.split_row(&(0, 0));
 
-        assert!(buf.pending.is_none());
+        assert_eq!(2, buf.rows());
+        assert!(buf.rows[0].is_empty());
+        assert_eq!(&['a', 'b', 'c'], buf.rows[1].column());
+        assert!(buf.cached());
+        assert!(buf.updated());
+        assert_eq!(1, buf.history.len());
     }
 
     #[test]
-    fn buffer_delete_row() {
+    fn buffer_split_row_end() {
         let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a']);
+        buf.insert_row(&(0, 0), &['a', 'b', 'c']);
         init_screen(&mut buf);
 
-        buf.delete_row(&(0, 0));
+        buf.split_row(&(3, 0));
 
-        assert_eq!(0, buf.rows());
+        assert_eq!(2, buf.rows());
+        assert_eq!(&['a', 'b', 'c'], buf.rows[0].column());
+        assert!(buf.rows[1].is_empty());
         assert!(buf.cached());
         assert!(buf.updated());
         assert_eq!(1, buf.history.len());
     }
 
     #[test]
-    fn buffer_delete_row_yoverflow() {
+    fn buffer_split_row_yoverflow() {
         let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a']);
+        buf.insert_row(&(0, 0), &['a', 'b', 'c']);
         init_screen(&mut buf);
 
-        buf.delete_row(&(0, 1));
+        buf.split_row(&(1, 1));
 
         assert_eq!(1, buf.rows());
+        assert_eq!(&['a', 'b', 'c'], buf.rows[0].column());
         assert!(!buf.cached());
         assert!(!buf.updated());
         assert_eq!(0, buf.history.len());
     }
 
     #[test]
-    fn buffer_delete_char() {
+    fn buffer_squash_row() {
         let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a', 'b']);
+        buf.insert_row(&(0, 0), &['a']);
+        buf.insert_row(&(0, 1), &['b']);
         init_screen(&mut buf);
 
-        buf.delete_char(&(1, 0));
+        buf.squash_row(&(0, 1));
 
-        assert_eq!(&['b'], buf.rows[0].column());
+        assert_eq!(1, buf.rows());
+        assert_eq!(&['a', 'b'], buf.rows[0].column());
         assert!(buf.cached());
         assert!(buf.updated());
         assert_eq!(1, buf.history.len());
     }
 
     #[test]
-    fn buffer_delete_char_xoverflow() {
+    fn buffer_squash_start() {
         let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a', 'b']);
+        buf.insert_row(&(0, 0), &['a']);
+        buf.insert_row(&(0, 1), &['b']);
         init_screen(&mut buf);
 
-        buf.delete_char(&(3, 0));
+        buf.squash_row(&(0, 0));
 
-        assert_eq!(&['a', 'b'], buf.rows[0].column());
+        assert_eq!(2, buf.rows());
+        assert_eq!(&['a'], buf.rows[0].column());
+        assert_eq!(&['b'], buf.rows[1].column());
         assert!(!buf.cached());
         assert!(!buf.updated());
         assert_eq!(0, buf.history.len());
     }
 
     #[test]
-    fn buffer_delete_char_yoverflow() {
+    fn buffer_squash_row_yoverflow() {
         let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a', 'b']);
+        buf.insert_row(&(0, 0), &['a']);
+        buf.insert_row(&(0, 1), &['b']);
         init_screen(&mut buf);
 
-        buf.delete_char(&(1, 1));
+        buf.squash_row(&(0, 2));
 
-        assert_eq!(&['a', 'b'], buf.rows[0].column());
+        assert_eq!(2, buf.rows());
+        assert_eq!(&['a'], buf.rows[0].column());
+        assert_eq!(&['b'], buf.rows[1].column());
         assert!(!buf.cached());
         assert!(!buf.updated());
         assert_eq!(0, buf.history.len());
     }
 
-    #[test]
-    fn buffer_delete_chars_1row() {
+    fn text_buffer(rows: &[&str]) -> Buffer {
         let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a', 'b']);
-        init_screen(&mut buf);
-
-        buf.delete_chars(&(1, 0), &(2, 0), SelectMode::None);
-
-        assert_eq!(1, buf.rows.len());
-        assert_eq!(&['a'], buf.rows[0].column());
-        assert!(buf.cached());
-        assert!(buf.updated());
-        assert_eq!(1, buf.history.len());
+        for (y, row) in rows.iter().enumerate() {
+            let chars = row.chars().collect::<Vec<char>>();
+            buf.insert_row(&(0, y), &chars);
+        }
+        buf
     }
 
     #[test]
-    fn buffer_delete_chars_2row() {
-        let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a', 'b']);
-        buf.insert_row(&(0, 1), &['c', 'd']);
+    fn buffer_add_number() {
+        let mut buf = text_buffer(&["a 9 b"]);
         init_screen(&mut buf);
 
-        buf.delete_chars(&(1, 0), &(1, 1), SelectMode::None);
-
-        assert_eq!(1, buf.rows.len());
-        assert_eq!(&['a', 'd'], buf.rows[0].column());
+        assert_eq!(Some(2..4), buf.add_number(&(2, 0), 1));
+        assert_eq!(Some("a 10 b".to_string()), buf.line_text(0));
         assert!(buf.cached());
-        assert!(buf.updated());
         assert_eq!(1, buf.history.len());
+
+        buf.undo();
+        assert_eq!(Some("a 9 b".to_string()), buf.line_text(0));
     }
 
     #[test]
-    fn buffer_delete_chars_2row_start() {
-        let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a', 'b']);
-        buf.insert_row(&(0, 1), &['c', 'd']);
-        init_screen(&mut buf);
-
-        buf.delete_chars(&(0, 0), &(0, 1), SelectMode::None);
+    fn buffer_add_number_leading_zeros() {
+        let mut buf = text_buffer(&["007", "010", "099"]);
 
-        assert_eq!(1, buf.rows.len());
-        assert_eq!(&['c', 'd'], buf.rows[0].column());
-        assert!(buf.cached());
-        assert!(buf.updated());
-        assert_eq!(1, buf.history.len());
+        assert_eq!(Some(0..3), buf.add_number(&(0, 0), -1));
+        assert_eq!(Some("006".to_string()), buf.line_text(0));
+        assert_eq!(Some(0..4), buf.add_number(&(1, 1), -11));
+        assert_eq!(Some("-001".to_string()), buf.line_text(1));
+        assert_eq!(Some(0..3), buf.add_number(&(2, 2), 1));
+        assert_eq!(Some("100".to_string()), buf.line_text(2));
     }
 
     #[test]
-    fn buffer_delete_chars_2row_end() {
-        let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a', 'b']);
-        buf.insert_row(&(0, 1), &['c', 'd']);
-        init_screen(&mut buf);
+    fn buffer_add_number_sign() {
+        let mut buf = text_buffer(&["x = -1;", "a-1"]);
 
-        buf.delete_chars(&(2, 0), &(2, 1), SelectMode::None);
+        assert_eq!(Some(4..5), buf.add_number(&(5, 0), 1));
+        assert_eq!(Some("x = 0;".to_string()), buf.line_text(0));
+        assert_eq!(Some(2..3), buf.add_number(&(2, 1), 1));
+        assert_eq!(Some("a-2".to_string()), buf.line_text(1));
+    }
 
-        assert_eq!(1, buf.rows.len());
-        assert_eq!(&['a', 'b'], buf.rows[0].column());
-        assert!(buf.cached());
-        assert!(buf.updated());
-        assert_eq!(1, buf.history.len());
+    #[test]
+    fn buffer_add_number_none() {
+        let mut buf = text_buffer(&["a b", "9223372036854775807"]);
+
+        assert_eq!(None, buf.add_number(&(1, 0), 1));
+        assert_eq!(None, buf.add_number(&(0, 1), 1));
+        assert_eq!(None, buf.add_number(&(0, 2), 1));
     }
 
     #[test]
-    fn buffer_delete_chars_2row_all() {
-        let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a', 'b']);
-        buf.insert_row(&(0, 1), &['c', 'd']);
-        init_screen(&mut buf);
+    fn buffer_number_range_at() {
+        let buf = text_buffer(&["ab 12 -3"]);
 
-        buf.delete_chars(&(0, 0), &(2, 1), SelectMode::None);
+        assert_eq!(Some(3..5), buf.number_range_at(&(3, 0)));
+        assert_eq!(Some(3..5), buf.number_range_at(&(5, 0)));
+        assert_eq!(Some(6..8), buf.number_range_at(&(7, 0)));
+        assert_eq!(None, buf.number_range_at(&(6, 0)));
+        assert_eq!(None, buf.number_range_at(&(1, 0)));
+    }
 
-        assert_eq!(1, buf.rows.len());
-        assert!(buf.rows[0].is_empty());
-        assert!(buf.cached());
-        assert!(buf.updated());
-        assert_eq!(1, buf.history.len());
+    #[test]
+    fn buffer_matching_bracket() {
+        let buf = text_buffer(&["a(b[c](d)", "e)"]);
+
+        assert_eq!(Some((1, 1)), buf.matching_bracket(&(1, 0)));
+        assert_eq!(Some((1, 0)), buf.matching_bracket(&(1, 1)));
+        assert_eq!(Some((5, 0)), buf.matching_bracket(&(3, 0)));
+        assert_eq!(Some((6, 0)), buf.matching_bracket(&(8, 0)));
+        assert_eq!(None, buf.matching_bracket(&(0, 0)));
     }
 
     #[test]
-    fn buffer_delete_chars_3row_none() {
-        let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a', 'b']);
-        buf.insert_row(&(0, 1), &['c', 'd']);
-        buf.insert_row(&(0, 2), &['e', 'f']);
-        init_screen(&mut buf);
+    fn buffer_matching_bracket_unbalanced() {
+        let buf = text_buffer(&["((a)"]);
 
-        buf.delete_chars(&(1, 0), &(1, 2), SelectMode::None);
+        assert_eq!(None, buf.matching_bracket(&(0, 0)));
+        assert_eq!(Some((3, 0)), buf.matching_bracket(&(1, 0)));
+    }
 
-        assert_eq!(1, buf.rows.len());
-        assert_eq!(&['a', 'f'], buf.rows[0].column());
-        assert!(buf.cached());
-        assert!(buf.updated());
-        assert_eq!(1, buf.history.len());
+    #[test]
+    fn buffer_unmatched_bracket_balanced() {
+        let buf = text_buffer(&["{", "  \"a\": [1, (2)],", "  'b': \"}\"", "}"]);
+
+        assert_eq!(None, buf.unmatched_bracket(|_| {}));
     }
 
     #[test]
-    fn buffer_delete_chars_3row_rectangle() {
-        let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a', 'b']);
-        buf.insert_row(&(0, 1), &['c', 'd']);
-        buf.insert_row(&(0, 2), &['e', 'f']);
-        init_screen(&mut buf);
+    fn buffer_unmatched_bracket_missing_closer() {
+        let buf = text_buffer(&["{", "  a: [1, 2", "}"]);
 
-        buf.delete_chars(&(1, 0), &(2, 2), SelectMode::Rectangle);
+        assert_eq!(Some(((0, 2), '}')), buf.unmatched_bracket(|_| {}));
 
-        assert_eq!(3, buf.rows.len());
-        assert_eq!(&['a'], buf.rows[0].column());
-        assert_eq!(&['c'], buf.rows[1].column());
-        assert_eq!(&['e'], buf.rows[2].column());
-        assert!(buf.cached());
-        assert!(buf.updated());
-        assert_eq!(1, buf.history.len());
+        let buf = text_buffer(&["(a", "[b]", "{c"]);
+
+        assert_eq!(Some(((0, 0), '(')), buf.unmatched_bracket(|_| {}));
     }
 
     #[test]
-    fn buffer_delete_chars_xoverflow() {
-        let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a', 'b']);
-        init_screen(&mut buf);
+    fn buffer_unmatched_bracket_extra_closer() {
+        let buf = text_buffer(&["(a)", "b])"]);
 
-        buf.delete_chars(&(3, 0), &(4, 0), SelectMode::None);
+        assert_eq!(Some(((1, 1), ']')), buf.unmatched_bracket(|_| {}));
+    }
 
-        assert_eq!(1, buf.rows.len());
-        assert_eq!(&['a', 'b'], buf.rows[0].column());
-        assert!(!buf.cached());
-        assert!(!buf.updated());
-        assert_eq!(0, buf.history.len());
+    #[test]
+    fn buffer_unmatched_bracket_quotes() {
+        let buf = text_buffer(&["a = \"(\\\"\" + ')'", "b = \"[", "]"]);
+
+        // The quote is closed at the end of row.
+        assert_eq!(Some(((0, 2), ']')), buf.unmatched_bracket(|_| {}));
     }
 
     #[test]
-    fn buffer_delete_chars_yoverflow() {
-        let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a', 'b']);
-        init_screen(&mut buf);
+    fn buffer_unmatched_bracket_large() {
+        let mut rows = vec!["("; 25000];
+        rows.extend(vec![")"; 25000]);
+        let buf = text_buffer(&rows);
+        let mut progress = vec![];
 
-        buf.delete_chars(&(1, 1), &(2, 1), SelectMode::None);
+        assert_eq!(None, buf.unmatched_bracket(|y| progress.push(y)));
+        assert_eq!(vec![10000, 20000, 30000, 40000], progress);
+    }
 
-        assert_eq!(1, buf.rows.len());
-        assert_eq!(&['a', 'b'], buf.rows[0].column());
-        assert!(!buf.cached());
-        assert!(!buf.updated());
-        assert_eq!(0, buf.history.len());
+    #[test]
+    fn buffer_enclosing_span_nested() {
+        let buf = text_buffer(&["f(a, [b, (c)], d)"]);
+
+        assert_eq!(
+            Some(((9, 0), (12, 0))),
+            buf.enclosing_span((10, 0), (11, 0))
+        );
+        assert_eq!(Some(((5, 0), (13, 0))), buf.enclosing_span((9, 0), (12, 0)));
+        assert_eq!(Some(((1, 0), (17, 0))), buf.enclosing_span((5, 0), (13, 0)));
+        assert_eq!(None, buf.enclosing_span((1, 0), (17, 0)));
     }
 
     #[test]
-    fn buffer_find_at_0() {
-        let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a', 'b', 'c']);
-        buf.insert_row(&(0, 1), &['a', 'b', 'c']);
-        buf.insert_row(&(0, 2), &['a', 'b', 'c']);
-        init_screen(&mut buf);
+    fn buffer_enclosing_span_skip_closed() {
+        let buf = text_buffer(&["(a, (b), c)"]);
 
-        let at = buf.find_at(&(0, 0), "bc");
+        assert_eq!(Some(((0, 0), (11, 0))), buf.enclosing_span((9, 0), (10, 0)));
+    }
 
-        assert_eq!(Some((1, 0)), at);
+    #[test]
+    fn buffer_enclosing_span_on_delimiter() {
+        let buf = text_buffer(&["a (b) \"c\""]);
+
+        assert_eq!(Some(((2, 0), (5, 0))), buf.enclosing_span((2, 0), (2, 0)));
+        assert_eq!(Some(((2, 0), (5, 0))), buf.enclosing_span((4, 0), (4, 0)));
+        assert_eq!(Some(((6, 0), (9, 0))), buf.enclosing_span((6, 0), (6, 0)));
+        assert_eq!(Some(((6, 0), (9, 0))), buf.enclosing_span((8, 0), (8, 0)));
+        assert_eq!(None, buf.enclosing_span((5, 0), (5, 0)));
     }
 
     #[test]
-    fn buffer_find_at_1() {
-        let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a', 'b', 'c']);
-        buf.insert_row(&(0, 1), &['a', 'b', 'c']);
-        buf.insert_row(&(0, 2), &['a', 'b', 'c']);
-        init_screen(&mut buf);
+    fn buffer_enclosing_span_unbalanced_quotes() {
+        let buf = text_buffer(&["'a' \"b 'c'"]);
 
-        let at = buf.find_at(&(2, 1), "bc");
+        assert_eq!(Some(((0, 0), (3, 0))), buf.enclosing_span((1, 0), (2, 0)));
+        assert_eq!(None, buf.enclosing_span((6, 0), (6, 0)));
+        assert_eq!(None, buf.enclosing_span((9, 0), (9, 0)));
+    }
 
-        assert_eq!(Some((1, 2)), at);
+    #[test]
+    fn buffer_enclosing_span_escaped_quote() {
+        let buf = text_buffer(&["\"a\\\"b\""]);
+
+        assert_eq!(Some(((0, 0), (6, 0))), buf.enclosing_span((4, 0), (4, 0)));
     }
 
     #[test]
-    fn buffer_find_at_notfound() {
-        let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a', 'b', 'c']);
-        buf.insert_row(&(0, 1), &['a', 'b', 'c']);
-        buf.insert_row(&(0, 2), &['a', 'b', 'c']);
-        init_screen(&mut buf);
+    fn buffer_enclosing_span_quotes_in_brackets() {
+        let buf = text_buffer(&["(\"a\")", "[x, \"(\"]"]);
 
-        let at = buf.find_at(&(2, 2), "bc");
+        assert_eq!(Some(((1, 0), (4, 0))), buf.enclosing_span((2, 0), (2, 0)));
+        assert_eq!(Some(((0, 0), (5, 0))), buf.enclosing_span((1, 0), (4, 0)));
+        assert_eq!(Some(((4, 1), (7, 1))), buf.enclosing_span((5, 1), (6, 1)));
+        assert_eq!(Some(((0, 1), (8, 1))), buf.enclosing_span((1, 1), (2, 1)));
+    }
 
-        assert_eq!(None, at);
+    #[test]
+    fn buffer_enclosing_span_rows() {
+        let buf = text_buffer(&["fn a() {", "    b", "}"]);
+
+        assert_eq!(Some(((7, 0), (1, 2))), buf.enclosing_span((4, 1), (5, 1)));
+        assert_eq!(None, buf.enclosing_span((0, 0), (1, 2)));
     }
 
     #[test]
-    fn buffer_get() {
-        let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a']);
-        init_screen(&mut buf);
+    fn buffer_paragraph_range() {
+        let buf = text_buffer(&["a", "b", "", "c", " ", "d"]);
 
-        let row = buf.get(0);
+        assert_eq!(Some(0..2), buf.paragraph_range(1));
+        assert_eq!(Some(3..4), buf.paragraph_range(3));
+        assert_eq!(Some(5..6), buf.paragraph_range(5));
+        assert_eq!(None, buf.paragraph_range(2));
+        assert_eq!(None, buf.paragraph_range(4));
+        assert_eq!(None, buf.paragraph_range(6));
+    }
 
-        assert!(row.is_some());
+    fn sort_buffer(rows: &[&str]) -> Buffer {
+        let mut buf = text_buffer(rows);
+        init_screen(&mut buf);
+        buf
     }
 
     #[test]
-    fn buffer_get_notfound() {
-        let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a']);
-        init_screen(&mut buf);
+    fn buffer_sort_rows() {
+        let mut buf = sort_buffer(&["c", "a", "b"]);
 
-        let row = buf.get(1);
+        let ret = buf.sort_rows(0..3, 0, None);
 
-        assert!(row.is_none());
+        assert!(ret);
+        assert_eq!("a", buf.rows[0].to_string_at(0));
+        assert_eq!("b", buf.rows[1].to_string_at(0));
+        assert_eq!("c", buf.rows[2].to_string_at(0));
+        assert!(buf.cached());
+        assert_eq!(1, buf.history.len());
+
+        buf.undo();
+        assert_eq!("c", buf.rows[0].to_string_at(0));
+        assert_eq!("a", buf.rows[1].to_string_at(0));
+        assert_eq!("b", buf.rows[2].to_string_at(0));
     }
 
     #[test]
-    fn buffer_get_range_1row() {
-        let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a', 'b']);
-        init_screen(&mut buf);
+    fn buffer_sort_rows_column() {
+        let mut buf = sort_buffer(&["001 0300 x", "002 0100 y", "003 0200 z"]);
 
-        let start = Cursor::from((1, 0));
-        let end = Cursor::from((2, 0));
-        let rows = buf.get_range(&start..&end, SelectMode::None);
+        let ret = buf.sort_rows(0..3, 4, Some(4));
 
-        let rows = rows.unwrap();
-        assert_eq!(1, rows.len());
-        assert_eq!(&['b'], rows[0].column());
+        assert!(ret);
+        assert_eq!("002 0100 y", buf.rows[0].to_string_at(0));
+        assert_eq!("003 0200 z", buf.rows[1].to_string_at(0));
+        assert_eq!("001 0300 x", buf.rows[2].to_string_at(0));
     }
 
     #[test]
-    fn buffer_get_range_3row() {
-        let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a', 'b']);
-        buf.insert_row(&(0, 1), &['c', 'd']);
-        buf.insert_row(&(0, 2), &['e', 'f']);
-        init_screen(&mut buf);
+    fn buffer_sort_rows_column_stable() {
+        let mut buf = sort_buffer(&["d   0200 1", "c   0100 2", "b   0200 3", "a   0100 4"]);
 
-        let start = Cursor::from((1, 0));
-        let end = Cursor::from((1, 2));
-        let rows = buf.get_range(&start..&end, SelectMode::None);
+        buf.sort_rows(0..4, 4, Some(4));
 
-        let rows = rows.unwrap();
-        assert_eq!(3, rows.len());
-        assert_eq!(&['b'], rows[0].column());
-        assert_eq!(&['c', 'd'], rows[1].column());
-        assert_eq!(&['e'], rows[2].column());
+        assert_eq!("c   0100 2", buf.rows[0].to_string_at(0));
+        assert_eq!("a   0100 4", buf.rows[1].to_string_at(0));
+        assert_eq!("d   0200 1", buf.rows[2].to_string_at(0));
+        assert_eq!("b   0200 3", buf.rows[3].to_string_at(0));
     }
 
     #[test]
-    fn buffer_insert_row_0() {
-        let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a']);
-        init_screen(&mut buf);
+    fn buffer_sort_rows_short_row() {
+        let mut buf = sort_buffer(&["a   2", "b", "c   1"]);
 
-        buf.insert_row(&(0, 0), &['b']);
+        buf.sort_rows(0..3, 4, None);
 
-        assert_eq!(&['b'], buf.rows[0].column());
-        assert!(buf.cached());
-        assert!(buf.updated());
-        assert_eq!(1, buf.history.len());
+        assert_eq!("b", buf.rows[0].to_string_at(0));
+        assert_eq!("c   1", buf.rows[1].to_string_at(0));
+        assert_eq!("a   2", buf.rows[2].to_string_at(0));
     }
 
     #[test]
-    fn buffer_insert_row_1() {
-        let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a']);
-        init_screen(&mut buf);
+    fn buffer_sort_rows_sorted() {
+        let mut buf = sort_buffer(&["a", "b", "c"]);
 
-        buf.insert_row(&(0, 1), &['b']);
+        let ret = buf.sort_rows(1..5, 0, None);
 
-        assert_eq!(&['b'], buf.rows[1].column());
-        assert!(buf.cached());
-        assert!(buf.updated());
-        assert_eq!(1, buf.history.len());
+        assert!(!ret);
+        assert!(!buf.cached());
+        assert_eq!(0, buf.history.len());
     }
 
     #[test]
-    fn buffer_insert_char_0() {
+    fn buffer_toggle_comment() {
         let mut buf = Buffer::default();
         buf.insert_row(&(0, 0), &['a']);
+        buf.insert_row(&(0, 1), &[]);
+        buf.insert_row(&(0, 2), &[' ', ' ', 'b']);
         init_screen(&mut buf);
 
-        buf.insert_char(&(0, 0), 'b');
+        let ret = buf.toggle_comment(0..3);
 
-        assert_eq!(&['b', 'a'], buf.rows[0].column());
+        assert!(ret);
+        assert_eq!("# a", buf.rows[0].to_string_at(0));
+        assert_eq!("", buf.rows[1].to_string_at(0));
+        assert_eq!("#   b", buf.rows[2].to_string_at(0));
         assert!(buf.cached());
         assert!(buf.updated());
         assert_eq!(1, buf.history.len());
     }
 
     #[test]
-    fn buffer_insert_char_1() {
+    fn buffer_toggle_comment_indented() {
         let mut buf = Buffer::default();
         buf.insert_row(&(0, 0), &['a']);
+        buf.insert_row(&(0, 1), &[' ', ' ', 'b']);
         init_screen(&mut buf);
 
-        buf.insert_char(&(1, 0), 'b');
+        let mut comment = Comment::default();
+        comment.set_indented(true);
+        buf.set_comment(comment);
 
-        assert_eq!(&['a', 'b'], buf.rows[0].column());
-        assert!(buf.cached());
-        assert!(buf.updated());
-        assert_eq!(1, buf.history.len());
+        buf.toggle_comment(0..2);
+
+        assert_eq!("# a", buf.rows[0].to_string_at(0));
+        assert_eq!("  # b", buf.rows[1].to_string_at(0));
     }
 
     #[test]
-    fn buffer_insert_char_xoverflow() {
+    fn buffer_toggle_comment_uncomment() {
         let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a']);
+        buf.insert_row(&(0, 0), &['#', ' ', 'a']);
+        buf.insert_row(&(0, 1), &[' ', ' ', '#', 'b']);
         init_screen(&mut buf);
 
-        buf.insert_char(&(2, 0), 'b');
+        buf.toggle_comment(0..2);
 
-        assert_eq!(&['a'], buf.rows[0].column());
-        assert!(!buf.cached());
-        assert!(!buf.updated());
-        assert_eq!(0, buf.history.len());
+        assert_eq!("a", buf.rows[0].to_string_at(0));
+        assert_eq!("  b", buf.rows[1].to_string_at(0));
     }
 
     #[test]
-    fn buffer_insert_chars_1row_0() {
+    fn buffer_toggle_comment_undo() {
         let mut buf = Buffer::default();
         buf.insert_row(&(0, 0), &['a']);
+        buf.insert_row(&(0, 1), &[' ', 'b']);
         init_screen(&mut buf);
 
-        buf.insert_chars(&(0, 0), &[Row::from("bc")], SelectMode::None);
+        buf.toggle_comment(0..2);
+        buf.undo();
 
-        assert_eq!(1, buf.rows.len());
-        assert_eq!(&['b', 'c', 'a'], buf.rows[0].column());
-        assert!(buf.cached());
-        assert!(buf.updated());
-        assert_eq!(1, buf.history.len());
+        assert_eq!("a", buf.rows[0].to_string_at(0));
+        assert_eq!(" b", buf.rows[1].to_string_at(0));
+        assert!(buf.history.is_empty());
     }
 
     #[test]
-    fn buffer_insert_chars_1row_1() {
-        let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a']);
+    fn buffer_set_settings() {
+        let mut buf = text_buffer(&["\ta"]);
         init_screen(&mut buf);
 
-        buf.insert_chars(&(1, 0), &[Row::from("bc")], SelectMode::None);
+        buf.set_settings(Config::parse("tab_width = 4\ncomment_prefix = \"//\"").resolve(None));
 
-        assert_eq!(1, buf.rows.len());
-        assert_eq!(&['a', 'b', 'c'], buf.rows[0].column());
-        assert!(buf.cached());
+        assert_eq!(5, buf.rows[0].width(buf.tab_width()));
+        assert_eq!("//", buf.comment().prefix());
         assert!(buf.updated());
-        assert_eq!(1, buf.history.len());
     }
 
     #[test]
-    fn buffer_insert_chars_2row_start() {
-        let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a']);
-        init_screen(&mut buf);
+    fn buffer_set_settings_filetype() {
+        let config = Config::parse("tab_width = 8\n[filetypes.rs]\ntab_width = 2");
+        let mut buf = text_buffer(&["\ta"]);
 
-        buf.insert_chars(&(0, 0), &[Row::from("b"), Row::from("c")], SelectMode::None);
+        buf.set_settings(config.resolve(Some(Path::new("a.rs"))));
+        assert_eq!(3, buf.rows[0].width(buf.tab_width()));
+        assert_eq!(1, buf.rows[0].width_range(1..2, buf.tab_width()));
 
-        assert_eq!(2, buf.rows.len());
-        assert_eq!(&['b'], buf.rows[0].column());
-        assert_eq!(&['c', 'a'], buf.rows[1].column());
-        assert!(buf.cached());
-        assert!(buf.updated());
-        assert_eq!(1, buf.history.len());
+        buf.set_settings(config.resolve(Some(Path::new("a.txt"))));
+        assert_eq!(9, buf.rows[0].width(buf.tab_width()));
     }
 
     #[test]
-    fn buffer_insert_chars_2row_start_empty() {
-        let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a']);
+    fn buffer_tabs_to_spaces() {
+        let mut buf = text_buffer(&["\ta\tb", "\t\t c", "", "d"]);
+        buf.set_settings(Config::parse("tab_width = 4").resolve(None));
         init_screen(&mut buf);
 
-        buf.insert_chars(&(0, 0), &[Row::from(""), Row::from("c")], SelectMode::None);
+        assert!(buf.tabs_to_spaces(0..4));
 
-        assert_eq!(2, buf.rows.len());
-        assert!(buf.rows[0].is_empty());
-        assert_eq!(&['c', 'a'], buf.rows[1].column());
-        assert!(buf.cached());
-        assert!(buf.updated());
-        assert_eq!(1, buf.history.len());
+        assert_eq!(Some("    a\tb".to_string()), buf.line_text(0));
+        assert_eq!(Some("         c".to_string()), buf.line_text(1));
+        assert_eq!(Some("d".to_string()), buf.line_text(3));
+        assert!(!buf.tabs_to_spaces(0..4));
+
+        buf.undo();
+        assert_eq!(Some("\ta\tb".to_string()), buf.line_text(0));
+        assert_eq!(Some("\t\t c".to_string()), buf.line_text(1));
     }
 
     #[test]
-    fn buffer_insert_chars_2row_end() {
-        let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a']);
+    fn buffer_set_tab_width() {
+        let mut buf = text_buffer(&["\ta", "x\tb"]);
         init_screen(&mut buf);
 
-        buf.insert_chars(&(1, 0), &[Row::from("b"), Row::from("c")], SelectMode::None);
+        for width in [2, 4, 8] {
+            buf.set_tab_width(width);
 
-        assert_eq!(2, buf.rows.len());
-        assert_eq!(&['a', 'b'], buf.rows[0].column());
-        assert_eq!(&['c'], buf.rows[1].column());
-        assert!(buf.cached());
-        assert!(buf.updated());
-        assert_eq!(1, buf.history.len());
+            assert_eq!(width, buf.settings().tab_width());
+            assert!(buf.row_updated(1));
+            assert_eq!(width + 1, buf.rows[0].width(buf.tab_width()));
+            assert_eq!(width + 1, buf.rows[1].width_range(0..3, buf.tab_width()));
+            assert_eq!((width, 0), Cursor::from((1, 0)).render(&buf));
+            assert_eq!((width, 1), Cursor::from((2, 1)).render(&buf));
+            init_screen(&mut buf);
+        }
+
+        buf.set_tab_width(0);
+        assert_eq!(8, buf.settings().tab_width());
     }
 
     #[test]
-    fn buffer_insert_chars_2row_end_empty() {
-        let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a']);
-        init_screen(&mut buf);
+    fn buffer_set_tab_width_per_buffer() {
+        let mut buf2 = text_buffer(&["\ta"]);
+        let mut buf4 = text_buffer(&["\ta"]);
 
-        buf.insert_chars(&(1, 0), &[Row::from("b"), Row::from("")], SelectMode::None);
+        buf2.set_tab_width(2);
+        buf4.set_tab_width(4);
 
-        assert_eq!(2, buf.rows.len());
-        assert_eq!(&['a', 'b'], buf.rows[0].column());
-        assert!(buf.rows[1].is_empty());
-        assert!(buf.cached());
-        assert!(buf.updated());
-        assert_eq!(1, buf.history.len());
+        assert_eq!(3, buf2.rows[0].width(buf2.tab_width()));
+        assert_eq!(5, buf4.rows[0].width(buf4.tab_width()));
+        assert_eq!((2, 0), Cursor::from((1, 0)).render(&buf2));
+        assert_eq!((4, 0), Cursor::from((1, 0)).render(&buf4));
     }
 
     #[test]
-    fn buffer_insert_chars_3row_1() {
-        let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a', 'b']);
+    fn buffer_spaces_to_tabs() {
+        let mut buf = text_buffer(&["    a  b", "      c", "  d", "\t e"]);
+        buf.set_settings(Config::parse("tab_width = 4").resolve(None));
         init_screen(&mut buf);
 
-        buf.insert_chars(
-            &(1, 0),
-            &[Row::from("c"), Row::from("d"), Row::from("e")],
-            SelectMode::None,
-        );
+        assert!(buf.spaces_to_tabs(0..3));
 
-        assert_eq!(3, buf.rows.len());
-        assert_eq!(&['a', 'c'], buf.rows[0].column());
-        assert_eq!(&['d'], buf.rows[1].column());
-        assert_eq!(&['e', 'b'], buf.rows[2].column());
-        assert!(buf.cached());
-        assert!(buf.updated());
+        assert_eq!(Some("\ta  b".to_string()), buf.line_text(0));
+        assert_eq!(Some("\t  c".to_string()), buf.line_text(1));
+        assert_eq!(Some("  d".to_string()), buf.line_text(2));
+        assert_eq!(Some("\t e".to_string()), buf.line_text(3));
         assert_eq!(1, buf.history.len());
     }
 
     #[test]
-    fn buffer_insert_chars_3row_rectangle() {
+    fn buffer_toggle_comment_blank() {
         let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a', 'b']);
+        buf.insert_row(&(0, 0), &[' ']);
         init_screen(&mut buf);
 
-        buf.insert_chars(
-            &(1, 0),
-            &[Row::from("c"), Row::from("d"), Row::from("e")],
-            SelectMode::Rectangle,
-        );
+        let ret = buf.toggle_comment(0..2);
 
-        assert_eq!(3, buf.rows.len());
-        assert_eq!(&['a', 'c', 'b'], buf.rows[0].column());
-        assert_eq!(&[' ', 'd'], buf.rows[1].column());
-        assert_eq!(&[' ', 'e'], buf.rows[2].column());
-        assert!(buf.cached());
-        assert!(buf.updated());
-        assert_eq!(1, buf.history.len());
+        assert!(!ret);
+        assert_eq!(" ", buf.rows[0].to_string_at(0));
+        assert!(!buf.cached());
     }
 
     #[test]
-    fn buffer_insert_chars_xoverflow() {
+    fn buffer_char_at() {
         let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a']);
+        buf.insert_row(&(0, 0), &['a', ',']);
+        buf.insert_row(&(0, 1), &[]);
         init_screen(&mut buf);
 
-        buf.insert_chars(&(2, 0), &[Row::from("bc")], SelectMode::None);
-
-        assert_eq!(1, buf.rows.len());
-        assert_eq!(&['a'], buf.rows[0].column());
-        assert!(!buf.cached());
-        assert!(!buf.updated());
-        assert_eq!(0, buf.history.len());
+        assert_eq!(Some('a'), buf.char_at(&(0, 0)));
+        assert_eq!(Some(','), buf.char_at(&(1, 0)));
+        assert_eq!(None, buf.char_at(&(2, 0)));
+        assert_eq!(None, buf.char_at(&(0, 1)));
+        assert_eq!(None, buf.char_at(&(0, 2)));
     }
 
-    #[test]
-    fn buffer_insert_char_yoverflow() {
+    fn indented_buffer() -> Buffer {
         let mut buf = Buffer::default();
         buf.insert_row(&(0, 0), &['a']);
+        buf.insert_row(&(0, 1), &[' ', ' ', 'b']);
+        buf.insert_row(&(0, 2), &[' ', ' ', ' ', ' ', 'c']);
+        buf.insert_row(&(0, 3), &[]);
+        buf.insert_row(&(0, 4), &['\t', 'd']);
+        buf.insert_row(&(0, 5), &[' ', ' ', 'e']);
+        buf.insert_row(&(0, 6), &['f']);
         init_screen(&mut buf);
+        buf
+    }
 
-        buf.insert_char(&(0, 1), 'b');
+    #[test]
+    fn buffer_next_indent_row() {
+        let buf = indented_buffer();
 
-        assert_eq!(1, buf.rows.len());
-        assert_eq!(&['a'], buf.rows[0].column());
-        assert!(!buf.cached());
-        assert!(!buf.updated());
-        assert_eq!(0, buf.history.len());
+        assert_eq!(Some(6), buf.next_indent_row(0));
+        assert_eq!(Some(5), buf.next_indent_row(1));
+        assert_eq!(Some(5), buf.next_indent_row(2));
+        assert_eq!(None, buf.next_indent_row(3));
+        assert_eq!(Some(5), buf.next_indent_row(4));
+        assert_eq!(None, buf.next_indent_row(6));
+        assert_eq!(None, buf.next_indent_row(7));
     }
 
     #[test]
-    fn buffer_replace() {
-        let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a', 'b', 'c']);
-        init_screen(&mut buf);
-
-        buf.replace(&(0, 0), 2, &['d']);
+    fn buffer_prev_indent_row() {
+        let buf = indented_buffer();
 
-        assert_eq!(&['d', 'c'], buf.rows[0].column());
+        assert_eq!(Some(0), buf.prev_indent_row(6));
+        assert_eq!(Some(1), buf.prev_indent_row(5));
+        assert_eq!(Some(2), buf.prev_indent_row(4));
+        assert_eq!(Some(1), buf.prev_indent_row(2));
+        assert_eq!(None, buf.prev_indent_row(0));
+        assert_eq!(None, buf.prev_indent_row(3));
     }
 
     #[test]
-    fn buffer_replace_yoverflow() {
+    fn buffer_indent_unit() {
         let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a', 'b', 'c']);
+        buf.insert_row(&(0, 0), &['a']);
+        buf.insert_row(&(0, 1), &[' ', ' ', 'b']);
+        buf.insert_row(&(0, 2), &[' ', ' ', ' ', ' ', 'c']);
         init_screen(&mut buf);
 
-        buf.replace(&(0, 1), 2, &['d']);
-
-        assert_eq!(&['a', 'b', 'c'], buf.rows[0].column());
+        assert_eq!(vec![' ', ' '], buf.indent_unit());
     }
 
     #[test]
-    fn buffer_rfind_at_0() {
+    fn buffer_indent_unit_tab() {
         let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a', 'b', 'c']);
-        buf.insert_row(&(0, 1), &['a', 'b', 'c']);
-        buf.insert_row(&(0, 2), &['a', 'b', 'c']);
+        buf.insert_row(&(0, 0), &['a']);
+        buf.insert_row(&(0, 1), &['\t', 'b']);
         init_screen(&mut buf);
 
-        let at = buf.rfind_at(&(0, 3), "bc");
-
-        assert_eq!(Some((1, 2)), at);
+        assert_eq!(vec!['\t'], buf.indent_unit());
     }
 
     #[test]
-    fn buffer_rfind_at_1() {
+    fn buffer_indent_unit_default() {
         let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a', 'b', 'c']);
-        buf.insert_row(&(0, 1), &['a', 'b', 'c']);
-        buf.insert_row(&(0, 2), &['a', 'b', 'c']);
+        buf.insert_row(&(0, 0), &['a']);
         init_screen(&mut buf);
 
-        let at = buf.rfind_at(&(1, 1), "bc");
+        assert_eq!(vec![' '; 4], buf.indent_unit());
 
-        assert_eq!(Some((1, 0)), at);
+        let mut indent = AutoIndent::default();
+        indent.set_unit(Some("\t"));
+        buf.set_auto_indent(indent);
+        assert_eq!(vec!['\t'], buf.indent_unit());
     }
 
     #[test]
-    fn buffer_rfind_at_notfound() {
+    fn buffer_new_line_indent() {
         let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a', 'b', 'c']);
-        buf.insert_row(&(0, 1), &['a', 'b', 'c']);
-        buf.insert_row(&(0, 2), &['a', 'b', 'c']);
+        buf.set_auto_indent(AutoIndent::from_extension("py"));
+        buf.insert_row(&(0, 0), &[' ', ' ', 'i', 'f', ' ', 'x', ':']);
         init_screen(&mut buf);
 
-        let at = buf.rfind_at(&(1, 0), "bc");
-
-        assert_eq!(None, at);
+        assert_eq!(vec![' '; 2], buf.new_line_indent(&(4, 0)));
+        // Detected unit is 2 spaces.
+        assert_eq!(vec![' '; 4], buf.new_line_indent(&(7, 0)));
+        assert_eq!(vec![' '; 1], buf.new_line_indent(&(1, 0)));
+        assert!(buf.new_line_indent(&(0, 1)).is_empty());
     }
 
     #[test]
-    fn buffer_row_char_len() {
+    fn buffer_new_line_indent_closer() {
         let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a']);
+        buf.set_auto_indent(AutoIndent::from_extension("rs"));
+        buf.insert_row(&(0, 0), &['\t', '{', '}']);
         init_screen(&mut buf);
 
-        let len = buf.row_char_len(&(0, 0));
-
-        assert_eq!(1, len);
+        assert_eq!(vec!['\t'], buf.new_line_indent(&(2, 0)));
+        assert_eq!(vec!['\t'], buf.new_line_indent(&(3, 0)));
     }
 
     #[test]
-    fn buffer_row_char_len_yoverflow() {
+    fn buffer_line_text() {
         let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a']);
+        buf.insert_row(&(0, 0), &['a', 'b']);
+        buf.insert_row(&(0, 1), &[]);
         init_screen(&mut buf);
 
-        let len = buf.row_char_len(&(0, 1));
-
-        assert_eq!(0, len);
+        assert_eq!(Some("ab".to_string()), buf.line_text(0));
+        assert_eq!(Some("".to_string()), buf.line_text(1));
+        assert_eq!(None, buf.line_text(2));
     }
 
     #[test]
-    fn buffer_rows() {
+    fn buffer_set_all_rows() {
         let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a']);
+        buf.insert_row(&(0, 0), &['a', 'b']);
+        buf.insert_row(&(0, 1), &['c']);
+        buf.insert_row(&(0, 2), &['d']);
         init_screen(&mut buf);
 
-        let len = buf.rows();
+        buf.set_all_rows(&(1, 2), vec![Row::from("x")]);
 
-        assert_eq!(1, len);
+        assert_eq!(1, buf.rows());
+        assert_eq!(&['x'], buf.rows[0].column());
+        assert!(buf.cached());
+        assert!(buf.row_updated(2));
+        assert_eq!(1, buf.history.len());
+
+        let mut cursor = Cursor::default();
+        cursor.set(&buf, &(1, 2));
+        assert_eq!(Cursor::from((0, 1)), cursor);
+
+        assert_eq!(Some((1, 2)), buf.undo());
+        assert_eq!(3, buf.rows());
+        assert_eq!(&['a', 'b'], buf.rows[0].column());
+        assert_eq!(&['c'], buf.rows[1].column());
+        assert_eq!(&['d'], buf.rows[2].column());
+        assert_eq!(0, buf.history.len());
     }
 
     #[test]
-    fn buffer_paste_pending() {
+    fn buffer_undo_poisoned_history() {
         let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a']);
-        buf.pending = Some((vec![Row::from("b")], SelectMode::None));
+        buf.insert_row(&(0, 0), &['a', 'b']);
         init_screen(&mut buf);
 
-        buf.paste_pending(&(0, 0));
+        let ops = vec![
+            Operation::Append((5, 0)),
+            Operation::DeleteChar((0, 0), 'x'),
+            Operation::DeleteChar((9, 0), 'x'),
+            Operation::DeleteChars((0, 9), vec![Row::from("x")], SelectMode::None),
+            Operation::DeleteChars((0, 9), vec![Row::from("x")], SelectMode::Rectangle),
+            Operation::DeleteRow((0, 9), Row::from("x")),
+            Operation::InsertChars((1, 0), (0, 0), SelectMode::None),
+            Operation::InsertChars((2, 0), (1, 1), SelectMode::Rectangle),
+            Operation::SquashRow((5, 0)),
+        ];
+        for op in ops {
+            buf.history.record((9, 9), op);
+        }
 
-        assert_eq!(&['b', 'a'], buf.rows[0].column());
-        assert!(buf.cached());
-        assert!(buf.updated());
-        assert_eq!(1, buf.history.len());
+        for _ in 0..9 {
+            assert_eq!(Some((9, 9)), buf.undo());
+        }
+
+        assert_eq!(None, buf.undo());
+        assert_eq!(1, buf.rows());
+        assert_eq!(&['a', 'b'], buf.rows[0].column());
     }
 
     #[test]
-    fn buffer_save() {
+    fn buffer_undo_poisoned_group() {
         let mut buf = Buffer::default();
-        buf.set_filename(&PathBuf::from("a.txt"));
         buf.insert_row(&(0, 0), &['a']);
-        buf.history.clear();
+        init_screen(&mut buf);
 
-        let ret = buf.save();
+        buf.insert_char(&(1, 0), 'b');
+        buf.history
+            .record((0, 0), Operation::DeleteRow((0, 5), Row::from("x")));
+        buf.history.group(0);
 
-        assert!(ret.is_ok());
-        assert!(!buf.cached());
-        assert!(buf.updated());
+        assert_eq!(Some((1, 0)), buf.undo());
+        assert_eq!(1, buf.rows());
+        assert_eq!(&['a'], buf.rows[0].column());
     }
 
     #[test]
-    fn buffer_save_none() {
+    fn buffer_redo_operations() {
         let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a']);
+        buf.insert_row(&(0, 0), &['a', 'b']);
+        init_screen(&mut buf);
         buf.history.clear();
+        let initial = texts(&buf);
 
-        let ret = buf.save();
+        buf.insert_char(&(1, 0), 'x');
+        buf.delete_char(&(3, 0));
+        buf.split_row(&(1, 0));
+        buf.append_row(&(0, 1), &['c', 'd']);
+        buf.shrink_row(&(2, 1));
+        buf.insert_row(&(0, 2), &['e']);
+        buf.squash_row(&(0, 2));
+        buf.delete_row(&(0, 0));
+        buf.replace(&(0, 0), 1, &['y', 'z']);
+        buf.set_all_rows(&(0, 0), vec![Row::from("w"), Row::from("yzce")]);
+        let edited = texts(&buf);
+        let steps = buf.history.len();
 
-        assert!(ret.is_ok());
-        assert!(buf.cached());
-        assert!(buf.updated());
+        for _ in 0..steps {
+            assert!(buf.undo().is_some());
+        }
+        assert_eq!(initial, texts(&buf));
+
+        for _ in 0..steps {
+            assert!(buf.redo().is_some());
+        }
+        assert_eq!(None, buf.redo());
+        assert_eq!(edited, texts(&buf));
+
+        for _ in 0..steps {
+            assert!(buf.undo().is_some());
+        }
+        assert_eq!(initial, texts(&buf));
     }
 
     #[test]
-    fn buffer_shrink_row() {
+    fn buffer_redo_insert_chars_multi_row() {
         let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a', 'b', 'c']);
+        buf.insert_row(&(0, 0), &['a', 'b']);
+        buf.insert_row(&(0, 1), &['c', 'd']);
         init_screen(&mut buf);
 
-        buf.shrink_row(&(1, 0));
+        let rows = vec![Row::from("x"), Row::from("y")];
+        buf.insert_chars(&(1, 0), &rows, SelectMode::Rectangle);
+        buf.insert_chars(&(0, 0), &rows, SelectMode::None);
+        let edited = texts(&buf);
 
-        assert_eq!(1, buf.rows());
-        assert_eq!(&['a'], buf.rows[0].column());
-        assert!(buf.cached());
-        assert!(buf.updated());
-        assert_eq!(1, buf.history.len());
+        assert_eq!(Some((0, 0)), buf.undo());
+        assert_eq!(Some((1, 0)), buf.undo());
+        assert_eq!(vec!["ab", "cd"], texts(&buf));
+
+        assert_eq!(Some((1, 0)), buf.redo());
+        assert_eq!(vec!["axb", "cyd"], texts(&buf));
+        assert_eq!(Some((0, 0)), buf.redo());
+        assert_eq!(edited, texts(&buf));
     }
 
     #[test]
-    fn buffer_shrink_row_yoverflow() {
+    fn buffer_redo_delete_chars_multi_row() {
         let mut buf = Buffer::default();
         buf.insert_row(&(0, 0), &['a', 'b', 'c']);
+        buf.insert_row(&(0, 1), &['d', 'e', 'f']);
         init_screen(&mut buf);
 
-        buf.shrink_row(&(1, 1));
+        buf.delete_chars(&(1, 0), &(2, 1), SelectMode::Rectangle);
+        assert_eq!(vec!["ac", "df"], texts(&buf));
+        buf.delete_chars(&(1, 0), &(1, 1), SelectMode::None);
+        assert_eq!(vec!["af"], texts(&buf));
 
-        assert_eq!(1, buf.rows());
-        assert_eq!(&['a', 'b', 'c'], buf.rows[0].column());
-        assert!(!buf.cached());
-        assert!(!buf.updated());
-        assert_eq!(0, buf.history.len());
+        buf.undo();
+        buf.undo();
+        assert_eq!(vec!["abc", "def"], texts(&buf));
+
+        buf.redo();
+        assert_eq!(vec!["ac", "df"], texts(&buf));
+        buf.redo();
+        assert_eq!(vec!["af"], texts(&buf));
     }
 
     #[test]
-    fn buffer_split_row() {
+    fn buffer_redo_group() {
         let mut buf = Buffer::default();
         buf.insert_row(&(0, 0), &['a', 'b', 'c']);
         init_screen(&mut buf);
 
-        buf.split_row(&(1, 0));
+        buf.replace_range(&(1, 0), &(2, 0), &[Row::from("x"), Row::from("y")]);
+        assert_eq!(vec!["ax", "yc"], texts(&buf));
 
-        assert_eq!(2, buf.rows());
-        assert_eq!(&['a'], buf.rows[0].column());
-        assert_eq!(&['b', 'c'], buf.rows[1].column());
-        assert!(buf.cached());
-        assert!(buf.updated());
-        assert_eq!(1, buf.history.len());
+        assert_eq!(Some((1, 0)), buf.undo());
+        assert_eq!(vec!["abc"], texts(&buf));
+        assert_eq!(Some((1, 0)), buf.redo());
+        assert_eq!(vec!["ax", "yc"], texts(&buf));
     }
 
     #[test]
-    fn buffer_split_row_start() {
+    fn buffer_redo_after_edit() {
         let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a', 'b', 'c']);
+        buf.insert_row(&(0, 0), &['a']);
         init_screen(&mut buf);
 
-        buf.split_row(&(0, 0));
+        buf.insert_char(&(1, 0), 'b');
+        buf.undo();
+        buf.insert_char(&(1, 0), 'c');
 
-        assert_eq!(2, buf.rows());
-        assert!(buf.rows[0].is_empty());
-        assert_eq!(&['a', 'b', 'c'], buf.rows[1].column());
-        assert!(buf.cached());
-        assert!(buf.updated());
-        assert_eq!(1, buf.history.len());
+        assert_eq!(None, buf.redo());
+        assert_eq!(vec!["ac"], texts(&buf));
     }
 
     #[test]
-    fn buffer_split_row_end() {
+    fn buffer_try_append_row() {
         let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a', 'b', 'c']);
+        buf.insert_row(&(0, 0), &['a']);
         init_screen(&mut buf);
 
-        buf.split_row(&(3, 0));
+        buf.try_append_row(&(0, 0), &['b']).unwrap();
 
-        assert_eq!(2, buf.rows());
-        assert_eq!(&['a', 'b', 'c'], buf.rows[0].column());
-        assert!(buf.rows[1].is_empty());
-        assert!(buf.cached());
-        assert!(buf.updated());
+        assert_eq!(&['a', 'b'], buf.rows[0].column());
         assert_eq!(1, buf.history.len());
     }
 
     #[test]
-    fn buffer_split_row_yoverflow() {
+    fn buffer_try_append_row_yoverflow() {
         let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a', 'b', 'c']);
+        buf.insert_row(&(0, 0), &['a']);
         init_screen(&mut buf);
 
-        buf.split_row(&(1, 1));
+        let ret = buf.try_append_row(&(0, 1), &['b']);
 
+        assert!(matches!(ret, Err(Error::OutOfRange(0, 1))));
         assert_eq!(1, buf.rows());
-        assert_eq!(&['a', 'b', 'c'], buf.rows[0].column());
         assert!(!buf.cached());
-        assert!(!buf.updated());
         assert_eq!(0, buf.history.len());
     }
 
     #[test]
-    fn buffer_squash_row() {
+    fn buffer_try_insert_char() {
         let mut buf = Buffer::default();
         buf.insert_row(&(0, 0), &['a']);
-        buf.insert_row(&(0, 1), &['b']);
         init_screen(&mut buf);
 
-        buf.squash_row(&(0, 1));
+        buf.try_insert_char(&(1, 0), 'b').unwrap();
 
-        assert_eq!(1, buf.rows());
         assert_eq!(&['a', 'b'], buf.rows[0].column());
-        assert!(buf.cached());
-        assert!(buf.updated());
         assert_eq!(1, buf.history.len());
     }
 
     #[test]
-    fn buffer_squash_start() {
+    fn buffer_try_insert_char_xoverflow() {
         let mut buf = Buffer::default();
         buf.insert_row(&(0, 0), &['a']);
-        buf.insert_row(&(0, 1), &['b']);
         init_screen(&mut buf);
 
-        buf.squash_row(&(0, 0));
+        let ret = buf.try_insert_char(&(2, 0), 'b');
 
-        assert_eq!(2, buf.rows());
+        assert!(matches!(ret, Err(Error::OutOfRange(2, 0))));
         assert_eq!(&['a'], buf.rows[0].column());
-        assert_eq!(&['b'], buf.rows[1].column());
-        assert!(!buf.cached());
-        assert!(!buf.updated());
         assert_eq!(0, buf.history.len());
     }
 
     #[test]
-    fn buffer_squash_row_yoverflow() {
+    fn buffer_try_insert_char_yoverflow() {
         let mut buf = Buffer::default();
-        buf.insert_row(&(0, 0), &['a']);
-        buf.insert_row(&(0, 1), &['b']);
         init_screen(&mut buf);
 
-        buf.squash_row(&(0, 2));
+        let ret = buf.try_insert_char(&(0, 0), 'b');
 
-        assert_eq!(2, buf.rows());
-        assert_eq!(&['a'], buf.rows[0].column());
-        assert_eq!(&['b'], buf.rows[1].column());
-        assert!(!buf.cached());
-        assert!(!buf.updated());
-        assert_eq!(0, buf.history.len());
+        assert!(matches!(ret, Err(Error::OutOfRange(0, 0))));
+        assert_eq!(0, buf.rows());
+    }
+
+    #[test]
+    fn buffer_word_at() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'b', ',', ' ', 'c']);
+        buf.insert_row(&(0, 1), &[]);
+        init_screen(&mut buf);
+
+        assert_eq!(Some((0..2, "ab".to_string())), buf.word_at(&(0, 0)));
+        assert_eq!(Some((0..2, "ab".to_string())), buf.word_at(&(2, 0)));
+        assert_eq!(None, buf.word_at(&(3, 0)));
+        assert_eq!(Some((4..5, "c".to_string())), buf.word_at(&(5, 0)));
+        assert_eq!(None, buf.word_at(&(0, 1)));
+        assert_eq!(None, buf.word_at(&(0, 2)));
+    }
+
+    #[test]
+    fn buffer_word_range_at() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['f', 'o', 'o', '-', 'b', 'a', 'r']);
+        init_screen(&mut buf);
+
+        assert_eq!(Some(0..3), buf.word_range_at(&(1, 0)));
+        assert_eq!(Some(0..3), buf.word_range_at(&(3, 0)));
+        assert_eq!(Some(4..7), buf.word_range_at(&(4, 0)));
+        assert_eq!(Some(4..7), buf.word_range_at(&(7, 0)));
+    }
+
+    #[test]
+    fn buffer_word_range_at_hyphen() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['f', 'o', 'o', '-', 'b', 'a', 'r']);
+        init_screen(&mut buf);
+
+        let mut word_chars = WordChars::default();
+        word_chars.set('-', true);
+        buf.set_word_chars(word_chars);
+
+        assert_eq!(Some(0..7), buf.word_range_at(&(1, 0)));
+        assert_eq!(Some(0..7), buf.word_range_at(&(3, 0)));
+        assert_eq!(Some(0..7), buf.word_range_at(&(7, 0)));
+    }
+
+    #[test]
+    fn buffer_word_range_at_notfound() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', ' ', ' ', 'b']);
+        init_screen(&mut buf);
+
+        assert_eq!(None, buf.word_range_at(&(2, 0)));
+        assert_eq!(None, buf.word_range_at(&(0, 1)));
     }
 
     // -------------------------------------------------------------------------------------------
@@ -1944,7 +6048,7 @@ mod tests {
     fn row_shrink_width_1() {
         let mut buf = Row::from(&['a', 'b', 'c'][..]);
 
-        let removed = buf.shrink_width(1);
+        let removed = buf.shrink_width(1, TAB_STOP);
 
         assert_eq!(&['b', 'c'], buf.column());
         assert_eq!(1, removed)
@@ -1954,7 +6058,7 @@ mod tests {
     fn row_shrink_width_2() {
         let mut buf = Row::from(&['あ', 'い', 'う'][..]);
 
-        let removed = buf.shrink_width(2);
+        let removed = buf.shrink_width(2, TAB_STOP);
 
         assert_eq!(&['い', 'う'], buf.column());
         assert_eq!(2, removed)
@@ -1964,7 +6068,7 @@ mod tests {
     fn row_shrink_width_3() {
         let mut buf = Row::from(&['あ', 'い', 'う'][..]);
 
-        let removed = buf.shrink_width(3);
+        let removed = buf.shrink_width(3, TAB_STOP);
 
         assert_eq!(&['う'], buf.column());
         assert_eq!(4, removed)
@@ -1974,7 +6078,7 @@ mod tests {
     fn row_shrink_width_all() {
         let mut buf = Row::from(&['a', 'b', 'c'][..]);
 
-        let removed = buf.shrink_width(3);
+        let removed = buf.shrink_width(3, TAB_STOP);
 
         assert!(buf.is_empty());
         assert_eq!(3, removed)
@@ -1984,7 +6088,7 @@ mod tests {
     fn row_slice_width_0() {
         let buf = Row::from(&['a', 'b', 'c'][..]);
 
-        let render = buf.slice_width(1..1);
+        let render = buf.slice_width(1..1, TAB_STOP);
 
         assert!(render.is_empty());
     }
@@ -1993,7 +6097,7 @@ mod tests {
     fn row_slice_width_1() {
         let buf = Row::from(&['a', 'b', 'c'][..]);
 
-        let render = buf.slice_width(1..2);
+        let render = buf.slice_width(1..2, TAB_STOP);
 
         assert_eq!(&['b'], render.column());
     }
@@ -2002,7 +6106,7 @@ mod tests {
     fn row_slice_width_2() {
         let buf = Row::from(&['あ', 'い', 'う'][..]);
 
-        let render = buf.slice_width(2..4);
+        let render = buf.slice_width(2..4, TAB_STOP);
 
         assert_eq!(&['い'], render.column());
     }
@@ -2011,7 +6115,7 @@ mod tests {
     fn row_slice_width_4() {
         let buf = Row::from(&['あ', 'い', 'う'][..]);
 
-        let render = buf.slice_width(1..5);
+        let render = buf.slice_width(1..5, TAB_STOP);
 
         assert_eq!(&[' ', 'い', ' '], render.column());
     }
@@ -2057,7 +6161,7 @@ mod tests {
     fn row_truncate_width_0() {
         let mut buf = Row::from(&['a', 'b', 'c'][..]);
 
-        let rest = buf.truncate_width(0);
+        let rest = buf.truncate_width(0, TAB_STOP);
 
         assert!(buf.is_empty());
         assert_eq!(0, rest);
@@ -2067,7 +6171,7 @@ mod tests {
     fn row_truncate_width_1() {
         let mut buf = Row::from(&['a', 'b', 'c'][..]);
 
-        let rest = buf.truncate_width(1);
+        let rest = buf.truncate_width(1, TAB_STOP);
 
         assert_eq!(&['a'], buf.column());
         assert_eq!(1, rest);
@@ -2077,7 +6181,7 @@ mod tests {
     fn row_truncate_width_2() {
         let mut buf = Row::from(&['あ', 'い', 'う'][..]);
 
-        let rest = buf.truncate_width(3);
+        let rest = buf.truncate_width(3, TAB_STOP);
 
         assert_eq!(&['あ'], buf.column());
         assert_eq!(2, rest);
@@ -2168,27 +6272,67 @@ mod tests {
     fn row_width_1() {
         let buf = Row::from(&['a', 'b', 'c'][..]);
 
-        assert_eq!(3, buf.width());
+        assert_eq!(3, buf.width(TAB_STOP));
     }
 
     #[test]
     fn row_width_2() {
         let buf = Row::from(&['あ', 'い', 'う'][..]);
 
-        assert_eq!(6, buf.width());
+        assert_eq!(6, buf.width(TAB_STOP));
     }
 
     #[test]
     fn row_width_range_1() {
         let buf = Row::from(&['a', 'b', 'c'][..]);
 
-        assert_eq!(2, buf.width_range(0..2));
+        assert_eq!(2, buf.width_range(0..2, TAB_STOP));
     }
 
     #[test]
     fn row_width_range_2() {
         let buf = Row::from(&['あ', 'い', 'う'][..]);
 
-        assert_eq!(4, buf.width_range(0..2));
+        assert_eq!(4, buf.width_range(0..2, TAB_STOP));
+    }
+
+    // -------------------------------------------------------------------------------------------
+
+    #[test]
+    fn is_binary_empty() {
+        assert!(!is_binary(b""));
+    }
+
+    #[test]
+    fn is_binary_text() {
+        assert!(!is_binary(
+            b"fn main() {\r\n\tprintln!(\"\x1B[0m\");\r\n}\r\n"
+        ));
+        assert!(!is_binary("あいう\n".as_bytes()));
+    }
+
+    #[test]
+    fn is_binary_utf16() {
+        let le = "\u{FEFF}abc\r\n"
+            .encode_utf16()
+            .flat_map(|c| c.to_le_bytes())
+            .collect::<Vec<u8>>();
+        let be = "abc\r\n"
+            .encode_utf16()
+            .flat_map(|c| c.to_be_bytes())
+            .collect::<Vec<u8>>();
+
+        assert!(!is_binary(&le));
+        assert!(!is_binary(&be));
+    }
+
+    #[test]
+    fn is_binary_nul() {
+        assert!(is_binary(b"MZ\x90\0\x03\0\0\0\x04\0\0\0\xFF\xFF\0\0"));
+    }
+
+    #[test]
+    fn is_binary_controls() {
+        assert!(is_binary(b"\x89PNG\r\n\x1A\n\x02\x03\x04\x05\x06\x07"));
     }
 }
@@ -0,0 +1,1092 @@
+use crate::highlight::{PrefixComment, Syntax};
+use crate::log_warn;
+use crate::Color;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use unicode_width::UnicodeWidthChar;
+
+const CONFIG_DIRECTORY: &str = "note";
+const CONFIG_FILENAME: &str = "config.toml";
+const TAB_WIDTH: usize = 8;
+const PASTE_CONFIRM_LIMIT: usize = 100_000;
+const UNDO_NEAR_SCREENS: usize = 1;
+const AUTO_SAVE_SECONDS: u64 = 30;
+const FILLER: char = '~';
+const WRAP_WIDTH: usize = 72;
+
+/// Auto-indent rule on new line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AutoIndent {
+    enabled: bool,
+    unit: Option<String>,
+    openers: Vec<String>,
+    closers: Vec<String>,
+}
+
+impl Default for AutoIndent {
+    /// Copy indentation of current row.
+    fn default() -> Self {
+        AutoIndent {
+            enabled: true,
+            unit: None,
+            openers: vec![],
+            closers: vec![],
+        }
+    }
+}
+
+impl AutoIndent {
+    /// Returns the rule for the file extension.
+    pub fn from_extension(extension: &str) -> Self {
+        let mut indent = AutoIndent::default();
+        match extension {
+            "py" => indent.set_openers(&[":"]),
+            "c" | "cc" | "cpp" | "cs" | "go" | "h" | "hpp" | "java" | "js" | "json" | "rs"
+            | "ts" => {
+                indent.set_openers(&["{", "[", "("]);
+                indent.set_closers(&["}", "]", ")"]);
+            }
+            _ => {}
+        }
+        indent
+    }
+
+    /// Returns whether the text starts with closer.
+    pub fn closes(&self, text: &str) -> bool {
+        let text = text.trim_start();
+        self.closers.iter().any(|c| text.starts_with(c.as_str()))
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Returns whether the text ends with opener.
+    pub fn opens(&self, text: &str) -> bool {
+        let text = text.trim_end();
+        self.openers.iter().any(|o| text.ends_with(o.as_str()))
+    }
+
+    pub fn set_closers(&mut self, closers: &[&str]) {
+        self.closers = closers.iter().map(|c| c.to_string()).collect();
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn set_openers(&mut self, openers: &[&str]) {
+        self.openers = openers.iter().map(|o| o.to_string()).collect();
+    }
+
+    /// Set indent unit, or `None` to detect from content.
+    pub fn set_unit(&mut self, unit: Option<&str>) {
+        self.unit = unit.map(|u| u.to_string());
+    }
+
+    pub fn unit(&self) -> Option<&str> {
+        self.unit.as_deref()
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+/// Line comment style.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Comment {
+    prefix: String,
+    indented: bool,
+}
+
+impl Default for Comment {
+    fn default() -> Self {
+        Comment {
+            prefix: "#".to_string(),
+            indented: false,
+        }
+    }
+}
+
+impl Comment {
+    /// Returns whether the prefix is inserted at first non-whitespace column.
+    pub fn indented(&self) -> bool {
+        self.indented
+    }
+
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Set whether the prefix is inserted at first non-whitespace column instead of column 0.
+    pub fn set_indented(&mut self, indented: bool) {
+        self.indented = indented;
+    }
+
+    pub fn set_prefix(&mut self, prefix: &str) {
+        self.prefix = prefix.to_string();
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+/// Global settings and overrides by file type.
+///
+/// ```toml
+/// tab_width = 8
+///
+/// [filetypes.rs]
+/// tab_width = 4
+/// expand_tabs = true
+///
+/// [filetypes."*.md"]
+/// trim_trailing_on_save = false
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Config {
+    global: Overrides,
+    filetypes: Vec<(String, Overrides)>,
+}
+
+impl Config {
+    /// Load `%APPDATA%\note\config.toml`, or returns default if not readable.
+    pub fn load() -> Self {
+        let path = env::var_os("APPDATA").map(|d| {
+            PathBuf::from(d)
+                .join(CONFIG_DIRECTORY)
+                .join(CONFIG_FILENAME)
+        });
+
+        match path.and_then(|p| fs::read_to_string(p).ok()) {
+            Some(text) => Config::parse(&text),
+            None => Config::default(),
+        }
+    }
+
+    /// Parse the subset of TOML. Invalid lines are logged and ignored.
+    pub fn parse(text: &str) -> Self {
+        let mut config = Config::default();
+        let mut section = Some(&mut config.global);
+
+        for (index, line) in text.lines().enumerate() {
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = match header.trim().strip_prefix("filetypes.") {
+                    Some(pattern) => {
+                        let pattern = unquote(pattern.trim()).to_string();
+                        config.filetypes.push((pattern, Overrides::default()));
+                        config.filetypes.last_mut().map(|(_, o)| o)
+                    }
+                    None => {
+                        log_warn!("config:{}: unknown section `{}`", index + 1, header);
+                        None
+                    }
+                };
+                continue;
+            }
+
+            let valid = match (line.split_once('='), section.as_mut()) {
+                (Some((key, value)), Some(overrides)) => overrides.set(key.trim(), value.trim()),
+                (Some(_), None) => true,
+                (None, _) => false,
+            };
+
+            if !valid {
+                log_warn!("config:{}: invalid setting `{}`", index + 1, line);
+            }
+        }
+
+        config
+    }
+
+    /// Returns the settings for the file.
+    /// The exact extension or file name takes precedence over glob, and glob over global.
+    pub fn resolve(&self, filename: Option<&Path>) -> Settings {
+        let mut settings = Settings::default();
+        self.global.apply(&mut settings);
+
+        let name = filename
+            .and_then(|f| f.file_name())
+            .and_then(|n| n.to_str());
+        let extension = filename
+            .and_then(|f| f.extension())
+            .and_then(|e| e.to_str());
+
+        if let Some(name) = name {
+            for (pattern, overrides) in &self.filetypes {
+                if is_glob(pattern) && glob_match(pattern, name) {
+                    overrides.apply(&mut settings);
+                }
+            }
+
+            for (pattern, overrides) in &self.filetypes {
+                if !is_glob(pattern) && (Some(pattern.as_str()) == extension || pattern == name) {
+                    overrides.apply(&mut settings);
+                }
+            }
+        }
+
+        settings
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+/// External command formatting stdin to stdout.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Formatter {
+    program: String,
+    args: Vec<String>,
+}
+
+impl Formatter {
+    pub fn new(program: &str, args: &[&str]) -> Self {
+        Formatter {
+            program: program.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+        }
+    }
+
+    /// Returns the formatter for the file extension.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "go" => Some(Formatter::new("gofmt", &[])),
+            "js" | "json" | "ts" => {
+                let path = format!("stdin.{}", extension);
+                Some(Formatter::new("prettier", &["--stdin-filepath", &path]))
+            }
+            "py" => Some(Formatter::new("black", &["-q", "-"])),
+            "rs" => Some(Formatter::new("rustfmt", &[])),
+            _ => None,
+        }
+    }
+
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    pub fn program(&self) -> &str {
+        &self.program
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+/// Line ending written at saving.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineEnding {
+    Crlf,
+    Lf,
+}
+
+impl fmt::Display for LineEnding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LineEnding::Crlf => write!(f, "crlf"),
+            LineEnding::Lf => write!(f, "lf"),
+        }
+    }
+}
+
+impl LineEnding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Crlf => "\r\n",
+            LineEnding::Lf => "\n",
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+/// Settings overridden by config.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Overrides {
+    tab_width: Option<usize>,
+    expand_tabs: Option<bool>,
+    comment_prefix: Option<String>,
+    comment_prefixes: Option<Vec<String>>,
+    word_chars: Option<String>,
+    filler: Option<char>,
+    line_ending: Option<LineEnding>,
+    trim_trailing_on_save: Option<bool>,
+    paste_confirm_limit: Option<usize>,
+    software_cursor: Option<bool>,
+    half_page_scroll: Option<bool>,
+    undo_cursor: Option<UndoCursor>,
+    undo_near_screens: Option<usize>,
+    wrap_width: Option<usize>,
+    verify_save: Option<bool>,
+    auto_pairs: Option<bool>,
+    insert_final_newline: Option<bool>,
+    write_bom: Option<bool>,
+    open_dropped_files: Option<bool>,
+    auto_save_seconds: Option<u64>,
+    text_color: Option<Color>,
+    comment_color: Option<Color>,
+    string_color: Option<Color>,
+    status_color: Option<Color>,
+    message_color: Option<Color>,
+}
+
+impl Overrides {
+    /// Overwrite the settings by specified values.
+    pub fn apply(&self, settings: &mut Settings) {
+        if let Some(tab_width) = self.tab_width {
+            settings.tab_width = tab_width;
+        }
+
+        if let Some(expand_tabs) = self.expand_tabs {
+            settings.expand_tabs = expand_tabs;
+        }
+
+        if let Some(comment_prefix) = self.comment_prefix.as_ref() {
+            settings.comment_prefix = comment_prefix.clone();
+        }
+
+        if let Some(prefixes) = self.comment_prefixes.as_ref() {
+            settings.comment_prefixes = PrefixComment::new(prefixes.clone());
+        }
+
+        if let Some(filler) = self.filler {
+            settings.filler = filler;
+        }
+
+        if let Some(extra) = self.word_chars.as_ref() {
+            settings.word_chars.extra = extra.chars().collect();
+        }
+
+        if let Some(line_ending) = self.line_ending {
+            settings.line_ending = line_ending;
+        }
+
+        if let Some(trim) = self.trim_trailing_on_save {
+            settings.trim_trailing_on_save = trim;
+        }
+
+        if let Some(limit) = self.paste_confirm_limit {
+            settings.paste_confirm_limit = limit;
+        }
+
+        if let Some(seconds) = self.auto_save_seconds {
+            settings.auto_save_seconds = seconds;
+        }
+
+        if let Some(color) = self.text_color {
+            settings.theme.text = color;
+        }
+
+        if let Some(color) = self.comment_color {
+            settings.theme.comment = color;
+        }
+
+        if let Some(color) = self.string_color {
+            settings.theme.string = color;
+        }
+
+        if let Some(color) = self.status_color {
+            settings.theme.status = color;
+        }
+
+        if let Some(color) = self.message_color {
+            settings.theme.message = color;
+        }
+
+        if let Some(software_cursor) = self.software_cursor {
+            settings.software_cursor = software_cursor;
+        }
+
+        if let Some(half_page_scroll) = self.half_page_scroll {
+            settings.half_page_scroll = half_page_scroll;
+        }
+
+        if let Some(undo_cursor) = self.undo_cursor {
+            settings.undo_cursor = undo_cursor;
+        }
+
+        if let Some(screens) = self.undo_near_screens {
+            settings.undo_near_screens = screens;
+        }
+
+        if let Some(width) = self.wrap_width {
+            settings.wrap_width = width;
+        }
+
+        if let Some(verify) = self.verify_save {
+            settings.verify_save = verify;
+        }
+
+        if let Some(auto_pairs) = self.auto_pairs {
+            settings.auto_pairs = auto_pairs;
+        }
+
+        if let Some(insert) = self.insert_final_newline {
+            settings.insert_final_newline = insert;
+        }
+
+        if let Some(bom) = self.write_bom {
+            settings.write_bom = bom;
+        }
+
+        if let Some(open) = self.open_dropped_files {
+            settings.open_dropped_files = open;
+        }
+    }
+
+    /// Set the value of the key, or returns `false` if the key or the value is invalid.
+    pub fn set(&mut self, key: &str, value: &str) -> bool {
+        match key {
+            "tab_width" => match value.parse::<usize>() {
+                Ok(width) if 0 < width => self.tab_width = Some(width),
+                _ => return false,
+            },
+            "expand_tabs" => match value.parse::<bool>() {
+                Ok(expand) => self.expand_tabs = Some(expand),
+                _ => return false,
+            },
+            "comment_prefix" => self.comment_prefix = Some(unquote(value).to_string()),
+            "filler" => {
+                let mut chars = unquote(value).chars();
+                match (chars.next(), chars.next()) {
+                    (Some(ch), None) if ch.width() == Some(1) => self.filler = Some(ch),
+                    _ => return false,
+                }
+            }
+            "word_chars" => self.word_chars = Some(unquote(value).to_string()),
+            "comment_prefixes" => {
+                let prefixes = unquote(value).split(',').map(|p| p.trim().to_string());
+                self.comment_prefixes = Some(prefixes.collect());
+            }
+            "line_ending" => match unquote(value) {
+                "crlf" => self.line_ending = Some(LineEnding::Crlf),
+                "lf" => self.line_ending = Some(LineEnding::Lf),
+                _ => return false,
+            },
+            "trim_trailing_on_save" => match value.parse::<bool>() {
+                Ok(trim) => self.trim_trailing_on_save = Some(trim),
+                _ => return false,
+            },
+            "paste_confirm_limit" => match value.parse::<usize>() {
+                Ok(limit) => self.paste_confirm_limit = Some(limit),
+                _ => return false,
+            },
+            "auto_save_seconds" => match value.parse::<u64>() {
+                Ok(seconds) => self.auto_save_seconds = Some(seconds),
+                _ => return false,
+            },
+            "text_color" => match parse_color(unquote(value)) {
+                Some(color) => self.text_color = Some(color),
+                None => return false,
+            },
+            "comment_color" => match parse_color(unquote(value)) {
+                Some(color) => self.comment_color = Some(color),
+                None => return false,
+            },
+            "string_color" => match parse_color(unquote(value)) {
+                Some(color) => self.string_color = Some(color),
+                None => return false,
+            },
+            "status_color" => match parse_color(unquote(value)) {
+                Some(color) => self.status_color = Some(color),
+                None => return false,
+            },
+            "message_color" => match parse_color(unquote(value)) {
+                Some(color) => self.message_color = Some(color),
+                None => return false,
+            },
+            "software_cursor" => match value.parse::<bool>() {
+                Ok(software_cursor) => self.software_cursor = Some(software_cursor),
+                _ => return false,
+            },
+            "half_page_scroll" => match value.parse::<bool>() {
+                Ok(half_page_scroll) => self.half_page_scroll = Some(half_page_scroll),
+                _ => return false,
+            },
+            "undo_cursor" => match unquote(value) {
+                "jump" => self.undo_cursor = Some(UndoCursor::Jump),
+                "stay" => self.undo_cursor = Some(UndoCursor::Stay),
+                "jump-if-near" => self.undo_cursor = Some(UndoCursor::JumpIfNear),
+                _ => return false,
+            },
+            "undo_near_screens" => match value.parse::<usize>() {
+                Ok(screens) => self.undo_near_screens = Some(screens),
+                _ => return false,
+            },
+            "wrap_width" => match value.parse::<usize>() {
+                Ok(width) if 0 < width => self.wrap_width = Some(width),
+                _ => return false,
+            },
+            "verify_save" => match value.parse::<bool>() {
+                Ok(verify) => self.verify_save = Some(verify),
+                _ => return false,
+            },
+            "auto_pairs" => match value.parse::<bool>() {
+                Ok(auto_pairs) => self.auto_pairs = Some(auto_pairs),
+                _ => return false,
+            },
+            "insert_final_newline" => match value.parse::<bool>() {
+                Ok(insert) => self.insert_final_newline = Some(insert),
+                _ => return false,
+            },
+            "write_bom" => match value.parse::<bool>() {
+                Ok(bom) => self.write_bom = Some(bom),
+                _ => return false,
+            },
+            "open_dropped_files" => match value.parse::<bool>() {
+                Ok(open) => self.open_dropped_files = Some(open),
+                _ => return false,
+            },
+            _ => return false,
+        }
+
+        true
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+/// Settings resolved for the file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    tab_width: usize,
+    expand_tabs: bool,
+    comment_prefix: String,
+    comment_prefixes: PrefixComment,
+    filler: char,
+    word_chars: WordChars,
+    line_ending: LineEnding,
+    trim_trailing_on_save: bool,
+    paste_confirm_limit: usize,
+    software_cursor: bool,
+    half_page_scroll: bool,
+    undo_cursor: UndoCursor,
+    undo_near_screens: usize,
+    wrap_width: usize,
+    verify_save: bool,
+    auto_pairs: bool,
+    insert_final_newline: bool,
+    write_bom: bool,
+    open_dropped_files: bool,
+    auto_save_seconds: u64,
+    theme: Theme,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            tab_width: TAB_WIDTH,
+            expand_tabs: false,
+            comment_prefix: Comment::default().prefix().to_string(),
+            comment_prefixes: PrefixComment::default(),
+            word_chars: WordChars::default(),
+            filler: FILLER,
+            line_ending: LineEnding::Crlf,
+            trim_trailing_on_save: false,
+            paste_confirm_limit: PASTE_CONFIRM_LIMIT,
+            software_cursor: false,
+            half_page_scroll: true,
+            undo_cursor: UndoCursor::Jump,
+            undo_near_screens: UNDO_NEAR_SCREENS,
+            wrap_width: WRAP_WIDTH,
+            verify_save: false,
+            auto_pairs: false,
+            insert_final_newline: false,
+            write_bom: false,
+            open_dropped_files: false,
+            auto_save_seconds: AUTO_SAVE_SECONDS,
+            theme: Theme::default(),
+        }
+    }
+}
+
+impl fmt::Display for Settings {
+    /// Format the settings of the file type as config.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "tab_width = {}, expand_tabs = {}, line_ending = {}, comment_prefix = \"{}\"",
+            self.tab_width, self.expand_tabs, self.line_ending, self.comment_prefix
+        )
+    }
+}
+
+impl Settings {
+    /// Returns whether the closing bracket or quote is inserted with the opening one.
+    pub fn auto_pairs(&self) -> bool {
+        self.auto_pairs
+    }
+
+    pub fn comment_prefix(&self) -> &str {
+        &self.comment_prefix
+    }
+
+    /// Returns whether tab key inserts spaces.
+    pub fn expand_tabs(&self) -> bool {
+        self.expand_tabs
+    }
+
+    /// Returns whether the line break is written at the end of file
+    /// even if the loaded file does not have it.
+    pub fn insert_final_newline(&self) -> bool {
+        self.insert_final_newline
+    }
+
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    /// Returns whether Ctrl+D and Ctrl+U scroll a half page.
+    pub fn half_page_scroll(&self) -> bool {
+        self.half_page_scroll
+    }
+
+    /// Returns whether the path of a file dropped onto the window is offered to open.
+    pub fn open_dropped_files(&self) -> bool {
+        self.open_dropped_files
+    }
+
+    /// Returns the number of pasted characters above which paste is confirmed.
+    /// `0` is never confirmed.
+    pub fn paste_confirm_limit(&self) -> usize {
+        self.paste_confirm_limit
+    }
+
+    /// Returns the seconds without changes after which the modified file is saved.
+    /// `0` is never saved automatically.
+    pub fn auto_save_seconds(&self) -> u64 {
+        self.auto_save_seconds
+    }
+
+    /// Set the value of the key in the same syntax as config,
+    /// or returns `false` if the key or the value is invalid.
+    pub fn set(&mut self, key: &str, value: &str) -> bool {
+        let mut overrides = Overrides::default();
+        if overrides.set(key, value) {
+            overrides.apply(self);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns whether the cursor is drawn as a block over the character instead of the caret.
+    pub fn software_cursor(&self) -> bool {
+        self.software_cursor
+    }
+
+    pub fn tab_width(&self) -> usize {
+        self.tab_width
+    }
+
+    /// Returns the highlighter of the configured comment prefixes, empty if none is configured.
+    pub fn comment_highlight(&self) -> &PrefixComment {
+        &self.comment_prefixes
+    }
+
+    /// Returns the character drawn on the rows past the end of buffer.
+    pub fn filler(&self) -> char {
+        self.filler
+    }
+
+    /// Returns the characters treated as a part of word.
+    pub fn word_chars(&self) -> &WordChars {
+        &self.word_chars
+    }
+
+    /// Returns the colors to draw the screen and the bars.
+    pub fn theme(&self) -> Theme {
+        self.theme
+    }
+
+    /// Returns whether trailing spaces and tabs are left out of the saved file, kept in the buffer.
+    pub fn trim_trailing_on_save(&self) -> bool {
+        self.trim_trailing_on_save
+    }
+
+    /// Returns the cursor movement at undo.
+    pub fn undo_cursor(&self) -> UndoCursor {
+        self.undo_cursor
+    }
+
+    /// Returns the number of screens within which undo moves the cursor by `jump-if-near`.
+    pub fn undo_near_screens(&self) -> usize {
+        self.undo_near_screens
+    }
+
+    /// Returns whether the saved file is loaded again to compare with the buffer.
+    pub fn verify_save(&self) -> bool {
+        self.verify_save
+    }
+
+    /// Returns whether BOM is written at saving even if the loaded file does not have it.
+    pub fn write_bom(&self) -> bool {
+        self.write_bom
+    }
+
+    /// Returns the width to reflow paragraph.
+    pub fn wrap_width(&self) -> usize {
+        self.wrap_width
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+/// Cursor movement to the undone change.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UndoCursor {
+    /// Always move to the change.
+    Jump,
+    /// Keep the cursor unless the change is on the screen.
+    Stay,
+    /// Move only if the change is within some screens.
+    JumpIfNear,
+}
+
+// -----------------------------------------------------------------------------------------------
+
+/// Foreground colors of the screen and the bars.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Theme {
+    pub text: Color,
+    pub comment: Color,
+    pub string: Color,
+    /// Drawn in reverse video.
+    pub status: Color,
+    pub message: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            text: Color::White,
+            comment: Color::Yellow,
+            string: Color::Green,
+            status: Color::White,
+            message: Color::White,
+        }
+    }
+}
+
+impl Theme {
+    /// Returns the color of the highlighted text.
+    pub fn syntax(&self, syntax: Syntax) -> Color {
+        match syntax {
+            Syntax::Comment => self.comment,
+            Syntax::String => self.string,
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+/// Characters treated as a part of word.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WordChars {
+    extra: Vec<char>,
+    wide: bool,
+}
+
+impl Default for WordChars {
+    /// Alphanumeric and underscore.
+    fn default() -> Self {
+        WordChars {
+            extra: vec!['_'],
+            wide: true,
+        }
+    }
+}
+
+impl WordChars {
+    /// Returns whether the character is a part of word.
+    pub fn contains(&self, ch: char) -> bool {
+        if !self.wide && 1 < ch.width_cjk().unwrap_or(1) {
+            return false;
+        }
+
+        ch.is_alphanumeric() || self.extra.contains(&ch)
+    }
+
+    /// Add or remove the character treated as a part of word except alphanumeric.
+    pub fn set(&mut self, ch: char, enabled: bool) {
+        self.extra.retain(|&c| c != ch);
+        if enabled {
+            self.extra.push(ch);
+        }
+    }
+
+    /// Returns whether both characters are whitespace, a part of word or neither.
+    pub fn same_class(&self, a: char, b: char) -> bool {
+        a.is_whitespace() == b.is_whitespace() && self.contains(a) == self.contains(b)
+    }
+
+    /// Set whether wide characters (e.g. CJK) are a part of word.
+    pub fn set_wide(&mut self, enabled: bool) {
+        self.wide = enabled;
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+/// Returns whether the name matches the pattern with `*` and `?` ignoring ASCII case.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<char>>();
+    let name = name.chars().collect::<Vec<char>>();
+
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&ch) if ch == '?' || ch.eq_ignore_ascii_case(&name[n]) => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((sp, sn)) => {
+                    p = sp + 1;
+                    n = sn + 1;
+                    star = Some((sp, sn + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&ch| ch == '*')
+}
+
+fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?'])
+}
+
+/// Returns the line without comment out of string.
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (index, ch) in line.char_indices() {
+        match ch {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..index],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn parse_color(value: &str) -> Option<Color> {
+    match value {
+        "blue" => Some(Color::Blue),
+        "green" => Some(Color::Green),
+        "cyan" => Some(Color::Cyan),
+        "red" => Some(Color::Red),
+        "magenta" => Some(Color::Magenta),
+        "yellow" => Some(Color::Yellow),
+        "white" => Some(Color::White),
+        "gray" => Some(Color::Gray),
+        _ => None,
+    }
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+// -----------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auto_indent_from_extension() {
+        let py = AutoIndent::from_extension("py");
+        assert!(py.opens("if x:"));
+        assert!(py.opens("else:  "));
+        assert!(!py.opens("x = 1"));
+        assert!(!py.closes(")"));
+
+        let rs = AutoIndent::from_extension("rs");
+        assert!(rs.opens("fn main() {"));
+        assert!(rs.closes("  }"));
+
+        let txt = AutoIndent::from_extension("txt");
+        assert!(txt.enabled());
+        assert!(!txt.opens("a:"));
+        assert!(!txt.closes("}"));
+    }
+
+    #[test]
+    fn config_parse() {
+        let config = Config::parse(
+            "tab_width = 4 # global\n\
+             paste_confirm_limit = 0\n\
+             undo_cursor = jump-if-near\n\
+             unknown = 1\n\
+             [filetypes.rs]\n\
+             expand_tabs = true\n\
+             comment_prefix = \"//\"\n\
+             [filetypes.\"*.md\"]\n\
+             trim_trailing_on_save = yes\n",
+        );
+
+        assert_eq!(Some(4), config.global.tab_width);
+        assert_eq!(Some(0), config.global.paste_confirm_limit);
+        assert_eq!(Some(UndoCursor::JumpIfNear), config.global.undo_cursor);
+        assert_eq!(2, config.filetypes.len());
+        assert_eq!("rs", config.filetypes[0].0);
+        assert_eq!(Some(true), config.filetypes[0].1.expand_tabs);
+        assert_eq!(Some("//".to_string()), config.filetypes[0].1.comment_prefix);
+        assert_eq!("*.md", config.filetypes[1].0);
+        assert_eq!(None, config.filetypes[1].1.trim_trailing_on_save);
+    }
+
+    #[test]
+    fn config_parse_comment_in_string() {
+        let config = Config::parse("comment_prefix = \"#\" # shell");
+
+        assert_eq!(Some("#".to_string()), config.global.comment_prefix);
+    }
+
+    #[test]
+    fn config_resolve_global() {
+        let config = Config::parse("tab_width = 2\n[filetypes.rs]\ntab_width = 4");
+
+        let settings = config.resolve(Some(Path::new("a.txt")));
+        assert_eq!(2, settings.tab_width());
+        assert!(!settings.expand_tabs());
+
+        let settings = config.resolve(None);
+        assert_eq!(2, settings.tab_width());
+        assert_eq!(
+            Settings::default().comment_prefix(),
+            settings.comment_prefix()
+        );
+    }
+
+    #[test]
+    fn config_resolve_precedence() {
+        let config = Config::parse(
+            "[filetypes.rs]\n\
+             tab_width = 4\n\
+             [filetypes.\"*.RS\"]\n\
+             tab_width = 2\n\
+             expand_tabs = true\n",
+        );
+
+        let settings = config.resolve(Some(Path::new("src\\main.rs")));
+        assert_eq!(4, settings.tab_width());
+        assert!(settings.expand_tabs());
+    }
+
+    #[test]
+    fn config_resolve_filename() {
+        let config = Config::parse("[filetypes.Makefile]\ntab_width = 4\nexpand_tabs = false");
+
+        let settings = config.resolve(Some(Path::new("Makefile")));
+        assert_eq!(4, settings.tab_width());
+        assert!(!settings.expand_tabs());
+
+        let settings = config.resolve(Some(Path::new("Makefile.am")));
+        assert_eq!(TAB_WIDTH, settings.tab_width());
+    }
+
+    #[test]
+    fn settings_set() {
+        let mut settings = Config::parse("tab_width = 2").resolve(None);
+
+        assert!(settings.set("line_ending", "lf"));
+        assert!(settings.set("tab_width", "4"));
+        assert!(!settings.set("tab_width", "0"));
+        assert!(!settings.set("unknown", "1"));
+
+        assert_eq!(LineEnding::Lf, settings.line_ending());
+        assert_eq!(4, settings.tab_width());
+        assert_eq!(
+            "tab_width = 4, expand_tabs = false, line_ending = lf, comment_prefix = \"#\"",
+            settings.to_string()
+        );
+
+        assert!(settings.set("filler", "\" \""));
+        assert!(!settings.set("filler", "\"ab\""));
+        assert!(!settings.set("filler", "\"あ\""));
+        assert_eq!(' ', settings.filler());
+
+        assert!(settings.set("word_chars", "\"-\""));
+        assert!(settings.word_chars().contains('-'));
+        assert!(!settings.word_chars().contains('_'));
+    }
+
+    #[test]
+    fn settings_theme() {
+        let config = Config::parse("comment_color = cyan\n[filetypes.rs]\nstatus_color = \"red\"");
+
+        let theme = config.resolve(Some(Path::new("a.rs"))).theme();
+        assert_eq!(Color::Cyan, theme.syntax(Syntax::Comment));
+        assert_eq!(Color::Green, theme.syntax(Syntax::String));
+        assert_eq!(Color::Red, theme.status);
+        assert_eq!(Color::White, theme.text);
+
+        let mut settings = config.resolve(None);
+        assert!(!settings.set("text_color", "black"));
+        assert!(settings.comment_highlight().is_empty());
+        assert!(settings.set("comment_prefixes", "\"--, ;\""));
+        assert!(!settings.comment_highlight().is_empty());
+        assert!(settings.set("comment_prefixes", "\"\""));
+        assert!(settings.comment_highlight().is_empty());
+        assert_eq!(Theme::default().status, settings.theme().status);
+    }
+
+    #[test]
+    fn glob_match_pattern() {
+        assert!(glob_match("*.md", "README.md"));
+        assert!(glob_match("*.MD", "readme.md"));
+        assert!(glob_match("a?c*", "abcde"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("*.md", "README.mdx"));
+        assert!(!glob_match("a?c", "ac"));
+    }
+
+    #[test]
+    fn word_chars_default() {
+        let chars = WordChars::default();
+
+        assert!(chars.contains('a'));
+        assert!(chars.contains('0'));
+        assert!(chars.contains('_'));
+        assert!(chars.contains('あ'));
+        assert!(!chars.contains('-'));
+        assert!(!chars.contains(' '));
+    }
+
+    #[test]
+    fn word_chars_set() {
+        let mut chars = WordChars::default();
+
+        chars.set('-', true);
+        chars.set('_', false);
+
+        assert!(chars.contains('-'));
+        assert!(!chars.contains('_'));
+    }
+
+    #[test]
+    fn word_chars_set_wide() {
+        let mut chars = WordChars::default();
+
+        chars.set_wide(false);
+
+        assert!(chars.contains('a'));
+        assert!(!chars.contains('あ'));
+    }
+}
@@ -17,6 +17,36 @@ impl From<(usize, usize)> for Cursor {
 }
 
 impl Cursor {
+    /// Returns whether the cursor is at end of row.
+    /// The row after the last row is both start and end.
+    ///
+    /// ```
+    /// use note::buffer::Buffer;
+    /// use note::cursor::Cursor;
+    ///
+    /// let mut buf = Buffer::default();
+    /// buf.insert_row(&(0, 0), &['a']);
+    ///
+    /// assert!(!Cursor::from((0, 0)).is_at_line_end(&buf));
+    /// assert!(Cursor::from((1, 0)).is_at_line_end(&buf));
+    /// assert!(Cursor::from((0, 1)).is_at_line_end(&buf));
+    /// ```
+    pub fn is_at_line_end(&self, content: &Buffer) -> bool {
+        content.row_char_len(self) <= self.x0
+    }
+
+    /// Returns whether the cursor is at start of row.
+    ///
+    /// ```
+    /// use note::cursor::Cursor;
+    ///
+    /// assert!(Cursor::from((0, 0)).is_at_line_start());
+    /// assert!(!Cursor::from((1, 0)).is_at_line_start());
+    /// ```
+    pub fn is_at_line_start(&self) -> bool {
+        self.x0 == 0
+    }
+
     /// Move down a row.
     pub fn move_down(&mut self, content: &Buffer) -> bool {
         let cur = self.clone();
@@ -41,17 +71,22 @@ impl Cursor {
         cur != *self
     }
 
-    /// Move down a screen height.
-    pub fn move_down_screen(&mut self, content: &Buffer, screen: &Screen) -> bool {
+    /// Move down the rows, clamped at the end of buffer.
+    pub fn move_down_rows(&mut self, content: &Buffer, rows: usize) -> bool {
         let cur = self.clone();
 
-        self.y0 += screen.height();
+        self.y0 += rows;
         self.move_to_ymax_ifoverflow(content);
         self.move_to_xmax_ifoverflow(content);
 
         cur != *self
     }
 
+    /// Move down a screen height.
+    pub fn move_down_screen(&mut self, content: &Buffer, screen: &Screen) -> bool {
+        self.move_down_rows(content, screen.height())
+    }
+
     /// Move to previous character.
     /// Move up 1 row and end of row if current is start of row.
     pub fn move_left(&mut self, content: &Buffer) -> bool {
@@ -106,17 +141,83 @@ impl Cursor {
         cur != *self
     }
 
+    /// Move up the rows, clamped at the start of buffer.
+    pub fn move_up_rows(&mut self, content: &Buffer, rows: usize) -> bool {
+        let cur = self.clone();
+
+        self.y0 = self.y0.saturating_sub(rows);
+        self.move_to_xmax_ifoverflow(content);
+
+        cur != *self
+    }
+
     /// Move up a screen height.
     pub fn move_up_screen(&mut self, content: &Buffer, screen: &Screen) -> bool {
+        self.move_up_rows(content, screen.height())
+    }
+
+    /// Move to the next row whose indentation is less than or equal to the current.
+    pub fn move_to_next_indent(&mut self, content: &Buffer) -> bool {
+        match content.next_indent_row(self.y0) {
+            Some(y) => self.move_to_indent(content, y),
+            None => false,
+        }
+    }
+
+    /// Move to the previous row whose indentation is less than or equal to the current.
+    pub fn move_to_prev_indent(&mut self, content: &Buffer) -> bool {
+        match content.prev_indent_row(self.y0) {
+            Some(y) => self.move_to_indent(content, y),
+            None => false,
+        }
+    }
+
+    /// Move to start of the word or the run of other characters before current,
+    /// skipping whitespaces first. Move up 1 row and end of row if current is start of row.
+    pub fn move_word_left(&mut self, content: &Buffer) -> bool {
+        let column = match content.get(self.y0) {
+            Some(row) if 0 < self.x0 => &row.column()[..self.x0.min(row.len())],
+            _ => return self.move_left(content),
+        };
+
         let cur = self.clone();
+        let chars = content.word_chars();
+
+        let word = column
+            .iter()
+            .rposition(|ch| !ch.is_whitespace())
+            .map_or(0, |p| p + 1);
+        self.x0 = match word.checked_sub(1) {
+            Some(last) => column[..word]
+                .iter()
+                .rposition(|&ch| !chars.same_class(ch, column[last]))
+                .map_or(0, |p| p + 1),
+            None => 0,
+        };
 
-        self.y0 = if screen.height() < self.y0 {
-            self.y0 - screen.height()
-        } else {
-            0
+        cur != *self
+    }
+
+    /// Move to start of the next word, skipping the rest of current word or the run of
+    /// other characters, and whitespaces.
+    /// Move down 1 row and start of row if current is end of row.
+    pub fn move_word_right(&mut self, content: &Buffer) -> bool {
+        let column = match content.get(self.y0) {
+            Some(row) if self.x0 < row.len() => row.column(),
+            _ => return self.move_right(content),
         };
 
-        self.move_to_xmax_ifoverflow(content);
+        let cur = self.clone();
+        let chars = content.word_chars();
+
+        let space = column[self.x0..]
+            .iter()
+            .position(|&ch| !chars.same_class(ch, column[self.x0]))
+            .map_or(column.len(), |p| self.x0 + p);
+        self.x0 = column[space..]
+            .iter()
+            .position(|ch| !ch.is_whitespace())
+            .map_or(column.len(), |p| space + p);
 
         cur != *self
     }
@@ -139,10 +240,30 @@ impl Cursor {
         cur != *self
     }
 
+    /// Move to start of buffer.
+    pub fn move_to_top(&mut self) -> bool {
+        let cur = self.clone();
+
+        self.x0 = 0;
+        self.y0 = 0;
+
+        cur != *self
+    }
+
+    /// Move to end of last row.
+    pub fn move_to_bottom(&mut self, content: &Buffer) -> bool {
+        let cur = self.clone();
+
+        self.y0 = content.rows().saturating_sub(1);
+        self.x0 = content.row_char_len(self);
+
+        cur != *self
+    }
+
     /// Returns coordinate of cursor in screen.
     pub fn render(&self, content: &Buffer) -> (usize, usize) {
         if let Some(row) = content.get(self.y0) {
-            let x = row.width_range(0..self.x0);
+            let x = row.width_range(0..self.x0, content.tab_width());
             (x, self.y0)
         } else {
             (0, content.rows())
@@ -180,12 +301,16 @@ impl Cursor {
     fn move_render_to_x(&mut self, content: &Buffer, render: usize) -> bool {
         let cur = self.clone();
 
+        let tab_width = content.tab_width();
         if let Some(row) = content.get(self.y0) {
-            while self.x0 < row.len() && row.width_range(0..self.x0) < render {
+            while self.x0 < row.len() && row.width_range(0..self.x0, tab_width) < render {
                 self.x0 += 1;
             }
 
-            while 0 < self.x0 && self.x0 <= row.len() && render < row.width_range(0..self.x0) {
+            while 0 < self.x0
+                && self.x0 <= row.len()
+                && render < row.width_range(0..self.x0, tab_width)
+            {
                 self.x0 -= 1;
             }
         }
@@ -193,6 +318,15 @@ impl Cursor {
         cur != *self
     }
 
+    fn move_to_indent(&mut self, content: &Buffer, y: usize) -> bool {
+        let cur = self.clone();
+
+        self.y0 = y;
+        self.x0 = content.get(y).and_then(|r| r.indent_len()).unwrap_or(0);
+
+        cur != *self
+    }
+
     fn move_to_xmax_ifoverflow(&mut self, content: &Buffer) -> bool {
         let cur = self.clone();
 
@@ -265,8 +399,28 @@ impl Coordinates for Cursor {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::Config;
     use crate::terminal;
 
+    #[test]
+    fn is_at_line_end() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a']);
+        buf.insert_row(&(0, 1), &[]);
+
+        assert!(!Cursor::from((0, 0)).is_at_line_end(&buf));
+        assert!(Cursor::from((1, 0)).is_at_line_end(&buf));
+        assert!(Cursor::from((0, 1)).is_at_line_end(&buf));
+        assert!(Cursor::from((0, 2)).is_at_line_end(&buf));
+    }
+
+    #[test]
+    fn is_at_line_start() {
+        assert!(Cursor::from((0, 0)).is_at_line_start());
+        assert!(Cursor::from((0, 2)).is_at_line_start());
+        assert!(!Cursor::from((1, 0)).is_at_line_start());
+    }
+
     #[test]
     fn move_down() {
         let mut buf = Buffer::default();
@@ -580,6 +734,140 @@ mod tests {
         assert!(!moved);
     }
 
+    #[test]
+    fn move_to_next_indent() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &[' ', 'a']);
+        buf.insert_row(&(0, 1), &[' ', ' ', 'b']);
+        buf.insert_row(&(0, 2), &[' ', 'c']);
+
+        let mut cur = Cursor::from((2, 0));
+        let moved = cur.move_to_next_indent(&buf);
+
+        assert!(moved);
+        assert_eq!(Cursor::from((1, 2)), cur);
+    }
+
+    #[test]
+    fn move_to_next_indent_at_end() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a']);
+        buf.insert_row(&(0, 1), &[' ', 'b']);
+
+        let mut cur = Cursor::from((0, 0));
+        let moved = cur.move_to_next_indent(&buf);
+
+        assert!(!moved);
+        assert_eq!(Cursor::from((0, 0)), cur);
+    }
+
+    #[test]
+    fn move_to_prev_indent() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &[' ', 'a']);
+        buf.insert_row(&(0, 1), &[' ', ' ', 'b']);
+        buf.insert_row(&(0, 2), &[' ', 'c']);
+
+        let mut cur = Cursor::from((0, 2));
+        let moved = cur.move_to_prev_indent(&buf);
+
+        assert!(moved);
+        assert_eq!(Cursor::from((1, 0)), cur);
+    }
+
+    #[test]
+    fn move_word_right() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['f', 'o', 'o', ' ', ' ', 'b', 'a', 'r']);
+        buf.insert_row(&(0, 1), &[' ', 'x']);
+
+        let mut cur = Cursor::from((1, 0));
+        let stops = [(5, 0), (8, 0), (0, 1), (1, 1), (2, 1), (0, 2), (0, 2)];
+        for at in stops {
+            cur.move_word_right(&buf);
+            assert_eq!(at, cur.as_coordinates());
+        }
+    }
+
+    #[test]
+    fn move_word_left() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['f', 'o', 'o', ' ', ' ', 'b', 'a', 'r']);
+        buf.insert_row(&(0, 1), &[' ', 'x']);
+
+        let mut cur = Cursor::from((1, 1));
+        let stops = [(0, 1), (8, 0), (5, 0), (0, 0), (0, 0)];
+        for at in stops {
+            cur.move_word_left(&buf);
+            assert_eq!(at, cur.as_coordinates());
+        }
+
+        let mut cur = Cursor::from((4, 0));
+        assert!(cur.move_word_left(&buf));
+        assert_eq!((0, 0), cur.as_coordinates());
+    }
+
+    #[test]
+    fn move_word_word_chars() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &"a.b-c d".chars().collect::<Vec<char>>());
+
+        let mut cur = Cursor::from((0, 0));
+        for at in [(1, 0), (2, 0), (3, 0), (4, 0), (6, 0)] {
+            cur.move_word_right(&buf);
+            assert_eq!(at, cur.as_coordinates());
+        }
+        cur.move_word_left(&buf);
+        assert_eq!((4, 0), cur.as_coordinates());
+
+        buf.set_settings(Config::parse("word_chars = \"_-\"").resolve(None));
+        let mut cur = Cursor::from((2, 0));
+        cur.move_word_right(&buf);
+        assert_eq!((6, 0), cur.as_coordinates());
+        cur.move_word_left(&buf);
+        assert_eq!((2, 0), cur.as_coordinates());
+    }
+
+    #[test]
+    fn move_to_top() {
+        let mut cur = Cursor::from((1, 2));
+        let moved = cur.move_to_top();
+
+        assert_eq!((0, 0), cur.as_coordinates());
+        assert!(moved);
+        assert!(!cur.move_to_top());
+    }
+
+    #[test]
+    fn move_to_bottom() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a']);
+        buf.insert_row(&(0, 1), &['a', 'b']);
+        buf.insert_row(&(0, 2), &['a', 'b', 'c']);
+
+        let mut cur = Cursor::from((1, 0));
+        let moved = cur.move_to_bottom(&buf);
+
+        assert_eq!((3, 2), cur.as_coordinates());
+        assert!(moved);
+        assert!(!cur.move_to_bottom(&buf));
+
+        let mut cur = Cursor::from((0, 3));
+        assert!(cur.move_to_bottom(&buf));
+        assert_eq!((3, 2), cur.as_coordinates());
+    }
+
+    #[test]
+    fn move_to_bottom_empty() {
+        let buf = Buffer::default();
+
+        let mut cur = Cursor::from((0, 0));
+        let moved = cur.move_to_bottom(&buf);
+
+        assert_eq!((0, 0), cur.as_coordinates());
+        assert!(!moved);
+    }
+
     #[test]
     fn move_to_x0() {
         let mut cur = Cursor::from((1, 0));
@@ -1,21 +1,70 @@
-use crate::buffer::{Buffer, Row};
+use crate::buffer::{closing_pair, Buffer, Encoding, Row, Snapshot};
+use crate::config::{Config, Formatter, UndoCursor};
 use crate::cursor::{AsCoordinates, Coordinates, Cursor};
 use crate::error::Error;
+use crate::escape;
 use crate::key_event::{Event, KeyEvent, KeyModifier, WindowEvent};
+use crate::log_warn;
+use crate::process::{self, Output};
 use crate::prompt::{self, Prompt};
-use crate::screen::{refresh_screen, resize_screen, MessageBar, Screen, StatusBar};
-use crate::terminal::Terminal;
+use crate::screen::{refresh_screen, resize_screen, MessageBar, Screen, Split, StatusBar};
+use crate::snippet::{Snippets, TabStops};
+use crate::terminal::{Terminal, POLL_INTERVAL};
 use std::cmp::{max, min};
+use std::mem;
+use std::ops::Range;
 use std::path::{Path, PathBuf};
-use std::process::exit;
+use std::time::{Duration, Instant};
 
-const TEXT_CONFIRM_KILL_BUFFER: &str = "Buffer is modified. Kill buffer (y/N) : ";
+const HIGHLIGHT_DURATION: Duration = Duration::from_secs(1);
+const NOTICE_DURATION: Duration = Duration::from_secs(3);
+const PASTE_PADDING_LIMIT: usize = 80;
+/// Characters arriving within this interval are read together, as pasted or dropped.
+const BURST_INTERVAL: Duration = Duration::from_millis(5);
+/// Maximum number of characters read together to detect the path of a dropped file.
+const BURST_MAX_CHARS: usize = 1024;
+/// Number of characters of a drive prefix such as `C:\`.
+const DRIVE_PREFIX_CHARS: usize = 3;
+/// Maximum number of closed files kept to reopen.
+const CLOSED_LIMIT: usize = 10;
 
+const TEXT_CONFIRM_KILL_BUFFER: &str = "Buffer is modified. Kill buffer";
+const TEXT_CONFIRM_OPEN_BINARY: &str = "File is binary. Open read-only";
+const TEXT_CONFIRM_EDIT_INVALID: &str = "Saving loses the original bytes. Edit anyway";
+const TEXT_CONFIRM_OPEN_DROPPED: &str = "Open dropped file";
+const TEXT_CHOICE_SAVE_BUFFER: &str = "Buffer is modified. Save changes";
+const TEXT_CHOICE_ENCODING: &str = "Reopen with encoding";
+
+const SAVE_OPTIONS: [(char, &str); 2] = [('s', "save"), ('d', "discard")];
+const ENCODING_OPTIONS: [(char, &str); 4] = [
+    ('u', "UTF-8"),
+    ('l', "UTF-16LE"),
+    ('b', "UTF-16BE"),
+    ('s', "Shift_JIS"),
+];
+
+const TEXT_MESSAGE_BALANCED: &str = "Brackets are balanced.";
+const TEXT_MESSAGE_BASELINE: &str = "Baseline is set.";
+const TEXT_MESSAGE_BRACKETS: &str = "Checking brackets...";
+const TEXT_MESSAGE_FORMATTED: &str = "Formatted.";
 const TEXT_MESSAGE_INPUT_FILENAME: &str = "Filename (ESC:quit): ";
-const TEXT_MESSAGE_INPUT_KEYWORD: &str = "Input keyword (ESC:quit F3:next S+F3:prev): ";
+const TEXT_MESSAGE_INPUT_KEYWORD: &str =
+    "Input keyword (ESC:quit F3:next S+F3:prev C+R:regex A+C:case): ";
 const TEXT_MESSAGE_INPUT_LINENO: &str = "Go to line (ESC:quit): ";
-const TEXT_MESSAGE_INPUT_REPLACE: &str = "Replace word (ESC:quit): ";
+const TEXT_MESSAGE_INPUT_REPLACE: &str = "Replace word (ESC:quit A+C:case): ";
+const TEXT_MESSAGE_INPUT_SETTING: &str = "Setting key = value (ESC:quit Enter:show): ";
+const TEXT_MESSAGE_INPUT_SORT_KEY: &str = "Sort key column[,length] (ESC:quit): ";
 const TEXT_MESSAGE_MENU: &str = "^Q:Quit ^S:Save ^F:Find";
+const TEXT_MESSAGE_NO_CLOSED: &str = "No closed file to reopen.";
+const TEXT_MESSAGE_NO_FILENAME: &str = "Buffer has no file to reopen.";
+const TEXT_MESSAGE_NO_FORMATTER: &str = "No formatter for this file type.";
+const TEXT_MESSAGE_READ_ONLY: &str = "Buffer is read-only.";
+const TEXT_MESSAGE_SAVED: &str = "Saved.";
+const TEXT_MESSAGE_AUTO_SAVED: &str = "Saved automatically.";
+const TEXT_MESSAGE_AUTO_SAVE_FAILED: &str = "Buffer is not saved automatically. Save it by Ctrl+S.";
+const TEXT_MESSAGE_SAVE_DIFFERS: &str = "Saved file differs from buffer at line";
+const TEXT_MESSAGE_WRAP_RECTANGLE: &str = "Rectangle selection can not be wrapped.";
+const TEXT_MESSAGE_ENTER_RECTANGLE: &str = "Rectangle selection can not be replaced by line break.";
 
 pub struct Editor<T: Terminal> {
     cursor: Cursor,
@@ -25,6 +74,23 @@ pub struct Editor<T: Terminal> {
     select: Select,
     status: StatusBar,
     message: MessageBar,
+    keyword: Option<Row>,
+    ignore_case: bool,
+    highlight: Option<(Cursor, Row, Instant)>,
+    decoration: bool,
+    match_count: MatchCount,
+    paste_padding_limit: usize,
+    snippets: Snippets,
+    tab_stops: TabStops,
+    expansions: Vec<(Cursor, Option<(Cursor, Cursor)>)>,
+    auto_closed: Vec<(usize, usize)>,
+    config: Config,
+    closed: Closed,
+    split: Option<(Split, View)>,
+    abort_on_discard: bool,
+    baseline: Option<Snapshot>,
+    show_changes: bool,
+    last_change: Option<(usize, Instant)>,
 }
 
 impl<T: Terminal> Editor<T> {
@@ -42,21 +108,118 @@ impl<T: Terminal> Editor<T> {
             select: Select::default(),
             status,
             message,
+            keyword: None,
+            ignore_case: false,
+            highlight: None,
+            decoration: false,
+            match_count: MatchCount::default(),
+            paste_padding_limit: PASTE_PADDING_LIMIT,
+            snippets: Snippets::default(),
+            tab_stops: TabStops::default(),
+            expansions: vec![],
+            auto_closed: vec![],
+            config: Config::default(),
+            closed: Closed::default(),
+            split: None,
+            abort_on_discard: false,
+            baseline: None,
+            show_changes: false,
+            last_change: None,
         })
     }
 
+    /// Close the editor without saving, or returns `None` if canceled.
+    pub fn abort(&mut self) -> Result<Option<Exit>, Error> {
+        if self.content.cached() && !self.confirm_exit()? {
+            return Ok(None);
+        }
+
+        Ok(Some(Exit::Abort))
+    }
+
+    /// Add the delta to the integer under the cursor, and move to the last digit.
+    pub fn add_number(&mut self, delta: i64) -> bool {
+        match self.content.add_number(&self.cursor, delta) {
+            Some(range) => {
+                let at = (range.end - 1, self.cursor.y());
+                self.cursor.set(&self.content, &at);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Leave the tab stops and clear the decoration.
+    pub fn cancel(&mut self) -> bool {
+        self.tab_stops = TabStops::default();
+        self.clear_decoration()
+    }
+
+    /// Check the brackets in the whole buffer, and move to the first unmatched one.
+    /// Returns `false` if the brackets are balanced.
+    pub fn check_brackets(&mut self) -> Result<bool, Error> {
+        let rows = self.content.rows();
+        let message = &mut self.message;
+        let terminal = &mut self.terminal;
+        let mut drawn = Ok(());
+        let found = self.content.unmatched_bracket(|y| {
+            message.set_message(Row::from(format!(
+                "{} {}/{} rows",
+                TEXT_MESSAGE_BRACKETS, y, rows
+            )));
+            if drawn.is_ok() {
+                drawn = message.draw(terminal);
+            }
+        });
+        drawn?;
+
+        match found {
+            Some((at, ch)) => {
+                self.jump_to(&at);
+                self.highlight = Some((
+                    self.cursor.clone(),
+                    Row::from(vec![ch]),
+                    Instant::now() + HIGHLIGHT_DURATION,
+                ));
+                let message = format!("Unmatched `{}` at line {}.", ch, at.1 + 1);
+                self.message.set_message(Row::from(message));
+                Ok(true)
+            }
+            None => {
+                self.message.set_message(Row::from(TEXT_MESSAGE_BALANCED));
+                Ok(false)
+            }
+        }
+    }
+
+    /// Close the view without focus, and show the focused view in the whole screen.
+    pub fn close_split(&mut self) -> Result<bool, Error> {
+        if self.split.take().is_none() {
+            return Ok(false);
+        }
+
+        self.resize_screen()?;
+        Ok(true)
+    }
+
+    /// Stop highlighting the occurrences of the keyword.
+    pub fn clear_decoration(&mut self) -> bool {
+        if self.decoration {
+            self.decoration = false;
+            // Delete text decoration.
+            self.screen.force_update();
+            true
+        } else {
+            false
+        }
+    }
+
     pub fn confirm_exit(&mut self) -> Result<bool, Error> {
-        let mut prompt = prompt::YesNo::new(
-            &mut self.cursor,
-            &mut self.content,
-            &mut self.screen,
-            &mut self.status,
-            &mut self.message,
-            &mut self.terminal,
-        );
-        let ret = prompt.confirm(TEXT_CONFIRM_KILL_BUFFER)?;
-        self.message.force_update();
-        Ok(ret)
+        self.confirm(TEXT_CONFIRM_KILL_BUFFER)
+    }
+
+    pub fn confirm_open_binary(&mut self) -> Result<bool, Error> {
+        self.confirm(TEXT_CONFIRM_OPEN_BINARY)
     }
 
     pub fn content(&self) -> &Buffer {
@@ -67,6 +230,34 @@ impl<T: Terminal> Editor<T> {
         &self.cursor
     }
 
+    /// Copy the selection or the current row to the clipboard.
+    pub fn copy(&mut self) -> bool {
+        if let (Some(start), Some(end)) = (self.select.start(), self.select.end()) {
+            self.content.copy_pending(start..end, self.select.mode());
+        } else {
+            self.content.copy_line(&self.cursor);
+        }
+        self.copy_to_clipboard();
+        false
+    }
+
+    /// Cut the selection or the current row to the clipboard.
+    pub fn cut(&mut self) -> bool {
+        let updated = if let (Some(start), Some(end)) = (self.select.start(), self.select.end()) {
+            self.content.delete_chars(start, end, self.select.mode());
+            self.cursor.set(&self.content, start);
+            true
+        } else if self.content.cut_line(&self.cursor) {
+            let at = self.cursor.as_coordinates();
+            self.cursor.set(&self.content, &at);
+            true
+        } else {
+            false
+        };
+        self.copy_to_clipboard();
+        updated
+    }
+
     pub fn delete_char(&mut self) -> bool {
         match self.cursor.as_coordinates() {
             (0, 0) => false,
@@ -89,29 +280,147 @@ impl<T: Terminal> Editor<T> {
         }
     }
 
+    /// Delete the selection, or the character at the cursor if nothing is selected.
+    pub fn delete_forward(&mut self) -> bool {
+        if self.delete_select() {
+            return true;
+        }
+
+        self.cursor.move_right(&self.content);
+        self.delete_char()
+    }
+
+    /// Delete the selection and move to its start, or returns `false` if nothing is selected.
+    pub fn delete_select(&mut self) -> bool {
+        let (start, end) = match self.select.corners() {
+            Some((s, e)) if self.select.enabled() => (s.clone(), e.clone()),
+            _ => return false,
+        };
+
+        self.content.delete_chars(&start, &end, self.select.mode());
+        self.cursor.set(&self.content, &start);
+        self.select.disable();
+        true
+    }
+
+    /// Delete the characters after the cursor, or the row if it is empty.
+    pub fn delete_row(&mut self) -> bool {
+        if self.content.row_char_len(&self.cursor) == 0 {
+            self.content.delete_row(&self.cursor);
+        } else {
+            self.content.shrink_row(&self.cursor);
+        }
+        true
+    }
+
+    /// Insert a copy of the current row below it, and move down to the same column of the copy.
+    pub fn duplicate_line(&mut self) -> bool {
+        let y = self.cursor.y();
+        let column = match self.content.get(y) {
+            Some(row) => row.column().to_vec(),
+            None => return false,
+        };
+
+        self.content.insert_row(&(0, y + 1), &column);
+        self.cursor.move_down(&self.content)
+    }
+
+    /// Swap the current row with the row above it, and move up with the row.
+    pub fn move_row_up(&mut self) -> bool {
+        let (x, y) = self.cursor.as_coordinates();
+        if y == 0 || !self.content.swap_rows(&(x, y), &(x, y - 1)) {
+            return false;
+        }
+
+        self.cursor.set(&self.content, &(x, y - 1));
+        true
+    }
+
+    /// Swap the current row with the row below it, and move down with the row.
+    pub fn move_row_down(&mut self) -> bool {
+        let (x, y) = self.cursor.as_coordinates();
+        if !self.content.swap_rows(&(x, y), &(x, y + 1)) {
+            return false;
+        }
+
+        self.cursor.set(&self.content, &(x, y + 1));
+        true
+    }
+
+    /// Replace the selection by a line break in a undo step, or split the row.
+    pub fn enter_over_select(&mut self) -> bool {
+        if self.select.enabled() && self.select.mode() == SelectMode::Rectangle {
+            self.message
+                .set_message(Row::from(TEXT_MESSAGE_ENTER_RECTANGLE));
+            return false;
+        }
+
+        self.replace_select(Self::enter)
+    }
+
     pub fn enter(&mut self) -> bool {
-        self.content.split_row(&self.cursor);
+        let indent = if self.content.auto_indent().enabled() {
+            self.content.new_line_indent(&self.cursor)
+        } else {
+            vec![]
+        };
+
+        let at = self.cursor.clone();
+        self.content.record_group(|content| {
+            content.split_row(&at);
+            if !indent.is_empty() {
+                let (_, y) = at.as_coordinates();
+                content.insert_chars(
+                    &(0, y + 1),
+                    &[Row::from(indent.as_slice())],
+                    SelectMode::None,
+                );
+            }
+        });
 
         let m1 = self.cursor.move_to_x0();
         let m2 = self.cursor.move_down(&self.content);
-        m1 || m2
+        let m3 = self.cursor.set_x(&self.content, indent.len());
+        m1 || m2 || m3
     }
 
-    pub fn exit(&mut self) -> Result<(), Error> {
-        if self.content.cached() && !self.confirm_exit()? {
-            return Ok(());
+    /// Returns the reason to close the editor, or `None` if canceled.
+    pub fn exit(&mut self) -> Result<Option<Exit>, Error> {
+        if !self.content.cached() {
+            return Ok(Some(Exit::Quit));
         }
 
-        exit(0);
+        match self.choose(TEXT_CHOICE_SAVE_BUFFER, &SAVE_OPTIONS)? {
+            Some('s') => {
+                self.save()?;
+                // Saving is canceled at the filename, or the buffer is read-only.
+                if self.content.cached() {
+                    Ok(None)
+                } else {
+                    Ok(Some(Exit::Quit))
+                }
+            }
+            // Changes are discarded explicitly.
+            Some(_) if self.abort_on_discard => Ok(Some(Exit::Abort)),
+            Some(_) => Ok(Some(Exit::Quit)),
+            None => Ok(None),
+        }
     }
 
     pub fn find(&mut self) -> Result<bool, Error> {
         let ret;
         let moved;
         let src;
+        let painted;
+        let regex;
+        let ignore_case;
+        let tabs_expanded;
+        let decorated = self.decoration;
         {
             let row = self.get_selected_text().and_then(|s| s.first().cloned());
             self.select.disable();
+            // A new search replaces the highlight of the last one, even if canceled.
+            self.decoration = false;
 
             let mut prompt = prompt::FindKeyword::new(
                 &mut self.cursor,
@@ -128,20 +437,364 @@ impl<T: Terminal> Editor<T> {
             )?;
             moved = prompt.source() != prompt.cursor();
             src = prompt.source().as_coordinates();
+            painted = prompt.painted();
+            regex = prompt.regex();
+            ignore_case = prompt.ignore_case();
+            tabs_expanded = prompt.tabs_expanded();
+        }
+
+        // The keyword searched as the pattern or with tabs expanded is not kept
+        // for F3 and the highlight, which search the text literally.
+        if let Some(keyword) = ret
+            .as_deref()
+            .filter(|k| !k.is_empty() && !regex && !tabs_expanded)
+        {
+            self.keyword = Some(Row::from(keyword));
+            self.ignore_case = ignore_case;
+            self.decoration = true;
         }
 
         if ret.is_none() {
             self.cursor.set(&self.content, &src);
         }
 
-        // Delete text decoration.
-        self.screen.force_update();
+        // Delete text decoration, the screen is left as is if the prompt is closed at once.
+        if painted || decorated || self.decoration {
+            self.screen.force_update();
+        }
 
         self.message.force_update();
 
         Ok(moved)
     }
 
+    /// Expand the selection to the word, the quoted string or the bracketed group,
+    /// the line and the paragraph in order.
+    pub fn expand_select(&mut self) -> bool {
+        let previous = match self.select.corners() {
+            Some((start, end)) if self.select.enabled() => Some((start.clone(), end.clone())),
+            _ => None,
+        };
+
+        let (start, end) = match &previous {
+            Some((start, end)) => (start.as_coordinates(), end.as_coordinates()),
+            None => (self.cursor.as_coordinates(), self.cursor.as_coordinates()),
+        };
+
+        let contains = |&(s, e): &((usize, usize), (usize, usize))| {
+            (s.1, s.0) <= (start.1, start.0)
+                && (end.1, end.0) <= (e.1, e.0)
+                && (s, e) != (start, end)
+        };
+
+        let word = if start.1 == end.1 {
+            self.content
+                .word_range_at(&start)
+                .map(|r| ((r.start, start.1), (r.end, start.1)))
+        } else {
+            None
+        };
+        let group = self.content.enclosing_span(start, end);
+        let line = ((0, start.1), (self.content.row_char_len(&end), end.1));
+        let first = self
+            .content
+            .paragraph_range(start.1)
+            .map_or(start.1, |r| r.start);
+        let last = self
+            .content
+            .paragraph_range(end.1)
+            .map_or(end.1, |r| r.end - 1);
+        let paragraph = ((0, first), (self.content.row_char_len(&(0, last)), last));
+
+        let span = [word, group, Some(line), Some(paragraph)]
+            .into_iter()
+            .flatten()
+            .find(contains);
+
+        match span {
+            Some((s, e)) => {
+                self.expansions.push((self.cursor.clone(), previous));
+                self.select_span(&s, &e);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Replace the selected text by the converted text in one undo step.
+    /// Rows are joined by line feed, and the converted text is split by it.
+    pub fn convert_select<F>(&mut self, convert: F) -> bool
+    where
+        F: FnOnce(&str) -> Option<String>,
+    {
+        let (start, end) = match self.select.corners() {
+            Some((start, end)) if self.select.mode() == SelectMode::None => {
+                (start.clone(), end.clone())
+            }
+            _ => return false,
+        };
+
+        let text = match self.content.get_range(&start..&end, SelectMode::None) {
+            Some(rows) => rows
+                .iter()
+                .map(|row| row.to_string_at(0))
+                .collect::<Vec<String>>()
+                .join("\n"),
+            None => return false,
+        };
+
+        let converted = match convert(&text) {
+            Some(converted) if converted != text => converted,
+            _ => return false,
+        };
+
+        let rows = converted.split('\n').map(Row::from).collect::<Vec<Row>>();
+        match self.content.replace_range(&start, &end, &rows) {
+            Some(at) => self.cursor.set(&self.content, &at),
+            None => self.cursor.set(&self.content, &start),
+        };
+
+        true
+    }
+
+    /// Expand the snippet named by the word before the cursor,
+    /// and move to the first tab stop.
+    pub fn expand_snippet(&mut self) -> bool {
+        let (x, y) = self.cursor.as_coordinates();
+
+        let (range, name) = match self.content.word_at(&self.cursor) {
+            Some((range, name)) if range.end == x => (range, name),
+            _ => return false,
+        };
+
+        let snippet = match self.snippets.get(&name) {
+            Some(snippet) => snippet.clone(),
+            None => return false,
+        };
+
+        let start = range.start;
+        let indent = self.content.get(y).map_or(vec![], |row| {
+            row.column()[..row.indent_len().unwrap_or(0)].to_vec()
+        });
+
+        // Following rows are indented as same as the cursor row.
+        let mut rows = snippet.rows().to_vec();
+        for row in rows.iter_mut().skip(1) {
+            row.insert_slice(0, &indent);
+        }
+
+        let end = self.content.replace_range(&(start, y), &(x, y), &rows);
+
+        let stops = snippet
+            .stops()
+            .iter()
+            .map(|&(sx, sy)| match sy {
+                0 => (start + sx, y),
+                _ => (indent.len() + sx, y + sy),
+            })
+            .collect::<Vec<(usize, usize)>>();
+        self.tab_stops = TabStops::new(&stops);
+
+        match self.tab_stops.next(&self.content).or(end) {
+            Some(at) => self.cursor.set(&self.content, &at),
+            None => self.cursor.set(&self.content, &(start, y)),
+        };
+
+        true
+    }
+
+    /// Move to the start of the next region changed from the baseline.
+    pub fn next_change(&mut self) -> bool {
+        let y = self.cursor.y();
+        match self.changes().into_iter().find(|r| y < r.start) {
+            Some(range) => self.jump_to(&(0, range.start)),
+            None => false,
+        }
+    }
+
+    /// Move to the start of the previous region changed from the baseline.
+    pub fn previous_change(&mut self) -> bool {
+        let y = self.cursor.y();
+        match self.changes().into_iter().rev().find(|r| r.start < y) {
+            Some(range) => self.jump_to(&(0, range.start)),
+            None => false,
+        }
+    }
+
+    /// Move to the next tab stop of the expanded snippet.
+    pub fn next_tab_stop(&mut self) -> bool {
+        if let Some(at) = self.tab_stops.next(&self.content) {
+            self.cursor.set(&self.content, &at);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Move to the next tab stop, or expand the snippet if no tab stop remains.
+    pub fn next_tab_stop_or_expand(&mut self) -> bool {
+        self.next_tab_stop() || self.expand_snippet()
+    }
+
+    /// Move to the next keyword of last search.
+    /// Open the find prompt if there is no keyword.
+    pub fn find_next(&mut self) -> Result<bool, Error> {
+        if let Some(keyword) = self.keyword.clone() {
+            let text = keyword.to_string_at(0);
+
+            let mut at = self.cursor.clone();
+            at.move_right(&self.content);
+
+            let find = |at: &Cursor| {
+                if self.ignore_case {
+                    self.content.find_at_icase(at, &text)
+                } else {
+                    self.content.find_at(at, &text)
+                }
+            };
+            let found = find(&at).or_else(|| find(&Cursor::default()));
+            Ok(self.jump_to_keyword(found, keyword))
+        } else {
+            self.find()
+        }
+    }
+
+    /// Move to the previous keyword of last search.
+    /// Open the find prompt if there is no keyword.
+    pub fn find_previous(&mut self) -> Result<bool, Error> {
+        if let Some(keyword) = self.keyword.clone() {
+            let text = keyword.to_string_at(0);
+
+            let mut at = self.cursor.clone();
+            at.move_left(&self.content);
+
+            let rfind = |at: &(usize, usize)| {
+                if self.ignore_case {
+                    self.content.rfind_at_icase(at, &text)
+                } else {
+                    self.content.rfind_at(at, &text)
+                }
+            };
+            let found = rfind(&at.as_coordinates()).or_else(|| rfind(&(0, self.content.rows())));
+            Ok(self.jump_to_keyword(found, keyword))
+        } else {
+            self.find()
+        }
+    }
+
+    /// Replace the content with the output of the formatter for the file type.
+    pub fn format(&mut self) -> bool {
+        self.format_with(process::run)
+    }
+
+    /// Replace the content with the output of `run` in one undo step.
+    /// The error of the formatter is shown in the message bar.
+    pub fn format_with<F>(&mut self, run: F) -> bool
+    where
+        F: FnOnce(&Formatter, &str) -> Result<Output, Error>,
+    {
+        let formatter = match self.content.formatter() {
+            Some(formatter) => formatter.clone(),
+            None => {
+                self.message
+                    .set_message(Row::from(TEXT_MESSAGE_NO_FORMATTER));
+                return false;
+            }
+        };
+
+        let mut input = String::new();
+        for y in 0..self.content.rows() {
+            input.push_str(&self.content.line_text(y).unwrap_or_default());
+            input.push('\n');
+        }
+
+        let rows = match run(&formatter, &input).and_then(|o| process::parse_output(&o)) {
+            Ok(rows) => rows,
+            Err(e) => {
+                let message = match e {
+                    Error::Process(m) => m.lines().next().unwrap_or_default().to_string(),
+                    e => format!("{:?}", e),
+                };
+                let message = format!("{}: {}", formatter.program(), message);
+                self.message.set_message(Row::from(message));
+                return false;
+            }
+        };
+
+        self.message.set_message(Row::from(TEXT_MESSAGE_FORMATTED));
+
+        let unchanged = rows.len() == self.content.rows()
+            && rows
+                .iter()
+                .enumerate()
+                .all(|(y, r)| self.content.line_text(y) == Some(r.to_string_at(0)));
+        if unchanged {
+            return false;
+        }
+
+        self.content.set_all_rows(&self.cursor, rows);
+        let at = self.cursor.as_coordinates();
+        self.cursor.set(&self.content, &at);
+        true
+    }
+
+    /// Paste pending text.
+    /// Confirm before pasting too many characters or padding rows with too many spaces.
+    pub fn paste(&mut self) -> Result<bool, Error> {
+        if let Some((rows, chars)) = self.content.pending_size() {
+            let limit = self.content.settings().paste_confirm_limit();
+            if 0 < limit && limit < chars {
+                let message = format!("Paste {} rows with {} characters. Continue", rows, chars);
+                if !self.confirm(&message)? {
+                    return Ok(false);
+                }
+            }
+        }
+
+        let padding = self.content.pending_padding(&self.cursor);
+
+        if let Some((rows, spaces)) = padding {
+            if spaces > self.paste_padding_limit {
+                let message = format!(
+                    "Paste will pad {} rows with up to {} spaces. Continue",
+                    rows, spaces
+                );
+                if !self.confirm(&message)? {
+                    return Ok(false);
+                }
+            }
+        }
+
+        let linewise = self.content.pending_linewise();
+        if let Some(pos) = self.content.paste_pending(&self.cursor) {
+            if linewise {
+                // The cursor keeps the column on the same row.
+                let at = (self.cursor.x(), pos.y());
+                self.cursor.set(&self.content, &at);
+            } else {
+                self.cursor.set(&self.content, &pos);
+            }
+
+            if let Some((rows, spaces)) = padding {
+                let message = format!("Padded {} rows with up to {} spaces.", rows, spaces);
+                self.message.set_message(Row::from(message));
+            }
+
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Paste the clipboard, or the pending rows if the clipboard is unavailable.
+    pub fn paste_clipboard(&mut self) -> Result<bool, Error> {
+        self.load_clipboard();
+        match self.content.pending() {
+            Some(_) => self.paste(),
+            None => Ok(false),
+        }
+    }
+
     pub fn goto(&mut self) -> Result<bool, Error> {
         let rows = self.content.rows();
 
@@ -169,99 +822,230 @@ impl<T: Terminal> Editor<T> {
         Ok(false)
     }
 
-    pub fn handle_events(&mut self) -> Result<(), Error> {
-        let event = T::read_event_timeout()?;
-        match event {
-            Event::Key(KeyEvent::BackSpace, _) => {
-                self.delete_char();
-            }
-            Event::Key(KeyEvent::Enter, _) => {
-                self.enter();
-            }
-            Event::Key(KeyEvent::End, _) => {
-                self.cursor.move_to_xmax(&self.content);
-            }
-            Event::Key(KeyEvent::PageUp, _) => {
-                self.screen.move_up();
-                self.cursor.move_up_screen(&self.content, &self.screen);
+    /// Handle an event, and returns the reason if the editor is closed.
+    /// The path of a file dropped onto the window is offered to open instead of typed.
+    pub fn handle_events(&mut self) -> Result<Option<Exit>, Error> {
+        let event = match T::poll_event(POLL_INTERVAL)? {
+            Some(event) => event,
+            None => {
+                self.auto_save(Instant::now());
+                return Ok(None);
             }
-            Event::Key(KeyEvent::PageDown, _) => {
-                self.screen.move_down(&self.content);
-                self.cursor.move_down_screen(&self.content, &self.screen);
-            }
-            Event::Key(KeyEvent::Home, _) => {
-                self.cursor.move_to_x0();
-            }
-            Event::Key(KeyEvent::ArrowLeft, _) => {
-                self.cursor.move_left(&self.content);
-            }
-            Event::Key(KeyEvent::ArrowUp, _) => {
-                self.cursor.move_up_render(&self.content);
+        };
+
+        if !self.content.settings().open_dropped_files() {
+            return self.handle_event(event);
+        }
+
+        let mut events = read_queued::<T>(event)?;
+        if path_started(&events) {
+            events = read_burst::<T>(events)?;
+            if let Some(path) = dropped_path(&events) {
+                let message = format!("{} {}", TEXT_CONFIRM_OPEN_DROPPED, path.display());
+                if self.confirm(&message)? {
+                    self.open(&path)?;
+                    return Ok(None);
+                }
             }
-            Event::Key(KeyEvent::ArrowRight, _) => {
-                self.cursor.move_right(&self.content);
+        }
+
+        for event in events {
+            if let Some(exit) = self.handle_event(event)? {
+                return Ok(Some(exit));
             }
-            Event::Key(KeyEvent::ArrowDown, _) => {
-                self.cursor.move_down_render(&self.content);
+        }
+        Ok(None)
+    }
+
+    fn handle_event(&mut self, event: Event) -> Result<Option<Exit>, Error> {
+        if let Event::Key(_, _) = event {
+            self.restore_message();
+        }
+
+        let changed = match self.translate(event) {
+            Some(action) => match self.dispatch(action)? {
+                (_, Some(exit)) => return Ok(Some(exit)),
+                (changed, None) => changed,
+            },
+            None => false,
+        };
+
+        if self.content.updated() {
+            self.clear_decoration();
+        }
+
+        if !char_typed(event) {
+            self.auto_closed.clear();
+        }
+
+        self.update_select(event, changed);
+        Ok(None)
+    }
+
+    /// Returns the action bound to the event.
+    pub fn translate(&self, event: Event) -> Option<EditorAction> {
+        let action = match event {
+            Event::Key(KeyEvent::BackSpace, _) => EditorAction::DeleteChar,
+            Event::Key(KeyEvent::Enter, _) => EditorAction::Enter,
+            Event::Key(KeyEvent::End, KeyModifier::CtrlLeft | KeyModifier::CtrlRight) => {
+                EditorAction::MoveBottom
             }
-            Event::Key(KeyEvent::Delete, _) => {
-                self.cursor.move_right(&self.content);
-                self.delete_char();
+            Event::Key(KeyEvent::End, _) => EditorAction::MoveEnd,
+            Event::Key(KeyEvent::PageUp, _) => EditorAction::PageUp,
+            Event::Key(KeyEvent::PageDown, _) => EditorAction::PageDown,
+            Event::Key(KeyEvent::HalfPageUp, _) if self.content.settings().half_page_scroll() => {
+                EditorAction::HalfPageUp
             }
-            Event::Key(KeyEvent::DeleteRow, _) => {
-                if self.content.row_char_len(&self.cursor) == 0 {
-                    self.content.delete_row(&self.cursor);
-                } else {
-                    self.content.shrink_row(&self.cursor);
-                }
+            Event::Key(KeyEvent::HalfPageDown, _) if self.content.settings().half_page_scroll() => {
+                EditorAction::HalfPageDown
             }
-            Event::Key(KeyEvent::Copy, _) => {
-                if let (Some(start), Some(end)) = (self.select.start(), self.select.end()) {
-                    self.content.copy_pending(start..end, self.select.mode());
-                }
+            Event::Key(KeyEvent::DuplicateLine, _) => EditorAction::DuplicateLine,
+            Event::Key(KeyEvent::Home, KeyModifier::CtrlLeft | KeyModifier::CtrlRight) => {
+                EditorAction::MoveTop
             }
-            Event::Key(KeyEvent::Cut, _) => {
-                if let (Some(start), Some(end)) = (self.select.start(), self.select.end()) {
-                    self.content.delete_chars(start, end, self.select.mode());
-                    self.cursor.set(&self.content, start);
-                }
+            Event::Key(KeyEvent::Home, _) => EditorAction::MoveHome,
+            Event::Key(KeyEvent::ArrowLeft, KeyModifier::CtrlLeft | KeyModifier::CtrlRight) => {
+                EditorAction::MoveWordLeft
             }
-            Event::Key(KeyEvent::Find, _) => {
-                self.find()?;
+            Event::Key(KeyEvent::ArrowRight, KeyModifier::CtrlLeft | KeyModifier::CtrlRight) => {
+                EditorAction::MoveWordRight
             }
-            Event::Key(KeyEvent::Exit, _) => {
-                self.exit()?;
+            Event::Key(KeyEvent::ArrowLeft, _) => EditorAction::MoveLeft,
+            Event::Key(KeyEvent::ArrowUp, KeyModifier::AltLeft) => EditorAction::MovePrevIndent,
+            Event::Key(KeyEvent::ArrowDown, KeyModifier::AltLeft) => EditorAction::MoveNextIndent,
+            Event::Key(KeyEvent::ArrowUp, KeyModifier::AltShift) => EditorAction::MoveRowUp,
+            Event::Key(KeyEvent::ArrowDown, KeyModifier::AltShift) => EditorAction::MoveRowDown,
+            Event::Key(KeyEvent::ArrowUp, _) => EditorAction::MoveUp,
+            Event::Key(KeyEvent::ArrowRight, _) => EditorAction::MoveRight,
+            Event::Key(KeyEvent::ArrowDown, _) => EditorAction::MoveDown,
+            Event::Key(KeyEvent::Delete, _) => EditorAction::DeleteForward,
+            Event::Key(KeyEvent::DeleteRow, _) => EditorAction::DeleteRow,
+            Event::Key(KeyEvent::Comment, _) => EditorAction::Comment,
+            Event::Key(KeyEvent::Copy, _) => EditorAction::Copy,
+            Event::Key(KeyEvent::Cut, _) => EditorAction::Cut,
+            Event::Key(KeyEvent::Find, _) => EditorAction::Find,
+            Event::Key(KeyEvent::F3, KeyModifier::None) => EditorAction::FindNext,
+            Event::Key(KeyEvent::F3, KeyModifier::Shift) => EditorAction::FindPrevious,
+            Event::Key(KeyEvent::Exit, _) => EditorAction::Exit,
+            Event::Key(KeyEvent::Format, _) => EditorAction::Format,
+            Event::Key(KeyEvent::Goto, _) => EditorAction::Goto,
+            Event::Key(KeyEvent::Save, _) => EditorAction::Save,
+            Event::Key(KeyEvent::SelectAll, _) => EditorAction::SelectAll,
+            Event::Key(KeyEvent::SetMark(slot), _) => EditorAction::SetMark(slot),
+            Event::Key(KeyEvent::JumpToMark(slot), _) => EditorAction::JumpToMark(slot),
+            Event::Key(KeyEvent::MatchBracket, _) => EditorAction::MatchBracket,
+            Event::Key(KeyEvent::Sort, _) => EditorAction::SortRows,
+            Event::Key(KeyEvent::Paste, _) => EditorAction::Paste,
+            Event::Key(KeyEvent::Replace, _) => EditorAction::Replace,
+            Event::Key(KeyEvent::Undo, _) => EditorAction::Undo,
+            Event::Key(KeyEvent::Redo, _) => EditorAction::Redo,
+            Event::Key(KeyEvent::Tab, KeyModifier::None) => EditorAction::InputTab,
+            // Shift+Tab is left unbound for unindenting.
+            Event::Key(KeyEvent::Tab, KeyModifier::Shift) => return None,
+            Event::Key(KeyEvent::Tab, _) => EditorAction::InputChar('\t'),
+            Event::Key(KeyEvent::Escape, _) => EditorAction::Cancel,
+            Event::Key(KeyEvent::Char(ch), modifier) if !ch.is_ascii_control() => match modifier {
+                KeyModifier::AltLeft => alt_action(ch).unwrap_or(EditorAction::TypeChar(ch)),
+                _ => EditorAction::TypeChar(ch),
+            },
+            Event::Window(WindowEvent::Resize) => EditorAction::Resize,
+            _ => return None,
+        };
+
+        Some(action)
+    }
+
+    /// Runs the action, and returns whether the cursor or the content is changed,
+    /// and the reason if the editor is closed.
+    pub fn dispatch(&mut self, action: EditorAction) -> Result<(bool, Option<Exit>), Error> {
+        let changed = match action {
+            EditorAction::Abort => return Ok((false, self.abort()?)),
+            EditorAction::AddNumber(delta) => self.add_number(delta),
+            EditorAction::Cancel => self.cancel(),
+            EditorAction::CheckBrackets => self.check_brackets()?,
+            EditorAction::CloseSplit => self.close_split()?,
+            EditorAction::Comment => self.toggle_comment(),
+            EditorAction::Copy => self.copy(),
+            EditorAction::Cut => self.cut(),
+            EditorAction::DeleteChar => self.delete_char(),
+            EditorAction::DeleteForward => self.delete_forward(),
+            EditorAction::DeleteRow => self.delete_row(),
+            EditorAction::DuplicateLine => self.duplicate_line(),
+            EditorAction::EditSettings => self.edit_settings()?,
+            EditorAction::Enter => self.enter_over_select(),
+            EditorAction::Exit => return Ok((false, self.exit()?)),
+            EditorAction::ExpandSelect => self.expand_select(),
+            EditorAction::Find => self.find()?,
+            EditorAction::FindNext => self.find_next()?,
+            EditorAction::FindPrevious => self.find_previous()?,
+            EditorAction::Format => self.format(),
+            EditorAction::Goto => self.goto()?,
+            EditorAction::HalfPageDown => self.scroll_down(self.screen.half_height()),
+            EditorAction::HalfPageUp => self.scroll_up(self.screen.half_height()),
+            EditorAction::InputChar(ch) => self.replace_select(|editor| editor.input_char(ch)),
+            EditorAction::InputTab => self.replace_select(Self::input_tab),
+            EditorAction::JsonEscape => self.convert_select(|text| Some(escape::json_escape(text))),
+            EditorAction::JsonUnescape => self.convert_select(escape::json_unescape),
+            EditorAction::JumpToMark(slot) => self.jump_to_mark(slot),
+            EditorAction::MatchBracket => self.jump_to_bracket(),
+            EditorAction::MoveBottom => self.cursor.move_to_bottom(&self.content),
+            EditorAction::MoveDown => self.cursor.move_down_render(&self.content),
+            EditorAction::MoveEnd => self.cursor.move_to_xmax(&self.content),
+            EditorAction::MoveHome => self.cursor.move_to_x0(),
+            EditorAction::MoveLeft => self.cursor.move_left(&self.content),
+            EditorAction::MoveNextIndent => self.cursor.move_to_next_indent(&self.content),
+            EditorAction::MovePrevIndent => self.cursor.move_to_prev_indent(&self.content),
+            EditorAction::MoveRight => self.cursor.move_right(&self.content),
+            EditorAction::MoveRowDown => self.move_row_down(),
+            EditorAction::MoveRowUp => self.move_row_up(),
+            EditorAction::MoveTop => self.cursor.move_to_top(),
+            EditorAction::MoveWordLeft => self.cursor.move_word_left(&self.content),
+            EditorAction::MoveWordRight => self.cursor.move_word_right(&self.content),
+            EditorAction::MoveUp => self.cursor.move_up_render(&self.content),
+            EditorAction::NextChange => self.next_change(),
+            EditorAction::PageDown => self.scroll_down(self.screen.height()),
+            EditorAction::PageUp => self.scroll_up(self.screen.height()),
+            EditorAction::Paste => self.paste_clipboard()?,
+            EditorAction::PreviousChange => self.previous_change(),
+            EditorAction::Redo => self.redo(),
+            EditorAction::Reflow => self.reflow(),
+            EditorAction::ReopenClosed => self.reopen_closed()?,
+            EditorAction::ReopenWithEncoding => self.reopen_with_encoding()?,
+            EditorAction::Replace => {
+                self.replace()?;
+                false
             }
-            Event::Key(KeyEvent::Goto, _) => {
-                self.goto()?;
+            EditorAction::Resize => {
+                self.resize_screen()?;
+                false
             }
-            Event::Key(KeyEvent::Save, _) => {
+            EditorAction::Save => {
                 self.save()?;
+                false
             }
-            Event::Key(KeyEvent::Paste, _) => {
-                if self.content.pending().is_some() {
-                    if let Some(pos) = self.content.paste_pending(&self.cursor) {
-                        self.cursor.set(&self.content, &pos);
-                    }
-                }
-            }
-            Event::Key(KeyEvent::Replace, _) => self.replace()?,
-            Event::Key(KeyEvent::Undo, _) => {
-                if let Some(cur) = self.content.undo() {
-                    self.cursor.set(&self.content, &cur);
-                }
-            }
-            Event::Key(KeyEvent::Char(ch), _) if !ch.is_ascii_control() => {
-                self.input_char(ch);
+            EditorAction::SelectAll => self.select_all(),
+            EditorAction::SelectLines => self.select_lines(),
+            EditorAction::SetBaseline => {
+                self.set_baseline();
+                false
             }
-            Event::Window(WindowEvent::Resize) => {
-                self.resize_screen()?;
+            EditorAction::SetMark(slot) => {
+                self.set_mark(slot);
+                false
             }
-            _ => {}
+            EditorAction::ShellQuote => self.convert_select(|text| Some(escape::shell_quote(text))),
+            EditorAction::ShrinkSelect => self.shrink_select(),
+            EditorAction::SortRows => self.sort_rows()?,
+            EditorAction::SpacesToTabs => self.spaces_to_tabs(),
+            EditorAction::Split(split) => self.split_screen(split)?,
+            EditorAction::SwitchView => self.switch_view(),
+            EditorAction::TabsToSpaces => self.tabs_to_spaces(),
+            EditorAction::ToggleChanges => self.toggle_changes(),
+            EditorAction::TypeChar(ch) => self.type_char(ch),
+            EditorAction::Undo => self.undo(),
         };
 
-        self.update_select(event);
-        Ok(())
+        Ok((changed, None))
     }
 
     pub fn input_char(&mut self, ch: char) -> bool {
@@ -273,7 +1057,41 @@ impl<T: Terminal> Editor<T> {
         self.cursor.move_right(&self.content)
     }
 
+    /// Move to the next tab stop or expand the snippet, otherwise insert tab.
+    pub fn input_tab(&mut self) -> bool {
+        self.next_tab_stop_or_expand() || self.insert_tab()
+    }
+
+    /// Insert spaces to the next tab stop if soft-tabs is enabled, otherwise insert tab.
+    pub fn insert_tab(&mut self) -> bool {
+        let settings = self.content.settings();
+        if !settings.expand_tabs() {
+            return self.input_char('\t');
+        }
+
+        let (x, y) = self.cursor.render(&self.content);
+        let spaces = vec![' '; settings.tab_width() - x % settings.tab_width()];
+        if self.content.rows() <= y {
+            self.content.insert_row(&self.cursor, &spaces);
+        } else {
+            self.content.insert_chars(
+                &self.cursor,
+                &[Row::from(spaces.as_slice())],
+                SelectMode::None,
+            );
+        }
+
+        self.cursor
+            .set_x(&self.content, self.cursor.x() + spaces.len())
+    }
+
     pub fn init(&mut self) -> Result<(), Error> {
+        if self.content.binary() && !self.confirm_open_binary()? {
+            self.content = Buffer::default();
+            self.status = StatusBar::new(&self.screen, None);
+        }
+        self.confirm_loaded()?;
+
         refresh_screen(
             &self.cursor,
             &mut self.content,
@@ -289,25 +1107,280 @@ impl<T: Terminal> Editor<T> {
         Ok(())
     }
 
-    pub fn refresh(&mut self) -> Result<(), Error> {
+    /// Open the file in place of the buffer, after confirming to discard the changes.
+    pub fn open(&mut self, path: &Path) -> Result<bool, Error> {
+        if self.content.cached() && !self.confirm_exit()? {
+            return Ok(false);
+        }
+
+        let content = match Buffer::try_from(Some(path)) {
+            Ok(content) => content,
+            Err(e) => {
+                let message = format!("{} is not opened: {:?}", path.display(), e);
+                self.message.set_message(Row::from(message));
+                return Ok(false);
+            }
+        };
+        // The buffer is kept if the binary file is declined.
+        if content.binary() && !self.confirm_open_binary()? {
+            return Ok(false);
+        }
+
+        if let Some(closed) = self.content.filename().filter(|&f| f != path) {
+            self.closed
+                .push(closed.to_path_buf(), self.cursor.as_coordinates());
+        }
+        self.content = content;
+        self.reset_view()?;
+
+        self.confirm_loaded()?;
+        Ok(true)
+    }
+
+    /// Open the file closed last by opening another, at the position the cursor was left.
+    pub fn reopen_closed(&mut self) -> Result<bool, Error> {
+        let (path, at) = match self.closed.pop() {
+            Some(closed) => closed,
+            None => {
+                self.message.set_message(Row::from(TEXT_MESSAGE_NO_CLOSED));
+                return Ok(false);
+            }
+        };
+
+        if !self.open(&path)? {
+            self.closed.push(path, at);
+            return Ok(false);
+        }
+        self.jump_to(&at);
+        Ok(true)
+    }
+
+    /// Load the file again decoded by the encoding chosen, discarding the changes if confirmed.
+    pub fn reopen_with_encoding(&mut self) -> Result<bool, Error> {
+        if self.content.filename().is_none() {
+            self.message
+                .set_message(Row::from(TEXT_MESSAGE_NO_FILENAME));
+            return Ok(false);
+        }
+
+        let encoding = match self.choose(TEXT_CHOICE_ENCODING, &ENCODING_OPTIONS)? {
+            Some('l') => Encoding::Utf16Le,
+            Some('b') => Encoding::Utf16Be,
+            Some('s') => Encoding::ShiftJis,
+            Some(_) => Encoding::Utf8,
+            None => return Ok(false),
+        };
+        if self.content.cached() && !self.confirm_exit()? {
+            return Ok(false);
+        }
+
+        let at = self.cursor.as_coordinates();
+        if let Err(e) = self.content.reload(Some(encoding)) {
+            let message = format!("Buffer is not reopened: {:?}", e);
+            self.message.set_message(Row::from(message));
+            return Ok(false);
+        }
+        self.reset_view()?;
+
+        self.confirm_loaded()?;
+        self.jump_to(&at);
+        Ok(true)
+    }
+
+    /// Reset the states of the view for the content loaded.
+    fn reset_view(&mut self) -> Result<(), Error> {
+        self.set_config(self.config.clone());
+
+        if self.split.take().is_some() {
+            self.resize_screen()?;
+        }
+        let filename = self.content.filename().and_then(|f| f.to_str());
+        self.status = StatusBar::new(&self.screen, filename);
+        self.cursor = Cursor::default();
+        self.select = Select::default();
+        self.highlight = None;
+        self.baseline = None;
+        self.expansions.clear();
+        self.auto_closed.clear();
+        self.tab_stops = TabStops::default();
+        self.jump_to(&(0, 0));
+        self.screen.force_update();
+        Ok(())
+    }
+
+    /// Show whether the loaded content is binary, and confirm to edit it if it contains
+    /// invalid sequences. Binary content is confirmed before it replaces the buffer.
+    fn confirm_loaded(&mut self) -> Result<(), Error> {
+        self.status.set_binary(self.content.binary());
+
+        let invalid = self.content.invalid_lines();
+        if 0 < invalid {
+            if self.confirm(TEXT_CONFIRM_EDIT_INVALID)? {
+                self.content.set_read_only(false);
+            }
+            let message = format!(
+                "{} lines contained invalid {} and were replaced.",
+                invalid,
+                self.content.encoding()
+            );
+            self.message.set_message(Row::from(message));
+        }
+
+        Ok(())
+    }
+
+    /// Move the cursor and the screen to the position.
+    pub fn jump_to<P: Coordinates>(&mut self, at: &P) -> bool {
+        let moved = self.cursor.set(&self.content, at);
+
+        let render = self.cursor.render(&self.content);
+        self.screen.fit(&self.content, &render);
+
+        moved
+    }
+
+    /// Move to the position in the numbered mark, or show the message if it is not set.
+    pub fn jump_to_mark(&mut self, slot: usize) -> bool {
+        match self.content.mark(slot) {
+            Some(at) => self.jump_to(&at),
+            None => {
+                let message = format!("Mark {} is not set.", slot);
+                self.message.set_message(Row::from(message));
+                false
+            }
+        }
+    }
+
+    /// Move to the bracket paired with the bracket at the cursor.
+    pub fn jump_to_bracket(&mut self) -> bool {
+        match self.content.matching_bracket(&self.cursor) {
+            Some(at) => self.jump_to(&at),
+            None => false,
+        }
+    }
+
+    /// Redo the last undone change, and move the cursor to it in the same way as undo.
+    pub fn redo(&mut self) -> bool {
+        match self.content.redo() {
+            Some(cur) => {
+                self.move_to_change(cur);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Reflow the paragraph at the cursor to the wrap width, and keep the cursor on the word.
+    pub fn reflow(&mut self) -> bool {
+        let width = self.content.settings().wrap_width();
+        match self.content.reflow_paragraph(&self.cursor, width) {
+            Some(at) => {
+                self.cursor.set(&self.content, &at);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn refresh(&mut self) -> Result<(), Error> {
+        let area = match self.split {
+            Some(_) => Some(self.layout_split()?),
+            None => None,
+        };
+
         let render = self.cursor.render(&self.content);
 
+        self.screen.clamp_to(&self.content);
         self.screen.fit(&self.content, &render);
 
-        refresh_screen(
-            &render,
-            &mut self.content,
-            &mut self.screen,
-            &mut self.select,
-            &mut self.status,
-            &mut self.message,
-            &mut self.terminal,
-        )?;
+        if let Some((at, _, deadline)) = self.highlight.as_ref() {
+            if *at != self.cursor || *deadline <= Instant::now() {
+                self.highlight = None;
+                // Delete text decoration.
+                self.screen.force_update();
+            }
+        }
 
-        self.terminal.set_cursor_position(
-            render.x() - self.screen.left(),
-            render.y() - self.screen.top(),
-        )?;
+        let software_cursor = self.content.settings().software_cursor();
+        if software_cursor {
+            self.screen.set_cursor(&self.cursor);
+        } else {
+            self.screen.clear_cursor();
+        }
+
+        let search = match self.keyword.as_ref().filter(|_| self.decoration) {
+            Some(keyword) => {
+                let keyword = keyword.to_string_at(0);
+                let at = self.cursor.as_coordinates();
+                let (index, total) =
+                    self.match_count
+                        .count(&self.content, &keyword, self.ignore_case, at);
+                Some(format!("/{} {}/{}", keyword, index, total))
+            }
+            None => None,
+        };
+        self.status.set_search(search);
+        self.status.set_line_ending(self.content.line_ending());
+        self.status.set_bom(self.content.has_bom());
+        self.status.set_encoding(self.content.encoding());
+        self.status
+            .set_color(self.content.settings().theme().status);
+        self.message
+            .set_fg_color(self.content.settings().theme().message);
+
+        // Redrawn rows lose the decoration.
+        let redrawn = self.screen.updated() || self.content.updated() || self.select.updated();
+
+        if let Some(area) = area {
+            self.refresh_split(&render, area)?;
+        } else {
+            refresh_screen(
+                &render,
+                &mut self.content,
+                &mut self.screen,
+                &mut self.select,
+                &mut self.status,
+                &mut self.message,
+                &mut self.terminal,
+            )?;
+        }
+
+        if let Some(keyword) = self.keyword.as_ref().filter(|_| self.decoration && redrawn) {
+            self.screen
+                .decorate(&self.content, keyword, self.ignore_case, &mut self.terminal)?;
+        }
+
+        if let Some(baseline) = self
+            .baseline
+            .as_ref()
+            .filter(|_| self.show_changes && redrawn)
+        {
+            let changes = self.content.changed_rows(baseline);
+            self.screen
+                .decorate_rows(&self.content, &changes, &mut self.terminal)?;
+        }
+
+        if let Some((at, keyword, _)) = self.highlight.as_ref() {
+            prompt::set_text_attribute(
+                at,
+                &self.content,
+                &self.screen,
+                &mut self.terminal,
+                keyword,
+            )?;
+        }
+
+        if software_cursor {
+            self.screen.draw_cursor(&self.content, &mut self.terminal)?;
+        }
+
+        // The caret is shown again by prompts.
+        self.terminal.set_cursor_visible(!software_cursor)?;
+
+        // The caret is kept in the text area, not to be drawn over the status bar.
+        let (x, y) = self.screen.position(&render);
+        let bottom = self.screen.origin().1 + self.screen.height() - 1;
+        self.terminal.set_cursor_position(x, min(y, bottom))?;
 
         Ok(())
     }
@@ -329,23 +1402,55 @@ impl<T: Terminal> Editor<T> {
             row.map(|r| r.to_string_at(0)).as_deref(),
         )?;
 
+        if let Some(keyword) = prompt.keyword() {
+            self.keyword = Some(keyword.clone());
+            self.ignore_case = prompt.ignore_case();
+        }
+
         // Delete text decoration.
-        self.screen.force_update();
+        if prompt.painted() {
+            self.screen.force_update();
+        }
 
         self.message.force_update();
         Ok(())
     }
 
+    /// Resize the screen keeping the row of cursor on screen if possible.
     pub fn resize_screen(&mut self) -> Result<(), Error> {
+        let render = self.cursor.render(&self.content);
+        let row = render.y().saturating_sub(self.screen.top());
+
         resize_screen(
             &mut self.screen,
             &mut self.status,
             &mut self.message,
             &mut self.terminal,
-        )
+        )?;
+
+        self.screen.keep_row(&render, row);
+        Ok(())
+    }
+
+    /// Scroll the screen and move the cursor down the rows together,
+    /// so the cursor stays at the same row of screen unless clamped.
+    pub fn scroll_down(&mut self, rows: usize) -> bool {
+        let scrolled = self.screen.scroll_down(&self.content, rows);
+        self.cursor.move_down_rows(&self.content, rows) || scrolled
+    }
+
+    /// Scroll the screen and move the cursor up the rows together.
+    pub fn scroll_up(&mut self, rows: usize) -> bool {
+        let scrolled = self.screen.scroll_up(rows);
+        self.cursor.move_up_rows(&self.content, rows) || scrolled
     }
 
     pub fn save(&mut self) -> Result<(), Error> {
+        if self.content.read_only() {
+            self.message.set_message(Row::from(TEXT_MESSAGE_READ_ONLY));
+            return Ok(());
+        }
+
         self.content.save()?;
 
         if self.content.cached() {
@@ -360,6 +1465,7 @@ impl<T: Terminal> Editor<T> {
 
             if let Some(filename) = prompt.handle_events(TEXT_MESSAGE_INPUT_FILENAME, None)? {
                 let path = PathBuf::from(filename);
+                self.content.set_settings(self.config.resolve(Some(&path)));
                 self.content.save_as(&path)?;
                 self.content.set_filename(&path);
                 self.status
@@ -369,237 +1475,3842 @@ impl<T: Terminal> Editor<T> {
             self.message.force_update();
         }
 
+        if !self.content.cached() {
+            self.saved(TEXT_MESSAGE_SAVED)?;
+        }
+
         Ok(())
     }
 
-    pub fn select(&self) -> &Select {
-        &self.select
-    }
+    /// Show the notice of the saved file, and check it loads back to the buffer if enabled.
+    fn saved(&mut self, notice: &str) -> Result<(), Error> {
+        self.message.set_notice(Row::from(notice), NOTICE_DURATION);
 
-    pub fn screen(&self) -> &Screen {
-        &self.screen
+        if self.content.settings().verify_save() {
+            if let Some(y) = self.content.verify_saved()? {
+                log_warn!(
+                    "{} {} ({:?}).",
+                    TEXT_MESSAGE_SAVE_DIFFERS,
+                    y + 1,
+                    self.content.filename()
+                );
+                self.message.set_message(Row::from(format!(
+                    "{} {}.",
+                    TEXT_MESSAGE_SAVE_DIFFERS,
+                    y + 1
+                )));
+            }
+        }
+
+        Ok(())
     }
 
-    fn get_selected_text(&self) -> Option<Vec<Row>> {
-        if let (Some(start), Some(end)) = (self.select.start(), self.select.end()) {
-            self.content.get_range(start..end, self.select.mode())
-        } else {
-            None
+    /// Save the modified file without the prompt after no changes for `auto_save_seconds`,
+    /// and returns whether it is saved. The buffer without a filename is skipped.
+    pub fn auto_save(&mut self, now: Instant) -> bool {
+        let revision = self.content.revision();
+        let changed_at = match self.last_change {
+            Some((last, at)) if last == revision => at,
+            _ => {
+                self.last_change = Some((revision, now));
+                return false;
+            }
+        };
+
+        let seconds = self.content.settings().auto_save_seconds();
+        if seconds == 0
+            || !self.content.cached()
+            || self.content.filename().is_none()
+            || self.content.read_only()
+            || now.duration_since(changed_at) < Duration::from_secs(seconds)
+        {
+            return false;
         }
-    }
 
-    fn update_select(&mut self, event: Event) {
-        if let Event::Key(e, m) = event {
-            if selected_moved(m) && row_moved(e) {
-                if self.select.enabled {
-                    self.select.set_end(&self.cursor);
-                } else {
-                    self.select.set_start(&self.cursor, SelectMode::from(m));
+        match self.content.save() {
+            Ok(()) => {
+                if let Err(e) = self.saved(TEXT_MESSAGE_AUTO_SAVED) {
+                    log_warn!("Saved file is not verified: {:?}", e);
                 }
-            } else {
-                self.select.disable();
+                true
+            }
+            Err(e) => {
+                log_warn!("Buffer is not saved automatically: {:?}", e);
+                let message = Row::from(TEXT_MESSAGE_AUTO_SAVE_FAILED);
+                self.message.set_notice(message, NOTICE_DURATION);
+                // Retry after the interval again instead of every poll.
+                self.last_change = Some((revision, now));
+                false
             }
-        } else {
-            self.select.disable();
         }
     }
-}
-
-// -----------------------------------------------------------------------------------------------
-
-#[derive(Clone, Copy, Debug, Default, PartialEq)]
-pub enum SelectMode {
-    #[default]
-    None,
-    Rectangle,
-}
 
-impl From<KeyModifier> for SelectMode {
-    fn from(value: KeyModifier) -> SelectMode {
-        match value {
-            KeyModifier::CtrlLeft => SelectMode::Rectangle,
-            _ => SelectMode::None,
+    /// Take the snapshot of the buffer to compare changes with.
+    pub fn set_baseline(&mut self) {
+        self.baseline = Some(self.content.snapshot());
+        self.message.set_message(Row::from(TEXT_MESSAGE_BASELINE));
+        if self.show_changes {
+            self.screen.force_update();
         }
     }
-}
 
-// -----------------------------------------------------------------------------------------------
+    /// Record the cursor position in the numbered mark.
+    pub fn set_mark(&mut self, slot: usize) {
+        self.content.set_mark(slot, &self.cursor);
+        self.message
+            .set_message(Row::from(format!("Mark {} is set.", slot)));
+    }
 
-#[derive(Clone, Debug, Default, PartialEq)]
-pub struct Select {
-    mode: SelectMode,
-    range: Option<(Cursor, Cursor)>,
-    previous: Option<Cursor>,
-    enabled: bool,
-    updated: bool,
-}
+    /// Change a setting of the current buffer by `key = value`, or show the settings if empty.
+    pub fn edit_settings(&mut self) -> Result<bool, Error> {
+        let settings = self.content.settings().clone();
 
-impl Select {
-    pub fn clear_updated(&mut self) {
-        self.updated = false;
-    }
+        let mut prompt = prompt::Input::new(
+            &mut self.cursor,
+            &mut self.content,
+            &mut self.screen,
+            &mut self.status,
+            &mut self.message,
+            &mut self.terminal,
+        );
 
-    pub fn disable(&mut self) {
-        let cur = self.clone();
+        while let Some(input) = prompt.handle_events(TEXT_MESSAGE_INPUT_SETTING, None)? {
+            let mut settings = settings.clone();
+            let changed = match input.split_once('=') {
+                Some((key, value)) if settings.set(key.trim(), value.trim()) => true,
+                _ if input.trim().is_empty() => false,
+                _ => continue,
+            };
 
-        if !self.enabled {
-            self.mode = SelectMode::None;
-            self.range = None;
-            self.previous = None;
+            if changed {
+                // The line ending set explicitly overrides the loaded one.
+                if input
+                    .split_once('=')
+                    .is_some_and(|(k, _)| k.trim() == "line_ending")
+                {
+                    self.content.set_line_ending(settings.line_ending());
+                }
+                self.content.set_settings(settings);
+                let at = self.cursor.as_coordinates();
+                self.cursor.set(&self.content, &at);
+            }
+
+            let message = self.content.settings().to_string();
+            self.message.set_message(Row::from(message.as_str()));
+            return Ok(changed);
         }
 
-        self.enabled = false;
+        self.message.force_update();
+        Ok(false)
+    }
 
-        self.updated |= cur != *self;
+    /// Select the current row as a whole line, or switch the selection to whole lines.
+    pub fn select_lines(&mut self) -> bool {
+        if self.select.enabled() {
+            self.select.set_mode(SelectMode::Line);
+        } else {
+            self.select.set_start(&self.cursor, SelectMode::Line);
+        }
+        true
     }
 
-    pub fn mode(&self) -> SelectMode {
-        self.mode
+    /// Select the whole buffer, and move the cursor to the end of it.
+    pub fn select_all(&mut self) -> bool {
+        let last = self.content.rows().saturating_sub(1);
+        let end = (self.content.row_char_len(&(0, last)), last);
+        self.select_span(&(0, 0), &end);
+        self.screen.force_update();
+        true
     }
 
-    pub fn enabled(&self) -> bool {
-        self.enabled
+    /// Split the screen into two views of the buffer, and focus on the leading view.
+    pub fn split_screen(&mut self, split: Split) -> Result<bool, Error> {
+        if self.split.is_some() {
+            return Ok(false);
+        }
+
+        let view = View {
+            cursor: self.cursor.clone(),
+            screen: self.screen.clone(),
+            leading: false,
+        };
+        self.split = Some((split, view));
+        self.layout_split()?;
+        Ok(true)
     }
 
-    pub fn end(&self) -> Option<&Cursor> {
-        if let (Some(s), Some(e)) = (
-            self.range.as_ref().map(|r| &r.0),
-            self.range.as_ref().map(|r| &r.1),
-        ) {
-            if s.y() < e.y() {
-                Some(e)
-            } else if e.y() < s.y() {
-                Some(s)
-            } else if s.x() < e.x() {
-                Some(e)
-            } else {
-                Some(s)
+    /// Move the focus to the other view.
+    pub fn switch_view(&mut self) -> bool {
+        match self.split.as_mut() {
+            Some((_, view)) => {
+                mem::swap(&mut self.cursor, &mut view.cursor);
+                mem::swap(&mut self.screen, &mut view.screen);
+                view.leading = !view.leading;
+
+                self.select.disable();
+                self.expansions.clear();
+                self.tab_stops = TabStops::default();
+
+                // Delete selection in the other view.
+                self.screen.force_update();
+                view.screen.force_update();
+                true
             }
-        } else {
-            None
+            None => false,
         }
     }
 
-    pub fn changes(&self, y: usize) -> bool {
-        match (self.start(), self.end()) {
-            (Some(start), Some(end)) => {
-                if start.y() <= y && y <= end.y() {
-                    true
-                } else if let Some(prev) = &self.previous {
-                    prev.y() == y
-                } else {
-                    false
+    /// Restore the selection before the last expansion.
+    pub fn shrink_select(&mut self) -> bool {
+        match self.expansions.pop() {
+            Some((cursor, previous)) => {
+                match previous {
+                    Some((start, end)) => self.select_span(&start, &end),
+                    None => self.select.disable(),
                 }
+                self.cursor = cursor;
+                // Rows out of the shrunk selection are redrawn.
+                self.screen.force_update();
+                true
             }
-            _ => false,
+            None => false,
         }
     }
 
-    pub fn set_end(&mut self, end: &Cursor) {
-        let cur = self.clone();
+    /// Sort the selected rows, or all rows if not selected, by the key column.
+    pub fn sort_rows(&mut self) -> Result<bool, Error> {
+        let rows = self.selected_rows().unwrap_or(0..self.content.rows());
 
-        self.previous = self.range.as_ref().map(|r| r.1.clone());
-        self.range.as_mut().unwrap().1 = end.clone();
+        let mut prompt = prompt::Input::new(
+            &mut self.cursor,
+            &mut self.content,
+            &mut self.screen,
+            &mut self.status,
+            &mut self.message,
+            &mut self.terminal,
+        );
 
-        self.updated |= cur != *self;
-    }
+        while let Some(key) = prompt.handle_events(TEXT_MESSAGE_INPUT_SORT_KEY, None)? {
+            if let Some((start, length)) = parse_sort_key(&key) {
+                self.message.force_update();
+                if !self.content.sort_rows(rows, start, length) {
+                    return Ok(false);
+                }
 
-    pub fn set_start(&mut self, start: &Cursor, mode: SelectMode) {
-        let cur = self.clone();
+                let at = self.cursor.as_coordinates();
+                self.cursor.set(&self.content, &at);
+                return Ok(true);
+            }
+        }
 
-        self.mode = mode;
-        self.previous = None;
-        self.range = Some((start.clone(), start.clone()));
-        self.enabled = true;
+        self.message.force_update();
+        Ok(false)
+    }
 
-        self.updated |= cur != *self;
+    /// Convert leading spaces to tabs in the selected rows or the whole buffer.
+    pub fn spaces_to_tabs(&mut self) -> bool {
+        let rows = self.selected_rows().unwrap_or(0..self.content.rows());
+        let converted = self.content.spaces_to_tabs(rows);
+        if converted {
+            let at = self.cursor.as_coordinates();
+            self.cursor.set(&self.content, &at);
+        }
+        converted
     }
 
-    pub fn start(&self) -> Option<&Cursor> {
-        if let (Some(s), Some(e)) = (
-            self.range.as_ref().map(|r| &r.0),
-            self.range.as_ref().map(|r| &r.1),
-        ) {
-            if s.y() < e.y() {
-                Some(s)
-            } else if e.y() < s.y() {
-                Some(e)
-            } else if s.x() < e.x() {
-                Some(s)
+    /// Convert leading tabs to spaces in the selected rows or the whole buffer.
+    pub fn tabs_to_spaces(&mut self) -> bool {
+        let rows = self.selected_rows().unwrap_or(0..self.content.rows());
+        let converted = self.content.tabs_to_spaces(rows);
+        if converted {
+            let at = self.cursor.as_coordinates();
+            self.cursor.set(&self.content, &at);
+        }
+        converted
+    }
+
+    pub fn toggle_comment(&mut self) -> bool {
+        let rows = self
+            .selected_rows()
+            .unwrap_or(self.cursor.y()..self.cursor.y() + 1);
+
+        if self.content.toggle_comment(rows) {
+            let at = self.cursor.as_coordinates();
+            self.cursor.set(&self.content, &at);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Toggle highlighting the rows changed from the baseline.
+    pub fn toggle_changes(&mut self) -> bool {
+        self.show_changes = !self.show_changes;
+        // Delete or draw the highlight.
+        self.screen.force_update();
+        self.show_changes
+    }
+
+    /// Input the typed character. The bracket or the quote surrounds the selected text,
+    /// or is inserted with the closing one if `auto_pairs` is enabled.
+    /// Typing the closing one inserted automatically moves over it.
+    pub fn type_char(&mut self, ch: char) -> bool {
+        let at = self.cursor.as_coordinates();
+        match closing_pair(ch) {
+            Some(close) if self.select.enabled() => self.wrap_select(ch, close),
+            _ if self.auto_closed.last() == Some(&at) && self.content.char_at(&at) == Some(ch) => {
+                self.auto_closed.pop();
+                self.cursor.move_right(&self.content)
+            }
+            Some(close) if self.content.settings().auto_pairs() => {
+                if self.content.rows() <= at.1 {
+                    self.content.insert_row(&at, &[ch, close]);
+                } else {
+                    self.content
+                        .insert_chars(&at, &[Row::from(vec![ch, close])], SelectMode::None);
+                }
+                self.shift_auto_closed(at, 2);
+                let moved = self.cursor.move_right(&self.content);
+                self.auto_closed.push(self.cursor.as_coordinates());
+                moved
+            }
+            _ => {
+                self.shift_auto_closed(at, 1);
+                self.input_char(ch)
+            }
+        }
+    }
+
+    /// Undo the last change, and move the cursor to it according to the settings.
+    pub fn undo(&mut self) -> bool {
+        match self.content.undo() {
+            Some(cur) => {
+                self.move_to_change(cur);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Surround the selected text by the pair of characters in one undo step,
+    /// and keep the text selected. Rectangle selection is not supported.
+    pub fn wrap_select(&mut self, open: char, close: char) -> bool {
+        let (start, end) = match self.select.corners() {
+            Some((s, e)) if self.select.enabled() => (s.as_coordinates(), e.as_coordinates()),
+            _ => return false,
+        };
+
+        if self.select.mode() == SelectMode::Rectangle {
+            self.message
+                .set_message(Row::from(TEXT_MESSAGE_WRAP_RECTANGLE));
+            return false;
+        }
+
+        // Insert the closing one first, so that the end is not shifted by the opening one.
+        self.content.record_group(|content| {
+            if content.rows() <= end.1 {
+                content.insert_row(&end, &[close]);
             } else {
-                Some(e)
+                content.insert_char(&end, close);
             }
+            content.insert_char(&start, open);
+        });
+
+        let selected_start = (start.0 + 1, start.1);
+        let selected_end = if start.1 == end.1 {
+            (end.0 + 1, end.1)
         } else {
-            None
+            end
+        };
+
+        if self.cursor.as_coordinates() == end {
+            self.select_span(&selected_start, &selected_end);
+        } else {
+            self.select_span(&selected_end, &selected_start);
         }
+        true
     }
 
-    pub fn updated(&self) -> bool {
-        self.updated
+    /// Set whether discarding changes at exit is an abort.
+    pub fn set_abort_on_discard(&mut self, abort_on_discard: bool) {
+        self.abort_on_discard = abort_on_discard;
     }
 
-    pub fn xrange(&self, y: usize) -> Option<(usize, usize)> {
-        if !self.enabled {
-            return None;
+    /// Set the config, and apply the settings for the current file.
+    pub fn set_config(&mut self, config: Config) {
+        let settings = config.resolve(self.content.filename());
+        self.screen.set_filler(settings.filler());
+        if let Some((_, view)) = self.split.as_mut() {
+            view.screen.set_filler(settings.filler());
         }
+        self.content.set_settings(settings);
+        self.config = config;
+    }
 
-        match self.mode {
-            SelectMode::None => self.xrange_none(y),
-            SelectMode::Rectangle => self.xrange_rectangle(y),
+    pub fn set_paste_padding_limit(&mut self, limit: usize) {
+        self.paste_padding_limit = limit;
+    }
+
+    pub fn set_snippets(&mut self, snippets: Snippets) {
+        self.snippets = snippets;
+    }
+
+    pub fn select(&self) -> &Select {
+        &self.select
+    }
+
+    pub fn screen(&self) -> &Screen {
+        &self.screen
+    }
+
+    fn choose(&mut self, message: &str, options: &[(char, &str)]) -> Result<Option<char>, Error> {
+        let mut prompt = prompt::YesNo::new(
+            &mut self.cursor,
+            &mut self.content,
+            &mut self.screen,
+            &mut self.status,
+            &mut self.message,
+            &mut self.terminal,
+        );
+        let ret = prompt.choose(message, options)?;
+        self.message.force_update();
+        Ok(ret)
+    }
+
+    /// Write the pending text to the system clipboard to paste in other applications.
+    fn copy_to_clipboard(&mut self) {
+        if let Some(text) = self.content.pending_text() {
+            if let Err(e) = self.terminal.set_clipboard(&text) {
+                log_warn!("Clipboard is not set: {:?}", e);
+            }
         }
     }
 
-    fn xrange_none(&self, y: usize) -> Option<(usize, usize)> {
-        match (self.start(), self.end()) {
-            (Some(start), Some(end)) => {
-                if y < start.y() {
-                    None
-                } else if y == start.y() {
-                    if start.y() == end.y() {
-                        Some((start.x(), end.x()))
-                    } else {
-                        Some((start.x(), usize::MAX))
-                    }
-                } else if end.y() == y {
-                    Some((0, end.x()))
-                } else if end.y() < y {
-                    None
-                } else {
-                    Some((0, usize::MAX))
-                }
+    /// Take the text copied in other applications as pending.
+    /// The pending text is kept if the clipboard is empty or unavailable.
+    fn load_clipboard(&mut self) {
+        match self.terminal.get_clipboard() {
+            Ok(Some(text))
+                if !text.is_empty() && self.content.pending_text().as_ref() != Some(&text) =>
+            {
+                self.content.set_pending_text(&text);
+            }
+            Ok(_) => {}
+            Err(e) => log_warn!("Clipboard is not read: {:?}", e),
+        }
+    }
+
+    fn confirm(&mut self, message: &str) -> Result<bool, Error> {
+        let mut prompt = prompt::YesNo::new(
+            &mut self.cursor,
+            &mut self.content,
+            &mut self.screen,
+            &mut self.status,
+            &mut self.message,
+            &mut self.terminal,
+        );
+        let ret = prompt.confirm(message)?;
+        self.message.force_update();
+        Ok(ret)
+    }
+
+    /// Returns the rows changed from the baseline, or nothing if the baseline is not set.
+    fn changes(&self) -> Vec<Range<usize>> {
+        self.baseline
+            .as_ref()
+            .map(|baseline| self.content.changed_rows(baseline))
+            .unwrap_or_default()
+    }
+
+    /// Returns the rows in the selected area.
+    /// The last row is excluded if the selection ends at the start of it.
+    fn selected_rows(&self) -> Option<Range<usize>> {
+        match (self.select.start(), self.select.end()) {
+            (Some(start), Some(end)) if start.y() < end.y() && end.x() == 0 => {
+                Some(start.y()..end.y())
             }
+            (Some(start), Some(end)) => Some(start.y()..end.y() + 1),
             _ => None,
         }
     }
 
-    fn xrange_rectangle(&self, y: usize) -> Option<(usize, usize)> {
-        match (self.start(), self.end()) {
-            (Some(start), Some(end)) => {
-                if start.y() <= y && y <= end.y() {
-                    let s = min(start.x(), end.x());
-                    let e = max(start.x(), end.x());
-                    Some((s, e))
+    fn select_span<P: Coordinates>(&mut self, start: &P, end: &P) {
+        let mut cursor = self.cursor.clone();
+        cursor.set(&self.content, start);
+        self.select.set_start(&cursor, SelectMode::None);
+        self.cursor.set(&self.content, end);
+        self.select.set_end(&self.cursor);
+    }
+
+    /// Delete the selection, and run the action at its start in a undo step.
+    fn replace_select<F: FnOnce(&mut Self) -> bool>(&mut self, action: F) -> bool {
+        let mark = self.content.history_mark();
+        let deleted = self.delete_select();
+        let done = action(self);
+        self.content.group_since(mark);
+        deleted || done
+    }
+
+    /// Move the closing characters inserted automatically after the position in the row.
+    fn shift_auto_closed(&mut self, (x0, y0): (usize, usize), count: usize) {
+        for (x, y) in self.auto_closed.iter_mut() {
+            if *y == y0 && x0 <= *x {
+                *x += count;
+            }
+        }
+    }
+
+    /// Set the regions of the views, and returns the size of the area of views.
+    fn layout_split(&mut self) -> Result<(usize, usize), Error> {
+        let mut whole = Screen::current(&self.terminal)?;
+        let area = (whole.width(), whole.height());
+
+        if let Some((split, view)) = self.split.as_mut() {
+            let (leading, trailing) = split.regions(area.0, area.1);
+            let (focused, other) = if view.leading {
+                (trailing, leading)
+            } else {
+                (leading, trailing)
+            };
+
+            let resized = self.screen.set_region(&focused) | view.screen.set_region(&other);
+            if resized {
+                whole.force_update();
+                self.status.resize(&whole);
+                self.message.resize(&whole);
+            }
+        }
+
+        Ok(area)
+    }
+
+    /// Draw the leading view before the trailing view.
+    /// The line written in the leading view is cleared up to the end of terminal.
+    fn refresh_split(
+        &mut self,
+        render: &(usize, usize),
+        area: (usize, usize),
+    ) -> Result<(), Error> {
+        let (width, height) = area;
+
+        if let Some((split, view)) = self.split.as_mut() {
+            let at = view.cursor.as_coordinates();
+            view.cursor.set(&self.content, &at);
+            view.screen
+                .fit(&self.content, &view.cursor.render(&self.content));
+
+            // Both views show the same buffer.
+            if self.content.updated() {
+                view.screen.force_update();
+            }
+
+            let other_select = Select::default();
+            let (leading, trailing) = if view.leading {
+                (
+                    (&mut view.screen, &other_select),
+                    (&mut self.screen, &self.select),
+                )
+            } else {
+                (
+                    (&mut self.screen, &self.select),
+                    (&mut view.screen, &other_select),
+                )
+            };
+
+            let drawn = leading.0.updated() || self.content.updated() || leading.1.updated();
+            if *split == Split::Vertical && drawn {
+                trailing.0.force_update();
+            }
+
+            leading
+                .0
+                .draw(&self.content, leading.1, &mut self.terminal)?;
+            if trailing.0.updated() {
+                split.draw_separator(width, height, &mut self.terminal)?;
+            }
+            trailing
+                .0
+                .draw(&self.content, trailing.1, &mut self.terminal)?;
+        }
+
+        self.content.clear_updated();
+        self.select.clear_updated();
+
+        self.status.set_cursor(render);
+        self.status.draw(&mut self.terminal)?;
+
+        self.message.draw(&mut self.terminal)?;
+        Ok(())
+    }
+
+    /// Move the cursor to the undone or redone change according to the settings.
+    fn move_to_change(&mut self, cur: (usize, usize)) {
+        let settings = self.content.settings();
+        let jump = match settings.undo_cursor() {
+            UndoCursor::Jump => true,
+            UndoCursor::Stay => self.screen.top() <= cur.1 && cur.1 <= self.screen.bottom(),
+            UndoCursor::JumpIfNear => {
+                let near = settings.undo_near_screens() * self.screen.height();
+                cur.1.abs_diff(self.cursor.y()) <= near
+            }
+        };
+
+        if jump {
+            self.cursor.set(&self.content, &cur);
+        } else {
+            let at = self.cursor.as_coordinates();
+            self.cursor.set(&self.content, &at);
+        }
+    }
+
+    fn restore_message(&mut self) {
+        if self.message.message().to_string_at(0) != TEXT_MESSAGE_MENU {
+            self.message.set_message(Row::from(TEXT_MESSAGE_MENU));
+        }
+    }
+
+    fn jump_to_keyword(&mut self, found: Option<(usize, usize)>, keyword: Row) -> bool {
+        if let Some(at) = found {
+            let moved = self.jump_to(&at);
+            self.decoration = true;
+            self.highlight = Some((
+                self.cursor.clone(),
+                keyword,
+                Instant::now() + HIGHLIGHT_DURATION,
+            ));
+            moved
+        } else {
+            false
+        }
+    }
+
+    fn get_selected_text(&self) -> Option<Vec<Row>> {
+        if let (Some(start), Some(end)) = (self.select.start(), self.select.end()) {
+            self.content.get_range(start..end, self.select.mode())
+        } else {
+            None
+        }
+    }
+
+    fn update_select(&mut self, event: Event, changed: bool) {
+        if let Event::Key(e, m) = event {
+            // Expansion, selecting all or lines and wrapping keep the selection.
+            if select_expanded(e, m)
+                || lines_selected(e, m)
+                || e == KeyEvent::SelectAll
+                || (self.select.enabled() && select_wrapped(e))
+            {
+                return;
+            }
+
+            self.expansions.clear();
+
+            if selected_moved(m) && row_moved(e) {
+                if self.select.enabled {
+                    // The end is kept not to redraw the selection if the cursor is not moved.
+                    if changed {
+                        self.select.set_end(&self.cursor);
+                    }
+                } else if word_moved(e, m) || edge_moved(e, m) {
+                    // Word movement and moving to the top or bottom extend
+                    // the rectangle selection, but do not start it.
+                    self.select.disable();
                 } else {
-                    None
+                    self.select.set_start(&self.cursor, SelectMode::from(m));
                 }
+            } else {
+                self.select.disable();
             }
-            _ => None,
         }
+
+        // Selection is kept on window events.
     }
 }
 
 // -----------------------------------------------------------------------------------------------
 
-fn row_moved(key: KeyEvent) -> bool {
-    key == KeyEvent::ArrowLeft
-        || key == KeyEvent::ArrowUp
-        || key == KeyEvent::ArrowRight
-        || key == KeyEvent::ArrowDown
-        || key == KeyEvent::End
-        || key == KeyEvent::Home
-        || key == KeyEvent::Char('\0')
+/// Action of the editor bound to an event.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EditorAction {
+    Abort,
+    AddNumber(i64),
+    Cancel,
+    CheckBrackets,
+    CloseSplit,
+    Comment,
+    Copy,
+    Cut,
+    DeleteChar,
+    DeleteForward,
+    DeleteRow,
+    DuplicateLine,
+    EditSettings,
+    Enter,
+    Exit,
+    ExpandSelect,
+    Find,
+    FindNext,
+    FindPrevious,
+    Format,
+    Goto,
+    HalfPageDown,
+    HalfPageUp,
+    InputChar(char),
+    InputTab,
+    JsonEscape,
+    JsonUnescape,
+    JumpToMark(usize),
+    MatchBracket,
+    MoveBottom,
+    MoveDown,
+    MoveEnd,
+    MoveHome,
+    MoveLeft,
+    MoveNextIndent,
+    MovePrevIndent,
+    MoveRight,
+    MoveRowDown,
+    MoveRowUp,
+    MoveTop,
+    MoveUp,
+    MoveWordLeft,
+    MoveWordRight,
+    NextChange,
+    PageDown,
+    PageUp,
+    Paste,
+    PreviousChange,
+    Redo,
+    Reflow,
+    ReopenClosed,
+    ReopenWithEncoding,
+    Replace,
+    Resize,
+    Save,
+    SelectAll,
+    SelectLines,
+    SetBaseline,
+    SetMark(usize),
+    ShellQuote,
+    ShrinkSelect,
+    SortRows,
+    SpacesToTabs,
+    Split(Split),
+    SwitchView,
+    TabsToSpaces,
+    ToggleChanges,
+    TypeChar(char),
+    Undo,
 }
 
-fn selected_moved(key: KeyModifier) -> bool {
-    key == KeyModifier::CtrlLeft || key == KeyModifier::Shift
+// -----------------------------------------------------------------------------------------------
+
+/// Reason to close the editor.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Exit {
+    Quit,
+    Abort,
+}
+
+impl Exit {
+    /// Returns the exit code of the process.
+    pub fn code(&self) -> u8 {
+        match self {
+            Exit::Quit => 0,
+            Exit::Abort => 1,
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SelectMode {
+    #[default]
+    None,
+    Rectangle,
+    /// Whole rows regardless of the columns.
+    Line,
+}
+
+impl From<KeyModifier> for SelectMode {
+    fn from(value: KeyModifier) -> SelectMode {
+        match value {
+            KeyModifier::CtrlLeft => SelectMode::Rectangle,
+            _ => SelectMode::None,
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Select {
+    mode: SelectMode,
+    range: Option<(Cursor, Cursor)>,
+    previous: Option<Cursor>,
+    enabled: bool,
+    updated: bool,
+}
+
+impl Select {
+    pub fn clear_updated(&mut self) {
+        self.updated = false;
+    }
+
+    pub fn disable(&mut self) {
+        let cur = self.clone();
+
+        if !self.enabled {
+            self.mode = SelectMode::None;
+            self.range = None;
+            self.previous = None;
+        }
+
+        self.enabled = false;
+
+        self.updated |= cur != *self;
+    }
+
+    pub fn mode(&self) -> SelectMode {
+        self.mode
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn end(&self) -> Option<&Cursor> {
+        self.corners().map(|(_, e)| e)
+    }
+
+    /// Returns whether the row needs to redraw.
+    pub fn changes(&self, corners: Option<(&Cursor, &Cursor)>, y: usize) -> bool {
+        match corners {
+            Some((start, end)) => {
+                if start.y() <= y && y <= end.y() {
+                    true
+                } else if let Some(prev) = &self.previous {
+                    prev.y() == y
+                } else {
+                    false
+                }
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the start and the end in order.
+    pub fn corners(&self) -> Option<(&Cursor, &Cursor)> {
+        let (s, e) = self.range.as_ref().map(|r| (&r.0, &r.1))?;
+        if (s.y(), s.x()) <= (e.y(), e.x()) {
+            Some((s, e))
+        } else {
+            Some((e, s))
+        }
+    }
+
+    pub fn set_end(&mut self, end: &Cursor) {
+        let cur = self.clone();
+
+        self.previous = self.range.as_ref().map(|r| r.1.clone());
+        self.range.as_mut().unwrap().1 = end.clone();
+
+        self.updated |= cur != *self;
+    }
+
+    pub fn set_mode(&mut self, mode: SelectMode) {
+        self.updated |= self.mode != mode;
+        self.mode = mode;
+    }
+
+    pub fn set_start(&mut self, start: &Cursor, mode: SelectMode) {
+        let cur = self.clone();
+
+        self.mode = mode;
+        self.previous = None;
+        self.range = Some((start.clone(), start.clone()));
+        self.enabled = true;
+
+        self.updated |= cur != *self;
+    }
+
+    pub fn start(&self) -> Option<&Cursor> {
+        self.corners().map(|(s, _)| s)
+    }
+
+    pub fn updated(&self) -> bool {
+        self.updated
+    }
+
+    /// Returns the selected range of the row.
+    pub fn xrange(&self, corners: Option<(&Cursor, &Cursor)>, y: usize) -> Option<(usize, usize)> {
+        if !self.enabled {
+            return None;
+        }
+
+        match self.mode {
+            SelectMode::None => xrange_none(corners, y),
+            SelectMode::Rectangle => xrange_rectangle(corners, y),
+            SelectMode::Line => xrange_line(corners, y),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+/// Matches of the keyword counted for the status bar, cached until the buffer is changed.
+#[derive(Default)]
+struct MatchCount {
+    /// The keyword, whether case is ignored, and the revision of the buffer.
+    key: Option<(String, bool, usize)>,
+    matches: Vec<(usize, usize)>,
+}
+
+impl MatchCount {
+    /// Returns the 1-based index of the match at or after the position, and the number of matches.
+    /// The index is of the first match if no match follows, as finding next wraps around.
+    fn count(
+        &mut self,
+        content: &Buffer,
+        keyword: &str,
+        ignore_case: bool,
+        (x, y): (usize, usize),
+    ) -> (usize, usize) {
+        let key = (keyword.to_string(), ignore_case, content.revision());
+        if self.key.as_ref() != Some(&key) {
+            self.matches = content.find_all(keyword, ignore_case);
+            self.key = Some(key);
+        }
+
+        if self.matches.is_empty() {
+            return (0, 0);
+        }
+
+        let index = self.matches.partition_point(|&(mx, my)| (my, mx) < (y, x));
+        (index % self.matches.len() + 1, self.matches.len())
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+/// Files closed by opening another with the cursor positions, reopened from the last.
+#[derive(Default)]
+struct Closed {
+    files: Vec<(PathBuf, (usize, usize))>,
+}
+
+impl Closed {
+    /// Push the file, moving it to the last if already closed, and drop the oldest over the limit.
+    fn push(&mut self, path: PathBuf, at: (usize, usize)) {
+        self.files.retain(|(f, _)| *f != path);
+        self.files.push((path, at));
+        if CLOSED_LIMIT < self.files.len() {
+            self.files.remove(0);
+        }
+    }
+
+    fn pop(&mut self) -> Option<(PathBuf, (usize, usize))> {
+        self.files.pop()
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+/// The view of the split screen without focus.
+struct View {
+    cursor: Cursor,
+    screen: Screen,
+    leading: bool,
+}
+
+// -----------------------------------------------------------------------------------------------
+
+/// Returns the 0-based start column and the length from `column[,length]` in 1-based column.
+/// Empty text is the whole row.
+fn parse_sort_key(text: &str) -> Option<(usize, Option<usize>)> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Some((0, None));
+    }
+
+    let (column, length) = match text.split_once(',') {
+        Some((column, length)) => (column, Some(length.trim().parse::<usize>().ok()?)),
+        None => (text, None),
+    };
+
+    match column.trim().parse::<usize>() {
+        Ok(column) if 0 < column => Some((column - 1, length)),
+        _ => None,
+    }
+}
+
+fn row_moved(key: KeyEvent) -> bool {
+    key == KeyEvent::ArrowLeft
+        || key == KeyEvent::ArrowUp
+        || key == KeyEvent::ArrowRight
+        || key == KeyEvent::ArrowDown
+        || key == KeyEvent::End
+        || key == KeyEvent::Home
+        || key == KeyEvent::Char('\0')
+}
+
+/// Returns the action bound to Alt with the character.
+fn alt_action(ch: char) -> Option<EditorAction> {
+    let action = match ch {
+        'a' => EditorAction::AddNumber(1),
+        'x' => EditorAction::AddNumber(-1),
+        's' => EditorAction::Split(Split::Horizontal),
+        'v' => EditorAction::Split(Split::Vertical),
+        'o' => EditorAction::SwitchView,
+        '1' => EditorAction::CloseSplit,
+        'k' => EditorAction::Abort,
+        't' => EditorAction::TabsToSpaces,
+        'y' => EditorAction::SpacesToTabs,
+        'w' => EditorAction::ExpandSelect,
+        'q' => EditorAction::ShrinkSelect,
+        'j' => EditorAction::JsonEscape,
+        'u' => EditorAction::JsonUnescape,
+        'h' => EditorAction::ShellQuote,
+        'b' => EditorAction::SetBaseline,
+        'n' => EditorAction::NextChange,
+        'm' => EditorAction::PreviousChange,
+        'd' => EditorAction::ToggleChanges,
+        'r' => EditorAction::Reflow,
+        'c' => EditorAction::CheckBrackets,
+        'p' => EditorAction::EditSettings,
+        'l' => EditorAction::SelectLines,
+        'f' => EditorAction::ReopenClosed,
+        'e' => EditorAction::ReopenWithEncoding,
+        _ => return None,
+    };
+
+    Some(action)
+}
+
+/// Returns the character typed without Ctrl or Alt.
+fn plain_char(event: Event) -> Option<char> {
+    match event {
+        Event::Key(KeyEvent::Char(ch), KeyModifier::None | KeyModifier::Shift)
+            if !ch.is_ascii_control() =>
+        {
+            Some(ch)
+        }
+        _ => None,
+    }
+}
+
+/// Returns whether the characters may start the path of a dropped file,
+/// `/`, `~`, a quote or a drive letter followed by `:\`.
+fn path_started(events: &[Event]) -> bool {
+    let chars = match events
+        .iter()
+        .map(|&e| plain_char(e))
+        .collect::<Option<Vec<char>>>()
+    {
+        Some(chars) => chars,
+        None => return false,
+    };
+
+    match chars.as_slice() {
+        ['/' | '~' | '"' | '\'', ..] => true,
+        [drive, ':', '\\', ..] => drive.is_ascii_alphabetic(),
+        _ => false,
+    }
+}
+
+/// Returns the event, and the events already queued after it up to a drive prefix
+/// if it is a letter. Typing is not waited for.
+fn read_queued<T: Terminal>(first: Event) -> Result<Vec<Event>, Error> {
+    let mut events = vec![first];
+    if plain_char(first).is_some_and(|ch| ch.is_ascii_alphabetic()) {
+        while events.len() < DRIVE_PREFIX_CHARS {
+            match T::poll_event(Duration::ZERO)? {
+                Some(event) => events.push(event),
+                None => break,
+            }
+        }
+    }
+    Ok(events)
+}
+
+/// Returns the events and the characters arriving right after them, up to the closing quote
+/// if the first one is a quote. The first event other than a character ends the burst.
+fn read_burst<T: Terminal>(mut events: Vec<Event>) -> Result<Vec<Event>, Error> {
+    let quote = plain_char(events[0]).filter(|&ch| ch == '"' || ch == '\'');
+    while events.len() < BURST_MAX_CHARS {
+        let event = match T::poll_event(BURST_INTERVAL)? {
+            Some(event) => event,
+            None => break,
+        };
+        events.push(event);
+
+        let ch = plain_char(event);
+        if ch.is_none() || ch == quote {
+            break;
+        }
+    }
+    Ok(events)
+}
+
+/// Returns the path if the characters are the absolute path of an existing file,
+/// which may be quoted as dropped onto the window.
+fn dropped_path(events: &[Event]) -> Option<PathBuf> {
+    let text = events
+        .iter()
+        .map(|&e| plain_char(e))
+        .collect::<Option<String>>()?;
+    let text = text.trim_end();
+    let text = ['"', '\'']
+        .iter()
+        .find_map(|&q| text.strip_prefix(q).and_then(|t| t.strip_suffix(q)))
+        .unwrap_or(text);
+
+    let path = PathBuf::from(text);
+    if path.is_absolute() && path.is_file() {
+        Some(path)
+    } else {
+        None
+    }
+}
+
+fn char_typed(event: Event) -> bool {
+    match event {
+        Event::Key(KeyEvent::Char(ch), modifier) => {
+            modifier != KeyModifier::AltLeft && !ch.is_ascii_control()
+        }
+        _ => false,
+    }
+}
+
+fn select_expanded(key: KeyEvent, modifier: KeyModifier) -> bool {
+    modifier == KeyModifier::AltLeft && (key == KeyEvent::Char('w') || key == KeyEvent::Char('q'))
+}
+
+fn lines_selected(key: KeyEvent, modifier: KeyModifier) -> bool {
+    modifier == KeyModifier::AltLeft && key == KeyEvent::Char('l')
+}
+
+fn select_wrapped(key: KeyEvent) -> bool {
+    match key {
+        KeyEvent::Char(ch) => closing_pair(ch).is_some(),
+        _ => false,
+    }
+}
+
+fn selected_moved(key: KeyModifier) -> bool {
+    key == KeyModifier::CtrlLeft || key == KeyModifier::Shift
+}
+
+/// Returns whether the key moves to the top or bottom of the buffer by Ctrl+Home or Ctrl+End.
+fn edge_moved(key: KeyEvent, modifier: KeyModifier) -> bool {
+    modifier == KeyModifier::CtrlLeft && (key == KeyEvent::Home || key == KeyEvent::End)
+}
+
+fn word_moved(key: KeyEvent, modifier: KeyModifier) -> bool {
+    modifier == KeyModifier::CtrlLeft && (key == KeyEvent::ArrowLeft || key == KeyEvent::ArrowRight)
+}
+
+fn xrange_none(corners: Option<(&Cursor, &Cursor)>, y: usize) -> Option<(usize, usize)> {
+    match corners {
+        Some((start, end)) => {
+            if y < start.y() {
+                None
+            } else if y == start.y() {
+                if start.y() == end.y() {
+                    Some((start.x(), end.x()))
+                } else {
+                    Some((start.x(), usize::MAX))
+                }
+            } else if end.y() == y {
+                Some((0, end.x()))
+            } else if end.y() < y {
+                None
+            } else {
+                Some((0, usize::MAX))
+            }
+        }
+        _ => None,
+    }
+}
+
+fn xrange_rectangle(corners: Option<(&Cursor, &Cursor)>, y: usize) -> Option<(usize, usize)> {
+    match corners {
+        Some((start, end)) => {
+            if start.y() <= y && y <= end.y() {
+                let s = min(start.x(), end.x());
+                let e = max(start.x(), end.x());
+                Some((s, e))
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+fn xrange_line(corners: Option<(&Cursor, &Cursor)>, y: usize) -> Option<(usize, usize)> {
+    match corners {
+        Some((start, end)) if start.y() <= y && y <= end.y() => Some((0, usize::MAX)),
+        _ => None,
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{AutoIndent, Config, Formatter, LineEnding};
+    use crate::terminal::Null;
+    use crate::Color;
+    use std::env;
+    use std::fs;
+
+    fn events(keys: &[KeyEvent]) {
+        let events = keys
+            .iter()
+            .map(|&k| Event::from((k, KeyModifier::None)))
+            .collect::<Vec<Event>>();
+        Null::push_events(&events);
+    }
+
+    fn null_editor(filename: Option<&Path>) -> Editor<Null> {
+        let mut null = Null::default();
+        null.set_screen_size(10, 5);
+        Editor::new(filename, null).unwrap()
+    }
+
+    fn rows_editor(rows: &[&str]) -> Editor<Null> {
+        let mut editor = null_editor(None);
+        for (y, row) in rows.iter().enumerate() {
+            let chars = row.chars().collect::<Vec<char>>();
+            editor.content.insert_row(&(0, y), &chars);
+        }
+        editor
+    }
+
+    /// Returns the editor of the rows numbered from 0, with the text area of the height.
+    fn numbered_editor(n: usize, height: usize) -> Editor<Null> {
+        let rows = (0..n).map(|i| i.to_string()).collect::<Vec<String>>();
+        let mut editor = rows_editor(&rows.iter().map(|r| r.as_str()).collect::<Vec<&str>>());
+        editor.terminal.set_screen_size(10, height + 2);
+        editor.resize_screen().unwrap();
+        editor
+    }
+
+    #[test]
+    fn editor_find_next() {
+        let mut editor = rows_editor(&["ab", "ab", "ab"]);
+        editor.keyword = Some(Row::from("b"));
+
+        Null::push_events(&[Event::from((KeyEvent::F3, KeyModifier::None))]);
+        editor.handle_events().unwrap();
+        assert_eq!((1, 0), editor.cursor().as_coordinates());
+
+        Null::push_events(&[Event::from((KeyEvent::F3, KeyModifier::None))]);
+        editor.handle_events().unwrap();
+        assert_eq!((1, 1), editor.cursor().as_coordinates());
+        assert!(editor.highlight.is_some());
+    }
+
+    #[test]
+    fn editor_find_next_wrap() {
+        let mut editor = rows_editor(&["ab", "ab", "ab"]);
+        editor.keyword = Some(Row::from("b"));
+        editor.cursor.set(&editor.content, &(1, 2));
+
+        Null::push_events(&[Event::from((KeyEvent::F3, KeyModifier::None))]);
+        editor.handle_events().unwrap();
+
+        assert_eq!((1, 0), editor.cursor().as_coordinates());
+    }
+
+    #[test]
+    fn editor_find_previous() {
+        let mut editor = rows_editor(&["ab", "ab", "ab"]);
+        editor.keyword = Some(Row::from("b"));
+        editor.cursor.set(&editor.content, &(0, 2));
+
+        Null::push_events(&[Event::from((KeyEvent::F3, KeyModifier::Shift))]);
+        editor.handle_events().unwrap();
+
+        assert_eq!((1, 1), editor.cursor().as_coordinates());
+    }
+
+    #[test]
+    fn editor_find_previous_wrap() {
+        let mut editor = rows_editor(&["ab", "ab", "ab"]);
+        editor.keyword = Some(Row::from("b"));
+
+        Null::push_events(&[Event::from((KeyEvent::F3, KeyModifier::Shift))]);
+        editor.handle_events().unwrap();
+
+        assert_eq!((1, 2), editor.cursor().as_coordinates());
+    }
+
+    #[test]
+    fn editor_find_ignore_case() {
+        let mut editor = rows_editor(&["ab", "aB", "AB"]);
+        editor.refresh().unwrap();
+
+        Null::push_events(&[
+            Event::from((KeyEvent::Find, KeyModifier::CtrlLeft)),
+            Event::from((KeyEvent::Char('c'), KeyModifier::AltLeft)),
+            Event::from((KeyEvent::Char('B'), KeyModifier::None)),
+            Event::from((KeyEvent::Enter, KeyModifier::None)),
+        ]);
+        editor.handle_events().unwrap();
+
+        assert_eq!((1, 0), editor.cursor().as_coordinates());
+        assert!(editor.ignore_case);
+
+        let painted = editor.terminal.attributes().len();
+        editor.refresh().unwrap();
+        assert_eq!(3, editor.terminal.attributes()[painted..].len());
+
+        Null::push_events(&[Event::from((KeyEvent::F3, KeyModifier::Shift))]);
+        editor.handle_events().unwrap();
+        assert_eq!((1, 2), editor.cursor().as_coordinates());
+    }
+
+    #[test]
+    fn editor_find_next_no_keyword() {
+        let mut editor = rows_editor(&["ab", "ab", "ab"]);
+
+        Null::push_events(&[Event::from((KeyEvent::F3, KeyModifier::None))]);
+        events(&[KeyEvent::Char('b'), KeyEvent::Enter]);
+        editor.handle_events().unwrap();
+
+        assert_eq!((1, 0), editor.cursor().as_coordinates());
+        assert_eq!("b", editor.keyword.unwrap().to_string_at(0));
+    }
+
+    #[test]
+    fn editor_refresh_caret_past_end() {
+        let mut editor = null_editor(None);
+        let height = editor.screen.height();
+        for y in 0..height {
+            editor.content.insert_row(&(0, y), &['a']);
+        }
+        editor.refresh().unwrap();
+
+        let down = (KeyEvent::ArrowDown, KeyModifier::None);
+        script(&mut editor, &vec![down; height + 1]);
+        editor.refresh().unwrap();
+
+        assert_eq!((0, height), editor.cursor().as_coordinates());
+        let (_, y) = editor.terminal.get_cursor_position().unwrap();
+        assert!(y < height);
+        let status = editor
+            .terminal
+            .writes()
+            .iter()
+            .rev()
+            .find(|w| w.1 == height);
+        assert!(status.is_some_and(|w| w.4));
+    }
+
+    fn screen_writes(editor: &Editor<Null>, from: usize) -> usize {
+        editor.terminal.writes()[from..]
+            .iter()
+            .filter(|w| w.1 < editor.screen.height())
+            .count()
+    }
+
+    #[test]
+    fn editor_prompt_cancel_no_redraw() {
+        let mut editor = rows_editor(&["ab", "cd"]);
+        editor.refresh().unwrap();
+
+        let written = editor.terminal.writes().len();
+        events(&[KeyEvent::Escape]);
+        editor.find().unwrap();
+        editor.refresh().unwrap();
+        events(&[KeyEvent::Escape]);
+        editor.replace().unwrap();
+        editor.refresh().unwrap();
+        events(&[KeyEvent::Escape]);
+        editor.goto().unwrap();
+        editor.refresh().unwrap();
+
+        assert_eq!(0, screen_writes(&editor, written));
+    }
+
+    #[test]
+    fn editor_find_cancel_redraw() {
+        let mut editor = rows_editor(&["ab", "cd"]);
+        editor.refresh().unwrap();
+
+        let written = editor.terminal.writes().len();
+        events(&[KeyEvent::Char('c'), KeyEvent::Escape]);
+        editor.find().unwrap();
+        let painted = editor.terminal.writes().len();
+        editor.refresh().unwrap();
+
+        // The highlight of the match is deleted.
+        assert!(0 < screen_writes(&editor, written));
+        assert!(0 < screen_writes(&editor, painted));
+        assert_eq!((0, 0), editor.cursor().as_coordinates());
+    }
+
+    fn scroll_editor(height: usize) -> Editor<Null> {
+        numbered_editor(20, height)
+    }
+
+    fn scroll(editor: &mut Editor<Null>, key: KeyEvent) {
+        events(&[key]);
+        editor.handle_events().unwrap();
+        editor.refresh().unwrap();
+    }
+
+    #[test]
+    fn editor_half_page_scroll() {
+        let mut editor = scroll_editor(7);
+        editor.cursor.set(&editor.content, &(0, 1));
+
+        scroll(&mut editor, KeyEvent::HalfPageDown);
+        assert_eq!((0, 4), editor.cursor().as_coordinates());
+        assert_eq!(3, editor.screen().top());
+
+        scroll(&mut editor, KeyEvent::HalfPageDown);
+        scroll(&mut editor, KeyEvent::HalfPageUp);
+        assert_eq!((0, 4), editor.cursor().as_coordinates());
+        assert_eq!(3, editor.screen().top());
+    }
+
+    #[test]
+    fn editor_half_page_scroll_even_height() {
+        let mut editor = scroll_editor(6);
+        editor.cursor.set(&editor.content, &(0, 2));
+
+        scroll(&mut editor, KeyEvent::HalfPageDown);
+        assert_eq!((0, 5), editor.cursor().as_coordinates());
+        assert_eq!(3, editor.screen().top());
+    }
+
+    #[test]
+    fn editor_half_page_scroll_at_top() {
+        let mut editor = scroll_editor(7);
+        editor.cursor.set(&editor.content, &(1, 1));
+
+        scroll(&mut editor, KeyEvent::HalfPageUp);
+        assert_eq!((1, 0), editor.cursor().as_coordinates());
+        assert_eq!(0, editor.screen().top());
+    }
+
+    #[test]
+    fn editor_half_page_scroll_at_bottom() {
+        let mut editor = scroll_editor(7);
+        editor.jump_to(&(0, 18));
+        editor.refresh().unwrap();
+        assert_eq!(12, editor.screen().top());
+
+        scroll(&mut editor, KeyEvent::HalfPageDown);
+        assert_eq!((0, 20), editor.cursor().as_coordinates());
+        assert_eq!(14, editor.screen().top());
+    }
+
+    #[test]
+    fn editor_half_page_scroll_disabled() {
+        let mut editor = scroll_editor(7);
+        editor.set_config(Config::parse("half_page_scroll = false"));
+
+        scroll(&mut editor, KeyEvent::HalfPageDown);
+        assert_eq!((0, 0), editor.cursor().as_coordinates());
+        assert_eq!(0, editor.screen().top());
+    }
+
+    #[test]
+    fn editor_software_cursor() {
+        let mut editor = rows_editor(&["あa"]);
+        editor.refresh().unwrap();
+        assert!(editor.terminal.cursor_visible());
+
+        editor.set_config(Config::parse("software_cursor = true"));
+        editor.refresh().unwrap();
+        assert!(!editor.terminal.cursor_visible());
+        assert_eq!(Some(&(0, 0, 2)), editor.terminal.attributes().last());
+
+        editor.cursor.set(&editor.content, &(1, 0));
+        editor.refresh().unwrap();
+        assert_eq!(Some(&(2, 0, 1)), editor.terminal.attributes().last());
+    }
+
+    fn undo_far_editor(config: &str) -> Editor<Null> {
+        let mut editor = rows_editor(&["a"; 30]);
+        editor.set_config(Config::parse(config));
+        editor.content.insert_char(&(0, 0), 'b');
+        editor.cursor.set(&editor.content, &(0, 20));
+        editor.refresh().unwrap();
+        editor
+    }
+
+    #[test]
+    fn editor_undo_cursor_jump() {
+        let mut editor = undo_far_editor("");
+
+        assert!(editor.undo());
+        editor.refresh().unwrap();
+        assert_eq!((0, 0), editor.cursor.as_coordinates());
+        assert_eq!(0, editor.screen.top());
+        assert_eq!("a", row_text(&editor, 0));
+    }
+
+    #[test]
+    fn editor_redo() {
+        let mut editor = undo_far_editor("");
+
+        events(&[KeyEvent::Undo, KeyEvent::Redo]);
+        editor.handle_events().unwrap();
+        editor.cursor.set(&editor.content, &(0, 20));
+        editor.handle_events().unwrap();
+
+        assert_eq!((0, 0), editor.cursor.as_coordinates());
+        assert_eq!("ba", row_text(&editor, 0));
+        assert!(!editor.redo());
+    }
+
+    #[test]
+    fn editor_undo_cursor_stay() {
+        let mut editor = undo_far_editor("undo_cursor = stay");
+        let top = editor.screen.top();
+
+        assert!(editor.undo());
+        editor.refresh().unwrap();
+        assert_eq!((0, 20), editor.cursor.as_coordinates());
+        assert_eq!(top, editor.screen.top());
+        assert_eq!("a", row_text(&editor, 0));
+    }
+
+    #[test]
+    fn editor_undo_cursor_stay_on_screen() {
+        let mut editor = undo_far_editor("undo_cursor = stay");
+        editor.content.insert_char(&(0, 19), 'b');
+
+        assert!(editor.undo());
+        assert_eq!((0, 19), editor.cursor.as_coordinates());
+    }
+
+    #[test]
+    fn editor_undo_cursor_jump_if_near() {
+        let mut editor = undo_far_editor("undo_cursor = jump-if-near");
+
+        assert!(editor.undo());
+        assert_eq!((0, 20), editor.cursor.as_coordinates());
+
+        let mut editor = undo_far_editor("undo_cursor = jump-if-near\nundo_near_screens = 7");
+
+        assert!(editor.undo());
+        assert_eq!((0, 0), editor.cursor.as_coordinates());
+    }
+
+    #[test]
+    fn editor_decoration_scroll() {
+        let mut editor = rows_editor(&["xab", "ab", "b", "ab", "ab"]);
+
+        events(&[KeyEvent::Char('a'), KeyEvent::Char('b'), KeyEvent::Enter]);
+        editor.find().unwrap();
+        editor.refresh().unwrap();
+        assert!(editor.terminal.attributes().contains(&(1, 0, 2)));
+        assert!(editor.terminal.attributes().contains(&(0, 1, 2)));
+
+        let painted = editor.terminal.attributes().len();
+        editor.jump_to(&(0, 4));
+        editor.refresh().unwrap();
+
+        // rows 2..5 are on screen.
+        let attributes = &editor.terminal.attributes()[painted..];
+        assert!(attributes.contains(&(0, 1, 2)));
+        assert!(attributes.contains(&(0, 2, 2)));
+        assert!(!attributes.iter().any(|a| a.1 == 0 && a.2 == 2));
+    }
+
+    #[test]
+    fn editor_decoration_clear_by_edit() {
+        let mut editor = rows_editor(&["ab", "ab"]);
+        events(&[KeyEvent::Char('a'), KeyEvent::Char('b'), KeyEvent::Enter]);
+        editor.find().unwrap();
+        editor.refresh().unwrap();
+
+        events(&[KeyEvent::Char('z')]);
+        editor.handle_events().unwrap();
+        let painted = editor.terminal.attributes().len();
+        editor.refresh().unwrap();
+
+        assert!(!editor.decoration);
+        assert!(!editor.terminal.attributes()[painted..].contains(&(0, 1, 2)));
+    }
+
+    #[test]
+    fn editor_decoration_clear_by_new_search() {
+        let mut editor = rows_editor(&["ab", "ab"]);
+        events(&[KeyEvent::Char('a'), KeyEvent::Char('b'), KeyEvent::Enter]);
+        editor.find().unwrap();
+        editor.refresh().unwrap();
+        assert!(editor.decoration);
+
+        events(&[KeyEvent::Escape]);
+        editor.find().unwrap();
+        let painted = editor.terminal.attributes().len();
+        editor.refresh().unwrap();
+
+        assert!(!editor.decoration);
+        assert_eq!(painted, editor.terminal.attributes().len());
+    }
+
+    fn written(editor: &Editor<Null>, text: &str) -> bool {
+        editor
+            .terminal
+            .writes()
+            .iter()
+            .any(|(_, _, w, _, _)| w.contains(text))
+    }
+
+    #[test]
+    fn editor_set_config_filler() {
+        let mut editor = rows_editor(&["a"]);
+        assert_eq!('~', editor.screen().filler());
+
+        editor.set_config(Config::parse("filler = \" \""));
+        assert_eq!(' ', editor.screen().filler());
+        editor.refresh().unwrap();
+        assert!(!written(&editor, "~"));
+    }
+
+    #[test]
+    fn editor_refresh_theme() {
+        let mut editor = rows_editor(&["a"]);
+        editor.set_config(Config::parse(
+            "status_color = blue\nmessage_color = magenta",
+        ));
+
+        editor.refresh().unwrap();
+        let bars = editor
+            .terminal
+            .writes()
+            .iter()
+            .filter(|(_, y, _, _, _)| 3 <= *y)
+            .map(|(_, y, _, color, rev)| (*y, *color, *rev))
+            .collect::<Vec<(usize, Color, bool)>>();
+        assert_eq!(
+            vec![(3, Color::Blue, true), (4, Color::Magenta, false)],
+            bars
+        );
+    }
+
+    #[test]
+    fn editor_match_count() {
+        let mut null = Null::default();
+        null.set_screen_size(60, 5);
+        let mut editor = Editor::new(None, null).unwrap();
+        editor
+            .content
+            .insert_row(&(0, 0), &"ab x ab".chars().collect::<Vec<char>>());
+        editor.content.insert_row(&(0, 1), &['a', 'b']);
+        events(&[KeyEvent::Char('a'), KeyEvent::Char('b'), KeyEvent::Enter]);
+        editor.find().unwrap();
+        editor.refresh().unwrap();
+        assert!(written(&editor, "/ab 1/3"));
+
+        editor.find_next().unwrap();
+        editor.refresh().unwrap();
+        assert!(written(&editor, "/ab 2/3"));
+
+        editor.find_previous().unwrap();
+        editor.find_previous().unwrap();
+        editor.refresh().unwrap();
+        assert!(written(&editor, "/ab 3/3"));
+
+        events(&[KeyEvent::Escape]);
+        editor.handle_events().unwrap();
+        editor.refresh().unwrap();
+        let status = editor.terminal.writes().iter().rfind(|w| w.1 == 3).unwrap();
+        assert!(!status.2.contains("/ab"));
+    }
+
+    #[test]
+    fn editor_match_count_cache() {
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'b', 'a']);
+        let mut count = MatchCount::default();
+
+        assert_eq!((1, 2), count.count(&buf, "a", false, (0, 0)));
+        assert_eq!((2, 2), count.count(&buf, "a", false, (1, 0)));
+        assert_eq!((1, 2), count.count(&buf, "a", false, (3, 0)));
+        assert_eq!((0, 0), count.count(&buf, "c", false, (0, 0)));
+
+        buf.insert_char(&(0, 0), 'A');
+        assert_eq!((1, 2), count.count(&buf, "a", false, (1, 0)));
+        assert_eq!((2, 3), count.count(&buf, "a", true, (1, 0)));
+    }
+
+    #[test]
+    fn editor_decoration_clear_by_escape() {
+        let mut editor = rows_editor(&["ab", "ab"]);
+        events(&[KeyEvent::Char('a'), KeyEvent::Char('b'), KeyEvent::Enter]);
+        editor.find().unwrap();
+        editor.refresh().unwrap();
+
+        events(&[KeyEvent::Escape]);
+        editor.handle_events().unwrap();
+
+        assert!(!editor.decoration);
+        assert!(editor.screen.updated());
+        let painted = editor.terminal.attributes().len();
+        editor.refresh().unwrap();
+        assert_eq!(painted, editor.terminal.attributes().len());
+    }
+
+    #[test]
+    fn editor_exit_quit() {
+        let path = env::temp_dir().join("note_editor_exit_quit.txt");
+        let mut editor = rows_editor(&["a"]);
+        editor.content.save_as(&path).unwrap();
+
+        events(&[KeyEvent::Exit]);
+
+        assert_eq!(Some(Exit::Quit), editor.handle_events().unwrap());
+        fs::remove_file(&path).unwrap();
+    }
+
+    /// Type the text and the answer to the prompt if shown.
+    fn drop_text(editor: &mut Editor<Null>, text: &str, answer: char) {
+        editor.set_config(Config::parse("open_dropped_files = true"));
+        let mut keys = text.chars().map(KeyEvent::Char).collect::<Vec<_>>();
+        keys.push(KeyEvent::Char(answer));
+        events(&keys);
+        for _ in 0..keys.len() {
+            editor.handle_events().unwrap();
+        }
+    }
+
+    #[test]
+    fn editor_drop_file() {
+        let path = env::temp_dir().join("note_editor_drop_file.txt");
+        fs::write(&path, "dropped\n").unwrap();
+        let mut editor = null_editor(None);
+
+        drop_text(&mut editor, &format!("\"{}\"", path.display()), 'y');
+
+        fs::remove_file(&path).unwrap();
+        assert_eq!(Some(path.as_path()), editor.content().filename());
+        assert_eq!(vec!["dropped"], row_texts(&editor));
+    }
+
+    #[test]
+    fn editor_drop_binary_declined() {
+        let path = env::temp_dir().join("note_editor_drop_binary_declined.bin");
+        fs::write(&path, b"MZ\x90\0\x03\0").unwrap();
+        let mut editor = rows_editor(&["kept"]);
+
+        editor.set_config(Config::parse("open_dropped_files = true"));
+        let text = format!("\"{}\"", path.display());
+        let mut keys = text.chars().map(KeyEvent::Char).collect::<Vec<_>>();
+        // Dropping is accepted, and opening the binary file is declined.
+        keys.extend([KeyEvent::Char('y'), KeyEvent::Char('n')]);
+        events(&keys);
+        for _ in 0..keys.len() {
+            editor.handle_events().unwrap();
+        }
+
+        fs::remove_file(&path).unwrap();
+        assert_eq!(None, editor.content().filename());
+        assert_eq!(vec!["kept"], row_texts(&editor));
+    }
+
+    #[test]
+    fn editor_drop_file_not_found() {
+        let path = env::temp_dir().join("note_editor_drop_file_not_found.txt");
+        let text = format!("\"{}\"", path.display());
+        let mut editor = null_editor(None);
+
+        // The answer is typed as no prompt is shown.
+        drop_text(&mut editor, &text, 'y');
+        assert_eq!(vec![format!("{}y", text)], row_texts(&editor));
+    }
+
+    #[test]
+    fn editor_drop_file_prose() {
+        let mut editor = null_editor(None);
+
+        drop_text(&mut editor, "Hello, world.", '!');
+        assert_eq!(vec!["Hello, world.!"], row_texts(&editor));
+    }
+
+    #[test]
+    fn editor_drop_file_disabled() {
+        let path = env::temp_dir().join("note_editor_drop_file_disabled.txt");
+        fs::write(&path, "dropped\n").unwrap();
+        let text = format!("\"{}\"", path.display());
+        let mut editor = null_editor(None);
+
+        let mut keys = text.chars().map(KeyEvent::Char).collect::<Vec<_>>();
+        keys.push(KeyEvent::Char('y'));
+        events(&keys);
+        for _ in 0..keys.len() {
+            editor.handle_events().unwrap();
+        }
+
+        fs::remove_file(&path).unwrap();
+        assert_eq!(None, editor.content().filename());
+        assert_eq!(vec![format!("{}y", text)], row_texts(&editor));
+    }
+
+    #[test]
+    fn editor_closed_push_pop() {
+        let mut closed = Closed::default();
+        for i in 0..=CLOSED_LIMIT {
+            closed.push(PathBuf::from(format!("{}.txt", i)), (i, 0));
+        }
+        closed.push(PathBuf::from("5.txt"), (0, 5));
+
+        assert_eq!(Some((PathBuf::from("5.txt"), (0, 5))), closed.pop());
+        assert_eq!(Some((PathBuf::from("10.txt"), (10, 0))), closed.pop());
+        let rest = closed.files.iter().map(|(f, _)| f.to_str().unwrap());
+        assert_eq!(
+            vec!["1.txt", "2.txt", "3.txt", "4.txt"],
+            rest.take(4).collect::<Vec<_>>()
+        );
+        assert_eq!(CLOSED_LIMIT - 2, closed.files.len());
+    }
+
+    #[test]
+    fn editor_reopen_closed() {
+        let first = env::temp_dir().join("note_editor_reopen_closed_1.txt");
+        let second = env::temp_dir().join("note_editor_reopen_closed_2.txt");
+        fs::write(&first, "first\nrow\n").unwrap();
+        fs::write(&second, "second\n").unwrap();
+        let mut editor = null_editor(Some(&first));
+        editor.jump_to(&(2, 1));
+
+        assert!(editor.open(&second).unwrap());
+        assert!(editor.reopen_closed().unwrap());
+        assert_eq!(Some(first.as_path()), editor.content().filename());
+        assert_eq!((2, 1), editor.cursor().as_coordinates());
+
+        assert!(editor.reopen_closed().unwrap());
+        assert_eq!(Some(second.as_path()), editor.content().filename());
+        assert!(editor.reopen_closed().unwrap());
+        assert_eq!(Some(first.as_path()), editor.content().filename());
+
+        fs::remove_file(&first).unwrap();
+        fs::remove_file(&second).unwrap();
+    }
+
+    #[test]
+    fn editor_reopen_closed_empty() {
+        let mut editor = null_editor(None);
+
+        assert!(!editor.reopen_closed().unwrap());
+        assert_eq!(
+            TEXT_MESSAGE_NO_CLOSED,
+            editor.message.message().to_string_at(0)
+        );
+    }
+
+    #[test]
+    fn editor_reopen_with_encoding() {
+        let path = env::temp_dir().join("note_editor_reopen_with_encoding.txt");
+        // "日本\nあ\n"
+        fs::write(&path, b"\x93\xFA\x96\x7B\n\x82\xA0\n").unwrap();
+        let mut editor = null_editor(Some(&path));
+        editor.jump_to(&(1, 1));
+
+        // Decoded as UTF-8, and left read-only.
+        events(&[KeyEvent::Char('u'), KeyEvent::Char('n')]);
+        assert!(editor.reopen_with_encoding().unwrap());
+        assert_eq!(
+            vec!["\u{FFFD}\u{FFFD}\u{FFFD}{", "\u{FFFD}\u{FFFD}"],
+            row_texts(&editor)
+        );
+        assert!(editor.content().read_only());
+        assert_eq!((1, 1), editor.cursor().as_coordinates());
+
+        events(&[KeyEvent::Char('s')]);
+        assert!(editor.reopen_with_encoding().unwrap());
+        fs::remove_file(&path).unwrap();
+        assert_eq!(vec!["日本", "あ"], row_texts(&editor));
+        assert_eq!(Encoding::ShiftJis, editor.content().encoding());
+        assert!(!editor.content().read_only());
+    }
+
+    #[test]
+    fn editor_reopen_with_encoding_modified() {
+        let path = env::temp_dir().join("note_editor_reopen_with_encoding_modified.txt");
+        fs::write(&path, "a\n").unwrap();
+        let mut editor = null_editor(Some(&path));
+        editor.input_char('b');
+
+        events(&[KeyEvent::Char('u'), KeyEvent::Char('n')]);
+        assert!(!editor.reopen_with_encoding().unwrap());
+        assert_eq!(vec!["ba"], row_texts(&editor));
+
+        events(&[KeyEvent::Char('u'), KeyEvent::Char('y')]);
+        assert!(editor.reopen_with_encoding().unwrap());
+        fs::remove_file(&path).unwrap();
+        assert_eq!(vec!["a"], row_texts(&editor));
+        assert!(!editor.content().cached());
+    }
+
+    #[test]
+    fn editor_path_started() {
+        let chars = |text: &str| {
+            text.chars()
+                .map(|ch| Event::from((KeyEvent::Char(ch), KeyModifier::None)))
+                .collect::<Vec<Event>>()
+        };
+
+        for text in ["/", "~", "\"", "'", "C:\\", "d:\\Users"] {
+            assert!(path_started(&chars(text)), "{}", text);
+        }
+        for text in ["", "a", "Hello", "C:", "C:/", "1:\\", "\\"] {
+            assert!(!path_started(&chars(text)), "{}", text);
+        }
+
+        let mut events = chars("C:");
+        events.push(Event::from((KeyEvent::Enter, KeyModifier::None)));
+        assert!(!path_started(&events));
+    }
+
+    #[test]
+    fn editor_exit_cancel() {
+        let mut editor = rows_editor(&["a"]);
+
+        events(&[KeyEvent::Exit, KeyEvent::Escape]);
+
+        assert_eq!(None, editor.handle_events().unwrap());
+        assert_eq!(1, editor.content().rows());
+    }
+
+    #[test]
+    fn editor_exit_save() {
+        let path = env::temp_dir().join("note_editor_exit_save.txt");
+        let mut editor = rows_editor(&["a"]);
+        editor.content.save_as(&path).unwrap();
+        editor.content.set_filename(&path);
+        editor.input_char('b');
+
+        events(&[KeyEvent::Exit, KeyEvent::Char('s')]);
+
+        assert_eq!(Some(Exit::Quit), editor.handle_events().unwrap());
+        assert_eq!("ba\r\n", fs::read_to_string(&path).unwrap());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn editor_exit_save_cancel() {
+        let mut editor = rows_editor(&["a"]);
+
+        events(&[KeyEvent::Exit, KeyEvent::Char('S'), KeyEvent::Escape]);
+
+        assert_eq!(None, editor.handle_events().unwrap());
+        assert!(editor.content().cached());
+    }
+
+    #[test]
+    fn editor_exit_discard() {
+        for (abort_on_discard, expected) in [(false, Exit::Quit), (true, Exit::Abort)] {
+            let mut editor = rows_editor(&["a"]);
+            editor.set_abort_on_discard(abort_on_discard);
+
+            events(&[KeyEvent::Exit, KeyEvent::Char('d')]);
+
+            assert_eq!(Some(expected), editor.handle_events().unwrap());
+        }
+    }
+
+    #[test]
+    fn editor_abort() {
+        let mut editor = null_editor(None);
+
+        Null::push_events(&[Event::from((KeyEvent::Char('k'), KeyModifier::AltLeft))]);
+
+        assert_eq!(Some(Exit::Abort), editor.handle_events().unwrap());
+        assert_eq!(1, Exit::Abort.code());
+        assert_eq!(0, Exit::Quit.code());
+    }
+
+    #[test]
+    fn editor_highlight_clear() {
+        let mut editor = rows_editor(&["ab", "ab", "ab"]);
+        editor.keyword = Some(Row::from("b"));
+        editor.find_next().unwrap();
+        editor.refresh().unwrap();
+        assert!(editor.highlight.is_some());
+
+        editor.cursor.move_right(&editor.content);
+        editor.refresh().unwrap();
+
+        assert!(editor.highlight.is_none());
+    }
+
+    fn pending_editor() -> Editor<Null> {
+        let mut editor = rows_editor(&["xyzw", "ab", "c"]);
+        let s = Cursor::from((0, 1));
+        let e = Cursor::from((2, 2));
+        editor.content.copy_pending(&s..&e, SelectMode::Rectangle);
+        editor
+    }
+
+    fn row_text(editor: &Editor<Null>, y: usize) -> String {
+        editor.content.get(y).unwrap().to_string_at(0)
+    }
+
+    #[test]
+    fn editor_copy_line() {
+        let mut editor = rows_editor(&["abc", "de"]);
+        editor.cursor.set(&editor.content, &(1, 0));
+
+        events(&[KeyEvent::Copy]);
+        editor.handle_events().unwrap();
+
+        assert_eq!(
+            vec!["abc".to_string(), String::new()],
+            editor
+                .content()
+                .pending()
+                .unwrap()
+                .iter()
+                .map(|r| r.to_string_at(0))
+                .collect::<Vec<String>>()
+        );
+        assert!(editor.content().pending_linewise());
+
+        editor.cursor.set(&editor.content, &(1, 1));
+        events(&[KeyEvent::Paste]);
+        editor.handle_events().unwrap();
+
+        assert_eq!(3, editor.content().rows());
+        assert_eq!("abc", row_text(&editor, 0));
+        assert_eq!("abc", row_text(&editor, 1));
+        assert_eq!("de", row_text(&editor, 2));
+        assert_eq!((1, 2), editor.cursor().as_coordinates());
+    }
+
+    #[test]
+    fn editor_copy_to_clipboard() {
+        let mut editor = rows_editor(&["abc", "de"]);
+        select_range(&mut editor, (1, 0), (1, 1));
+
+        events(&[KeyEvent::Copy]);
+        editor.handle_events().unwrap();
+
+        assert_eq!(Some("bc\r\nd"), editor.terminal.clipboard());
+
+        events(&[KeyEvent::Cut]);
+        editor.handle_events().unwrap();
+
+        assert_eq!(Some("bc\r\nd"), editor.terminal.clipboard());
+        assert_eq!("ae", row_text(&editor, 0));
+    }
+
+    #[test]
+    fn editor_paste_from_clipboard() {
+        let mut editor = rows_editor(&["abc"]);
+        editor.content.copy_line(&(0, 0));
+        editor.terminal.set_clipboard("x\r\ny\nz").unwrap();
+        editor.cursor.set(&editor.content, &(1, 0));
+
+        events(&[KeyEvent::Paste]);
+        editor.handle_events().unwrap();
+
+        assert_eq!(3, editor.content().rows());
+        assert_eq!("ax", row_text(&editor, 0));
+        assert_eq!("y", row_text(&editor, 1));
+        assert_eq!("zbc", row_text(&editor, 2));
+        assert_eq!((1, 2), editor.cursor().as_coordinates());
+    }
+
+    #[test]
+    fn editor_paste_empty_clipboard() {
+        let mut editor = rows_editor(&["abc"]);
+        editor.terminal.set_clipboard("").unwrap();
+
+        events(&[KeyEvent::Paste]);
+        editor.handle_events().unwrap();
+        assert_eq!(1, editor.content().rows());
+
+        editor.content.copy_line(&(0, 0));
+        events(&[KeyEvent::Paste]);
+        editor.handle_events().unwrap();
+        assert_eq!(2, editor.content().rows());
+        assert_eq!("abc", row_text(&editor, 1));
+    }
+
+    #[test]
+    fn editor_cut_line() {
+        let mut editor = rows_editor(&["abc", "de"]);
+        editor.cursor.set(&editor.content, &(2, 0));
+
+        events(&[KeyEvent::Cut]);
+        editor.handle_events().unwrap();
+
+        assert_eq!(1, editor.content().rows());
+        assert_eq!("de", row_text(&editor, 0));
+        assert_eq!((2, 0), editor.cursor().as_coordinates());
+
+        editor.cursor.set(&editor.content, &(0, 1));
+        events(&[KeyEvent::Paste]);
+        editor.handle_events().unwrap();
+
+        assert_eq!("de", row_text(&editor, 0));
+        assert_eq!("abc", row_text(&editor, 1));
+        assert_eq!((0, 2), editor.cursor().as_coordinates());
+    }
+
+    #[test]
+    fn editor_copy_selection_not_line() {
+        let mut editor = rows_editor(&["abc"]);
+        Null::push_events(&[
+            Event::from((KeyEvent::Char('\0'), KeyModifier::Shift)),
+            Event::from((KeyEvent::ArrowRight, KeyModifier::Shift)),
+            Event::from((KeyEvent::Copy, KeyModifier::None)),
+        ]);
+        editor.handle_events().unwrap();
+        editor.handle_events().unwrap();
+        editor.handle_events().unwrap();
+
+        assert_eq!("a", editor.content().pending().unwrap()[0].to_string_at(0));
+        assert!(!editor.content().pending_linewise());
+    }
+
+    #[test]
+    fn editor_select_all_copy() {
+        let mut editor = rows_editor(&["ab", "", "cd"]);
+        editor.cursor.set(&editor.content, &(1, 1));
+        events(&[KeyEvent::SelectAll, KeyEvent::Copy]);
+
+        editor.handle_events().unwrap();
+        assert_eq!(Some(((0, 0), (2, 2))), select_span(&editor));
+        assert_eq!((2, 2), editor.cursor().as_coordinates());
+        assert!(editor.screen.updated());
+
+        editor.handle_events().unwrap();
+        let pending = editor.content().pending().unwrap();
+        let texts = pending
+            .iter()
+            .map(|r| r.to_string_at(0))
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["ab", "", "cd"], texts);
+    }
+
+    #[test]
+    fn editor_select_all_cut() {
+        let mut editor = rows_editor(&["ab", "cd"]);
+        events(&[KeyEvent::SelectAll, KeyEvent::Cut]);
+        editor.handle_events().unwrap();
+        editor.handle_events().unwrap();
+
+        assert_eq!(1, editor.content().rows());
+        assert_eq!("", row_text(&editor, 0));
+        assert_eq!((0, 0), editor.cursor().as_coordinates());
+    }
+
+    #[test]
+    fn editor_paste_size_under_limit() {
+        let mut editor = pending_editor();
+        editor.set_config(Config::parse("paste_confirm_limit = 4"));
+        editor.cursor.set(&editor.content, &(0, 0));
+
+        assert!(editor.paste().unwrap());
+        assert_eq!("abxyzw", row_text(&editor, 0));
+    }
+
+    #[test]
+    fn editor_paste_size_over_limit_yes() {
+        let mut editor = pending_editor();
+        editor.set_config(Config::parse("paste_confirm_limit = 3"));
+        editor.cursor.set(&editor.content, &(0, 0));
+
+        events(&[KeyEvent::Char('y')]);
+        assert!(editor.paste().unwrap());
+        assert_eq!("abxyzw", row_text(&editor, 0));
+        assert_eq!("c ab", row_text(&editor, 1));
+    }
+
+    #[test]
+    fn editor_paste_size_over_limit_cancel() {
+        for limit in ["3", "1"] {
+            let mut editor = pending_editor();
+            editor.set_config(Config::parse(&format!("paste_confirm_limit = {}", limit)));
+            editor.cursor.set(&editor.content, &(0, 0));
+
+            events(&[KeyEvent::Escape]);
+            assert!(!editor.paste().unwrap());
+            assert_eq!("xyzw", row_text(&editor, 0));
+            assert_eq!("ab", row_text(&editor, 1));
+        }
+    }
+
+    #[test]
+    fn editor_paste_padding_under_limit() {
+        let mut editor = pending_editor();
+        editor.set_paste_padding_limit(2);
+        editor.cursor.set(&editor.content, &(4, 0));
+
+        assert!(editor.paste().unwrap());
+        assert_eq!("xyzwab", row_text(&editor, 0));
+        assert_eq!("ab  c ", row_text(&editor, 1));
+        assert_eq!("c", row_text(&editor, 2));
+        assert_eq!(
+            "Padded 1 rows with up to 2 spaces.",
+            editor.message.message().to_string_at(0)
+        );
+    }
+
+    #[test]
+    fn editor_paste_padding_over_limit_yes() {
+        let mut editor = pending_editor();
+        editor.set_paste_padding_limit(1);
+        editor.cursor.set(&editor.content, &(4, 0));
+
+        events(&[KeyEvent::Char('y')]);
+        assert!(editor.paste().unwrap());
+        assert_eq!("xyzwab", row_text(&editor, 0));
+        assert_eq!("ab  c ", row_text(&editor, 1));
+        assert_eq!(
+            "Padded 1 rows with up to 2 spaces.",
+            editor.message.message().to_string_at(0)
+        );
+    }
+
+    #[test]
+    fn editor_paste_padding_over_limit_cancel() {
+        let mut editor = pending_editor();
+        editor.set_paste_padding_limit(1);
+        editor.cursor.set(&editor.content, &(4, 0));
+
+        events(&[KeyEvent::Escape]);
+        assert!(!editor.paste().unwrap());
+        assert_eq!("xyzw", row_text(&editor, 0));
+        assert_eq!("ab", row_text(&editor, 1));
+        assert_eq!("c", row_text(&editor, 2));
+        assert_eq!(TEXT_MESSAGE_MENU, editor.message.message().to_string_at(0));
+    }
+
+    fn snippet_editor(rows: &[&str]) -> Editor<Null> {
+        let mut editor = rows_editor(rows);
+        let mut snippets = Snippets::default();
+        snippets.insert("fn", "fn $1($2) {\n    $0\n}");
+        editor.set_snippets(snippets);
+        editor
+    }
+
+    #[test]
+    fn editor_expand_snippet() {
+        let mut editor = snippet_editor(&["  fn"]);
+        editor.cursor.set(&editor.content, &(4, 0));
+
+        events(&[KeyEvent::Tab]);
+        editor.handle_events().unwrap();
+
+        assert_eq!(3, editor.content.rows());
+        assert_eq!("  fn () {", row_text(&editor, 0));
+        assert_eq!("      ", row_text(&editor, 1));
+        assert_eq!("  }", row_text(&editor, 2));
+        assert_eq!((5, 0), editor.cursor().as_coordinates());
+
+        editor.content.undo();
+        assert_eq!(1, editor.content.rows());
+        assert_eq!("  fn", row_text(&editor, 0));
+    }
+
+    #[test]
+    fn editor_expand_snippet_tab_stops() {
+        let mut editor = snippet_editor(&["fn"]);
+        editor.cursor.set(&editor.content, &(2, 0));
+
+        events(&[KeyEvent::Tab]);
+        editor.handle_events().unwrap();
+        editor.input_char('a');
+
+        events(&[KeyEvent::Tab]);
+        editor.handle_events().unwrap();
+        assert_eq!((5, 0), editor.cursor().as_coordinates());
+
+        events(&[KeyEvent::Tab]);
+        editor.handle_events().unwrap();
+        assert_eq!((4, 1), editor.cursor().as_coordinates());
+        assert!(editor.tab_stops.is_empty());
+    }
+
+    #[test]
+    fn editor_expand_snippet_unknown() {
+        let mut editor = snippet_editor(&["if"]);
+        editor.cursor.set(&editor.content, &(2, 0));
+
+        assert!(!editor.expand_snippet());
+        assert_eq!("if", row_text(&editor, 0));
+    }
+
+    #[test]
+    fn editor_input_tab() {
+        let mut editor = rows_editor(&["a"]);
+
+        events(&[KeyEvent::Tab, KeyEvent::Char('b')]);
+        editor.handle_events().unwrap();
+        editor.handle_events().unwrap();
+
+        assert_eq!("\tba", row_text(&editor, 0));
+        assert_eq!((2, 0), editor.cursor().as_coordinates());
+        assert_eq!((9, 0), editor.cursor().render(&editor.content));
+    }
+
+    const FILETYPES_CONFIG: &str = "expand_tabs = true\n\
+                                    [filetypes.rs]\n\
+                                    tab_width = 4\n\
+                                    [filetypes.Makefile]\n\
+                                    expand_tabs = false\n";
+
+    #[test]
+    fn editor_input_tab_filetype_rs() {
+        let path = env::temp_dir().join("note_editor_input_tab_filetype.rs");
+        fs::write(&path, "a\r\n").unwrap();
+        let mut editor = null_editor(Some(&path));
+        editor.set_config(Config::parse(FILETYPES_CONFIG));
+        editor.cursor.set(&editor.content, &(1, 0));
+
+        events(&[KeyEvent::Tab]);
+        editor.handle_events().unwrap();
+
+        assert_eq!("a   ", row_text(&editor, 0));
+        assert_eq!((4, 0), editor.cursor().as_coordinates());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn editor_input_tab_filetype_makefile() {
+        let directory = env::temp_dir().join("note_editor_input_tab_filetype_makefile");
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("Makefile");
+        fs::write(&path, "a:\r\n").unwrap();
+        let mut editor = null_editor(Some(&path));
+        editor.set_config(Config::parse(FILETYPES_CONFIG));
+        editor.cursor.set(&editor.content, &(0, 1));
+
+        events(&[KeyEvent::Tab]);
+        editor.handle_events().unwrap();
+
+        assert_eq!("\t", row_text(&editor, 1));
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn editor_save_as_resolves_settings() {
+        let path = env::temp_dir().join("note_editor_save_as_resolves_settings.rs");
+        let mut editor = rows_editor(&["a"]);
+        editor.set_config(Config::parse(FILETYPES_CONFIG));
+        assert_eq!(8, editor.content().settings().tab_width());
+
+        let keys = path.to_str().unwrap().chars().map(KeyEvent::Char);
+        events(&keys.chain([KeyEvent::Enter]).collect::<Vec<KeyEvent>>());
+        editor.save().unwrap();
+
+        assert_eq!(4, editor.content().settings().tab_width());
+        assert!(editor.content().settings().expand_tabs());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn editor_save_verify() {
+        let path = env::temp_dir().join("note_editor_save_verify.txt");
+        let mut editor = rows_editor(&["a", "b\r"]);
+        editor.set_config(Config::parse("verify_save = true\nline_ending = lf"));
+
+        let keys = path.to_str().unwrap().chars().map(KeyEvent::Char);
+        events(&keys.chain([KeyEvent::Enter]).collect::<Vec<KeyEvent>>());
+        editor.save().unwrap();
+
+        assert_eq!(
+            "Saved file differs from buffer at line 2.",
+            editor.message.message().to_string_at(0)
+        );
+        assert!(editor.message.notice().is_none());
+
+        editor.content.delete_char(&(2, 1));
+        editor.message.set_message(Row::default());
+        editor.save().unwrap();
+        assert_eq!("", editor.message.message().to_string_at(0));
+        let notice = editor.message.notice().map(|n| n.to_string_at(0));
+        assert_eq!(Some(TEXT_MESSAGE_SAVED.to_string()), notice);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn editor_auto_save() {
+        let path = env::temp_dir().join("note_editor_auto_save.txt");
+        let mut editor = rows_editor(&["a"]);
+        editor.set_config(Config::parse("line_ending = lf"));
+        let now = Instant::now();
+        assert!(!editor.auto_save(now));
+
+        // No filename
+        editor.content.insert_char(&(1, 0), 'b');
+        assert!(!editor.auto_save(now));
+        assert!(!editor.auto_save(now + Duration::from_secs(60)));
+        assert!(editor.content.cached());
+
+        editor.content.set_filename(&path);
+        editor.content.insert_char(&(2, 0), 'c');
+        assert!(!editor.auto_save(now));
+        assert!(!editor.auto_save(now + Duration::from_secs(29)));
+        assert!(editor.auto_save(now + Duration::from_secs(30)));
+        assert!(!editor.content.cached());
+        assert_eq!("abc\n", fs::read_to_string(&path).unwrap());
+        let notice = editor.message.notice().map(|n| n.to_string_at(0));
+        assert_eq!(Some(TEXT_MESSAGE_AUTO_SAVED.to_string()), notice);
+        assert!(!editor.auto_save(now + Duration::from_secs(60)));
+
+        editor.set_config(Config::parse("auto_save_seconds = 0"));
+        editor.content.insert_char(&(3, 0), 'd');
+        assert!(!editor.auto_save(now));
+        assert!(!editor.auto_save(now + Duration::from_secs(60)));
+        assert!(editor.content.cached());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn editor_auto_save_verify() {
+        let path = env::temp_dir().join("note_editor_auto_save_verify.txt");
+        let mut editor = rows_editor(&["a", "b\r"]);
+        editor.set_config(Config::parse("verify_save = true\nline_ending = lf"));
+        editor.content.set_filename(&path);
+        let now = Instant::now();
+
+        editor.content.insert_char(&(1, 0), 'c');
+        assert!(!editor.auto_save(now));
+        assert!(editor.auto_save(now + Duration::from_secs(30)));
+        assert_eq!(
+            "Saved file differs from buffer at line 2.",
+            editor.message.message().to_string_at(0)
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn editor_auto_save_failed() {
+        let path = env::temp_dir().join("note_editor_auto_save_failed");
+        fs::create_dir_all(&path).unwrap();
+        let mut editor = rows_editor(&["a"]);
+        editor.content.set_filename(&path);
+        let now = Instant::now();
+
+        editor.content.insert_char(&(1, 0), 'b');
+        assert!(!editor.auto_save(now));
+        assert!(!editor.auto_save(now + Duration::from_secs(30)));
+        assert!(editor.content.cached());
+        let notice = editor.message.notice().map(|n| n.to_string_at(0));
+        assert_eq!(Some(TEXT_MESSAGE_AUTO_SAVE_FAILED.to_string()), notice);
+
+        fs::remove_dir(&path).unwrap();
+    }
+
+    #[test]
+    fn editor_tabs_to_spaces_selection() {
+        let mut editor = rows_editor(&["\ta", "\t\tb", "\tc"]);
+        editor.set_config(Config::parse("tab_width = 4"));
+        Null::push_events(&[
+            Event::from((KeyEvent::Char('\0'), KeyModifier::Shift)),
+            Event::from((KeyEvent::ArrowDown, KeyModifier::Shift)),
+            Event::from((KeyEvent::ArrowDown, KeyModifier::Shift)),
+        ]);
+        for _ in 0..3 {
+            editor.handle_events().unwrap();
+        }
+
+        assert!(editor.tabs_to_spaces());
+        assert_eq!("    a", row_text(&editor, 0));
+        assert_eq!("        b", row_text(&editor, 1));
+        assert_eq!("\tc", row_text(&editor, 2));
+
+        assert!(editor.spaces_to_tabs());
+        assert_eq!("\ta", row_text(&editor, 0));
+        assert_eq!("\t\tb", row_text(&editor, 1));
+    }
+
+    #[test]
+    fn editor_input_tab_shift() {
+        let mut editor = snippet_editor(&["fn"]);
+        editor.cursor.set(&editor.content, &(2, 0));
+
+        Null::push_events(&[Event::from((KeyEvent::Tab, KeyModifier::Shift))]);
+        editor.handle_events().unwrap();
+
+        assert_eq!("fn", row_text(&editor, 0));
+        assert_eq!((2, 0), editor.cursor().as_coordinates());
+    }
+
+    #[test]
+    fn editor_enter_copy_indent() {
+        let mut editor = rows_editor(&["  a"]);
+        editor.cursor.set(&editor.content, &(3, 0));
+
+        editor.enter();
+
+        assert_eq!("  a", row_text(&editor, 0));
+        assert_eq!("  ", row_text(&editor, 1));
+        assert_eq!((2, 1), editor.cursor().as_coordinates());
+    }
+
+    #[test]
+    fn editor_enter_opener() {
+        let mut editor = rows_editor(&["if x:"]);
+        editor
+            .content
+            .set_auto_indent(AutoIndent::from_extension("py"));
+        editor.cursor.set(&editor.content, &(5, 0));
+
+        editor.enter();
+
+        assert_eq!("if x:", row_text(&editor, 0));
+        assert_eq!("    ", row_text(&editor, 1));
+        assert_eq!((4, 1), editor.cursor().as_coordinates());
+
+        editor.content.undo();
+        assert_eq!(1, editor.content.rows());
+        assert_eq!("if x:", row_text(&editor, 0));
+    }
+
+    #[test]
+    fn editor_enter_disabled() {
+        let mut editor = rows_editor(&["  a"]);
+        let mut indent = AutoIndent::default();
+        indent.set_enabled(false);
+        editor.content.set_auto_indent(indent);
+        editor.cursor.set(&editor.content, &(3, 0));
+
+        editor.enter();
+
+        assert_eq!("", row_text(&editor, 1));
+        assert_eq!((0, 1), editor.cursor().as_coordinates());
+    }
+
+    fn format_editor(rows: &[&str]) -> Editor<Null> {
+        let mut editor = rows_editor(rows);
+        editor
+            .content
+            .set_formatter(Some(Formatter::new("fmt", &[])));
+        editor
+    }
+
+    #[test]
+    fn editor_format() {
+        let mut editor = format_editor(&["a  =1", "b=2  "]);
+        editor.cursor.set(&editor.content, &(4, 1));
+
+        let formatted = editor.format_with(|formatter, input| {
+            assert_eq!("fmt", formatter.program());
+            assert_eq!("a  =1\nb=2  \n", input);
+            Ok(Output {
+                success: true,
+                stdout: b"a = 1\r\nb = 2\r\n".to_vec(),
+                stderr: vec![],
+            })
+        });
+
+        assert!(formatted);
+        assert_eq!("a = 1", row_text(&editor, 0));
+        assert_eq!("b = 2", row_text(&editor, 1));
+        assert_eq!("Formatted.", editor.message.message().to_string_at(0));
+
+        editor.content.undo();
+        assert_eq!("a  =1", row_text(&editor, 0));
+        assert_eq!("b=2  ", row_text(&editor, 1));
+    }
+
+    #[test]
+    fn editor_format_unchanged() {
+        let mut editor = format_editor(&["a = 1"]);
+
+        let formatted = editor.format_with(|_, input| {
+            Ok(Output {
+                success: true,
+                stdout: input.as_bytes().to_vec(),
+                stderr: vec![],
+            })
+        });
+
+        assert!(!formatted);
+        assert_eq!("a = 1", row_text(&editor, 0));
+        assert_eq!("Formatted.", editor.message.message().to_string_at(0));
+    }
+
+    #[test]
+    fn editor_format_error() {
+        let mut editor = format_editor(&["a("]);
+
+        let formatted = editor.format_with(|_, _| {
+            Ok(Output {
+                success: false,
+                stdout: vec![],
+                stderr: b"error: unclosed delimiter\n  --> <stdin>:1:2\n".to_vec(),
+            })
+        });
+
+        assert!(!formatted);
+        assert_eq!("a(", row_text(&editor, 0));
+        assert_eq!(
+            "fmt: error: unclosed delimiter",
+            editor.message.message().to_string_at(0)
+        );
+    }
+
+    #[test]
+    fn editor_format_no_formatter() {
+        let mut editor = rows_editor(&["a"]);
+
+        let formatted = editor.format_with(|_, _| unreachable!());
+
+        assert!(!formatted);
+        assert_eq!(
+            TEXT_MESSAGE_NO_FORMATTER,
+            editor.message.message().to_string_at(0)
+        );
+    }
+
+    fn resize_editor(height: usize, y: usize, row: usize) -> Editor<Null> {
+        let mut editor = numbered_editor(30, height);
+        editor.cursor.set(&editor.content, &(0, y));
+        editor.screen.keep_row(&(0, y), row);
+        editor
+    }
+
+    fn resize(editor: &mut Editor<Null>, height: usize) {
+        editor.terminal.set_screen_size(10, height + 2);
+        Null::push_events(&[Event::from(WindowEvent::Resize)]);
+        editor.handle_events().unwrap();
+        editor.refresh().unwrap();
+    }
+
+    #[test]
+    fn editor_resize_keep_row_shrink() {
+        let mut editor = resize_editor(10, 15, 5);
+
+        resize(&mut editor, 6);
+
+        assert_eq!(6, editor.screen().height());
+        assert_eq!(10, editor.screen().top());
+    }
+
+    #[test]
+    fn editor_resize_keep_row_grow() {
+        let mut editor = resize_editor(10, 15, 5);
+
+        resize(&mut editor, 20);
+
+        assert_eq!(20, editor.screen().height());
+        assert_eq!(10, editor.screen().top());
+    }
+
+    #[test]
+    fn editor_resize_keep_row_clamp() {
+        let mut editor = resize_editor(10, 18, 8);
+
+        resize(&mut editor, 4);
+        assert_eq!(15, editor.screen().top());
+
+        resize(&mut editor, 10);
+        assert_eq!(15, editor.screen().top());
+    }
+
+    #[test]
+    fn editor_next_change() {
+        let rows = (0..10).map(|i| i.to_string()).collect::<Vec<String>>();
+        let mut editor = rows_editor(&rows.iter().map(|r| r.as_str()).collect::<Vec<&str>>());
+        assert!(!editor.next_change());
+
+        editor.set_baseline();
+        editor.content.insert_char(&(0, 2), 'a');
+        editor.content.insert_row(&(0, 6), &['b']);
+        editor.content.delete_row(&(0, 9));
+
+        assert!(editor.next_change());
+        assert_eq!((0, 2), editor.cursor.as_coordinates());
+        assert!(editor.next_change());
+        assert_eq!((0, 6), editor.cursor.as_coordinates());
+        assert!(editor.next_change());
+        assert_eq!((0, 9), editor.cursor.as_coordinates());
+        assert!(!editor.next_change());
+
+        assert!(editor.previous_change());
+        assert_eq!((0, 6), editor.cursor.as_coordinates());
+        assert!(editor.previous_change());
+        assert_eq!((0, 2), editor.cursor.as_coordinates());
+        assert!(!editor.previous_change());
+    }
+
+    #[test]
+    fn editor_toggle_changes() {
+        let mut editor = rows_editor(&["a", "b", "c"]);
+        editor.set_baseline();
+        editor.content.insert_char(&(1, 1), 'x');
+
+        assert!(editor.toggle_changes());
+        editor.refresh().unwrap();
+        assert_eq!(&[(0, 1, 2)], editor.terminal.attributes());
+
+        assert!(!editor.toggle_changes());
+        editor.refresh().unwrap();
+        assert_eq!(1, editor.terminal.attributes().len());
+    }
+
+    #[test]
+    fn editor_check_brackets() {
+        let mut editor = rows_editor(&["(a", "b", "c]"]);
+
+        assert!(editor.check_brackets().unwrap());
+        assert_eq!((1, 2), editor.cursor.as_coordinates());
+        assert!(editor.highlight.is_some());
+        assert_eq!(
+            "Unmatched `]` at line 3.",
+            editor.message.message().to_string_at(0)
+        );
+
+        editor.content.replace(&(1, 2), 1, &[')']);
+        assert!(!editor.check_brackets().unwrap());
+        assert_eq!(
+            TEXT_MESSAGE_BALANCED,
+            editor.message.message().to_string_at(0)
+        );
+    }
+
+    #[test]
+    fn editor_reflow() {
+        let mut editor = rows_editor(&["x", "", "aa bb", "cc dd ee", "", "y"]);
+        editor.set_config(Config::parse("wrap_width = 6"));
+        editor.cursor.set(&editor.content, &(7, 3));
+
+        assert!(editor.reflow());
+        assert_eq!(
+            vec!["x", "", "aa bb", "cc dd", "ee", "", "y"],
+            (0..7)
+                .map(|y| row_text(&editor, y))
+                .collect::<Vec<String>>()
+        );
+        assert_eq!((1, 4), editor.cursor.as_coordinates());
+        assert!(!editor.reflow());
+
+        assert!(editor.undo());
+        assert_eq!("cc dd ee", row_text(&editor, 3));
+        assert_eq!("", row_text(&editor, 4));
+    }
+
+    #[test]
+    fn editor_cut_all_scrolled() {
+        let rows = (0..100).map(|i| i.to_string()).collect::<Vec<String>>();
+        let mut editor = rows_editor(&rows.iter().map(|r| r.as_str()).collect::<Vec<&str>>());
+        editor.jump_to(&(0, 100));
+        editor.refresh().unwrap();
+        assert_eq!(98, editor.screen().top());
+
+        select_range(&mut editor, (0, 0), (0, 100));
+        events(&[KeyEvent::Cut]);
+        editor.handle_events().unwrap();
+        editor.refresh().unwrap();
+
+        assert_eq!(0, editor.screen().top());
+        assert_eq!((0, 0), editor.cursor.as_coordinates());
+    }
+
+    #[test]
+    fn editor_delete_rows_below_screen_top() {
+        let rows = (0..10).map(|i| i.to_string()).collect::<Vec<String>>();
+        let mut editor = rows_editor(&rows.iter().map(|r| r.as_str()).collect::<Vec<&str>>());
+        editor.jump_to(&(0, 9));
+        editor.refresh().unwrap();
+        assert_eq!(7, editor.screen().top());
+
+        editor.cursor.set(&editor.content, &(0, 7));
+        select_range(&mut editor, (1, 7), (1, 9));
+        events(&[KeyEvent::Cut]);
+        editor.handle_events().unwrap();
+        editor.refresh().unwrap();
+
+        // The rows above are shown instead of the filler rows.
+        assert_eq!(8, editor.content.rows());
+        assert_eq!(6, editor.screen().top());
+        assert!(editor
+            .terminal
+            .writes()
+            .iter()
+            .any(|w| w.1 == 0 && w.2.trim_end() == "6"));
+    }
+
+    fn split_editor(split: Split) -> Editor<Null> {
+        let mut editor = numbered_editor(30, 10);
+        editor.terminal.set_screen_size(20, 12);
+        assert!(editor.split_screen(split).unwrap());
+        editor
+    }
+
+    #[test]
+    fn editor_split_screen_offsets() {
+        let mut editor = split_editor(Split::Horizontal);
+        assert!(!editor.split_screen(Split::Vertical).unwrap());
+        assert_eq!((0, 0), editor.screen().origin());
+        assert_eq!(4, editor.screen().height());
+
+        editor.jump_to(&(0, 20));
+        editor.refresh().unwrap();
+        assert_eq!(17, editor.screen().top());
+
+        assert!(editor.switch_view());
+        editor.refresh().unwrap();
+        assert_eq!((0, 0), editor.cursor().as_coordinates());
+        assert_eq!((0, 5), editor.screen().origin());
+        assert_eq!(0, editor.screen().top());
+        assert_eq!((0, 5), editor.terminal.get_cursor_position().unwrap());
+
+        editor.switch_view();
+        assert_eq!((0, 20), editor.cursor().as_coordinates());
+        assert_eq!(17, editor.screen().top());
+    }
+
+    #[test]
+    fn editor_split_screen_vertical() {
+        let mut editor = split_editor(Split::Vertical);
+
+        editor.refresh().unwrap();
+
+        let writes = editor.terminal.writes();
+        assert!(writes
+            .iter()
+            .any(|w| (w.0, w.1, w.2.as_str()) == (9, 0, "|")));
+        assert!(writes
+            .iter()
+            .any(|w| (w.0, w.1) == (10, 0) && w.2.starts_with('0')));
+        assert_eq!(9, editor.screen().width());
+    }
+
+    #[test]
+    fn editor_split_screen_edit() {
+        let mut editor = split_editor(Split::Horizontal);
+        editor.switch_view();
+        editor.jump_to(&(0, 29));
+        editor.switch_view();
+
+        editor.content.delete_row(&(0, 0));
+        editor.refresh().unwrap();
+
+        editor.switch_view();
+        assert_eq!((0, 29), editor.cursor().as_coordinates());
+        editor.refresh().unwrap();
+        assert_eq!(25, editor.screen().top());
+    }
+
+    #[test]
+    fn editor_close_split() {
+        let mut editor = split_editor(Split::Vertical);
+        editor.switch_view();
+
+        assert!(editor.close_split().unwrap());
+        assert!(!editor.close_split().unwrap());
+        assert!(!editor.switch_view());
+        assert_eq!((0, 0), editor.screen().origin());
+        assert_eq!(20, editor.screen().width());
+        assert_eq!(10, editor.screen().height());
+    }
+
+    #[test]
+    fn editor_select_not_moved() {
+        let mut editor = rows_editor(&["ab"]);
+        editor.cursor.set(&editor.content, &(1, 0));
+        let key = (KeyEvent::ArrowLeft, KeyModifier::Shift);
+
+        script(&mut editor, &[key]);
+        assert!(editor.select.enabled());
+        editor.select.clear_updated();
+
+        script(&mut editor, &[key]);
+        assert!(editor.select.enabled());
+        assert!(!editor.select.updated());
+    }
+
+    #[test]
+    fn editor_select_resize() {
+        let mut editor = rows_editor(&["a", "b", "c", "d"]);
+
+        Null::push_events(&[
+            Event::from((KeyEvent::Char('\0'), KeyModifier::Shift)),
+            Event::from((KeyEvent::ArrowDown, KeyModifier::Shift)),
+        ]);
+        editor.handle_events().unwrap();
+        editor.handle_events().unwrap();
+        editor.refresh().unwrap();
+
+        editor.terminal.set_screen_size(20, 6);
+        let painted = editor.terminal.attributes().len();
+        Null::push_events(&[Event::from(WindowEvent::Resize)]);
+        editor.handle_events().unwrap();
+        editor.refresh().unwrap();
+        assert!(editor.select.enabled());
+        assert!(painted < editor.terminal.attributes().len());
+        assert_eq!(
+            Some((0, usize::MAX)),
+            editor.select.xrange(editor.select.corners(), 0)
+        );
+        assert_eq!(20, editor.screen.width());
+
+        Null::push_events(&[Event::from((KeyEvent::ArrowDown, KeyModifier::Shift))]);
+        editor.handle_events().unwrap();
+
+        assert_eq!(Some(&Cursor::from((0, 0))), editor.select.start());
+        assert_eq!(Some(&Cursor::from((0, 2))), editor.select.end());
+    }
+
+    fn select_span(editor: &Editor<Null>) -> Option<((usize, usize), (usize, usize))> {
+        match (editor.select().start(), editor.select().end()) {
+            (Some(start), Some(end)) if editor.select().enabled() => {
+                Some((start.as_coordinates(), end.as_coordinates()))
+            }
+            _ => None,
+        }
+    }
+
+    fn alt_key(editor: &mut Editor<Null>, ch: char) {
+        Null::push_events(&[Event::from((KeyEvent::Char(ch), KeyModifier::AltLeft))]);
+        editor.handle_events().unwrap();
+    }
+
+    #[test]
+    fn editor_expand_select() {
+        let mut editor = rows_editor(&["let a = f(b, \"c d\");", "x", "", "y"]);
+        editor.cursor.set(&editor.content, &(14, 0));
+
+        alt_key(&mut editor, 'w');
+        assert_eq!(Some(((14, 0), (15, 0))), select_span(&editor));
+        alt_key(&mut editor, 'w');
+        assert_eq!(Some(((13, 0), (18, 0))), select_span(&editor));
+        alt_key(&mut editor, 'w');
+        assert_eq!(Some(((9, 0), (19, 0))), select_span(&editor));
+        alt_key(&mut editor, 'w');
+        assert_eq!(Some(((0, 0), (20, 0))), select_span(&editor));
+        alt_key(&mut editor, 'w');
+        assert_eq!(Some(((0, 0), (1, 1))), select_span(&editor));
+        assert_eq!((1, 1), editor.cursor().as_coordinates());
+
+        assert!(!editor.expand_select());
+        assert_eq!(Some(((0, 0), (1, 1))), select_span(&editor));
+    }
+
+    #[test]
+    fn editor_shrink_select() {
+        let mut editor = rows_editor(&["f(ab)"]);
+        editor.cursor.set(&editor.content, &(3, 0));
+
+        alt_key(&mut editor, 'w');
+        alt_key(&mut editor, 'w');
+        assert_eq!(Some(((1, 0), (5, 0))), select_span(&editor));
+
+        alt_key(&mut editor, 'q');
+        assert_eq!(Some(((2, 0), (4, 0))), select_span(&editor));
+        alt_key(&mut editor, 'q');
+        assert_eq!(None, select_span(&editor));
+        assert_eq!((3, 0), editor.cursor().as_coordinates());
+        assert!(!editor.shrink_select());
+    }
+
+    #[test]
+    fn editor_expand_select_other_key() {
+        let mut editor = rows_editor(&["ab cd"]);
+
+        alt_key(&mut editor, 'w');
+        assert_eq!(Some(((0, 0), (2, 0))), select_span(&editor));
+
+        events(&[KeyEvent::ArrowRight]);
+        editor.handle_events().unwrap();
+        assert_eq!(None, select_span(&editor));
+        assert!(!editor.shrink_select());
+
+        alt_key(&mut editor, 'w');
+        assert_eq!(Some(((3, 0), (5, 0))), select_span(&editor));
+    }
+
+    fn shift_select(editor: &mut Editor<Null>, modifier: KeyModifier, keys: &[KeyEvent]) {
+        let mut events = vec![Event::from((KeyEvent::Char('\0'), modifier))];
+        events.extend(keys.iter().map(|&k| Event::from((k, modifier))));
+        Null::push_events(&events);
+        for _ in 0..events.len() {
+            editor.handle_events().unwrap();
+        }
+    }
+
+    fn type_keys(editor: &mut Editor<Null>, text: &str) {
+        let keys = text.chars().map(KeyEvent::Char).collect::<Vec<_>>();
+        events(&keys);
+        for _ in 0..keys.len() {
+            editor.handle_events().unwrap();
+        }
+    }
+
+    #[test]
+    fn editor_wrap_select_same_row() {
+        let mut editor = rows_editor(&["abcd"]);
+        editor.cursor.set(&editor.content, &(1, 0));
+        shift_select(
+            &mut editor,
+            KeyModifier::Shift,
+            &[KeyEvent::ArrowRight, KeyEvent::ArrowRight],
+        );
+
+        type_keys(&mut editor, "(");
+        assert_eq!("a(bc)d", row_text(&editor, 0));
+        assert_eq!(Some(((2, 0), (4, 0))), select_span(&editor));
+        assert_eq!((4, 0), editor.cursor().as_coordinates());
+
+        type_keys(&mut editor, "\"");
+        assert_eq!("a(\"bc\")d", row_text(&editor, 0));
+        assert_eq!(Some(((3, 0), (5, 0))), select_span(&editor));
+
+        assert!(editor.undo());
+        assert_eq!("a(bc)d", row_text(&editor, 0));
+        assert!(editor.undo());
+        assert_eq!("abcd", row_text(&editor, 0));
+    }
+
+    #[test]
+    fn editor_wrap_select_multi_rows() {
+        let mut editor = rows_editor(&["ab", "cd"]);
+        editor.cursor.set(&editor.content, &(1, 1));
+        shift_select(&mut editor, KeyModifier::Shift, &[KeyEvent::ArrowUp]);
+
+        type_keys(&mut editor, "[");
+        assert_eq!("a[b", row_text(&editor, 0));
+        assert_eq!("c]d", row_text(&editor, 1));
+        assert_eq!(Some(((2, 0), (1, 1))), select_span(&editor));
+        assert_eq!((2, 0), editor.cursor().as_coordinates());
+    }
+
+    #[test]
+    fn editor_enter_over_select_same_row() {
+        let mut editor = rows_editor(&["abcd"]);
+        editor.select_span(&(1, 0), &(3, 0));
+
+        script(&mut editor, &[(KeyEvent::Enter, KeyModifier::None)]);
+        assert_eq!(vec!["a", "d"], row_texts(&editor));
+        assert_eq!((0, 1), editor.cursor().as_coordinates());
+        assert!(!editor.select().enabled());
+
+        script(&mut editor, &[(KeyEvent::Undo, KeyModifier::None)]);
+        assert_eq!(vec!["abcd"], row_texts(&editor));
+    }
+
+    #[test]
+    fn editor_enter_over_select_multi_rows() {
+        let mut editor = rows_editor(&["ab", "cd", "ef"]);
+        editor.select_span(&(1, 2), &(1, 0));
+
+        script(&mut editor, &[(KeyEvent::Enter, KeyModifier::None)]);
+        assert_eq!(vec!["a", "f"], row_texts(&editor));
+        assert_eq!((0, 1), editor.cursor().as_coordinates());
+
+        script(&mut editor, &[(KeyEvent::Undo, KeyModifier::None)]);
+        assert_eq!(vec!["ab", "cd", "ef"], row_texts(&editor));
+        script(&mut editor, &[(KeyEvent::Redo, KeyModifier::None)]);
+        assert_eq!(vec!["a", "f"], row_texts(&editor));
+    }
+
+    #[test]
+    fn editor_enter_over_select_rectangle() {
+        let mut editor = rows_editor(&["ab", "cd"]);
+        let start = editor.cursor.clone();
+        editor.select.set_start(&start, SelectMode::Rectangle);
+        editor.cursor.set(&editor.content, &(1, 1));
+        editor.select.set_end(&editor.cursor.clone());
+
+        script(&mut editor, &[(KeyEvent::Enter, KeyModifier::None)]);
+        assert_eq!(vec!["ab", "cd"], row_texts(&editor));
+        assert_eq!(
+            TEXT_MESSAGE_ENTER_RECTANGLE,
+            editor.message.message().to_string_at(0)
+        );
+    }
+
+    #[test]
+    fn editor_tab_delete_over_select() {
+        let mut editor = rows_editor(&["abcd"]);
+        editor.select_span(&(1, 0), &(3, 0));
+        script(&mut editor, &[(KeyEvent::Tab, KeyModifier::None)]);
+        assert_eq!(vec!["a\td"], row_texts(&editor));
+        script(&mut editor, &[(KeyEvent::Undo, KeyModifier::None)]);
+        assert_eq!(vec!["abcd"], row_texts(&editor));
+
+        editor.select_span(&(1, 0), &(3, 0));
+        script(&mut editor, &[(KeyEvent::Delete, KeyModifier::None)]);
+        assert_eq!(vec!["ad"], row_texts(&editor));
+        assert_eq!((1, 0), editor.cursor().as_coordinates());
+    }
+
+    #[test]
+    fn editor_select_lines() {
+        let mut editor = rows_editor(&["ab", "cd", "ef"]);
+        editor.cursor.set(&editor.content, &(1, 0));
+
+        alt_key(&mut editor, 'l');
+        script(&mut editor, &[(KeyEvent::ArrowDown, KeyModifier::Shift)]);
+        assert_eq!(SelectMode::Line, editor.select.mode());
+        let corners = editor.select.corners();
+        assert_eq!(Some((0, usize::MAX)), editor.select.xrange(corners, 1));
+        assert_eq!(None, editor.select.xrange(corners, 2));
+
+        script(&mut editor, &[(KeyEvent::Copy, KeyModifier::None)]);
+        editor.cursor.set(&editor.content, &(1, 2));
+        script(&mut editor, &[(KeyEvent::Paste, KeyModifier::None)]);
+        assert_eq!(vec!["ab", "cd", "ab", "cd", "ef"], row_texts(&editor));
+        assert_eq!((1, 4), editor.cursor().as_coordinates());
+
+        editor.cursor.set(&editor.content, &(1, 3));
+        alt_key(&mut editor, 'l');
+        script(&mut editor, &[(KeyEvent::Cut, KeyModifier::None)]);
+        assert_eq!(vec!["ab", "cd", "ab", "ef"], row_texts(&editor));
+        script(&mut editor, &[(KeyEvent::Undo, KeyModifier::None)]);
+        assert_eq!(vec!["ab", "cd", "ab", "cd", "ef"], row_texts(&editor));
+    }
+
+    #[test]
+    fn editor_wrap_select_rectangle() {
+        let mut editor = rows_editor(&["ab", "cd"]);
+        let start = editor.cursor.clone();
+        editor.select.set_start(&start, SelectMode::Rectangle);
+        editor.cursor.set(&editor.content, &(1, 1));
+        editor.select.set_end(&editor.cursor.clone());
+
+        type_keys(&mut editor, "(");
+        assert_eq!("ab", row_text(&editor, 0));
+        assert_eq!("cd", row_text(&editor, 1));
+        assert_eq!(Some(((0, 0), (1, 1))), select_span(&editor));
+        assert_eq!(
+            TEXT_MESSAGE_WRAP_RECTANGLE,
+            editor.message.message().to_string_at(0)
+        );
+    }
+
+    #[test]
+    fn editor_auto_pairs() {
+        let mut editor = rows_editor(&["x"]);
+        type_keys(&mut editor, "(");
+        assert_eq!("(x", row_text(&editor, 0));
+
+        let mut editor = rows_editor(&["x"]);
+        editor.set_config(Config::parse("auto_pairs = true"));
+        type_keys(&mut editor, "(a[b])");
+        assert_eq!("(a[b])x", row_text(&editor, 0));
+        assert_eq!((6, 0), editor.cursor().as_coordinates());
+
+        type_keys(&mut editor, ")");
+        assert_eq!("(a[b]))x", row_text(&editor, 0));
+    }
+
+    #[test]
+    fn editor_auto_pairs_skip_after_move() {
+        let mut editor = rows_editor(&[""]);
+        editor.set_config(Config::parse("auto_pairs = true"));
+        type_keys(&mut editor, "\"");
+        assert_eq!("\"\"", row_text(&editor, 0));
+
+        events(&[KeyEvent::ArrowLeft, KeyEvent::ArrowRight]);
+        editor.handle_events().unwrap();
+        editor.handle_events().unwrap();
+
+        type_keys(&mut editor, "\"");
+        assert_eq!("\"\"\"\"", row_text(&editor, 0));
+        assert_eq!((2, 0), editor.cursor().as_coordinates());
+    }
+
+    #[test]
+    fn editor_add_number() {
+        let mut editor = rows_editor(&["x 99 y"]);
+        editor.cursor.set(&editor.content, &(2, 0));
+
+        alt_key(&mut editor, 'a');
+        assert_eq!("x 100 y", row_text(&editor, 0));
+        assert_eq!((4, 0), editor.cursor().as_coordinates());
+
+        alt_key(&mut editor, 'x');
+        alt_key(&mut editor, 'x');
+        assert_eq!("x 98 y", row_text(&editor, 0));
+        assert_eq!((3, 0), editor.cursor().as_coordinates());
+    }
+
+    #[test]
+    fn editor_sort_rows_selected() {
+        let mut editor = rows_editor(&["z", "a 2", "b 1", "c 1", "y"]);
+        let mut start = Cursor::default();
+        start.set(&editor.content, &(0, 1));
+        let mut end = Cursor::default();
+        end.set(&editor.content, &(0, 4));
+        editor.select.set_start(&start, SelectMode::None);
+        editor.select.set_end(&end);
+
+        events(&[
+            KeyEvent::Char('3'),
+            KeyEvent::Char(','),
+            KeyEvent::Char('1'),
+            KeyEvent::Enter,
+        ]);
+        assert!(editor.sort_rows().unwrap());
+
+        assert_eq!("z", row_text(&editor, 0));
+        assert_eq!("b 1", row_text(&editor, 1));
+        assert_eq!("c 1", row_text(&editor, 2));
+        assert_eq!("a 2", row_text(&editor, 3));
+        assert_eq!("y", row_text(&editor, 4));
+    }
+
+    #[test]
+    fn editor_sort_rows_all() {
+        let mut editor = rows_editor(&["b", "c", "a"]);
+
+        events(&[KeyEvent::Enter]);
+        assert!(editor.sort_rows().unwrap());
+
+        assert_eq!("a", row_text(&editor, 0));
+        assert_eq!("b", row_text(&editor, 1));
+        assert_eq!("c", row_text(&editor, 2));
+    }
+
+    #[test]
+    fn editor_sort_rows_cancel() {
+        let mut editor = rows_editor(&["b", "a"]);
+
+        events(&[KeyEvent::Escape]);
+        assert!(!editor.sort_rows().unwrap());
+
+        assert_eq!("b", row_text(&editor, 0));
+    }
+
+    fn select_range(editor: &mut Editor<Null>, start: (usize, usize), end: (usize, usize)) {
+        let mut s = Cursor::default();
+        s.set(&editor.content, &start);
+        let mut e = Cursor::default();
+        e.set(&editor.content, &end);
+        editor.select.set_start(&s, SelectMode::None);
+        editor.select.set_end(&e);
+    }
+
+    #[test]
+    fn editor_convert_select_json() {
+        let mut editor = rows_editor(&["x \"a\"", "b\\c y"]);
+        select_range(&mut editor, (2, 0), (4, 1));
+
+        assert!(editor.convert_select(|text| Some(escape::json_escape(text))));
+        assert_eq!(1, editor.content.rows());
+        assert_eq!(r#"x \"a\"\nb\\c y"#, row_text(&editor, 0));
+        assert_eq!((14, 0), editor.cursor.as_coordinates());
+
+        select_range(&mut editor, (2, 0), (14, 0));
+        assert!(editor.convert_select(escape::json_unescape));
+        assert_eq!("x \"a\"", row_text(&editor, 0));
+        assert_eq!("b\\c y", row_text(&editor, 1));
+        assert_eq!((4, 1), editor.cursor.as_coordinates());
+    }
+
+    #[test]
+    fn editor_convert_select_undo() {
+        let mut editor = rows_editor(&["it's", "ok"]);
+        select_range(&mut editor, (0, 0), (2, 1));
+
+        assert!(editor.convert_select(|text| Some(escape::shell_quote(text))));
+        assert_eq!("'it'\\''s", row_text(&editor, 0));
+        assert_eq!("ok'", row_text(&editor, 1));
+
+        assert!(editor.undo());
+        assert_eq!("it's", row_text(&editor, 0));
+        assert_eq!("ok", row_text(&editor, 1));
+    }
+
+    #[test]
+    fn editor_convert_select_invalid() {
+        let mut editor = rows_editor(&["a\\x"]);
+        select_range(&mut editor, (0, 0), (3, 0));
+
+        assert!(!editor.convert_select(escape::json_unescape));
+        assert_eq!("a\\x", row_text(&editor, 0));
+        assert!(!editor.convert_select(|_| None));
+    }
+
+    fn text_events(text: &str, last: KeyEvent) {
+        let mut keys = text.chars().map(KeyEvent::Char).collect::<Vec<KeyEvent>>();
+        keys.push(last);
+        events(&keys);
+    }
+
+    fn script(editor: &mut Editor<Null>, keys: &[(KeyEvent, KeyModifier)]) -> Option<Exit> {
+        let events = keys.iter().map(|&k| Event::from(k)).collect::<Vec<Event>>();
+        Null::push_events(&events);
+        let mut exit = None;
+        for _ in 0..events.len() {
+            exit = exit.or(editor.handle_events().unwrap());
+        }
+        exit
+    }
+
+    fn row_texts(editor: &Editor<Null>) -> Vec<String> {
+        (0..editor.content.rows())
+            .map(|y| row_text(editor, y))
+            .collect()
+    }
+
+    #[test]
+    fn editor_translate() {
+        let mut editor = rows_editor(&["abc"]);
+        let key = |key, modifier| editor.translate(Event::Key(key, modifier));
+        let none = KeyModifier::None;
+        let alt = KeyModifier::AltLeft;
+
+        assert_eq!(Some(EditorAction::MoveUp), key(KeyEvent::ArrowUp, none));
+        assert_eq!(
+            Some(EditorAction::MovePrevIndent),
+            key(KeyEvent::ArrowUp, alt)
+        );
+        assert_eq!(Some(EditorAction::InputTab), key(KeyEvent::Tab, none));
+        assert_eq!(None, key(KeyEvent::Tab, KeyModifier::Shift));
+        assert_eq!(
+            Some(EditorAction::FindPrevious),
+            key(KeyEvent::F3, KeyModifier::Shift)
+        );
+        assert_eq!(None, key(KeyEvent::F3, KeyModifier::CtrlLeft));
+        assert_eq!(
+            Some(EditorAction::AddNumber(-1)),
+            key(KeyEvent::Char('x'), alt)
+        );
+        assert_eq!(
+            Some(EditorAction::Split(Split::Vertical)),
+            key(KeyEvent::Char('v'), alt)
+        );
+        assert_eq!(
+            Some(EditorAction::ReopenClosed),
+            key(KeyEvent::Char('f'), alt)
+        );
+        assert_eq!(
+            Some(EditorAction::TypeChar('z')),
+            key(KeyEvent::Char('z'), alt)
+        );
+        assert_eq!(None, key(KeyEvent::Char('\0'), KeyModifier::Shift));
+        assert_eq!(
+            Some(EditorAction::Resize),
+            editor.translate(Event::Window(WindowEvent::Resize))
+        );
+
+        editor.set_config(Config::parse("half_page_scroll = false"));
+        assert_eq!(
+            None,
+            editor.translate(Event::Key(KeyEvent::HalfPageUp, KeyModifier::None))
+        );
+        assert_eq!(
+            None,
+            editor.translate(Event::Key(KeyEvent::HalfPageDown, KeyModifier::None))
+        );
+        assert_eq!(
+            Some(EditorAction::DuplicateLine),
+            editor.translate(Event::Key(KeyEvent::DuplicateLine, KeyModifier::None))
+        );
+    }
+
+    #[test]
+    fn editor_move_row() {
+        let mut editor = rows_editor(&["a", "bcd", "e"]);
+        editor.cursor.set(&editor.content, &(2, 1));
+        let up = (KeyEvent::ArrowUp, KeyModifier::AltShift);
+        let down = (KeyEvent::ArrowDown, KeyModifier::AltShift);
+
+        script(&mut editor, &[up]);
+        assert_eq!(vec!["bcd", "a", "e"], row_texts(&editor));
+        assert_eq!((2, 0), editor.cursor().as_coordinates());
+        assert!(!editor.select.enabled());
+
+        script(&mut editor, &[up]);
+        assert_eq!(vec!["bcd", "a", "e"], row_texts(&editor));
+        assert_eq!((2, 0), editor.cursor().as_coordinates());
+
+        script(&mut editor, &[down, down, down]);
+        assert_eq!(vec!["a", "e", "bcd"], row_texts(&editor));
+        assert_eq!((2, 2), editor.cursor().as_coordinates());
+
+        script(&mut editor, &[(KeyEvent::Undo, KeyModifier::None)]);
+        assert_eq!(vec!["a", "bcd", "e"], row_texts(&editor));
+    }
+
+    #[test]
+    fn editor_numbered_mark() {
+        let mut editor = rows_editor(&["a", "bc", "d"]);
+        editor.cursor.set(&editor.content, &(1, 1));
+
+        script(&mut editor, &[(KeyEvent::SetMark(3), KeyModifier::None)]);
+        assert_eq!("Mark 3 is set.", editor.message.message().to_string_at(0));
+
+        editor.cursor.set(&editor.content, &(0, 2));
+        script(&mut editor, &[(KeyEvent::JumpToMark(3), KeyModifier::None)]);
+        assert_eq!((1, 1), editor.cursor().as_coordinates());
+
+        editor.cursor.set(&editor.content, &(0, 1));
+        script(
+            &mut editor,
+            &[
+                (KeyEvent::DeleteRow, KeyModifier::None),
+                (KeyEvent::DeleteRow, KeyModifier::None),
+            ],
+        );
+        editor.cursor.set(&editor.content, &(0, 0));
+        script(&mut editor, &[(KeyEvent::JumpToMark(3), KeyModifier::None)]);
+        assert_eq!((0, 0), editor.cursor().as_coordinates());
+        assert_eq!(
+            "Mark 3 is not set.",
+            editor.message.message().to_string_at(0)
+        );
+    }
+
+    #[test]
+    fn editor_jump_to_bracket() {
+        let mut editor = rows_editor(&["f(a[0],", "  b)"]);
+        let key = (KeyEvent::MatchBracket, KeyModifier::CtrlLeft);
+
+        editor.cursor.set(&editor.content, &(1, 0));
+        script(&mut editor, &[key]);
+        assert_eq!((3, 1), editor.cursor().as_coordinates());
+        script(&mut editor, &[key]);
+        assert_eq!((1, 0), editor.cursor().as_coordinates());
+
+        editor.cursor.set(&editor.content, &(2, 0));
+        script(&mut editor, &[key]);
+        assert_eq!((2, 0), editor.cursor().as_coordinates());
+    }
+
+    #[test]
+    fn editor_duplicate_line() {
+        let mut editor = rows_editor(&["aあb", "c"]);
+        editor.cursor.set(&editor.content, &(2, 0));
+        let key = (KeyEvent::DuplicateLine, KeyModifier::None);
+
+        script(&mut editor, &[key]);
+        assert_eq!(vec!["aあb", "aあb", "c"], row_texts(&editor));
+        assert_eq!((2, 1), editor.cursor().as_coordinates());
+        assert_eq!(
+            editor
+                .content
+                .get(0)
+                .unwrap()
+                .width(editor.content.tab_width()),
+            editor
+                .content
+                .get(1)
+                .unwrap()
+                .width(editor.content.tab_width())
+        );
+
+        script(&mut editor, &[(KeyEvent::Undo, KeyModifier::None)]);
+        assert_eq!(vec!["aあb", "c"], row_texts(&editor));
+
+        editor.cursor.set(&editor.content, &(0, 2));
+        script(&mut editor, &[key]);
+        assert_eq!(vec!["aあb", "c"], row_texts(&editor));
+        assert_eq!((0, 2), editor.cursor().as_coordinates());
+    }
+
+    #[test]
+    fn editor_script_move_keys() {
+        let mut editor = rows_editor(&["abc", "  def", "", "  g"]);
+        let none = KeyModifier::None;
+        let steps = [
+            ((KeyEvent::ArrowRight, none), (1, 0)),
+            ((KeyEvent::ArrowDown, none), (1, 1)),
+            ((KeyEvent::End, none), (5, 1)),
+            ((KeyEvent::Home, none), (0, 1)),
+            ((KeyEvent::ArrowUp, none), (0, 0)),
+            ((KeyEvent::ArrowLeft, none), (0, 0)),
+            ((KeyEvent::ArrowDown, none), (0, 1)),
+            ((KeyEvent::ArrowDown, KeyModifier::AltLeft), (2, 3)),
+            ((KeyEvent::ArrowDown, KeyModifier::AltLeft), (2, 3)),
+            ((KeyEvent::ArrowUp, KeyModifier::AltLeft), (2, 1)),
+            ((KeyEvent::PageDown, none), (0, 4)),
+            ((KeyEvent::PageUp, none), (0, 1)),
+            ((KeyEvent::HalfPageDown, none), (0, 2)),
+            ((KeyEvent::HalfPageUp, none), (0, 1)),
+            ((KeyEvent::End, KeyModifier::CtrlLeft), (3, 3)),
+            ((KeyEvent::Home, KeyModifier::CtrlRight), (0, 0)),
+        ];
+        for (key, at) in steps {
+            assert_eq!(None, script(&mut editor, &[key]));
+            assert_eq!(at, editor.cursor().as_coordinates(), "{:?}", key);
+        }
+    }
+
+    #[test]
+    fn editor_move_bottom_scroll() {
+        let rows = (0..30).map(|i| i.to_string()).collect::<Vec<String>>();
+        let mut editor = rows_editor(&rows.iter().map(|r| r.as_str()).collect::<Vec<&str>>());
+
+        script(&mut editor, &[(KeyEvent::End, KeyModifier::CtrlLeft)]);
+        editor.refresh().unwrap();
+        assert_eq!((2, 29), editor.cursor().as_coordinates());
+        assert_eq!(30 - editor.screen().height(), editor.screen().top());
+
+        script(&mut editor, &[(KeyEvent::Home, KeyModifier::CtrlLeft)]);
+        editor.refresh().unwrap();
+        assert_eq!((0, 0), editor.cursor().as_coordinates());
+        assert_eq!(0, editor.screen().top());
+    }
+
+    #[test]
+    fn editor_move_top_enter() {
+        let mut editor = rows_editor(&["ab", "cd"]);
+        script(&mut editor, &[(KeyEvent::End, KeyModifier::None)]);
+
+        script(&mut editor, &[(KeyEvent::Home, KeyModifier::CtrlLeft)]);
+        assert!(!editor.select().enabled());
+        script(&mut editor, &[(KeyEvent::Enter, KeyModifier::None)]);
+        assert_eq!(vec!["", "ab", "cd"], row_texts(&editor));
+        assert_eq!((0, 1), editor.cursor().as_coordinates());
+
+        // Moving to the bottom still extends the selection.
+        script(
+            &mut editor,
+            &[
+                (KeyEvent::ArrowRight, KeyModifier::Shift),
+                (KeyEvent::End, KeyModifier::CtrlLeft),
+            ],
+        );
+        assert!(editor.select().enabled());
+        assert_eq!((2, 2), editor.cursor().as_coordinates());
+    }
+
+    #[test]
+    fn editor_word_move() {
+        let mut editor = rows_editor(&["foo  bar", "baz qux"]);
+        let ctrl = KeyModifier::CtrlLeft;
+
+        script(&mut editor, &[(KeyEvent::ArrowRight, ctrl)]);
+        assert_eq!((5, 0), editor.cursor().as_coordinates());
+        assert!(!editor.select().enabled());
+        script(
+            &mut editor,
+            &[(KeyEvent::ArrowLeft, KeyModifier::CtrlRight)],
+        );
+        assert_eq!((0, 0), editor.cursor().as_coordinates());
+
+        script(
+            &mut editor,
+            &[(KeyEvent::ArrowDown, ctrl), (KeyEvent::ArrowRight, ctrl)],
+        );
+        assert_eq!((4, 1), editor.cursor().as_coordinates());
+        assert_eq!(SelectMode::Rectangle, editor.select().mode());
+        assert_eq!(Some(((0, 1), (4, 1))), select_span(&editor));
+    }
+
+    #[test]
+    fn editor_script_edit_keys() {
+        let mut editor = rows_editor(&["abc", "def"]);
+        let (none, shift) = (KeyModifier::None, KeyModifier::Shift);
+        let (ctrl, alt) = (KeyModifier::CtrlLeft, KeyModifier::AltLeft);
+        type Step = (
+            (KeyEvent, KeyModifier),
+            &'static [&'static str],
+            (usize, usize),
+        );
+        let steps: [Step; 11] = [
+            ((KeyEvent::Char('x'), none), &["xabc", "def"], (1, 0)),
+            ((KeyEvent::BackSpace, none), &["abc", "def"], (0, 0)),
+            ((KeyEvent::Delete, none), &["bc", "def"], (0, 0)),
+            ((KeyEvent::Enter, none), &["", "bc", "def"], (0, 1)),
+            ((KeyEvent::Tab, none), &["", "\tbc", "def"], (1, 1)),
+            ((KeyEvent::Tab, shift), &["", "\tbc", "def"], (1, 1)),
+            ((KeyEvent::Comment, ctrl), &["", "# \tbc", "def"], (1, 1)),
+            ((KeyEvent::DeleteRow, none), &["", "#", "def"], (1, 1)),
+            ((KeyEvent::Undo, none), &["", "# \tbc", "def"], (1, 1)),
+            ((KeyEvent::Redo, none), &["", "#", "def"], (1, 1)),
+            ((KeyEvent::Char('x'), alt), &["", "#", "def"], (1, 1)),
+        ];
+        for (key, rows, at) in steps {
+            assert_eq!(None, script(&mut editor, &[key]));
+            assert_eq!(rows, row_texts(&editor), "{:?}", key);
+            assert_eq!(at, editor.cursor().as_coordinates(), "{:?}", key);
+        }
+
+        editor.cursor.set(&editor.content, &(0, 2));
+        script(&mut editor, &[(KeyEvent::DeleteRow, none)]);
+        script(&mut editor, &[(KeyEvent::DeleteRow, none)]);
+        assert_eq!(vec!["", "#"], row_texts(&editor));
+    }
+
+    #[test]
+    fn editor_script_clipboard_keys() {
+        let mut editor = rows_editor(&["ab", "cd"]);
+        let none = KeyModifier::None;
+        let shift = KeyModifier::Shift;
+
+        script(&mut editor, &[(KeyEvent::Copy, none)]);
+        assert_eq!(Some("ab\r\n"), editor.terminal.clipboard());
+
+        script(
+            &mut editor,
+            &[(KeyEvent::ArrowDown, none), (KeyEvent::Paste, none)],
+        );
+        assert_eq!(vec!["ab", "ab", "cd"], row_texts(&editor));
+        assert_eq!((0, 2), editor.cursor().as_coordinates());
+
+        script(
+            &mut editor,
+            &[
+                (KeyEvent::Char('\0'), shift),
+                (KeyEvent::ArrowRight, shift),
+                (KeyEvent::Cut, none),
+            ],
+        );
+        assert_eq!(vec!["ab", "ab", "d"], row_texts(&editor));
+        assert_eq!(Some("c"), editor.terminal.clipboard());
+        assert_eq!((0, 2), editor.cursor().as_coordinates());
+
+        script(
+            &mut editor,
+            &[(KeyEvent::ArrowUp, none), (KeyEvent::Cut, none)],
+        );
+        assert_eq!(vec!["ab", "d"], row_texts(&editor));
+        assert_eq!(Some("ab\r\n"), editor.terminal.clipboard());
+    }
+
+    #[test]
+    fn editor_script_alt_keys() {
+        let mut editor = rows_editor(&["a 1", "\tb", "    c"]);
+        editor.set_config(Config::parse("tab_width = 4"));
+        let alt = |ch| (KeyEvent::Char(ch), KeyModifier::AltLeft);
+
+        editor.cursor.set(&editor.content, &(2, 0));
+        script(&mut editor, &[alt('a'), alt('a'), alt('x')]);
+        assert_eq!("a 2", row_text(&editor, 0));
+
+        script(&mut editor, &[alt('t')]);
+        assert_eq!(vec!["a 2", "    b", "    c"], row_texts(&editor));
+        script(&mut editor, &[alt('y')]);
+        assert_eq!(vec!["a 2", "\tb", "\tc"], row_texts(&editor));
+
+        editor.cursor.set(&editor.content, &(0, 0));
+        script(&mut editor, &[alt('w'), alt('j')]);
+        assert_eq!("a 2", row_text(&editor, 0));
+        script(&mut editor, &[alt('w'), alt('u')]);
+        assert_eq!("a 2", row_text(&editor, 0));
+        script(&mut editor, &[alt('w'), alt('q'), alt('h')]);
+        assert_eq!("'a' 2", row_text(&editor, 0));
+
+        script(
+            &mut editor,
+            &[alt('b'), (KeyEvent::Char('z'), KeyModifier::None)],
+        );
+        script(&mut editor, &[alt('d')]);
+        assert!(editor.show_changes);
+        script(&mut editor, &[alt('n'), alt('m')]);
+        assert_eq!(0, editor.cursor().y());
+
+        script(&mut editor, &[alt('s')]);
+        assert!(editor.split.is_some());
+        script(&mut editor, &[alt('o'), alt('1')]);
+        assert!(editor.split.is_none());
+
+        script(&mut editor, &[alt('r'), alt('c')]);
+        assert_eq!(
+            TEXT_MESSAGE_BALANCED,
+            editor.message.message().to_string_at(0)
+        );
+
+        script(&mut editor, &[alt('v'), alt('1')]);
+        assert!(editor.split.is_none());
+    }
+
+    #[test]
+    fn editor_script_prompt_keys() {
+        let path = env::temp_dir().join("note_editor_script_prompt_keys.txt");
+        fs::write(&path, "ab\r\nab\r\n").unwrap();
+        let mut editor = null_editor(Some(&path));
+        let none = KeyModifier::None;
+        let esc = (KeyEvent::Escape, none);
+
+        script(&mut editor, &[(KeyEvent::Find, none), esc]);
+        script(&mut editor, &[(KeyEvent::Goto, none), esc]);
+        script(&mut editor, &[(KeyEvent::Replace, none), esc]);
+        script(&mut editor, &[(KeyEvent::Sort, none), esc]);
+        script(
+            &mut editor,
+            &[(KeyEvent::Char('p'), KeyModifier::AltLeft), esc],
+        );
+        script(
+            &mut editor,
+            &[(KeyEvent::F3, none), (KeyEvent::F3, KeyModifier::Shift)],
+        );
+        assert_eq!((0, 0), editor.cursor().as_coordinates());
+        assert!(!editor.content.cached());
+
+        script(
+            &mut editor,
+            &[
+                (KeyEvent::Find, none),
+                (KeyEvent::Char('b'), none),
+                (KeyEvent::Enter, none),
+            ],
+        );
+        assert_eq!((1, 0), editor.cursor().as_coordinates());
+        script(&mut editor, &[(KeyEvent::F3, none)]);
+        assert_eq!((1, 1), editor.cursor().as_coordinates());
+        script(&mut editor, &[(KeyEvent::F3, KeyModifier::Shift)]);
+        assert_eq!((1, 0), editor.cursor().as_coordinates());
+
+        script(
+            &mut editor,
+            &[(KeyEvent::Char('x'), none), (KeyEvent::Save, none)],
+        );
+        assert_eq!("axb\r\nab\r\n", fs::read_to_string(&path).unwrap());
+        script(&mut editor, &[(KeyEvent::Format, none)]);
+        assert_eq!(
+            TEXT_MESSAGE_NO_FORMATTER,
+            editor.message.message().to_string_at(0)
+        );
+
+        Null::push_events(&[Event::from(WindowEvent::Resize)]);
+        editor.handle_events().unwrap();
+        assert_eq!(
+            Some(Exit::Quit),
+            script(&mut editor, &[(KeyEvent::Exit, none)])
+        );
+
+        script(&mut editor, &[(KeyEvent::Char('y'), none)]);
+        assert_eq!(
+            Some(Exit::Abort),
+            script(
+                &mut editor,
+                &[
+                    (KeyEvent::Char('k'), KeyModifier::AltLeft),
+                    (KeyEvent::Char('y'), none)
+                ]
+            )
+        );
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn editor_edit_settings() {
+        let mut editor = rows_editor(&["\ta"]);
+        editor.set_config(Config::parse("tab_width = 8"));
+
+        text_events("tab_width = 2", KeyEvent::Enter);
+        assert!(editor.edit_settings().unwrap());
+
+        assert_eq!(2, editor.content.settings().tab_width());
+        assert_eq!(
+            3,
+            editor
+                .content
+                .get(0)
+                .unwrap()
+                .width(editor.content.tab_width())
+        );
+        assert!(editor
+            .message
+            .message()
+            .to_string_at(0)
+            .starts_with("tab_width = 2,"));
+    }
+
+    #[test]
+    fn editor_edit_settings_line_ending() {
+        let mut editor = rows_editor(&["a"]);
+        editor.content.set_line_ending(LineEnding::Lf);
+
+        text_events("line_ending = crlf", KeyEvent::Enter);
+        assert!(editor.edit_settings().unwrap());
+
+        assert_eq!(LineEnding::Crlf, editor.content.line_ending());
+    }
+
+    #[test]
+    fn editor_edit_settings_show() {
+        let mut editor = rows_editor(&["a"]);
+
+        events(&[KeyEvent::Enter]);
+        assert!(!editor.edit_settings().unwrap());
+
+        assert_eq!(
+            editor.content.settings().to_string(),
+            editor.message.message().to_string_at(0)
+        );
+    }
+
+    #[test]
+    fn editor_edit_settings_invalid() {
+        let mut editor = rows_editor(&["a"]);
+
+        text_events("tab_width = 0", KeyEvent::Enter);
+        assert!(!editor.edit_settings().unwrap());
+
+        assert_eq!(8, editor.content.settings().tab_width());
+    }
+
+    #[test]
+    fn editor_parse_sort_key() {
+        assert_eq!(Some((0, None)), parse_sort_key(""));
+        assert_eq!(Some((4, None)), parse_sort_key("5"));
+        assert_eq!(Some((4, Some(4))), parse_sort_key(" 5, 4 "));
+        assert_eq!(None, parse_sort_key("0"));
+        assert_eq!(None, parse_sort_key("5,"));
+        assert_eq!(None, parse_sort_key("a"));
+    }
+
+    #[test]
+    fn editor_init_binary_yes() {
+        let path = env::temp_dir().join("note_editor_init_binary_yes.bin");
+        fs::write(&path, b"MZ\x90\0\x03\0\0\0").unwrap();
+        let mut editor = null_editor(Some(&path));
+
+        events(&[KeyEvent::Char('y')]);
+        editor.init().unwrap();
+
+        assert_eq!(1, editor.content().rows());
+        assert!(editor.content().read_only());
+        assert!(editor.save().is_ok());
+        assert_eq!(
+            TEXT_MESSAGE_READ_ONLY,
+            editor.message.message().to_string_at(0)
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn editor_init_invalid_utf8() {
+        let path = env::temp_dir().join("note_editor_init_invalid_utf8.txt");
+        fs::write(&path, b"a\xff\nb\nc\xfe").unwrap();
+
+        for (key, read_only) in [(KeyEvent::Escape, true), (KeyEvent::Char('y'), false)] {
+            let mut editor = null_editor(Some(&path));
+            events(&[key]);
+            editor.init().unwrap();
+
+            assert_eq!(read_only, editor.content().read_only());
+            assert_eq!(
+                "2 lines contained invalid UTF-8 and were replaced.",
+                editor.message.message().to_string_at(0)
+            );
+        }
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn editor_init_binary_cancel() {
+        let path = env::temp_dir().join("note_editor_init_binary_cancel.bin");
+        fs::write(&path, b"MZ\x90\0\x03\0\0\0").unwrap();
+        let mut editor = null_editor(Some(&path));
+
+        events(&[KeyEvent::Escape]);
+        editor.init().unwrap();
+
+        assert_eq!(0, editor.content().rows());
+        assert!(!editor.content().read_only());
+
+        fs::remove_file(&path).unwrap();
+    }
 }
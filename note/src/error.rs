@@ -1,7 +1,11 @@
 #[derive(Debug)]
 pub enum Error {
+    Argument(String),
     Io(std::io::Error),
+    OutOfRange(usize, usize),
+    Process(String),
     Utf16(std::char::DecodeUtf16Error),
+    #[cfg(windows)]
     Win32(windows::core::Error),
 }
 
@@ -17,6 +21,7 @@ impl From<std::char::DecodeUtf16Error> for Error {
     }
 }
 
+#[cfg(windows)]
 impl From<windows::core::Error> for Error {
     fn from(error: windows::core::Error) -> Self {
         Error::Win32(error)
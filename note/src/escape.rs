@@ -0,0 +1,129 @@
+/// Returns the text escaped as the content of JSON string.
+/// Line breaks are escaped, so the multi-line text is escaped into one line.
+///
+/// ```
+/// use note::escape::json_escape;
+///
+/// assert_eq!(r#"say \"hi\"\nC:\\temp"#, json_escape("say \"hi\"\nC:\\temp"));
+/// ```
+pub fn json_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            '\u{8}' => escaped.push_str("\\b"),
+            '\u{C}' => escaped.push_str("\\f"),
+            _ if ch < '\u{20}' => escaped.push_str(&format!("\\u{:04x}", ch as u32)),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/// Returns the text unescaped from the content of JSON string,
+/// or `None` if the text contains invalid escape sequence.
+pub fn json_unescape(text: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            unescaped.push(ch);
+            continue;
+        }
+
+        match chars.next()? {
+            '"' => unescaped.push('"'),
+            '\\' => unescaped.push('\\'),
+            '/' => unescaped.push('/'),
+            'n' => unescaped.push('\n'),
+            'r' => unescaped.push('\r'),
+            't' => unescaped.push('\t'),
+            'b' => unescaped.push('\u{8}'),
+            'f' => unescaped.push('\u{C}'),
+            'u' => {
+                let high = hex4(&mut chars)?;
+                let code = if (0xD800..0xDC00).contains(&high) {
+                    // surrogate pair
+                    if chars.next()? != '\\' || chars.next()? != 'u' {
+                        return None;
+                    }
+                    let low = hex4(&mut chars)?;
+                    if !(0xDC00..0xE000).contains(&low) {
+                        return None;
+                    }
+                    0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+                } else {
+                    high
+                };
+                unescaped.push(char::from_u32(code)?);
+            }
+            _ => return None,
+        }
+    }
+    Some(unescaped)
+}
+
+/// Returns the text quoted by single quotes for POSIX shell.
+/// Line breaks are kept in the quotes.
+///
+/// ```
+/// use note::escape::shell_quote;
+///
+/// assert_eq!(r#"'it'\''s'"#, shell_quote("it's"));
+/// ```
+pub fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+fn hex4(chars: &mut impl Iterator<Item = char>) -> Option<u32> {
+    let mut code = 0;
+    for _ in 0..4 {
+        code = code * 16 + chars.next()?.to_digit(16)?;
+    }
+    Some(code)
+}
+
+// -----------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_escape_quotes_and_newlines() {
+        let text = "a \"b\"\r\n\tc\\d\u{1}";
+
+        let escaped = json_escape(text);
+
+        assert_eq!("a \\\"b\\\"\\r\\n\\tc\\\\d\\u0001", escaped);
+        assert_eq!(Some(text.to_string()), json_unescape(&escaped));
+    }
+
+    #[test]
+    fn json_unescape_unicode() {
+        assert_eq!(
+            Some("あ/😀".to_string()),
+            json_unescape("\\u3042\\/\\ud83d\\ude00")
+        );
+        assert_eq!(Some("あ".to_string()), json_unescape("あ"));
+    }
+
+    #[test]
+    fn json_unescape_invalid() {
+        assert_eq!(None, json_unescape("a\\"));
+        assert_eq!(None, json_unescape("\\x"));
+        assert_eq!(None, json_unescape("\\u12"));
+        assert_eq!(None, json_unescape("\\ud83d"));
+        assert_eq!(None, json_unescape("\\ude00"));
+    }
+
+    #[test]
+    fn shell_quote_multi_line() {
+        assert_eq!("'a\nb'", shell_quote("a\nb"));
+        assert_eq!("''", shell_quote(""));
+    }
+}
@@ -0,0 +1,189 @@
+use std::ops::Range;
+use std::path::Path;
+
+/// Kind of the highlighted text, drawn in the color of the theme.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Syntax {
+    Comment,
+    String,
+}
+
+/// Syntax highlighting of a row.
+pub trait Highlighter {
+    /// Returns the highlighted ranges of char index in the row, in order and not overlapped.
+    /// The rest of the row is drawn in the text color.
+    fn spans(&self, row: &[char], y: usize) -> Vec<(Range<usize>, Syntax)>;
+}
+
+/// Comment from `#` to end of row, such as shell and TOML.
+pub struct HashComment;
+
+impl Highlighter for HashComment {
+    fn spans(&self, row: &[char], _: usize) -> Vec<(Range<usize>, Syntax)> {
+        match row.iter().position(|&ch| ch == '#') {
+            Some(x) => vec![(x..row.len(), Syntax::Comment)],
+            None => vec![],
+        }
+    }
+}
+
+/// Comment from `//` to end of row and string literal in double quotes, such as Rust and C.
+pub struct SlashComment;
+
+impl Highlighter for SlashComment {
+    fn spans(&self, row: &[char], _: usize) -> Vec<(Range<usize>, Syntax)> {
+        let mut spans = vec![];
+
+        let mut x = 0;
+        while x < row.len() {
+            match row[x] {
+                '"' => {
+                    let start = x;
+                    x += 1;
+                    while x < row.len() {
+                        match row[x] {
+                            '\\' => x += 2,
+                            '"' => {
+                                x += 1;
+                                break;
+                            }
+                            _ => x += 1,
+                        }
+                    }
+                    // The string not closed in the row lasts to end of row.
+                    x = x.min(row.len());
+                    spans.push((start..x, Syntax::String));
+                }
+                '/' if row.get(x + 1) == Some(&'/') => {
+                    spans.push((x..row.len(), Syntax::Comment));
+                    break;
+                }
+                _ => x += 1,
+            }
+        }
+
+        spans
+    }
+}
+
+/// Comment from the earliest of the prefixes to end of row, configured for the file type.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PrefixComment {
+    prefixes: Vec<String>,
+}
+
+impl PrefixComment {
+    pub fn new(prefixes: Vec<String>) -> Self {
+        let prefixes = prefixes.into_iter().filter(|p| !p.is_empty()).collect();
+        PrefixComment { prefixes }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.prefixes.is_empty()
+    }
+}
+
+impl Highlighter for PrefixComment {
+    fn spans(&self, row: &[char], _: usize) -> Vec<(Range<usize>, Syntax)> {
+        let starts_at = |x: usize| {
+            self.prefixes.iter().any(|prefix| {
+                let mut chars = row[x..].iter();
+                prefix.chars().all(|ch| chars.next() == Some(&ch))
+            })
+        };
+
+        match (0..row.len()).find(|&x| starts_at(x)) {
+            Some(x) => vec![(x..row.len(), Syntax::Comment)],
+            None => vec![],
+        }
+    }
+}
+
+/// No highlight, such as plain text.
+pub struct Plain;
+
+impl Highlighter for Plain {
+    fn spans(&self, _: &[char], _: usize) -> Vec<(Range<usize>, Syntax)> {
+        vec![]
+    }
+}
+
+/// Returns the highlighter by the file extension, or no highlight for unknown one.
+pub fn by_path(path: Option<&Path>) -> &'static dyn Highlighter {
+    let extension = path
+        .and_then(|p| p.extension())
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+
+    match extension.as_deref() {
+        Some("bash" | "conf" | "ps1" | "py" | "rb" | "sh" | "toml" | "yaml" | "yml") => {
+            &HashComment
+        }
+        Some(
+            "c" | "cc" | "cpp" | "cs" | "go" | "h" | "hpp" | "java" | "js" | "kt" | "rs" | "swift"
+            | "ts",
+        ) => &SlashComment,
+        _ => &Plain,
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spans(highlighter: &dyn Highlighter, row: &str) -> Vec<(Range<usize>, Syntax)> {
+        highlighter.spans(&row.chars().collect::<Vec<char>>(), 0)
+    }
+
+    #[test]
+    fn hash_comment_spans() {
+        assert_eq!(
+            vec![(4..9, Syntax::Comment)],
+            spans(&HashComment, "a = # b#c")
+        );
+        assert!(spans(&HashComment, "a = b").is_empty());
+    }
+
+    #[test]
+    fn slash_comment_spans() {
+        assert_eq!(
+            vec![(4..12, Syntax::String), (13..17, Syntax::Comment)],
+            spans(&SlashComment, "f = \"a\\\"//b\" // c")
+        );
+        assert_eq!(
+            vec![(2..5, Syntax::String)],
+            spans(&SlashComment, "a \"b\\")
+        );
+        assert!(spans(&SlashComment, "a / b").is_empty());
+    }
+
+    #[test]
+    fn prefix_comment_spans() {
+        let slash = PrefixComment::new(vec!["//".to_string()]);
+        assert_eq!(vec![(4..9, Syntax::Comment)], spans(&slash, "a/b //c//"));
+        assert!(spans(&slash, "a / b").is_empty());
+        assert!(spans(&slash, "a /").is_empty());
+
+        // The earliest prefix wins whichever is configured first.
+        let sql = PrefixComment::new(vec!["--".to_string(), ";".to_string()]);
+        assert_eq!(vec![(2..6, Syntax::Comment)], spans(&sql, "a ; --"));
+        assert_eq!(vec![(2..5, Syntax::Comment)], spans(&sql, "a --;"));
+        assert!(spans(&sql, "a - b").is_empty());
+
+        assert!(PrefixComment::new(vec![String::new()]).is_empty());
+    }
+
+    #[test]
+    fn highlighter_by_path() {
+        let row = "# a // b".chars().collect::<Vec<char>>();
+        let by = |path: Option<&str>| by_path(path.map(Path::new)).spans(&row, 0);
+
+        assert_eq!(vec![(0..8, Syntax::Comment)], by(Some("a.TOML")));
+        assert_eq!(vec![(4..8, Syntax::Comment)], by(Some("src/main.rs")));
+        assert!(by(Some("a.txt")).is_empty());
+        assert!(by(Some("Makefile")).is_empty());
+        assert!(by(None).is_empty());
+    }
+}
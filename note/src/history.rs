@@ -1,15 +1,42 @@
 use crate::buffer::Row;
-use crate::cursor::Coordinates;
 use crate::editor::SelectMode;
 
+/// Maximum number of characters stored inline in a text of the entries.
+const INLINE_CHARS: usize = 5;
+
+/// Undo history of the buffer. Short texts in the operations are stored inline.
 #[derive(Default)]
-pub struct History<P: Coordinates> {
-    entries: Vec<(P, Operation<P>)>,
+pub struct History {
+    entries: Vec<(Position, Operation<Text>)>,
+    undone: Vec<(Position, Operation<Text>)>,
+    /// Number of the entries at saving, or `None` if the saved content can not be restored.
+    saved_at: Option<usize>,
 }
 
-impl<P: Coordinates> History<P> {
+impl History {
+    /// Returns `true` if undo or redo has restored the content at saving.
+    pub fn at_saved(&self) -> bool {
+        self.saved_at == Some(self.entries.len())
+    }
+
     pub fn clear(&mut self) {
         self.entries.clear();
+        self.undone.clear();
+        self.saved_at = None;
+    }
+
+    /// Merge the entries after the index into a entry.
+    pub fn group(&mut self, start: usize) {
+        if start + 1 < self.entries.len() {
+            self.saved_at = match self.saved_at {
+                Some(saved) if saved == self.entries.len() => Some(start + 1),
+                Some(saved) if start < saved => None,
+                saved => saved,
+            };
+            let entries = self.entries.split_off(start);
+            let cursor = entries[0].0;
+            self.entries.push((cursor, Operation::Group(entries)));
+        }
     }
 
     pub fn is_empty(&self) -> bool {
@@ -20,27 +47,210 @@ impl<P: Coordinates> History<P> {
         self.entries.len()
     }
 
-    pub fn record(&mut self, cursor: P, op: Operation<P>) {
-        self.entries.push((cursor, op));
+    /// Record the current entries as the content at saving.
+    pub fn mark_saved(&mut self) {
+        self.saved_at = Some(self.entries.len());
+    }
+
+    /// Record the operation to undo. The undone operations can not be redone after this.
+    pub fn record(&mut self, cursor: Position, op: Operation) {
+        if self
+            .saved_at
+            .is_some_and(|saved| self.entries.len() < saved)
+        {
+            // The saved content is in the undone operations to be discarded.
+            self.saved_at = None;
+        }
+        self.entries.push((cursor, op.map_text(&Text::from)));
+        self.undone.clear();
+    }
+
+    /// Returns the last undone operation to redo.
+    pub fn redo(&mut self) -> Option<(Position, Operation)> {
+        self.undone
+            .pop()
+            .map(|(cursor, op)| (cursor, op.map_text(&Row::from)))
+    }
+
+    /// Record the operation to redo the rollback.
+    pub fn record_undone(&mut self, cursor: Position, op: Operation) {
+        self.undone.push((cursor, op.map_text(&Text::from)));
+    }
+
+    /// Record the operation to undo the redo, keeping the others to redo.
+    pub fn record_redone(&mut self, cursor: Position, op: Operation) {
+        self.entries.push((cursor, op.map_text(&Text::from)));
+    }
+
+    pub fn rollback(&mut self) -> Option<(Position, Operation)> {
+        self.entries
+            .pop()
+            .map(|(cursor, op)| (cursor, op.map_text(&Row::from)))
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+type Position = (usize, usize);
+
+/// Operation to undo. `T` is the type of the removed texts.
+pub enum Operation<T = Row> {
+    Append(Position),
+    DeleteChar(Position, char),
+    DeleteChars(Position, Vec<T>, SelectMode),
+    DeleteRow(Position, T),
+    Group(Vec<(Position, Operation<T>)>),
+    InsertChar(Position),
+    InsertChars(Position, Position, SelectMode),
+    InsertRow(Position),
+    Replace(Position, usize, T),
+    SetAllRows(Vec<T>),
+    ShrinkRow(Position, T),
+    SplitRow(Position),
+    SquashRow(Position),
+    SwapRows(Position, Position),
+}
+
+impl<T> Operation<T> {
+    /// Returns the operation with the texts converted by the function.
+    fn map_text<U>(self, f: &impl Fn(T) -> U) -> Operation<U> {
+        match self {
+            Operation::Append(cord) => Operation::Append(cord),
+            Operation::DeleteChar(cord, ch) => Operation::DeleteChar(cord, ch),
+            Operation::DeleteChars(cord, texts, mode) => {
+                Operation::DeleteChars(cord, texts.into_iter().map(f).collect(), mode)
+            }
+            Operation::DeleteRow(cord, text) => Operation::DeleteRow(cord, f(text)),
+            Operation::Group(entries) => Operation::Group(
+                entries
+                    .into_iter()
+                    .map(|(cur, op)| (cur, op.map_text(f)))
+                    .collect(),
+            ),
+            Operation::InsertChar(cord) => Operation::InsertChar(cord),
+            Operation::InsertChars(start, end, mode) => Operation::InsertChars(start, end, mode),
+            Operation::InsertRow(cord) => Operation::InsertRow(cord),
+            Operation::Replace(cord, length, text) => Operation::Replace(cord, length, f(text)),
+            Operation::SetAllRows(texts) => {
+                Operation::SetAllRows(texts.into_iter().map(f).collect())
+            }
+            Operation::ShrinkRow(cord, text) => Operation::ShrinkRow(cord, f(text)),
+            Operation::SplitRow(cord) => Operation::SplitRow(cord),
+            Operation::SquashRow(cord) => Operation::SquashRow(cord),
+            Operation::SwapRows(a, b) => Operation::SwapRows(a, b),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+/// Characters of a row, stored inline if short enough.
+enum Text {
+    Inline(u8, [char; INLINE_CHARS]),
+    Heap(Box<[char]>),
+}
+
+impl Text {
+    fn chars(&self) -> &[char] {
+        match self {
+            Text::Inline(len, chars) => &chars[..*len as usize],
+            Text::Heap(chars) => chars,
+        }
     }
+}
 
-    pub fn rollback(&mut self) -> Option<(P, Operation<P>)> {
-        self.entries.pop()
+impl From<Row> for Text {
+    fn from(value: Row) -> Self {
+        let column = value.column();
+        if column.len() <= INLINE_CHARS {
+            let mut chars = ['\0'; INLINE_CHARS];
+            chars[..column.len()].copy_from_slice(column);
+            Text::Inline(column.len() as u8, chars)
+        } else {
+            Text::Heap(column.into())
+        }
+    }
+}
+
+impl From<Text> for Row {
+    fn from(value: Text) -> Self {
+        Row::from(value.chars())
     }
 }
 
 // -----------------------------------------------------------------------------------------------
 
-pub enum Operation<P: Coordinates> {
-    Append(P),
-    DeleteChar(P, char),
-    DeleteChars(P, Vec<Row>, SelectMode),
-    DeleteRow(P, Row),
-    InsertChar(P),
-    InsertChars(P, P, SelectMode),
-    InsertRow(P),
-    Replace(P, usize, Row),
-    ShrinkRow(P, Row),
-    SplitRow(P),
-    SquashRow(P),
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem;
+
+    #[test]
+    fn history_text_size() {
+        assert!(mem::size_of::<Text>() <= mem::size_of::<Row>());
+        assert!(mem::size_of::<Operation<Text>>() <= mem::size_of::<Operation>());
+    }
+
+    #[test]
+    fn history_text_inline() {
+        assert!(matches!(Text::from(Row::from("")), Text::Inline(0, _)));
+        assert!(matches!(Text::from(Row::from("abcde")), Text::Inline(5, _)));
+        assert!(matches!(Text::from(Row::from("abcdef")), Text::Heap(_)));
+    }
+
+    #[test]
+    fn history_rollback_restores_text() {
+        let mut history = History::default();
+        history.record((0, 0), Operation::DeleteRow((0, 1), Row::from("ab")));
+        history.record((0, 0), Operation::DeleteRow((0, 2), Row::from("abcdefg")));
+        history.group(0);
+
+        let entries = match history.rollback() {
+            Some((_, Operation::Group(entries))) => entries,
+            _ => unreachable!(),
+        };
+        let texts = entries
+            .iter()
+            .map(|(_, op)| match op {
+                Operation::DeleteRow(_, row) => row.column().iter().collect::<String>(),
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["ab", "abcdefg"], texts);
+        assert!(history.is_empty());
+    }
+
+    #[test]
+    fn history_at_saved() {
+        let mut history = History::default();
+        history.record((0, 0), Operation::InsertChar((0, 0)));
+        history.mark_saved();
+        assert!(history.at_saved());
+
+        let (cursor, op) = history.rollback().unwrap();
+        history.record_undone(cursor, op);
+        assert!(!history.at_saved());
+
+        let (cursor, op) = history.redo().unwrap();
+        history.record_redone(cursor, op);
+        assert!(history.at_saved());
+
+        history.rollback();
+        history.record((0, 0), Operation::InsertChar((1, 0)));
+        assert!(!history.at_saved());
+        history.rollback();
+        assert!(!history.at_saved());
+    }
+
+    #[test]
+    fn history_at_saved_group() {
+        let mut history = History::default();
+        history.record((0, 0), Operation::InsertChar((0, 0)));
+        history.record((0, 0), Operation::InsertChar((1, 0)));
+        history.mark_saved();
+
+        history.group(0);
+
+        assert!(history.at_saved());
+    }
 }
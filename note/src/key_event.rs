@@ -20,6 +20,7 @@ impl From<WindowEvent> for Event {
 pub enum KeyEvent {
     // virtual key codes
     BackSpace,
+    Tab,
     Enter,
     Escape,
     End,
@@ -33,25 +34,67 @@ pub enum KeyEvent {
     Delete,
     F3,
     // ctrl modifier
+    Comment,
     Copy,
     Cut,
     DeleteRow,
+    DuplicateLine,
     Find,
     Exit,
+    Format,
     Goto,
+    HalfPageDown,
+    HalfPageUp,
+    JumpToMark(usize),
+    MatchBracket,
     Paste,
+    Redo,
     Replace,
     Save,
+    SelectAll,
+    SetMark(usize),
+    Sort,
     Undo,
     // other
     Char(char),
 }
 
+impl KeyEvent {
+    /// Returns the key bound to the control character typed with Ctrl.
+    pub fn from_control(code: u8) -> Option<Self> {
+        // https://doc.rust-lang.org/std/ascii/enum.Char.html
+        match code {
+            1 => Some(KeyEvent::Home),          // Ctrl+'A'
+            3 => Some(KeyEvent::Copy),          // Ctrl+'C'
+            4 => Some(KeyEvent::HalfPageDown),  // Ctrl+'D'
+            5 => Some(KeyEvent::End),           // Ctrl+'E'
+            6 => Some(KeyEvent::Find),          // Ctrl+'F'
+            7 => Some(KeyEvent::Goto),          // Ctrl+'G'
+            8 => Some(KeyEvent::Replace),       // Ctrl+'H'
+            11 => Some(KeyEvent::DeleteRow),    // Ctrl+'K'
+            14 => Some(KeyEvent::ArrowDown),    // Ctrl+'N'
+            16 => Some(KeyEvent::ArrowUp),      // Ctrl+'P'
+            17 => Some(KeyEvent::Exit),         // Ctrl+'Q'
+            18 => Some(KeyEvent::Format),       // Ctrl+'R'
+            19 => Some(KeyEvent::Save),         // Ctrl+'S'
+            20 => Some(KeyEvent::Sort),         // Ctrl+'T'
+            21 => Some(KeyEvent::HalfPageUp),   // Ctrl+'U'
+            22 => Some(KeyEvent::Paste),        // Ctrl+'V'
+            24 => Some(KeyEvent::Cut),          // Ctrl+'X'
+            25 => Some(KeyEvent::Redo),         // Ctrl+'Y'
+            26 => Some(KeyEvent::Undo),         // Ctrl+'Z'
+            29 => Some(KeyEvent::MatchBracket), // Ctrl+']'
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum KeyModifier {
     None,
     AltLeft,
     AltRight,
+    AltShift,
     CtrlLeft,
     CtrlRight,
     Shift,
@@ -1,17 +1,27 @@
+pub mod args;
 pub mod buffer;
+pub mod config;
 pub mod cursor;
 pub mod editor;
 pub mod error;
+pub mod escape;
+pub mod highlight;
 pub mod history;
 pub mod key_event;
+pub mod log;
+pub mod process;
 pub mod prompt;
 pub mod screen;
+pub mod snippet;
 pub mod terminal;
 
+#[cfg(unix)]
+mod unix;
+#[cfg(windows)]
 mod windows;
 
 // https://learn.microsoft.com/en-us/windows/console/char-info-str
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Color {
     Blue = 1,
     Green = 2,
@@ -20,4 +30,5 @@ pub enum Color {
     Magenta = 5,
     Yellow = 6,
     White = 7,
+    Gray = 8,
 }
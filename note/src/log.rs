@@ -0,0 +1,219 @@
+use std::env;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const LOG_DIRECTORY: &str = "note";
+const LOG_FILENAME: &str = "note.log";
+const MAX_LOG_SIZE: u64 = 1024 * 1024;
+
+static LOGGER: OnceLock<Option<Logger>> = OnceLock::new();
+static CONTEXT: Mutex<String> = Mutex::new(String::new());
+
+/// Write the error message to the log.
+#[macro_export]
+macro_rules! log_error {
+    ($($arg:tt)*) => {
+        $crate::log::write($crate::log::Level::Error, &format!($($arg)*))
+    };
+}
+
+/// Write the warning message to the log.
+#[macro_export]
+macro_rules! log_warn {
+    ($($arg:tt)*) => {
+        $crate::log::write($crate::log::Level::Warn, &format!($($arg)*))
+    };
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Level {
+    Error,
+    Warn,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Level::Error => write!(f, "ERROR"),
+            Level::Warn => write!(f, "WARN"),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+/// Append-only log file rotated by size.
+pub struct Logger {
+    path: PathBuf,
+    max_size: u64,
+    file: Mutex<Option<File>>,
+}
+
+impl Logger {
+    pub fn new(path: &Path, max_size: u64) -> Self {
+        Logger {
+            path: path.to_path_buf(),
+            max_size,
+            file: Mutex::new(None),
+        }
+    }
+
+    /// Returns the path of rotated log file.
+    pub fn rotated_path(&self) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(".1");
+        PathBuf::from(path)
+    }
+
+    pub fn write(&self, level: Level, message: &str) -> Result<(), io::Error> {
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+
+        let size = fs::metadata(&self.path).map(|m| m.len()).unwrap_or(0);
+        if self.max_size <= size {
+            *file = None;
+            fs::rename(&self.path, self.rotated_path())?;
+        }
+
+        if file.is_none() {
+            let f = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+            *file = Some(f);
+        }
+
+        let line = format_line(SystemTime::now(), level, message);
+        file.as_mut().unwrap().write_all(line.as_bytes())
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+/// Returns a log line as `YYYY-MM-DDThh:mm:ssZ LEVEL message`.
+pub fn format_line(time: SystemTime, level: Level, message: &str) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, rest) = (secs / 86400, secs % 86400);
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z {} {}\n",
+        year,
+        month,
+        day,
+        rest / 3600,
+        rest % 3600 / 60,
+        rest % 60,
+        level,
+        message.trim_end()
+    )
+}
+
+/// Set the editor state written with panic message.
+pub fn set_context(context: String) {
+    *CONTEXT.lock().unwrap_or_else(|e| e.into_inner()) = context;
+}
+
+/// Write panic message and editor state to the log before default panic hook.
+pub fn set_panic_hook() {
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let context = CONTEXT.lock().map(|c| c.clone()).unwrap_or_default();
+        write(Level::Error, &format!("{} ({})", info, context));
+        hook(info);
+    }));
+}
+
+/// Write the message to `%APPDATA%\note\note.log`.
+/// Nothing is written if the file is not writable.
+pub fn write(level: Level, message: &str) {
+    let logger = LOGGER.get_or_init(|| {
+        let directory = PathBuf::from(env::var_os("APPDATA")?).join(LOG_DIRECTORY);
+        fs::create_dir_all(&directory).ok()?;
+        Some(Logger::new(&directory.join(LOG_FILENAME), MAX_LOG_SIZE))
+    });
+
+    if let Some(logger) = logger {
+        let _ = logger.write(level, message);
+    }
+}
+
+// https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+// -----------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn format_line_epoch() {
+        let line = format_line(UNIX_EPOCH, Level::Error, "a");
+
+        assert_eq!("1970-01-01T00:00:00Z ERROR a\n", line);
+    }
+
+    #[test]
+    fn format_line_time() {
+        let time = UNIX_EPOCH + Duration::from_secs(1709251199);
+        let line = format_line(time, Level::Warn, "b\r\n");
+
+        assert_eq!("2024-02-29T23:59:59Z WARN b\n", line);
+    }
+
+    #[test]
+    fn logger_write() {
+        let directory = env::temp_dir().join("note_logger_write");
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("note.log");
+
+        let logger = Logger::new(&path, 1024);
+        logger.write(Level::Error, "a").unwrap();
+        logger.write(Level::Warn, "b").unwrap();
+
+        let text = fs::read_to_string(&path).unwrap();
+        let lines = text.lines().collect::<Vec<&str>>();
+        assert_eq!(2, lines.len());
+        assert!(lines[0].ends_with("Z ERROR a"));
+        assert!(lines[1].ends_with("Z WARN b"));
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn logger_rotate() {
+        let directory = env::temp_dir().join("note_logger_rotate");
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("note.log");
+
+        let logger = Logger::new(&path, 30);
+        logger.write(Level::Error, "first").unwrap();
+        logger.write(Level::Error, "second").unwrap();
+        logger.write(Level::Error, "third").unwrap();
+
+        let rotated = fs::read_to_string(logger.rotated_path()).unwrap();
+        assert!(rotated.ends_with("ERROR second\n"));
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(text.ends_with("ERROR third\n"));
+        assert_eq!(1, text.lines().count());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
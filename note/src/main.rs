@@ -1,22 +1,58 @@
-use note::editor::Editor;
+use note::args::Args;
+use note::config::Config;
+use note::cursor::AsCoordinates;
+use note::editor::{Editor, Exit};
 use note::error::Error;
-use note::terminal::{Terminal, WindowsCon};
+use note::log;
+use note::log_error;
+use note::terminal::Terminal;
+#[cfg(unix)]
+use note::terminal::UnixTty as Console;
+#[cfg(windows)]
+use note::terminal::WindowsCon as Console;
 use std::env;
-use std::path::PathBuf;
+use std::process::ExitCode;
 
-fn main() -> Result<(), Error> {
-    let filename = env::args().nth(1).map(PathBuf::from);
+const EXIT_CODE_ERROR: u8 = 2;
 
-    let mut terminal = WindowsCon {};
+fn main() -> ExitCode {
+    log::set_panic_hook();
+
+    match run() {
+        Ok(exit) => ExitCode::from(exit.code()),
+        Err(e) => {
+            log_error!("{:?}", e);
+            eprintln!("Error: {:?}", e);
+            ExitCode::from(EXIT_CODE_ERROR)
+        }
+    }
+}
+
+fn run() -> Result<Exit, Error> {
+    let args = Args::parse(env::args().skip(1))?;
+
+    // Console is restored when the editor is dropped.
+    let mut terminal = Console::default();
     terminal.alternate_screen_buffer()?;
     terminal.enable_raw_mode()?;
 
-    let mut editor = Editor::new(filename.as_deref(), terminal)?;
+    let mut editor = Editor::new(args.filename(), terminal)?;
+    editor.set_config(Config::load());
+    editor.set_abort_on_discard(args.abort_on_discard());
 
     editor.init()?;
 
     loop {
-        editor.handle_events()?;
+        if let Some(exit) = editor.handle_events()? {
+            return Ok(exit);
+        }
         editor.refresh()?;
+
+        log::set_context(format!(
+            "filename={:?} cursor={:?} rows={}",
+            editor.content().filename(),
+            editor.cursor().as_coordinates(),
+            editor.content().rows()
+        ));
     }
 }
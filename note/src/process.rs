@@ -0,0 +1,120 @@
+use crate::buffer::Row;
+use crate::config::Formatter;
+use crate::error::Error;
+use std::io::{BufRead, Write};
+use std::process::{self, Command, Stdio};
+use std::thread;
+
+/// Output of the external command.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Output {
+    pub success: bool,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+impl From<process::Output> for Output {
+    fn from(value: process::Output) -> Self {
+        Output {
+            success: value.status.success(),
+            stdout: value.stdout,
+            stderr: value.stderr,
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+/// Returns the rows of stdout, or the error with stderr if the command failed.
+pub fn parse_output(output: &Output) -> Result<Vec<Row>, Error> {
+    if !output.success {
+        let message = String::from_utf8_lossy(&output.stderr);
+        return Err(Error::Process(message.trim().to_string()));
+    }
+
+    let mut rows = vec![];
+    for line in output.stdout.as_slice().lines() {
+        rows.push(Row::from(line?));
+    }
+    Ok(rows)
+}
+
+/// Run the formatter with the text as stdin.
+pub fn run(formatter: &Formatter, input: &str) -> Result<Output, Error> {
+    let mut child = Command::new(formatter.program())
+        .args(formatter.args())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // Write from other thread not to block when stdout pipe is full.
+    let mut stdin = child.stdin.take().unwrap();
+    let input = input.to_string();
+    let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+
+    let output = child.wait_with_output()?;
+    // The command may exit without reading all input.
+    let _ = writer.join();
+
+    Ok(Output::from(output))
+}
+
+// -----------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(success: bool, stdout: &str, stderr: &str) -> Output {
+        Output {
+            success,
+            stdout: stdout.as_bytes().to_vec(),
+            stderr: stderr.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn parse_output_rows() {
+        let rows = parse_output(&output(true, "fn a() {}\r\n\nb\n", "")).unwrap();
+
+        assert_eq!(3, rows.len());
+        assert_eq!("fn a() {}", rows[0].to_string_at(0));
+        assert_eq!("", rows[1].to_string_at(0));
+        assert_eq!("b", rows[2].to_string_at(0));
+    }
+
+    #[test]
+    fn parse_output_no_newline() {
+        let rows = parse_output(&output(true, "a\nb", "")).unwrap();
+
+        assert_eq!(2, rows.len());
+        assert_eq!("b", rows[1].to_string_at(0));
+    }
+
+    #[test]
+    fn parse_output_empty() {
+        let rows = parse_output(&output(true, "", "")).unwrap();
+
+        assert!(rows.is_empty());
+    }
+
+    #[test]
+    fn parse_output_failure() {
+        match parse_output(&output(false, "a\n", "error: expected `;`\n")) {
+            Err(Error::Process(message)) => assert_eq!("error: expected `;`", message),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn parse_output_invalid_utf8() {
+        let out = Output {
+            success: true,
+            stdout: vec![0xFF, b'\n'],
+            stderr: vec![],
+        };
+
+        assert!(matches!(parse_output(&out), Err(Error::Io(_))));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::buffer::{Buffer, Row};
+use crate::buffer::{Buffer, Row, TAB_STOP};
 use crate::cursor::{Coordinates, Cursor};
 use crate::editor::Select;
 use crate::error::Error;
@@ -6,8 +6,18 @@ use crate::key_event::{Event, KeyEvent, KeyModifier, WindowEvent};
 use crate::screen::{refresh_screen, resize_screen, MessageBar, Screen, StatusBar};
 use crate::terminal::Terminal;
 use crate::Color;
+use regex::{Regex, RegexBuilder};
 use std::cmp::min;
 
+const MIN_INPUT_WIDTH: usize = 10;
+
+const TEXT_FIND_IGNORE_CASE: &str = "[i] ";
+const TEXT_FIND_REGEX: &str = "Regex ";
+const TEXT_FIND_TABS_EXPANDED: &str = "[t] ";
+const TEXT_REPLACE_FAILED: &str = "Could not replace here. ";
+
+const REPLACE_OPTIONS: &[(char, &str)] = &[('y', "yes"), ('n', "no"), ('a', "all")];
+
 pub enum KeyInput {
     Ok,
     Continue,
@@ -28,25 +38,82 @@ pub trait Prompt<T: Terminal> {
 
     fn cursor_mut(&mut self) -> &mut Cursor;
 
+    /// Returns the key of the option chosen by a single keypress, or `None` by Escape.
+    /// Other keys are ignored.
+    fn handle_choice(
+        &mut self,
+        message: &str,
+        options: &[(char, &str)],
+    ) -> Result<Option<char>, Error> {
+        let message = choice_message(message, options);
+
+        let mut prompt = self.message().clone();
+        prompt.set_fg_color(Color::Cyan);
+        prompt.set_message(message.clone());
+        prompt.draw(self.terminal_mut())?;
+
+        loop {
+            match self.read_event_timeout()? {
+                Event::Key(KeyEvent::Escape, _) => {
+                    self.return_editor(None)?;
+                    return Ok(None);
+                }
+                Event::Key(KeyEvent::Char(ch), _) => {
+                    let key = options.iter().find(|(k, _)| k.eq_ignore_ascii_case(&ch));
+                    if let Some(&(key, _)) = key {
+                        self.return_editor(None)?;
+                        return Ok(Some(key));
+                    }
+                }
+                Event::Window(WindowEvent::Resize) => {
+                    self.resize_screen(&mut prompt, &[])?;
+                    prompt.set_message(message.clone());
+                    prompt.draw(self.terminal_mut())?;
+                }
+                _ => {}
+            }
+        }
+    }
+
     fn handle_events(
         &mut self,
         message: &str,
         value: Option<&str>,
     ) -> Result<Option<String>, Error> {
+        // The caret may be hidden by the software cursor.
+        self.terminal_mut().set_cursor_visible(true)?;
+
         let mut prompt = self.message().clone();
         prompt.set_fg_color(Color::Cyan);
-        prompt.set_message(Row::from(message));
+        prompt.set_message(elide_message(&self.label(message), self.screen().width()));
 
         prompt.draw(self.terminal_mut())?;
         let (mut prompt_x, mut prompt_y) = self.terminal_mut().get_cursor_position()?;
 
+        // Default value is accepted with Enter, or cleared by input.
         let mut chars = value.map(Row::from).unwrap_or_default();
-        chars.truncate_width(self.screen().width() - prompt_x - 1);
-        self.terminal_mut()
-            .write(prompt_x, prompt_y, chars.column(), Color::White, false)?;
+        let mut provisional = !chars.is_empty();
+        let window = input_window(&chars, self.screen().width().saturating_sub(prompt_x + 1));
+        self.terminal_mut().write(
+            prompt_x,
+            prompt_y,
+            window.column(),
+            input_color(provisional),
+            false,
+        )?;
 
-        let mut event = self.read_event_timeout()?;
+        let mut event = input_event(self.read_event_timeout()?, self.insert_tab());
+        let mut flashed = false;
         while match event {
+            Event::Key(KeyEvent::BackSpace, _) if provisional => {
+                provisional = false;
+                chars.clear();
+                match self.handle_input_event(chars.column())? {
+                    KeyInput::Ok => false,
+                    KeyInput::Continue => true,
+                    KeyInput::Cancel => return self.return_editor(None),
+                }
+            }
             Event::Key(KeyEvent::BackSpace, _) => {
                 if !chars.is_empty() {
                     chars.remove(chars.len() - 1);
@@ -61,7 +128,21 @@ pub trait Prompt<T: Terminal> {
             }
             Event::Key(KeyEvent::Enter, _) => false,
             Event::Key(KeyEvent::Escape, _) => return self.return_editor(None),
-            Event::Key(KeyEvent::Char(ch), _) if !ch.is_ascii_control() => {
+            // Alt+key is a command of the prompt, not input.
+            Event::Key(KeyEvent::Char(_), KeyModifier::AltLeft) => {
+                match self.handle_event(&event, chars.column())? {
+                    KeyInput::Ok => false,
+                    KeyInput::Continue => true,
+                    KeyInput::Cancel => return self.return_editor(None),
+                }
+            }
+            Event::Key(KeyEvent::Char(ch), _)
+                if !ch.is_ascii_control() || (ch == '\t' && self.insert_tab()) =>
+            {
+                if provisional {
+                    provisional = false;
+                    chars.clear();
+                }
                 chars.insert(chars.len(), ch);
                 match self.handle_input_event(chars.column())? {
                     KeyInput::Ok => false,
@@ -69,28 +150,65 @@ pub trait Prompt<T: Terminal> {
                     KeyInput::Cancel => return self.return_editor(None),
                 }
             }
+            // Other control characters are rejected.
+            Event::Key(KeyEvent::Char(_), _) => true,
             Event::Key(..) => match self.handle_event(&event, chars.column())? {
                 KeyInput::Ok => false,
                 KeyInput::Continue => true,
                 KeyInput::Cancel => return self.return_editor(None),
             },
             Event::Window(WindowEvent::Resize) => {
-                (prompt_x, prompt_y) = self.resize_screen(&mut prompt, chars.column())?;
+                self.resize_screen(&mut prompt, chars.column())?;
+                prompt.set_message(elide_message(&self.label(message), self.screen().width()));
+                prompt.draw(self.terminal_mut())?;
+                (prompt_x, prompt_y) = self.terminal_mut().get_cursor_position()?;
                 true
             }
         } {
             self.callback_event(&event, &mut chars)?;
 
+            // Flash the prompt until next key if the character is rejected.
+            let flash = match event {
+                Event::Key(KeyEvent::Char(ch), _) => {
+                    ch.is_ascii_control() && !(ch == '\t' && self.insert_tab())
+                }
+                _ => false,
+            };
+            if flash || flashed {
+                prompt.set_fg_color(if flash { Color::Red } else { Color::Cyan });
+            }
+            flashed = flash;
+
+            let label = elide_message(&self.label(message), self.screen().width());
+            if label.column() != prompt.message().column() {
+                prompt.set_message(label);
+                prompt.draw(self.terminal_mut())?;
+                (prompt_x, prompt_y) = self.terminal_mut().get_cursor_position()?;
+            }
+
             prompt.draw(self.terminal_mut())?;
-            chars.truncate_width(self.screen().width() - prompt_x - 1);
-            self.terminal_mut()
-                .write(prompt_x, prompt_y, chars.column(), Color::White, false)?;
-            event = self.read_event_timeout()?;
+            let window = input_window(&chars, self.screen().width().saturating_sub(prompt_x + 1));
+            self.terminal_mut().write(
+                prompt_x,
+                prompt_y,
+                window.column(),
+                input_color(provisional),
+                false,
+            )?;
+            if let Some((x, y)) = self.caret() {
+                self.terminal_mut().set_cursor_position(x, y)?;
+            }
+            event = input_event(self.read_event_timeout()?, self.insert_tab());
         }
 
         self.return_editor(Some(chars))
     }
 
+    /// Returns the position in terminal to place the caret instead of the end of input.
+    fn caret(&self) -> Option<(usize, usize)> {
+        None
+    }
+
     #[allow(unused_variables)]
     fn handle_event(&mut self, event: &Event, chars: &[char]) -> Result<KeyInput, Error> {
         Ok(KeyInput::Continue)
@@ -101,6 +219,16 @@ pub trait Prompt<T: Terminal> {
         Ok(KeyInput::Continue)
     }
 
+    /// Returns whether a tab is inserted into the input.
+    fn insert_tab(&self) -> bool {
+        false
+    }
+
+    /// Returns the message of prompt with the state of input.
+    fn label(&self, message: &str) -> String {
+        message.to_string()
+    }
+
     fn message(&self) -> &MessageBar;
 
     fn message_mut(&mut self) -> &mut MessageBar;
@@ -315,24 +443,18 @@ impl<'a, T: Terminal> YesNo<'a, T> {
         }
     }
 
-    pub fn confirm(&mut self, message: &str) -> Result<bool, Error> {
-        while let Some(yes_no) = self.handle_events(message, None)? {
-            let answer = yes_no.to_ascii_lowercase();
-
-            if answer == "y" || answer == "yes" {
-                return Ok(true);
-            }
-
-            if answer == "n" || answer == "no" {
-                return Ok(false);
-            }
-
-            if answer.is_empty() {
-                return Ok(false);
-            }
-        }
+    /// Returns the key of the chosen option, or `None` if canceled.
+    pub fn choose(
+        &mut self,
+        message: &str,
+        options: &[(char, &str)],
+    ) -> Result<Option<char>, Error> {
+        self.handle_choice(message, options)
+    }
 
-        Ok(false)
+    pub fn confirm(&mut self, message: &str) -> Result<bool, Error> {
+        let answer = self.choose(message, &[('y', "yes"), ('n', "no")])?;
+        Ok(answer == Some('y'))
     }
 }
 
@@ -346,9 +468,20 @@ pub struct FindKeyword<'a, T: Terminal> {
     message: &'a mut MessageBar,
     terminal: &'a mut T,
     source: Cursor,
+    caret: Option<(usize, usize)>,
+    painted: bool,
+    regex: bool,
+    ignore_case: bool,
+    tabs_expanded: bool,
+    pattern: Option<(String, Result<Regex, String>)>,
 }
 
 impl<'a, T: Terminal> Prompt<T> for FindKeyword<'a, T> {
+    /// The caret is at the match moved by F3 until the input is changed.
+    fn caret(&self) -> Option<(usize, usize)> {
+        self.caret
+    }
+
     fn content(&self) -> &Buffer {
         self.content
     }
@@ -410,6 +543,20 @@ impl<'a, T: Terminal> Prompt<T> for FindKeyword<'a, T> {
                 self.move_previous_keyword(&keyword)?;
                 Ok(KeyInput::Continue)
             }
+            Event::Key(KeyEvent::Format, _) => {
+                // Ctrl+R toggles the regex mode.
+                self.regex = !self.regex;
+                self.handle_input_event(chars)
+            }
+            Event::Key(KeyEvent::Char('c'), KeyModifier::AltLeft) => {
+                self.ignore_case = !self.ignore_case;
+                self.pattern = None;
+                self.handle_input_event(chars)
+            }
+            Event::Key(KeyEvent::Char('t'), KeyModifier::AltLeft) => {
+                self.tabs_expanded = !self.tabs_expanded;
+                self.handle_input_event(chars)
+            }
             _ => Ok(KeyInput::Continue),
         }
     }
@@ -417,15 +564,43 @@ impl<'a, T: Terminal> Prompt<T> for FindKeyword<'a, T> {
     fn handle_input_event(&mut self, chars: &[char]) -> Result<KeyInput, Error> {
         let keyword = Row::from(chars);
         if keyword.is_empty() {
+            self.pattern = None;
             self.cursor.set(self.content, &(0, 0));
             self.clear_screen()?;
         } else {
             self.incremental_keyword(&keyword)?;
         }
 
+        self.caret = None;
         Ok(KeyInput::Continue)
     }
 
+    fn insert_tab(&self) -> bool {
+        true
+    }
+
+    /// The regex mode and ignoring case are shown, with the error of the pattern if invalid.
+    fn label(&self, message: &str) -> String {
+        let case = if self.ignore_case {
+            TEXT_FIND_IGNORE_CASE
+        } else {
+            ""
+        };
+        if !self.regex {
+            let tabs = if self.tabs_expanded {
+                TEXT_FIND_TABS_EXPANDED
+            } else {
+                ""
+            };
+            return format!("{}{}{}", case, tabs, message);
+        }
+
+        match &self.pattern {
+            Some((_, Err(e))) => format!("{}{}{}: ", TEXT_FIND_REGEX, case, e),
+            _ => format!("{}{}{}", TEXT_FIND_REGEX, case, message),
+        }
+    }
+
     fn message(&self) -> &MessageBar {
         self.message
     }
@@ -449,6 +624,7 @@ impl<'a, T: Terminal> Prompt<T> for FindKeyword<'a, T> {
         )?;
 
         self.incremental_keyword(&Row::from(chars))?;
+        self.caret = None;
 
         Ok(pos)
     }
@@ -492,22 +668,115 @@ impl<'a, T: Terminal> FindKeyword<'a, T> {
             message,
             terminal,
             source,
+            caret: None,
+            painted: false,
+            regex: false,
+            ignore_case: false,
+            tabs_expanded: false,
+            pattern: None,
         }
     }
 
+    /// Returns whether the rows of screen are drawn over by this prompt.
+    pub fn painted(&self) -> bool {
+        self.painted
+    }
+
+    /// Returns whether the keyword is searched ignoring case.
+    pub fn ignore_case(&self) -> bool {
+        self.ignore_case
+    }
+
+    /// Returns whether the keyword is searched in the rows with tabs expanded to spaces.
+    pub fn tabs_expanded(&self) -> bool {
+        self.tabs_expanded
+    }
+
+    /// Returns whether the keyword is searched as regex.
+    pub fn regex(&self) -> bool {
+        self.regex
+    }
+
     pub fn source(&self) -> &Cursor {
         &self.source
     }
 
     fn clear_screen(&mut self) -> Result<(), Error> {
+        self.painted = true;
         draw_screen(self.content, self.screen, self.terminal)?;
         draw_status(self.cursor, self.status, self.terminal)?;
         Ok(())
     }
 
+    /// Compile the keyword as regex only if changed.
+    fn compile(&mut self, keyword: &Row) -> Option<Regex> {
+        let pattern = keyword.to_string_at(0);
+        if self.pattern.as_ref().map(|(p, _)| p) != Some(&pattern) {
+            // The last line of error is the reason without the pattern.
+            let regex = RegexBuilder::new(&pattern)
+                .case_insensitive(self.ignore_case)
+                .build()
+                .map_err(|e| e.to_string().lines().last().unwrap_or_default().to_string());
+            self.pattern = Some((pattern, regex));
+        }
+
+        match &self.pattern {
+            Some((_, Ok(regex))) => Some(regex.clone()),
+            _ => None,
+        }
+    }
+
+    /// Returns the match at or after the position, and the matched text.
+    fn find_match(&mut self, at: &Cursor, keyword: &Row) -> Option<((usize, usize), Row)> {
+        if !self.regex && self.tabs_expanded {
+            let keyword_text = keyword.to_string_at(0);
+            return self
+                .content
+                .find_expanded_at(at, &keyword_text, self.ignore_case)
+                .map(|(at, len)| (at, matched_text(self.content, &at, len)));
+        }
+
+        if !self.regex {
+            return find_at(at, self.content, keyword, self.ignore_case)
+                .map(|at| (at, keyword.clone()));
+        }
+
+        let found = self
+            .compile(keyword)
+            .and_then(|r| self.content.find_regex_at(at, &r));
+        found.map(|(at, len)| (at, matched_text(self.content, &at, len)))
+    }
+
+    /// Returns the match at or before the position, and the matched text.
+    fn rfind_match(&mut self, at: &Cursor, keyword: &Row) -> Option<((usize, usize), Row)> {
+        if !self.regex && self.tabs_expanded {
+            let keyword_text = keyword.to_string_at(0);
+            return self
+                .content
+                .rfind_expanded_at(at, &keyword_text, self.ignore_case)
+                .map(|(at, len)| (at, matched_text(self.content, &at, len)));
+        }
+
+        if !self.regex {
+            let keyword_text = keyword.to_string_at(0);
+            let found = if self.ignore_case {
+                self.content.rfind_at_icase(at, &keyword_text)
+            } else {
+                self.content.rfind_at(at, &keyword_text)
+            };
+            return found.map(|at| (at, keyword.clone()));
+        }
+
+        let found = self
+            .compile(keyword)
+            .and_then(|r| self.content.rfind_regex_at(at, &r));
+        found.map(|(at, len)| (at, matched_text(self.content, &at, len)))
+    }
+
     fn incremental_keyword(&mut self, keyword: &Row) -> Result<(), Error> {
-        if let Some(at) = find_at(self.cursor, self.content, keyword) {
-            self.mark_match(&at, keyword)?;
+        let cursor = self.cursor.clone();
+        if let Some((at, matched)) = self.find_match(&cursor, keyword) {
+            self.mark_match(&at, &matched)?;
         } else {
             self.clear_screen()?;
         }
@@ -517,6 +786,11 @@ impl<'a, T: Terminal> FindKeyword<'a, T> {
     fn mark_match<P: Coordinates>(&mut self, cursor: &P, keyword: &Row) -> Result<(), Error> {
         move_screen(self.cursor, cursor, self.content, self.screen, keyword);
         self.clear_screen()?;
+        // The other matches on the screen are highlighted too, if searched literally.
+        if !self.regex && !self.tabs_expanded {
+            self.screen
+                .decorate(self.content, keyword, self.ignore_case, self.terminal)?;
+        }
         set_text_attribute(
             self.cursor,
             self.content,
@@ -524,20 +798,28 @@ impl<'a, T: Terminal> FindKeyword<'a, T> {
             self.terminal,
             keyword,
         )?;
+        let render = self.cursor.render(self.content);
+        self.caret = Some(self.screen.position(&render));
         Ok(())
     }
 
     fn move_next_keyword(&mut self, keyword: &Row) -> Result<(), Error> {
-        if let Some(at) = find_next_at(self.cursor, self.content, keyword) {
-            self.mark_match(&at, keyword)?;
+        let mut c = self.cursor.clone();
+        c.move_right(self.content);
+
+        if let Some((at, matched)) = self.find_match(&c, keyword) {
+            self.mark_match(&at, &matched)?;
         }
 
         Ok(())
     }
 
     fn move_previous_keyword(&mut self, keyword: &Row) -> Result<(), Error> {
-        if let Some(at) = rfind_next_at(self.cursor, self.content, keyword) {
-            self.mark_match(&at, keyword)?;
+        let mut c = self.cursor.clone();
+        c.move_left(self.content);
+
+        if let Some((at, matched)) = self.rfind_match(&c, keyword) {
+            self.mark_match(&at, &matched)?;
         }
 
         Ok(())
@@ -555,6 +837,8 @@ pub struct Replace<'a, T: Terminal> {
     terminal: &'a mut T,
     source: Cursor,
     keywords: Option<(Row, Row)>,
+    painted: bool,
+    ignore_case: bool,
 }
 
 impl<'a, T: Terminal> Prompt<T> for Replace<'a, T> {
@@ -574,31 +858,19 @@ impl<'a, T: Terminal> Prompt<T> for Replace<'a, T> {
         self.cursor
     }
 
-    fn callback_event(&mut self, _: &Event, chars: &mut Row) -> Result<(), Error> {
-        if self.keywords.is_some() {
-            chars.clear();
+    fn handle_event(&mut self, event: &Event, _chars: &[char]) -> Result<KeyInput, Error> {
+        if let Event::Key(KeyEvent::Char('c'), KeyModifier::AltLeft) = event {
+            self.ignore_case = !self.ignore_case;
         }
-
-        Ok(())
+        Ok(KeyInput::Continue)
     }
 
-    fn handle_input_event(&mut self, chars: &[char]) -> Result<KeyInput, Error> {
-        if let Some((source, replaced)) = self.keywords.clone() {
-            match chars.iter().collect::<String>().as_str() {
-                "y" => {
-                    self.content
-                        .replace(self.cursor, source.len(), replaced.column());
-                }
-                "n" => {}
-                _ => return Ok(KeyInput::Continue),
-            }
-
-            if !self.move_next_keyword(&source)? {
-                return Ok(KeyInput::Cancel);
-            }
+    fn label(&self, message: &str) -> String {
+        if self.ignore_case {
+            format!("{}{}", TEXT_FIND_IGNORE_CASE, message)
+        } else {
+            message.to_string()
         }
-
-        Ok(KeyInput::Continue)
     }
 
     fn message(&self) -> &MessageBar {
@@ -670,9 +942,26 @@ impl<'a, T: Terminal> Replace<'a, T> {
             terminal,
             source,
             keywords: None,
+            painted: false,
+            ignore_case: false,
         }
     }
 
+    /// Returns whether the keyword is searched ignoring case.
+    pub fn ignore_case(&self) -> bool {
+        self.ignore_case
+    }
+
+    /// Returns the keyword to replace.
+    pub fn keyword(&self) -> Option<&Row> {
+        self.keywords.as_ref().map(|k| &k.0)
+    }
+
+    /// Returns whether the rows of screen are drawn over by this prompt.
+    pub fn painted(&self) -> bool {
+        self.painted
+    }
+
     pub fn replace(&mut self, message: &str, value: Option<&str>) -> Result<(), Error> {
         let mut esc_at = self.source.clone();
 
@@ -682,8 +971,34 @@ impl<'a, T: Terminal> Replace<'a, T> {
                 self.keywords = Some((source.clone(), replaced.clone()));
 
                 if self.move_keyword_at_current(&source)? {
-                    let msg = format!("{}{} (y/n): ", &msg, &replaced.to_string_at(0));
-                    while self.handle_events(&msg, None)?.is_some() {}
+                    let msg = format!("{}{}", &msg, &replaced.to_string_at(0));
+                    let mut failed = false;
+                    while let Some(answer) = self.handle_choice(
+                        &format!("{}{}", if failed { TEXT_REPLACE_FAILED } else { "" }, msg),
+                        REPLACE_OPTIONS,
+                    )? {
+                        if answer == 'a' {
+                            self.replace_all(&source, &replaced);
+                            break;
+                        }
+
+                        failed = false;
+                        if answer == 'y' {
+                            let at = (self.cursor.x(), self.cursor.y());
+                            match self.content.replace(&at, source.len(), replaced.column()) {
+                                Some(_) => {
+                                    // The next match is searched after the replaced text.
+                                    let last = (at.0 + replaced.len() - 1, at.1);
+                                    self.cursor.set(self.content, &last);
+                                }
+                                None => failed = true,
+                            }
+                        }
+
+                        if !self.move_next_keyword(&source)? {
+                            break;
+                        }
+                    }
 
                     esc_at = self.cursor.clone();
                 }
@@ -695,11 +1010,31 @@ impl<'a, T: Terminal> Replace<'a, T> {
     }
 
     fn clear_screen(&mut self) -> Result<(), Error> {
+        self.painted = true;
         draw_screen(self.content, self.screen, self.terminal)?;
         draw_status(self.cursor, self.status, self.terminal)?;
         Ok(())
     }
 
+    /// Replace the current and following matches to the end of buffer as a undo step.
+    /// The cursor moves to the last replaced.
+    fn replace_all(&mut self, source: &Row, replaced: &Row) {
+        let ignore_case = self.ignore_case;
+        let mut at = (self.cursor.x(), self.cursor.y());
+
+        self.content.record_group(|content| {
+            content.replace(&at, source.len(), replaced.column());
+            while let Some(next) =
+                find_at(&(at.0 + replaced.len(), at.1), content, source, ignore_case)
+            {
+                at = next;
+                content.replace(&at, source.len(), replaced.column());
+            }
+        });
+
+        self.cursor.set(self.content, &at);
+    }
+
     fn input(&mut self, message: &str, value: Option<&str>) -> Result<Option<Row>, Error> {
         while let Some(value) = self.handle_events(message, value)? {
             if value.is_empty() {
@@ -727,7 +1062,7 @@ impl<'a, T: Terminal> Replace<'a, T> {
     }
 
     fn move_first_keyword(&mut self, keyword: &Row) -> Result<bool, Error> {
-        if let Some(at) = find_at(&Cursor::default(), self.content, keyword) {
+        if let Some(at) = find_at(&(0, 0), self.content, keyword, self.ignore_case) {
             self.mark_match(&at, keyword)?;
             Ok(true)
         } else {
@@ -736,7 +1071,7 @@ impl<'a, T: Terminal> Replace<'a, T> {
     }
 
     fn move_keyword_at_current(&mut self, keyword: &Row) -> Result<bool, Error> {
-        if let Some(at) = find_at(self.cursor, self.content, keyword) {
+        if let Some(at) = find_at(self.cursor, self.content, keyword, self.ignore_case) {
             self.mark_match(&at, keyword)?;
             Ok(true)
         } else {
@@ -745,7 +1080,7 @@ impl<'a, T: Terminal> Replace<'a, T> {
     }
 
     fn move_next_keyword(&mut self, keyword: &Row) -> Result<bool, Error> {
-        if let Some(at) = find_next_at(self.cursor, self.content, keyword) {
+        if let Some(at) = find_next_at(self.cursor, self.content, keyword, self.ignore_case) {
             self.mark_match(&at, keyword)?;
             Ok(true)
         } else {
@@ -756,6 +1091,15 @@ impl<'a, T: Terminal> Replace<'a, T> {
 
 // -----------------------------------------------------------------------------------------------
 
+/// Returns the message followed by the options as `message (y:yes n:no ESC:quit): `.
+fn choice_message(message: &str, options: &[(char, &str)]) -> Row {
+    let options = options
+        .iter()
+        .map(|(key, label)| format!("{}:{} ", key, label))
+        .collect::<String>();
+    Row::from(format!("{} ({}ESC:quit): ", message, options))
+}
+
 fn draw_screen<T: Terminal>(
     content: &Buffer,
     screen: &mut Screen,
@@ -778,18 +1122,86 @@ fn draw_status<T: Terminal>(
     Ok(())
 }
 
-fn find_at(cursor: &Cursor, content: &Buffer, keyword: &Row) -> Option<(usize, usize)> {
-    content.find_at(cursor, &keyword.to_string_at(0))
+/// Returns the message truncated to keep minimum width for input.
+fn elide_message(message: &str, width: usize) -> Row {
+    let mut row = Row::from(message);
+    let max_width = width.saturating_sub(MIN_INPUT_WIDTH);
+    if max_width < row.width(TAB_STOP) {
+        row.truncate_width(max_width.saturating_sub(1), TAB_STOP);
+        row.insert(row.len(), '~');
+    }
+    row
 }
 
-fn find_next_at(cursor: &Cursor, content: &Buffer, keyword: &Row) -> Option<(usize, usize)> {
+fn find_at<P: Coordinates>(
+    at: &P,
+    content: &Buffer,
+    keyword: &Row,
+    ignore_case: bool,
+) -> Option<(usize, usize)> {
+    if ignore_case {
+        content.find_at_icase(at, &keyword.to_string_at(0))
+    } else {
+        content.find_at(at, &keyword.to_string_at(0))
+    }
+}
+
+fn find_next_at(
+    cursor: &Cursor,
+    content: &Buffer,
+    keyword: &Row,
+    ignore_case: bool,
+) -> Option<(usize, usize)> {
     let mut c = cursor.clone();
     c.move_right(content);
+    find_at(&c, content, keyword, ignore_case)
+}
+
+/// Returns the tail of input which fits the width, with leading `<` if scrolled.
+fn input_window(chars: &Row, width: usize) -> Row {
+    if chars.width(TAB_STOP) <= width {
+        return chars.clone();
+    }
 
-    if let Some((x, y)) = content.find_at(&c, &keyword.to_string_at(0)) {
-        Some((x, y))
+    if width == 0 {
+        return Row::default();
+    }
+
+    let len = chars.len();
+    let mut start = len;
+    // Keep a column for `<`.
+    while 0 < start && chars.width_range(start - 1..len, TAB_STOP) < width {
+        start -= 1;
+    }
+
+    let mut window = vec!['<'];
+    window.extend_from_slice(&chars.column()[start..]);
+    Row::from(window)
+}
+
+/// Returns the text of the length from the position.
+fn matched_text(content: &Buffer, at: &(usize, usize), len: usize) -> Row {
+    let column = content.get(at.1).map(|r| r.column()).unwrap_or_default();
+    Row::from(&column[min(at.0, column.len())..min(at.0 + len, column.len())])
+}
+
+/// Returns the event as typed, for a line break or a tab pasted via console.
+fn input_event(event: Event, insert_tab: bool) -> Event {
+    match event {
+        Event::Key(KeyEvent::Char('\n' | '\r'), modifier) => Event::Key(KeyEvent::Enter, modifier),
+        Event::Key(KeyEvent::Tab, KeyModifier::None) if insert_tab => {
+            Event::Key(KeyEvent::Char('\t'), KeyModifier::None)
+        }
+        _ => event,
+    }
+}
+
+/// Returns the color of input, dimmed for default value.
+fn input_color(provisional: bool) -> Color {
+    if provisional {
+        Color::Gray
     } else {
-        None
+        Color::White
     }
 }
 
@@ -802,7 +1214,7 @@ fn move_screen<P: Coordinates>(
 ) {
     cursor.set(content, at);
 
-    let keyword_width = keyword.width();
+    let keyword_width = keyword.width(content.tab_width());
     if 0 < keyword_width && keyword_width < screen.width() {
         let mut last_ch = cursor.clone();
         last_ch.set_x(content, cursor.x() + keyword.len() - 1);
@@ -820,10 +1232,12 @@ fn resize<T: Terminal>(
     message: &mut MessageBar,
     terminal: &mut T,
 ) -> Result<(usize, usize), Error> {
-    resize_screen(screen, status, message, terminal)?;
-
     let render = cursor.render(content);
+    let row = render.y().saturating_sub(screen.top());
 
+    resize_screen(screen, status, message, terminal)?;
+
+    screen.keep_row(&render, row);
     screen.fit(content, &render);
 
     let mut select = Select::default();
@@ -841,18 +1255,7 @@ fn resize<T: Terminal>(
     terminal.get_cursor_position()
 }
 
-fn rfind_next_at(cursor: &Cursor, content: &Buffer, keyword: &Row) -> Option<(usize, usize)> {
-    let mut c = cursor.clone();
-    c.move_left(content);
-
-    if let Some((x, y)) = content.rfind_at(&c, &keyword.to_string_at(0)) {
-        Some((x, y))
-    } else {
-        None
-    }
-}
-
-fn set_text_attribute<T: Terminal>(
+pub(crate) fn set_text_attribute<T: Terminal>(
     cursor: &Cursor,
     content: &Buffer,
     screen: &Screen,
@@ -860,12 +1263,713 @@ fn set_text_attribute<T: Terminal>(
     keyword: &Row,
 ) -> Result<(), Error> {
     let render = cursor.render(content);
-    let keyword_width = keyword.width();
+    // The width of a tab in the match depends on where it is.
+    let keyword_width = match content.get(cursor.y()) {
+        Some(row) => {
+            let end = cursor.x() + keyword.len();
+            let (start_width, end_width) = row.widths_at(cursor.x(), end, content.tab_width());
+            end_width - start_width
+        }
+        None => keyword.width(content.tab_width()),
+    };
     let length = min(keyword_width, screen.right() - render.x() + 1);
-    terminal.set_text_attribute(
-        render.x() - screen.left(),
-        render.y() - screen.top(),
-        length,
-    )?;
+    let (x, y) = screen.position(&render);
+    terminal.set_text_attribute(x, y, length)?;
     Ok(())
 }
+
+// -----------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terminal::Null;
+
+    #[test]
+    fn elide_message_fit() {
+        assert_eq!("abc", elide_message("abc", 13).to_string_at(0));
+    }
+
+    #[test]
+    fn elide_message_over() {
+        assert_eq!("ab~", elide_message("abcd", 13).to_string_at(0));
+        assert_eq!("~", elide_message("abcd", 5).to_string_at(0));
+    }
+
+    #[test]
+    fn input_window_fit() {
+        let chars = Row::from("abc");
+
+        assert_eq!("abc", input_window(&chars, 3).to_string_at(0));
+    }
+
+    #[test]
+    fn input_window_scroll() {
+        let chars = Row::from("abcdef");
+
+        assert_eq!("<def", input_window(&chars, 4).to_string_at(0));
+        assert_eq!("", input_window(&chars, 0).to_string_at(0));
+    }
+
+    #[test]
+    fn input_window_scroll_wide() {
+        let chars = Row::from("aあいう");
+
+        assert_eq!("<う", input_window(&chars, 4).to_string_at(0));
+        assert_eq!("<いう", input_window(&chars, 5).to_string_at(0));
+    }
+
+    struct Recorder<'a> {
+        cursor: Cursor,
+        content: Buffer,
+        screen: Screen,
+        status: StatusBar,
+        message: MessageBar,
+        terminal: &'a mut Null,
+        events: Vec<Event>,
+    }
+
+    impl<'a> Prompt<Null> for Recorder<'a> {
+        fn content(&self) -> &Buffer {
+            &self.content
+        }
+
+        fn content_mut(&mut self) -> &mut Buffer {
+            &mut self.content
+        }
+
+        fn cursor(&self) -> &Cursor {
+            &self.cursor
+        }
+
+        fn cursor_mut(&mut self) -> &mut Cursor {
+            &mut self.cursor
+        }
+
+        fn handle_event(&mut self, event: &Event, _: &[char]) -> Result<KeyInput, Error> {
+            self.events.push(*event);
+            Ok(KeyInput::Continue)
+        }
+
+        fn message(&self) -> &MessageBar {
+            &self.message
+        }
+
+        fn message_mut(&mut self) -> &mut MessageBar {
+            &mut self.message
+        }
+
+        fn resize_screen(
+            &mut self,
+            _: &mut MessageBar,
+            _: &[char],
+        ) -> Result<(usize, usize), Error> {
+            Ok((0, 0))
+        }
+
+        fn screen(&self) -> &Screen {
+            &self.screen
+        }
+
+        fn screen_mut(&mut self) -> &mut Screen {
+            &mut self.screen
+        }
+
+        fn status(&self) -> &StatusBar {
+            &self.status
+        }
+
+        fn status_mut(&mut self) -> &mut StatusBar {
+            &mut self.status
+        }
+
+        fn terminal_mut(&mut self) -> &mut Null {
+            self.terminal
+        }
+    }
+
+    fn recorder(terminal: &mut Null) -> Recorder<'_> {
+        terminal.set_screen_size(30, 5);
+        let screen = Screen::current(terminal).unwrap();
+        Recorder {
+            cursor: Cursor::default(),
+            content: Buffer::default(),
+            status: StatusBar::new(&screen, None),
+            message: MessageBar::new(&screen, ""),
+            screen,
+            terminal,
+            events: vec![],
+        }
+    }
+
+    fn keys(chars: &str) -> Vec<Event> {
+        chars
+            .chars()
+            .map(|ch| Event::from((KeyEvent::Char(ch), KeyModifier::None)))
+            .collect()
+    }
+
+    #[test]
+    fn prompt_default_accept() {
+        let mut terminal = Null::default();
+        let mut prompt = recorder(&mut terminal);
+
+        Null::push_events(&[Event::from((KeyEvent::Enter, KeyModifier::None))]);
+        let ret = prompt.handle_events("", Some("abc")).unwrap();
+
+        assert_eq!(Some("abc".to_string()), ret);
+        let (_, _, text, color, _) = terminal.writes().last().unwrap();
+        assert_eq!("abc", text);
+        assert_eq!(Color::Gray, *color);
+    }
+
+    #[test]
+    fn prompt_default_replace() {
+        let mut terminal = Null::default();
+        let mut prompt = recorder(&mut terminal);
+
+        let mut events = keys("xy");
+        events.push(Event::from((KeyEvent::Enter, KeyModifier::None)));
+        Null::push_events(&events);
+        let ret = prompt.handle_events("", Some("abc")).unwrap();
+
+        assert_eq!(Some("xy".to_string()), ret);
+        let (_, _, text, color, _) = terminal.writes().last().unwrap();
+        assert_eq!("xy", text);
+        assert_eq!(Color::White, *color);
+    }
+
+    #[test]
+    fn prompt_default_backspace() {
+        let mut terminal = Null::default();
+        let mut prompt = recorder(&mut terminal);
+
+        let mut events = vec![Event::from((KeyEvent::BackSpace, KeyModifier::None))];
+        events.extend(keys("z"));
+        events.push(Event::from((KeyEvent::BackSpace, KeyModifier::None)));
+        events.push(Event::from((KeyEvent::Enter, KeyModifier::None)));
+        Null::push_events(&events);
+        let ret = prompt.handle_events("", Some("abc")).unwrap();
+
+        assert_eq!(Some("".to_string()), ret);
+    }
+
+    #[test]
+    fn prompt_default_escape() {
+        let mut terminal = Null::default();
+        let mut prompt = recorder(&mut terminal);
+
+        Null::push_events(&[Event::from((KeyEvent::Escape, KeyModifier::None))]);
+        let ret = prompt.handle_events("", Some("abc")).unwrap();
+
+        assert_eq!(None, ret);
+    }
+
+    #[test]
+    fn prompt_handle_event_tab() {
+        let mut terminal = Null::default();
+        let mut prompt = recorder(&mut terminal);
+
+        Null::push_events(&[
+            Event::from((KeyEvent::Char('a'), KeyModifier::None)),
+            Event::from((KeyEvent::Tab, KeyModifier::None)),
+            Event::from((KeyEvent::Tab, KeyModifier::Shift)),
+            Event::from((KeyEvent::Enter, KeyModifier::None)),
+        ]);
+        let ret = prompt.handle_events("", None).unwrap();
+
+        assert_eq!(Some("a".to_string()), ret);
+        assert_eq!(
+            vec![
+                Event::from((KeyEvent::Tab, KeyModifier::None)),
+                Event::from((KeyEvent::Tab, KeyModifier::Shift)),
+            ],
+            prompt.events
+        );
+    }
+
+    #[test]
+    fn prompt_choice_key() {
+        let mut terminal = Null::default();
+        let mut prompt = recorder(&mut terminal);
+
+        Null::push_events(&keys("zA"));
+        let ret = prompt.handle_choice("m", &[('y', "yes"), ('a', "all")]);
+
+        assert_eq!(Some('a'), ret.unwrap());
+        let (_, _, text, _, _) = terminal.writes().last().unwrap();
+        assert_eq!("m (y:yes a:all ESC:quit): ", text);
+    }
+
+    #[test]
+    fn prompt_choice_escape() {
+        let mut terminal = Null::default();
+        let mut prompt = recorder(&mut terminal);
+
+        Null::push_events(&[Event::from((KeyEvent::Escape, KeyModifier::None))]);
+        let ret = prompt.handle_choice("m", &[('y', "yes")]);
+
+        assert_eq!(None, ret.unwrap());
+    }
+
+    #[test]
+    fn prompt_choice_resize() {
+        let mut terminal = Null::default();
+        let mut prompt = recorder(&mut terminal);
+
+        let mut events = vec![Event::Window(WindowEvent::Resize)];
+        events.extend(keys("n"));
+        Null::push_events(&events);
+        let ret = prompt.handle_choice("m", &[('n', "no")]);
+
+        assert_eq!(Some('n'), ret.unwrap());
+        let drawn = terminal
+            .writes()
+            .iter()
+            .filter(|(_, _, text, _, _)| text == "m (n:no ESC:quit): ")
+            .count();
+        assert_eq!(2, drawn);
+    }
+
+    fn replace(rows: &[&str], answers: &str) -> Buffer {
+        replace_with(rows, "b", "x", answers)
+    }
+
+    fn replace_with(rows: &[&str], source: &str, replaced: &str, answers: &str) -> Buffer {
+        let mut terminal = Null::default();
+        terminal.set_screen_size(30, 5);
+        let mut cursor = Cursor::default();
+        let mut content = Buffer::default();
+        for (y, row) in rows.iter().enumerate() {
+            content.insert_row(&(0, y), &row.chars().collect::<Vec<char>>());
+        }
+        let mut screen = Screen::current(&terminal).unwrap();
+        let mut status = StatusBar::new(&screen, None);
+        let mut message = MessageBar::new(&screen, "");
+
+        let mut events = keys(source);
+        events.push(Event::from((KeyEvent::Enter, KeyModifier::None)));
+        events.extend(keys(replaced));
+        events.push(Event::from((KeyEvent::Enter, KeyModifier::None)));
+        events.extend(keys(answers));
+        Null::push_events(&events);
+
+        let mut prompt = Replace::new(
+            &mut cursor,
+            &mut content,
+            &mut screen,
+            &mut status,
+            &mut message,
+            &mut terminal,
+        );
+        prompt.replace("", None).unwrap();
+        content
+    }
+
+    #[test]
+    fn replace_ignore_case() {
+        Null::push_events(&[Event::from((KeyEvent::Char('c'), KeyModifier::AltLeft))]);
+        let content = replace_with(&["aB", "b", "cb"], "b", "x", "a");
+
+        assert_eq!(vec!["ax", "x", "cx"], texts(&content));
+    }
+
+    fn texts(content: &Buffer) -> Vec<String> {
+        (0..content.rows())
+            .map(|y| content.get(y).unwrap().to_string_at(0))
+            .collect()
+    }
+
+    #[test]
+    fn replace_choice_yes_no() {
+        let content = replace(&["ab", "b", "cb"], "ynzy");
+
+        assert_eq!(vec!["ax", "b", "cx"], texts(&content));
+    }
+
+    #[test]
+    fn replace_choice_escape() {
+        let content = replace(&["ab", "b", "cb"], "y");
+
+        assert_eq!(vec!["ax", "b", "cb"], texts(&content));
+    }
+
+    #[test]
+    fn replace_choice_all() {
+        let content = replace(&["ab", "bb", "cb"], "na");
+
+        assert_eq!(vec!["ab", "xx", "cx"], texts(&content));
+    }
+
+    #[test]
+    fn replace_choice_shorter_in_row() {
+        let content = replace_with(&["abcd abcd", "abcd"], "abcd", "x", "yyy");
+
+        assert_eq!(vec!["x x", "x"], texts(&content));
+    }
+
+    #[test]
+    fn replace_choice_longer_in_row() {
+        let content = replace_with(&["a a"], "a", "aa", "yy");
+
+        assert_eq!(vec!["aa aa"], texts(&content));
+    }
+
+    #[test]
+    fn replace_choice_after_wide() {
+        let content = replace(&["あいb b"], "yy");
+
+        assert_eq!(vec!["あいx x"], texts(&content));
+    }
+
+    #[test]
+    fn replace_choice_all_undo() {
+        let mut content = replace(&["b", "b", "b"], "a");
+        content.undo();
+
+        assert_eq!(vec!["b", "b", "b"], texts(&content));
+    }
+
+    #[test]
+    fn prompt_input_resize_color() {
+        let mut terminal = Null::default();
+        terminal.set_screen_size(30, 5);
+        let mut cursor = Cursor::default();
+        let mut content = Buffer::default();
+        let mut screen = Screen::current(&terminal).unwrap();
+        let mut status = StatusBar::new(&screen, None);
+        let mut message = MessageBar::new(&screen, "");
+
+        let mut events = vec![Event::Window(WindowEvent::Resize)];
+        events.extend(keys("a\r"));
+        Null::push_events(&events);
+        let mut prompt = Input::new(
+            &mut cursor,
+            &mut content,
+            &mut screen,
+            &mut status,
+            &mut message,
+            &mut terminal,
+        );
+        let ret = prompt.handle_events("Name: ", None);
+
+        assert_eq!(Some("a".to_string()), ret.unwrap());
+        let colors = terminal
+            .writes()
+            .iter()
+            .filter(|(_, _, text, _, _)| text == "Name: ")
+            .map(|(_, _, _, color, _)| *color)
+            .collect::<Vec<Color>>();
+        assert_eq!(vec![Color::Cyan; 3], colors);
+    }
+
+    #[test]
+    fn prompt_paste_line_break() {
+        let mut terminal = Null::default();
+        terminal.set_screen_size(30, 5);
+        let mut cursor = Cursor::default();
+        let mut content = Buffer::default();
+        let mut screen = Screen::current(&terminal).unwrap();
+        let mut status = StatusBar::new(&screen, None);
+        let mut message = MessageBar::new(&screen, "");
+
+        Null::push_events(&keys("C:\\temp\\file.txt\r\n"));
+        let mut prompt = Input::new(
+            &mut cursor,
+            &mut content,
+            &mut screen,
+            &mut status,
+            &mut message,
+            &mut terminal,
+        );
+        let ret = prompt.handle_events("Filename (ESC:quit): ", None);
+
+        assert_eq!(Some("C:\\temp\\file.txt".to_string()), ret.unwrap());
+    }
+
+    #[test]
+    fn prompt_paste_control() {
+        let mut terminal = Null::default();
+        let mut prompt = recorder(&mut terminal);
+
+        let mut events = keys("a\tb\x01");
+        events.push(Event::from((KeyEvent::Enter, KeyModifier::None)));
+        Null::push_events(&events);
+        let ret = prompt.handle_events("m", None);
+
+        assert_eq!(Some("ab".to_string()), ret.unwrap());
+        let colors = terminal
+            .writes()
+            .iter()
+            .filter(|(_, _, text, _, _)| text == "m")
+            .map(|(_, _, _, color, _)| *color)
+            .collect::<Vec<Color>>();
+        assert_eq!(
+            vec![Color::Cyan, Color::Red, Color::Cyan, Color::Red],
+            colors
+        );
+    }
+
+    #[test]
+    fn find_keyword_tab() {
+        let mut terminal = Null::default();
+        terminal.set_screen_size(30, 5);
+        let mut cursor = Cursor::default();
+        let mut content = Buffer::default();
+        content.insert_row(&(0, 0), &['a', '\t', 'b']);
+        let mut screen = Screen::current(&terminal).unwrap();
+        let mut status = StatusBar::new(&screen, None);
+        let mut message = MessageBar::new(&screen, "");
+
+        Null::push_events(&[
+            Event::from((KeyEvent::Tab, KeyModifier::None)),
+            Event::from((KeyEvent::Char('b'), KeyModifier::None)),
+            Event::from((KeyEvent::Enter, KeyModifier::None)),
+        ]);
+        let mut prompt = FindKeyword::new(
+            &mut cursor,
+            &mut content,
+            &mut screen,
+            &mut status,
+            &mut message,
+            &mut terminal,
+        );
+        let ret = prompt.handle_events("Input keyword (ESC:quit): ", None);
+
+        assert_eq!(Some("\tb".to_string()), ret.unwrap());
+        assert_eq!(Cursor::from((1, 0)), cursor);
+    }
+
+    fn find_caret(events: &[Event]) -> ((usize, usize), (usize, usize), usize) {
+        let mut terminal = Null::default();
+        terminal.set_screen_size(30, 5);
+        let mut cursor = Cursor::default();
+        let mut content = Buffer::default();
+        content.insert_row(&(0, 0), &['a', 'b']);
+        let text = format!("{}ab", "x".repeat(40))
+            .chars()
+            .collect::<Vec<char>>();
+        content.insert_row(&(0, 1), &text);
+        let mut screen = Screen::current(&terminal).unwrap();
+        let mut status = StatusBar::new(&screen, None);
+        let mut message = MessageBar::new(&screen, "");
+
+        let mut all = keys("ab");
+        all.extend_from_slice(events);
+        all.push(Event::from((KeyEvent::Enter, KeyModifier::None)));
+        Null::push_events(&all);
+
+        let mut prompt = FindKeyword::new(
+            &mut cursor,
+            &mut content,
+            &mut screen,
+            &mut status,
+            &mut message,
+            &mut terminal,
+        );
+        prompt
+            .handle_events("Input keyword (ESC:quit): ", None)
+            .unwrap();
+
+        // The input is written last.
+        let (x, y, input, _, _) = terminal.writes().last().unwrap();
+        let input = (x + input.chars().count(), *y);
+        (
+            terminal.get_cursor_position().unwrap(),
+            input,
+            screen.left(),
+        )
+    }
+
+    #[test]
+    fn find_keyword_caret_at_match() {
+        let (caret, _, left) = find_caret(&[Event::from((KeyEvent::F3, KeyModifier::None))]);
+
+        assert!(0 < left);
+        assert_eq!((40 - left, 1), caret);
+    }
+
+    #[test]
+    fn find_keyword_caret_at_input() {
+        let (caret, input, _) = find_caret(&[
+            Event::from((KeyEvent::F3, KeyModifier::None)),
+            Event::from((KeyEvent::BackSpace, KeyModifier::None)),
+        ]);
+
+        assert_eq!(input, caret);
+    }
+
+    #[test]
+    fn find_keyword_narrow() {
+        let keyword = "0123456789".repeat(5);
+
+        let mut terminal = Null::default();
+        terminal.set_screen_size(30, 5);
+        let mut cursor = Cursor::default();
+        let mut content = Buffer::default();
+        content.insert_row(&(0, 0), &['a']);
+        let text = format!("b{}", keyword).chars().collect::<Vec<char>>();
+        content.insert_row(&(0, 1), &text);
+        let mut screen = Screen::current(&terminal).unwrap();
+        let mut status = StatusBar::new(&screen, None);
+        let mut message = MessageBar::new(&screen, "");
+
+        let mut events = keyword
+            .chars()
+            .map(|ch| Event::from((KeyEvent::Char(ch), KeyModifier::None)))
+            .collect::<Vec<Event>>();
+        events.push(Event::from((KeyEvent::Enter, KeyModifier::None)));
+        Null::push_events(&events);
+
+        let mut prompt = FindKeyword::new(
+            &mut cursor,
+            &mut content,
+            &mut screen,
+            &mut status,
+            &mut message,
+            &mut terminal,
+        );
+        let ret = prompt.handle_events("Input keyword (ESC:quit): ", None);
+
+        assert_eq!(Some(keyword.clone()), ret.unwrap());
+        assert_eq!(Cursor::from((1, 1)), cursor);
+
+        let (x, _, text, _, _) = terminal.writes().last().unwrap();
+        assert_eq!(20, *x);
+        assert_eq!("<23456789", text);
+    }
+
+    #[test]
+    fn find_keyword_highlight_all() {
+        let mut terminal = Null::default();
+        terminal.set_screen_size(30, 5);
+        let mut cursor = Cursor::default();
+        let mut content = Buffer::default();
+        content.insert_row(&(0, 0), &['a', 'b']);
+        content.insert_row(&(0, 1), &['x', 'a', 'b', 'a', 'b']);
+        let mut screen = Screen::current(&terminal).unwrap();
+        let mut status = StatusBar::new(&screen, None);
+        let mut message = MessageBar::new(&screen, "");
+
+        let mut events = keys("ab");
+        events.push(Event::from((KeyEvent::Enter, KeyModifier::None)));
+        Null::push_events(&events);
+
+        let mut prompt = FindKeyword::new(
+            &mut cursor,
+            &mut content,
+            &mut screen,
+            &mut status,
+            &mut message,
+            &mut terminal,
+        );
+        prompt.handle_events("Find: ", None).unwrap();
+
+        let attributes = terminal.attributes();
+        let drawn = &attributes[attributes.len() - 4..];
+        assert_eq!(&[(0, 0, 2), (1, 1, 2), (3, 1, 2), (0, 0, 2)], drawn);
+    }
+
+    #[test]
+    fn find_keyword_tabs_expanded() {
+        let mut terminal = Null::default();
+        terminal.set_screen_size(30, 5);
+        let mut cursor = Cursor::default();
+        let mut content = Buffer::default();
+        content.insert_row(&(0, 0), &['a', '\t', 'b', 'c']);
+        content.insert_row(&(0, 1), &['a', ' ', ' ', ' ', 'b']);
+        let mut screen = Screen::current(&terminal).unwrap();
+        let mut status = StatusBar::new(&screen, None);
+        let mut message = MessageBar::new(&screen, "");
+
+        let mut events = vec![Event::from((KeyEvent::Char('t'), KeyModifier::AltLeft))];
+        events.extend(keys("  b"));
+        events.push(Event::from((KeyEvent::F3, KeyModifier::None)));
+        events.push(Event::from((KeyEvent::Enter, KeyModifier::None)));
+        Null::push_events(&events);
+
+        let mut prompt = FindKeyword::new(
+            &mut cursor,
+            &mut content,
+            &mut screen,
+            &mut status,
+            &mut message,
+            &mut terminal,
+        );
+        let ret = prompt.handle_events("Find: ", None).unwrap();
+        assert!(prompt.tabs_expanded());
+
+        assert_eq!(Some("  b".to_string()), ret);
+        assert_eq!(Cursor::from((2, 1)), cursor);
+        // The match in the first row is the tab and `b`, highlighted over their width.
+        let last = terminal.attributes().iter().rev().find(|a| a.1 == 0);
+        assert_eq!(Some(&(1, 0, 8)), last);
+        assert!(terminal
+            .writes()
+            .iter()
+            .any(|(_, _, text, _, _)| text.starts_with("[t] Find: ")));
+    }
+
+    fn find_regex(rows: &[&str], events: &[Event]) -> (Option<String>, Cursor, Null, Vec<String>) {
+        let mut terminal = Null::default();
+        terminal.set_screen_size(40, 5);
+        let mut cursor = Cursor::default();
+        let mut content = Buffer::default();
+        for (y, row) in rows.iter().enumerate() {
+            content.insert_row(&(0, y), &row.chars().collect::<Vec<char>>());
+        }
+        let mut screen = Screen::current(&terminal).unwrap();
+        let mut status = StatusBar::new(&screen, None);
+        let mut message = MessageBar::new(&screen, "");
+
+        let mut all = vec![Event::from((KeyEvent::Format, KeyModifier::None))];
+        all.extend_from_slice(events);
+        Null::push_events(&all);
+
+        let mut prompt = FindKeyword::new(
+            &mut cursor,
+            &mut content,
+            &mut screen,
+            &mut status,
+            &mut message,
+            &mut terminal,
+        );
+        let ret = prompt.handle_events("Find: ", None).unwrap();
+        assert!(prompt.regex());
+
+        let labels = terminal
+            .writes()
+            .iter()
+            .filter(|(x, y, _, color, _)| *x == 0 && *y == 4 && *color == Color::Cyan)
+            .map(|(_, _, text, _, _)| text.clone())
+            .collect();
+        (ret, cursor, terminal, labels)
+    }
+
+    #[test]
+    fn find_keyword_regex_wide() {
+        let mut events = keys("い+");
+        events.push(Event::from((KeyEvent::Enter, KeyModifier::None)));
+
+        let (ret, cursor, terminal, labels) = find_regex(&["abc", "xいいy"], &events);
+
+        assert_eq!(Some("い+".to_string()), ret);
+        assert_eq!(Cursor::from((1, 1)), cursor);
+        assert_eq!(Some(&(1, 1, 4)), terminal.attributes().last());
+        assert_eq!(Some("Regex Find: "), labels.last().map(|l| l.as_str()));
+    }
+
+    #[test]
+    fn find_keyword_regex_invalid() {
+        let mut events = keys("b(");
+        events.push(Event::from((KeyEvent::BackSpace, KeyModifier::None)));
+        events.push(Event::from((KeyEvent::Enter, KeyModifier::None)));
+
+        let (ret, cursor, _, labels) = find_regex(&["abc"], &events);
+
+        assert_eq!(Some("b".to_string()), ret);
+        assert_eq!(Cursor::from((1, 0)), cursor);
+        assert!(labels.iter().any(|l| l == "Regex error: unclosed group: "));
+        assert_eq!(Some("Regex Find: "), labels.last().map(|l| l.as_str()));
+    }
+}
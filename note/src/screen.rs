@@ -1,18 +1,45 @@
-use crate::buffer::{Buffer, Row};
+use crate::buffer::{fold_case, Buffer, Encoding, Row, TAB_STOP};
+use crate::config::{LineEnding, Settings, Theme};
 use crate::cursor::{AsCoordinates, Coordinates};
 use crate::editor::Select;
 use crate::error::Error;
+use crate::highlight::Syntax;
 use crate::terminal::Terminal;
 use crate::Color;
 use std::cmp::{max, min};
+use std::iter;
+use std::ops::Range;
+use std::time::{Duration, Instant};
 
-#[derive(Clone, Debug, Default, PartialEq)]
+const TEXT_BANNER: &str = concat!("note editor -- version ", env!("CARGO_PKG_VERSION"));
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct Screen {
+    origin: (usize, usize),
     left0: usize,
     top0: usize,
     height: usize,
     width: usize,
+    filler: char,
     updated: bool,
+    cursor: Option<(usize, usize)>,
+    dirty: Option<usize>,
+}
+
+impl Default for Screen {
+    fn default() -> Self {
+        Screen {
+            origin: (0, 0),
+            left0: 0,
+            top0: 0,
+            height: 0,
+            width: 0,
+            filler: '~',
+            updated: false,
+            cursor: None,
+            dirty: None,
+        }
+    }
 }
 
 impl Screen {
@@ -28,13 +55,101 @@ impl Screen {
         self.top0 + (self.height - 1)
     }
 
-    /// Clean the screen window.
+    /// Move the screen window up not to show rows after end of buffer
+    /// more than the row of end, e.g. after the rows are deleted.
+    pub fn clamp_to(&mut self, content: &Buffer) -> bool {
+        // The row after the last row is also where the cursor can be.
+        let top = (content.rows() + 1).saturating_sub(self.height);
+        if top < self.top0 {
+            self.top0 = top;
+            self.updated = true;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Clean the text area of the screen window, and leave other regions untouched.
     pub fn clear(&mut self, terminal: &mut impl Terminal) -> Result<(), Error> {
-        terminal.scroll_up(self.height)?;
+        let (x0, y0) = self.origin;
+        terminal.clear_region(x0, y0, self.width, self.height)?;
         self.updated |= true;
         Ok(())
     }
 
+    /// Stop drawing the software cursor, and redraw the row of it.
+    pub fn clear_cursor(&mut self) {
+        if let Some((_, y)) = self.cursor.take() {
+            self.dirty = Some(y);
+        }
+    }
+
+    /// Highlight the occurrences of the keyword in the rows on this screen.
+    pub fn decorate(
+        &self,
+        content: &Buffer,
+        keyword: &Row,
+        ignore_case: bool,
+        terminal: &mut impl Terminal,
+    ) -> Result<(), Error> {
+        let fold = |ch: &char| if ignore_case { fold_case(*ch) } else { *ch };
+        let keyword = keyword.column().iter().map(fold).collect::<Vec<char>>();
+        if keyword.is_empty() {
+            return Ok(());
+        }
+
+        let tab_width = content.tab_width();
+        let end = min(content.rows(), self.bottom() + 1);
+        for index in self.top0..end {
+            let row = content.get(index).unwrap();
+            let column = row.column().iter().map(fold).collect::<Vec<char>>();
+
+            let mut x = 0;
+            while x + keyword.len() <= column.len() {
+                if !column[x..].starts_with(&keyword) {
+                    x += 1;
+                    continue;
+                }
+
+                let (start_width, end_width) = row.widths_at(x, x + keyword.len(), tab_width);
+                let startx = max(start_width, self.left0);
+                let endx = min(end_width, self.right() + 1);
+                if startx < endx {
+                    let (tx, ty) = self.position(&(startx, index));
+                    terminal.set_text_attribute(tx, ty, endx - startx)?;
+                }
+
+                x += keyword.len();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Highlight the rows in the ranges on this screen over their width, or a column if empty.
+    pub fn decorate_rows(
+        &self,
+        content: &Buffer,
+        rows: &[Range<usize>],
+        terminal: &mut impl Terminal,
+    ) -> Result<(), Error> {
+        let (x0, y0) = self.origin;
+        let end = min(content.rows(), self.bottom() + 1);
+        for index in rows
+            .iter()
+            .flat_map(|r| max(r.start, self.top0)..min(r.end, end))
+        {
+            let row = content.get(index).unwrap();
+            let width = min(
+                row.width(content.tab_width()).saturating_sub(self.left0),
+                self.width,
+            );
+            terminal.set_text_attribute(x0, y0 + index - self.top0, max(width, 1))?;
+        }
+
+        Ok(())
+    }
+
     /// Draw screen.
     pub fn draw(
         &mut self,
@@ -42,7 +157,7 @@ impl Screen {
         select: &Select,
         terminal: &mut impl Terminal,
     ) -> Result<(), Error> {
-        if !self.updated && !content.updated() && !select.updated() {
+        if !self.updated && !content.updated() && !select.updated() && self.dirty.is_none() {
             return Ok(());
         }
 
@@ -50,46 +165,45 @@ impl Screen {
             self.clear(terminal)?;
         }
 
+        let (x0, y0) = self.origin;
+
+        let corners = select.corners();
+        let theme = content.settings().theme();
+        let highlighter = content.highlighter();
+        let tab_width = content.tab_width();
         let end = min(content.rows(), self.bottom() + 1);
         for index in self.top0..end {
-            if !self.updated && !content.row_updated(index) && !select.changes(index) {
+            if !self.updated
+                && !content.row_updated(index)
+                && !select.changes(corners, index)
+                && self.dirty != Some(index)
+            {
                 continue;
             }
 
             let row = content.get(index).unwrap();
-            let buffer = row.slice_width(self.left0..self.right() + 1);
+            let buffer = row.slice_width(self.left0..self.right() + 1, tab_width);
+            let idx = y0 + index - self.top0;
 
             if !buffer.is_empty() {
-                let idx = index - self.top0;
-
-                if let Some(comment) = buffer.column().iter().position(|&ch| ch == '#') {
-                    let line = buffer.column().split_at(comment);
-                    terminal.write(0, idx, line.0, Color::White, false)?;
-                    terminal.write(
-                        buffer.width_range(0..comment),
-                        idx,
-                        line.1,
-                        Color::Yellow,
-                        false,
-                    )?;
-                } else {
-                    terminal.write(0, idx, buffer.column(), Color::White, false)?;
-                }
-
-                if let Some((start, end)) = select.xrange(index) {
-                    let start_width = row.width_range(0..min(row.len(), start));
+                let spans = highlighter.spans(row.column(), index);
+                self.draw_spans(
+                    (x0, idx),
+                    row,
+                    &buffer,
+                    &spans,
+                    content.settings(),
+                    terminal,
+                )?;
+
+                if let Some((start, end)) = select.xrange(corners, index) {
+                    let (start_width, end_width) = row.widths_at(start, end, tab_width);
                     let startx = max(start_width, self.left0);
-
-                    let end_width = row.width_range(0..min(row.len(), end));
                     let endx = min(end_width, self.right() + 1);
 
                     if startx <= endx {
-                        let x = if start_width < self.left0 {
-                            0
-                        } else {
-                            start_width - self.left0
-                        };
-                        terminal.set_text_attribute(x, index, endx - startx)?;
+                        let x = start_width.saturating_sub(self.left0);
+                        terminal.set_text_attribute(x0 + x, idx, endx - startx)?;
                     } else {
                         // highlight area is left of 'self.left0'.
                     }
@@ -97,15 +211,95 @@ impl Screen {
             }
         }
 
+        let banner = if content.rows() == 0 && content.filename().is_none() {
+            Some(self.height / 3)
+        } else {
+            None
+        };
+
         for index in end..=self.bottom() {
             let idx = index - self.top0;
-            terminal.write(0, idx, &[char::from(b'~')], Color::White, false)?;
+            if banner == Some(idx) {
+                let row = self.banner();
+                terminal.write(x0, y0 + idx, row.column(), theme.text, false)?;
+            } else {
+                terminal.write(x0, y0 + idx, &[self.filler], theme.text, false)?;
+            }
         }
 
         self.updated = false;
+        self.dirty = None;
         Ok(())
     }
 
+    /// Write the visible slice of the row, a write for each span and for each gap between them.
+    fn draw_spans(
+        &self,
+        (x0, y): (usize, usize),
+        row: &Row,
+        buffer: &Row,
+        spans: &[(Range<usize>, Syntax)],
+        settings: &Settings,
+        terminal: &mut impl Terminal,
+    ) -> Result<(), Error> {
+        let theme = settings.theme();
+        let tab_width = settings.tab_width();
+        let right = self.left0 + buffer.width(tab_width);
+
+        let mut x = self.left0;
+        for (range, syntax) in spans {
+            let (start_width, end_width) = row.widths_at(range.start, range.end, tab_width);
+            let startx = start_width.clamp(x, right);
+            let endx = end_width.clamp(startx, right);
+
+            for (a, b, color) in [
+                (x, startx, theme.text),
+                (startx, endx, theme.syntax(*syntax)),
+            ] {
+                if a < b {
+                    let part = buffer.slice_width(a - self.left0..b - self.left0, tab_width);
+                    terminal.write(x0 + a - self.left0, y, part.column(), color, false)?;
+                }
+            }
+            x = endx;
+        }
+
+        if x < right {
+            let part = buffer.slice_width(x - self.left0..right - self.left0, tab_width);
+            terminal.write(x0 + x - self.left0, y, part.column(), theme.text, false)?;
+        }
+
+        Ok(())
+    }
+
+    /// Draw the character at the software cursor in reverse video over its render width.
+    pub fn draw_cursor(&self, content: &Buffer, terminal: &mut impl Terminal) -> Result<(), Error> {
+        let (x, y) = match self.cursor {
+            Some(cursor) => cursor,
+            None => return Ok(()),
+        };
+
+        let tab_width = content.tab_width();
+        let (start, end) = match content.get(y) {
+            Some(row) if x < row.len() => row.widths_at(x, x + 1, tab_width),
+            Some(row) => (row.width(tab_width), row.width(tab_width) + 1),
+            None => (0, 1),
+        };
+
+        if y < self.top0 || self.bottom() < y || start < self.left0 || self.right() < start {
+            return Ok(());
+        }
+
+        let (tx, ty) = self.position(&(start, y));
+        terminal.set_text_attribute(tx, ty, min(end, self.right() + 1) - start)?;
+        Ok(())
+    }
+
+    /// Returns the character drawn at the row after end of buffer.
+    pub fn filler(&self) -> char {
+        self.filler
+    }
+
     /// Move the screen window if the position is out of the window.
     pub fn fit<P: Coordinates>(&mut self, content: &Buffer, pos: &P) -> bool {
         let cur = self.clone();
@@ -143,22 +337,62 @@ impl Screen {
         self.height
     }
 
+    /// Move the screen window to show the position at the row of screen.
+    /// The row is clamped to the bottom of screen.
+    pub fn keep_row<P: Coordinates>(&mut self, pos: &P, row: usize) -> bool {
+        let cur = self.clone();
+
+        let row = min(row, self.height.saturating_sub(1));
+        self.top0 = pos.y().saturating_sub(row);
+
+        self.updated |= cur != *self;
+        cur != *self
+    }
+
     /// Returns the coordinates index of this screen left.
     pub fn left(&self) -> usize {
         self.left0
     }
 
-    /// Indicates need to update screen.
+    /// Returns the position of this screen in terminal.
+    pub fn origin(&self) -> (usize, usize) {
+        self.origin
+    }
+
+    /// Returns the position in terminal of the render position.
+    pub fn position<P: Coordinates>(&self, render: &P) -> (usize, usize) {
+        (
+            self.origin.0 + render.x() - self.left0,
+            self.origin.1 + render.y() - self.top0,
+        )
+    }
+
+    /// Indicates need to update screen, including the row of previous software cursor.
     pub fn updated(&self) -> bool {
-        self.updated
+        self.updated || self.dirty.is_some()
     }
 
     /// Move down a height.
     pub fn move_down(&mut self, content: &Buffer) -> bool {
+        self.scroll_down(content, self.height)
+    }
+
+    /// Move up a height.
+    pub fn move_up(&mut self) -> bool {
+        self.scroll_up(self.height)
+    }
+
+    /// Returns the rows scrolled by half page, at least 1.
+    pub fn half_height(&self) -> usize {
+        max(self.height / 2, 1)
+    }
+
+    /// Move down the rows, clamped to show the end of buffer at the bottom.
+    pub fn scroll_down(&mut self, content: &Buffer, rows: usize) -> bool {
         let cur = self.clone();
 
         if self.height < content.rows() {
-            self.top0 += self.height;
+            self.top0 += rows;
             if content.rows() < self.bottom() {
                 self.top0 = content.rows() - (self.height - 1);
             }
@@ -168,15 +402,11 @@ impl Screen {
         cur != *self
     }
 
-    /// Move up a height.
-    pub fn move_up(&mut self) -> bool {
+    /// Move up the rows, clamped at the start of buffer.
+    pub fn scroll_up(&mut self, rows: usize) -> bool {
         let cur = self.clone();
 
-        self.top0 = if self.height < self.top0 {
-            self.top0 - self.height
-        } else {
-            0
-        };
+        self.top0 = self.top0.saturating_sub(rows);
 
         self.updated |= cur != *self;
         cur != *self
@@ -189,9 +419,37 @@ impl Screen {
         // - message bar
         self.height = height - 2;
         self.width = width;
+        self.origin = (0, 0);
         self.updated |= true;
     }
 
+    /// Set the position of the software cursor, and redraw the row of previous one.
+    pub fn set_cursor<P: Coordinates>(&mut self, cursor: &P) {
+        let at = (cursor.x(), cursor.y());
+        if self.cursor != Some(at) {
+            self.clear_cursor();
+            self.cursor = Some(at);
+        }
+    }
+
+    /// Set the character drawn at the row after end of buffer.
+    pub fn set_filler(&mut self, filler: char) {
+        self.updated |= self.filler != filler;
+        self.filler = filler;
+    }
+
+    /// Set the region of this screen in terminal.
+    pub fn set_region(&mut self, region: &Region) -> bool {
+        let cur = self.clone();
+
+        self.origin = (region.x, region.y);
+        self.height = region.height;
+        self.width = region.width;
+
+        self.updated |= cur != *self;
+        cur != *self
+    }
+
     /// Returns the coordinates index of this screen right.
     pub fn right(&self) -> usize {
         self.left0 + (self.width - 1)
@@ -206,6 +464,21 @@ impl Screen {
     pub fn width(&self) -> usize {
         self.width
     }
+
+    fn banner(&self) -> Row {
+        let mut banner = Row::from(TEXT_BANNER);
+        let width = banner.truncate_width(self.width, TAB_STOP);
+
+        let padding = (self.width - width) / 2;
+        if 0 < padding {
+            let mut row = iter::repeat(' ').take(padding).collect::<Vec<char>>();
+            row[0] = self.filler;
+            row.extend_from_slice(banner.column());
+            banner = Row::from(row);
+        }
+
+        banner
+    }
 }
 
 // -----------------------------------------------------------------------------------------------
@@ -216,6 +489,14 @@ pub struct StatusBar {
     width: usize,
     filename: Option<String>,
     position: (usize, usize),
+    total_rows: usize,
+    modified: bool,
+    binary: bool,
+    bom: bool,
+    encoding: Encoding,
+    line_ending: LineEnding,
+    search: Option<String>,
+    color: Color,
     updated: bool,
 }
 
@@ -226,6 +507,14 @@ impl StatusBar {
             width: screen.width(),
             filename: filename.map(|f| f.to_string()),
             position: (0, 0),
+            total_rows: 0,
+            modified: false,
+            binary: false,
+            bom: false,
+            encoding: Encoding::Utf8,
+            line_ending: LineEnding::Crlf,
+            search: None,
+            color: Theme::default().status,
             updated: true,
         }
     }
@@ -236,20 +525,49 @@ impl StatusBar {
         }
 
         let filename = self.filename.as_deref().unwrap_or("<buffered>");
+        let modified = if self.modified { " [+]" } else { "" };
+        let binary = if self.binary { " [binary]" } else { "" };
+        let encoding = match (self.encoding, self.bom) {
+            (Encoding::Utf8, false) => String::new(),
+            (encoding, true) => format!(" [{} BOM]", encoding),
+            (encoding, false) => format!(" [{}]", encoding),
+        };
         let message = format!(
-            " {:?}  {}:{}",
+            " {:?}{}{}{}  {}:{}/{}",
             filename,
+            modified,
+            binary,
+            encoding,
             self.position.0 + 1,
-            self.position.1 + 1
+            self.position.1 + 1,
+            self.total_rows
         );
         let mut buffer = Row::from(message);
-        buffer.truncate_width(self.width);
 
-        for _ in buffer.width()..self.width {
+        // The trailing fields are dropped first when they do not fit.
+        let percent = match self.total_rows {
+            0 => 100,
+            rows => min(100, (self.position.1 + 1) * 100 / rows),
+        };
+        let fields = [
+            Some(format!("  {}%", percent)),
+            Some(format!("  {}", self.line_ending)),
+            self.search.as_ref().map(|search| format!("  {}", search)),
+        ];
+        for field in fields.into_iter().flatten() {
+            let field = Row::from(field);
+            if buffer.width(TAB_STOP) + field.width(TAB_STOP) > self.width {
+                break;
+            }
+            buffer.append(field.column());
+        }
+        buffer.truncate_width(self.width, TAB_STOP);
+
+        for _ in buffer.width(TAB_STOP)..self.width {
             buffer.append(&[char::from(b' ')]);
         }
 
-        terminal.write(0, self.y0, buffer.column(), Color::White, true)?;
+        terminal.write(0, self.y0, buffer.column(), self.color, true)?;
 
         self.updated = false;
         Ok(())
@@ -261,17 +579,63 @@ impl StatusBar {
         self.updated |= true;
     }
 
+    /// Set the color of the theme drawn in reverse video.
+    pub fn set_color(&mut self, color: Color) {
+        if self.color != color {
+            self.color = color;
+            self.updated |= true;
+        }
+    }
+
+    /// Set the number of rows in the buffer shown after the position.
+    pub fn set_total_rows(&mut self, total_rows: usize) {
+        self.updated |= self.total_rows != total_rows;
+        self.total_rows = total_rows;
+    }
+
+    /// Set whether the buffer has changes not saved yet, shown by `[+]` after the filename.
+    pub fn set_modified(&mut self, modified: bool) {
+        self.updated |= self.modified != modified;
+        self.modified = modified;
+    }
+
+    pub fn set_binary(&mut self, binary: bool) {
+        self.updated |= self.binary != binary;
+        self.binary = binary;
+    }
+
+    pub fn set_bom(&mut self, bom: bool) {
+        self.updated |= self.bom != bom;
+        self.bom = bom;
+    }
+
+    pub fn set_encoding(&mut self, encoding: Encoding) {
+        self.updated |= self.encoding != encoding;
+        self.encoding = encoding;
+    }
+
     pub fn set_cursor<P: AsCoordinates>(&mut self, pos: &P) {
         let cur = self.position;
         self.position = pos.as_coordinates();
         self.updated |= cur != self.position;
     }
 
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        self.updated |= self.line_ending != line_ending;
+        self.line_ending = line_ending;
+    }
+
     pub fn set_filename(&mut self, filename: &str) {
         self.filename = Some(filename.to_string());
         self.updated |= true;
     }
 
+    /// Set the text of the search shown after the position, or hide it by `None`.
+    pub fn set_search(&mut self, search: Option<String>) {
+        self.updated |= self.search != search;
+        self.search = search;
+    }
+
     pub fn updated(&self) -> bool {
         self.updated
     }
@@ -284,6 +648,7 @@ pub struct MessageBar {
     y0: usize,
     width: usize,
     message: Row,
+    notice: Option<(Row, Instant)>,
     updated: bool,
     fg_color: Color,
 }
@@ -294,19 +659,30 @@ impl MessageBar {
             y0: screen.height() + 1,
             width: screen.width(),
             message: Row::from(message),
+            notice: None,
             updated: true,
-            fg_color: Color::White,
+            fg_color: Theme::default().message,
         }
     }
 
     pub fn draw(&mut self, terminal: &mut impl Terminal) -> Result<(), Error> {
+        if let Some((_, deadline)) = self.notice.as_ref() {
+            if *deadline <= Instant::now() {
+                self.notice = None;
+                self.updated |= true;
+            }
+        }
+
         if !self.updated {
             return Ok(());
         }
 
-        let mut buffer = self.message.clone();
-        buffer.truncate_width(self.width);
-        terminal.write(0, self.y0, buffer.column(), self.fg_color, false)?;
+        let (mut buffer, color) = match self.notice.as_ref() {
+            Some((notice, _)) => (notice.clone(), Color::Yellow),
+            None => (self.message.clone(), self.fg_color),
+        };
+        buffer.truncate_width(self.width, TAB_STOP);
+        terminal.write(0, self.y0, buffer.column(), color, false)?;
 
         self.updated = false;
         Ok(())
@@ -320,6 +696,11 @@ impl MessageBar {
         &self.message
     }
 
+    /// Returns the notice shown over the message until it expires.
+    pub fn notice(&self) -> Option<&Row> {
+        self.notice.as_ref().map(|(notice, _)| notice)
+    }
+
     pub fn resize(&mut self, screen: &Screen) {
         self.y0 = screen.height() + 1;
         self.width = screen.width();
@@ -327,12 +708,22 @@ impl MessageBar {
     }
 
     pub fn set_fg_color(&mut self, color: Color) {
-        self.fg_color = color;
-        self.updated |= true;
+        if self.fg_color != color {
+            self.fg_color = color;
+            self.updated |= true;
+        }
     }
 
+    /// Set the message, and drop the notice not to hide the message such as a prompt.
     pub fn set_message(&mut self, message: Row) {
         self.message = message;
+        self.notice = None;
+        self.updated |= true;
+    }
+
+    /// Show the notice over the message for the duration, then the message again by `draw`.
+    pub fn set_notice(&mut self, notice: Row, duration: Duration) {
+        self.notice = Some((notice, Instant::now() + duration));
         self.updated |= true;
     }
 
@@ -343,6 +734,101 @@ impl MessageBar {
 
 // -----------------------------------------------------------------------------------------------
 
+/// Rectangle area in terminal.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Region {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// Direction to split the screen into two views.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Split {
+    /// Views are stacked top and bottom.
+    Horizontal,
+    /// Views are placed left and right.
+    Vertical,
+}
+
+impl Split {
+    /// Draw the line between two views.
+    pub fn draw_separator(
+        &self,
+        width: usize,
+        height: usize,
+        terminal: &mut impl Terminal,
+    ) -> Result<(), Error> {
+        let separator = self.separator(width, height);
+        match self {
+            Split::Horizontal => {
+                let line = vec!['-'; separator.width];
+                terminal.write(separator.x, separator.y, &line, Color::White, false)?;
+            }
+            Split::Vertical => {
+                for y in separator.y..separator.y + separator.height {
+                    terminal.write(separator.x, y, &['|'], Color::White, false)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the regions of leading and trailing views in the area at the origin.
+    pub fn regions(&self, width: usize, height: usize) -> (Region, Region) {
+        let separator = self.separator(width, height);
+        match self {
+            Split::Horizontal => (
+                Region {
+                    height: separator.y,
+                    width,
+                    ..Region::default()
+                },
+                Region {
+                    y: separator.y + 1,
+                    height: max(height.saturating_sub(separator.y + 1), 1),
+                    width,
+                    ..Region::default()
+                },
+            ),
+            Split::Vertical => (
+                Region {
+                    width: separator.x,
+                    height,
+                    ..Region::default()
+                },
+                Region {
+                    x: separator.x + 1,
+                    width: max(width.saturating_sub(separator.x + 1), 1),
+                    height,
+                    ..Region::default()
+                },
+            ),
+        }
+    }
+
+    /// Returns the line between two views.
+    fn separator(&self, width: usize, height: usize) -> Region {
+        match self {
+            Split::Horizontal => Region {
+                y: max(height.saturating_sub(1) / 2, 1),
+                width,
+                height: 1,
+                ..Region::default()
+            },
+            Split::Vertical => Region {
+                x: max(width.saturating_sub(1) / 2, 1),
+                width: 1,
+                height,
+                ..Region::default()
+            },
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
 pub fn refresh_screen<T: Terminal, P: AsCoordinates + Coordinates>(
     cursor: &P,
     content: &mut Buffer,
@@ -357,6 +843,8 @@ pub fn refresh_screen<T: Terminal, P: AsCoordinates + Coordinates>(
     select.clear_updated();
 
     status.set_cursor(cursor);
+    status.set_total_rows(content.rows());
+    status.set_modified(content.cached());
     status.draw(terminal)?;
 
     message.draw(terminal)?;
@@ -385,7 +873,14 @@ pub fn resize_screen<T: Terminal>(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::buffer::WIDTH_RANGE_CALLS;
+    use crate::config::Config;
+    use crate::cursor::Cursor;
+    use crate::editor::SelectMode;
+    use crate::highlight::Highlighter;
     use crate::terminal;
+    use std::path::{Path, PathBuf};
+    use unicode_width::UnicodeWidthStr;
 
     #[test]
     fn screen_current() {
@@ -413,6 +908,27 @@ mod tests {
         screen.clear(&mut null).unwrap();
 
         assert!(screen.updated());
+        assert_eq!(&[(0, 0, 1, 1)], null.regions());
+    }
+
+    #[test]
+    fn screen_draw_force_update_keeps_bars() {
+        let mut null = terminal::Null::default();
+        null.set_screen_size(4, 5);
+        let mut screen = Screen::current(&null).unwrap();
+
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'b']);
+        screen.draw(&buf, &Select::default(), &mut null).unwrap();
+        let drawn = null.writes().len();
+
+        screen.force_update();
+        screen.draw(&buf, &Select::default(), &mut null).unwrap();
+
+        assert_eq!(&[(0, 0, 4, 3), (0, 0, 4, 3)], null.regions());
+        let writes = &null.writes()[drawn..];
+        assert_eq!(3, writes.len());
+        assert!(writes.iter().all(|(_, y, _, _, _)| *y < 3));
     }
 
     #[test]
@@ -431,6 +947,373 @@ mod tests {
         assert!(!screen.updated());
     }
 
+    #[test]
+    fn screen_draw_highlight() {
+        let mut null = terminal::Null::default();
+        null.set_screen_size(20, 3);
+        let mut screen = Screen::current(&null).unwrap();
+
+        let mut buf = Buffer::default();
+        buf.set_filename(Path::new("a.rs"));
+        buf.insert_row(&(0, 0), &"x = \"ab\"; // c".chars().collect::<Vec<char>>());
+
+        screen.draw(&buf, &Select::default(), &mut null).unwrap();
+        let spans = null.writes()[..5]
+            .iter()
+            .map(|(x, y, text, color, _)| (*x, *y, text.as_str(), *color))
+            .collect::<Vec<(usize, usize, &str, Color)>>();
+        assert_eq!(
+            vec![
+                (0, 0, "x = ", Color::White),
+                (4, 0, "\"ab\"", Color::Green),
+                (8, 0, "; ", Color::White),
+                (10, 0, "// c", Color::Yellow),
+                (14, 0, "      ", Color::White),
+            ],
+            spans
+        );
+
+        // The span cut at the left edge is drawn from the edge.
+        let mut null = terminal::Null::default();
+        null.set_screen_size(10, 3);
+        let mut screen = Screen::current(&null).unwrap();
+        screen.fit(&buf, &(14, 0));
+        screen.draw(&buf, &Select::default(), &mut null).unwrap();
+        let spans = null.writes()[..4]
+            .iter()
+            .map(|(x, _, text, color, _)| (*x, text.as_str(), *color))
+            .collect::<Vec<(usize, &str, Color)>>();
+        assert_eq!(
+            vec![
+                (0, "ab\"", Color::Green),
+                (3, "; ", Color::White),
+                (5, "// c", Color::Yellow),
+                (9, " ", Color::White),
+            ],
+            spans
+        );
+
+        // Renaming the file switches the highlighter.
+        buf.set_filename(Path::new("a.txt"));
+        let mut null = terminal::Null::default();
+        null.set_screen_size(20, 3);
+        let mut screen = Screen::current(&null).unwrap();
+        screen.draw(&buf, &Select::default(), &mut null).unwrap();
+        let (x, _, text, color, _) = &null.writes()[0];
+        assert_eq!(
+            (0, "x = \"ab\"; // c      ", Color::White),
+            (*x, text.as_str(), *color)
+        );
+
+        // The configured comment prefixes take precedence over the extension.
+        buf.set_settings(Config::parse("comment_prefixes = \";\"").resolve(None));
+        let mut null = terminal::Null::default();
+        null.set_screen_size(20, 3);
+        let mut screen = Screen::current(&null).unwrap();
+        screen.draw(&buf, &Select::default(), &mut null).unwrap();
+        let (x, _, text, color, _) = &null.writes()[1];
+        assert_eq!((8, "; // c", Color::Yellow), (*x, text.as_str(), *color));
+    }
+
+    #[test]
+    fn screen_draw_theme() {
+        let mut null = terminal::Null::default();
+        null.set_screen_size(20, 4);
+        let mut screen = Screen::current(&null).unwrap();
+
+        let mut buf = Buffer::default();
+        buf.set_filename(Path::new("a.rs"));
+        buf.insert_row(&(0, 0), &"x // c".chars().collect::<Vec<char>>());
+        let config = Config::parse("text_color = gray\ncomment_color = cyan");
+        buf.set_settings(config.resolve(Some(Path::new("a.rs"))));
+
+        screen.draw(&buf, &Select::default(), &mut null).unwrap();
+        let writes = null
+            .writes()
+            .iter()
+            .map(|(x, y, text, color, rev)| (*x, *y, text.as_str(), *color, *rev))
+            .collect::<Vec<(usize, usize, &str, Color, bool)>>();
+        assert_eq!(
+            vec![
+                (0, 0, "x ", Color::Gray, false),
+                (2, 0, "// c", Color::Cyan, false),
+                (6, 0, "              ", Color::Gray, false),
+                (0, 1, "~", Color::Gray, false),
+            ],
+            writes[..4]
+        );
+    }
+
+    /// Highlights each vowel as a string.
+    struct Vowels;
+
+    impl Highlighter for Vowels {
+        fn spans(&self, row: &[char], _: usize) -> Vec<(Range<usize>, Syntax)> {
+            (0..row.len())
+                .filter(|&x| "aeiou".contains(row[x]))
+                .map(|x| (x..x + 1, Syntax::String))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn screen_draw_spans_highlighter() {
+        let mut null = terminal::Null::default();
+        null.set_screen_size(20, 4);
+        let screen = Screen::current(&null).unwrap();
+        let settings = Settings::default();
+        let theme = settings.theme();
+
+        let row = Row::from("ai\tou");
+        let buffer = row.slice_width(0..screen.right() + 1, settings.tab_width());
+        let spans = Vowels.spans(row.column(), 0);
+        screen
+            .draw_spans((0, 0), &row, &buffer, &spans, &settings, &mut null)
+            .unwrap();
+
+        let writes = null
+            .writes()
+            .iter()
+            .map(|(x, y, text, color, rev)| (*x, *y, text.as_str(), *color, *rev))
+            .collect::<Vec<(usize, usize, &str, Color, bool)>>();
+        let string = theme.syntax(Syntax::String);
+        assert_eq!(
+            vec![
+                (0, 0, "a", string, false),
+                (1, 0, "i", string, false),
+                (2, 0, "      ", theme.text, false),
+                (8, 0, "o", string, false),
+                (9, 0, "u", string, false),
+                (10, 0, "          ", theme.text, false),
+            ],
+            writes
+        );
+    }
+
+    #[test]
+    fn status_bar_set_color() {
+        let mut null = terminal::Null::default();
+        null.set_screen_size(20, 3);
+        let screen = Screen::current(&null).unwrap();
+        let mut bar = StatusBar::new(&screen, None);
+        bar.draw(&mut null).unwrap();
+
+        bar.set_color(Color::White);
+        assert!(!bar.updated());
+
+        bar.set_color(Color::Blue);
+        assert!(bar.updated());
+        bar.draw(&mut null).unwrap();
+        let (_, _, _, color, rev) = null.writes().last().unwrap();
+        assert_eq!((Color::Blue, true), (*color, *rev));
+    }
+
+    #[test]
+    fn screen_draw_select() {
+        let mut null = terminal::Null::default();
+        null.set_screen_size(10, 5);
+        let mut screen = Screen::current(&null).unwrap();
+
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'b', 'c']);
+        buf.insert_row(&(0, 1), &['\t', 'd']);
+        buf.insert_row(&(0, 2), &['e', 'f', 'g']);
+
+        let mut select = Select::default();
+        select.set_start(&Cursor::from((1, 2)), SelectMode::None);
+        select.set_end(&Cursor::from((1, 0)));
+
+        WIDTH_RANGE_CALLS.with(|c| c.set(0));
+        screen.draw(&buf, &select, &mut null).unwrap();
+
+        assert!(WIDTH_RANGE_CALLS.with(|c| c.get()) <= 2 * 3);
+        assert_eq!(&[(1, 0, 2), (0, 1, 9), (0, 2, 1)], null.attributes());
+    }
+
+    #[test]
+    fn screen_draw_select_rectangle() {
+        let mut null = terminal::Null::default();
+        null.set_screen_size(10, 5);
+        let mut screen = Screen::current(&null).unwrap();
+
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'b', 'c']);
+        buf.insert_row(&(0, 1), &['あ', 'd']);
+
+        let mut select = Select::default();
+        select.set_start(&Cursor::from((2, 1)), SelectMode::Rectangle);
+        select.set_end(&Cursor::from((1, 0)));
+
+        screen.draw(&buf, &select, &mut null).unwrap();
+
+        assert_eq!(&[(1, 0, 1), (2, 1, 1)], null.attributes());
+    }
+
+    #[test]
+    fn screen_decorate() {
+        let mut null = terminal::Null::default();
+        null.set_screen_size(10, 5);
+        let screen = Screen::current(&null).unwrap();
+
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'a', 'a', 'b', 'A', 'a']);
+        buf.insert_row(&(0, 1), &['\t', 'a', 'a']);
+        buf.insert_row(&(0, 2), &['a', 'b']);
+
+        screen
+            .decorate(&buf, &Row::from("aa"), false, &mut null)
+            .unwrap();
+        assert_eq!(&[(0, 0, 2), (8, 1, 2)], null.attributes());
+
+        let mut null = terminal::Null::default();
+        null.set_screen_size(10, 5);
+        screen
+            .decorate(&buf, &Row::from("aa"), true, &mut null)
+            .unwrap();
+        assert_eq!(&[(0, 0, 2), (4, 0, 2), (8, 1, 2)], null.attributes());
+    }
+
+    #[test]
+    fn screen_decorate_scrolled() {
+        let mut null = terminal::Null::default();
+        null.set_screen_size(4, 4);
+        let mut screen = Screen::current(&null).unwrap();
+
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'b', 'a', 'b', 'a', 'b']);
+        buf.insert_row(&(0, 1), &['a', 'b']);
+        buf.insert_row(&(0, 2), &['c', 'a', 'b']);
+        screen.fit(&buf, &(6, 0));
+
+        // The match cut at the left edge is highlighted partially.
+        screen
+            .decorate(&buf, &Row::from("ab"), false, &mut null)
+            .unwrap();
+        assert_eq!(&[(0, 0, 1), (1, 0, 2)], null.attributes());
+    }
+
+    #[test]
+    fn screen_draw_cursor() {
+        let mut null = terminal::Null::default();
+        null.set_screen_size(10, 5);
+        let mut screen = Screen::current(&null).unwrap();
+
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a', 'あ']);
+
+        screen.set_cursor(&(1, 0));
+        screen.draw_cursor(&buf, &mut null).unwrap();
+        screen.set_cursor(&(0, 0));
+        screen.draw_cursor(&buf, &mut null).unwrap();
+        screen.set_cursor(&(2, 0));
+        screen.draw_cursor(&buf, &mut null).unwrap();
+
+        assert_eq!(&[(1, 0, 2), (0, 0, 1), (3, 0, 1)], null.attributes());
+    }
+
+    #[test]
+    fn screen_draw_cursor_moved() {
+        let mut null = terminal::Null::default();
+        null.set_screen_size(10, 5);
+        let mut screen = Screen::current(&null).unwrap();
+
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a']);
+        buf.insert_row(&(0, 1), &['あ']);
+
+        screen.set_cursor(&(0, 1));
+        screen.draw(&buf, &Select::default(), &mut null).unwrap();
+        buf.clear_updated();
+        let drawn = null.writes().len();
+
+        screen.set_cursor(&(0, 0));
+        assert!(screen.updated());
+        screen.draw(&buf, &Select::default(), &mut null).unwrap();
+        screen.draw_cursor(&buf, &mut null).unwrap();
+
+        let rows = null.writes()[drawn..]
+            .iter()
+            .filter(|(_, _, text, _, _)| text.starts_with('あ') || text.starts_with('a'))
+            .map(|(_, y, _, _, _)| *y)
+            .collect::<Vec<usize>>();
+        assert_eq!(vec![1], rows);
+        assert_eq!(Some(&(0, 0, 1)), null.attributes().last());
+        assert!(!screen.updated());
+    }
+
+    #[test]
+    fn screen_draw_banner() {
+        for (width, height, y, x) in [(80, 24, 7, 26), (40, 11, 3, 6), (10, 5, 1, 0)] {
+            let mut null = terminal::Null::default();
+            null.set_screen_size(width, height);
+            let mut screen = Screen::current(&null).unwrap();
+
+            screen
+                .draw(&Buffer::default(), &Select::default(), &mut null)
+                .unwrap();
+
+            let banner = null.writes().iter().find(|w| w.1 == y).unwrap();
+            let text = banner.2.trim_start_matches(['~', ' ']);
+            assert_eq!(x, banner.2.width() - text.width());
+            assert!(banner.2.width() <= width);
+            assert!(TEXT_BANNER.starts_with(text));
+        }
+    }
+
+    #[test]
+    fn screen_draw_banner_named() {
+        let mut null = terminal::Null::default();
+        null.set_screen_size(80, 24);
+        let mut screen = Screen::current(&null).unwrap();
+
+        let mut buf = Buffer::default();
+        buf.set_filename(&PathBuf::from("a.txt"));
+
+        screen.draw(&buf, &Select::default(), &mut null).unwrap();
+
+        assert!(null.writes().iter().all(|w| w.2 == "~"));
+    }
+
+    #[test]
+    fn screen_clamp_to() {
+        let mut null = terminal::Null::default();
+        null.set_screen_size(5, 5);
+        let mut screen = Screen::current(&null).unwrap();
+        screen.top0 = 7;
+        screen.updated = false;
+
+        let mut buf = Buffer::default();
+        for y in 0..5 {
+            buf.insert_row(&(0, y), &['a']);
+        }
+
+        assert!(screen.clamp_to(&buf));
+        assert_eq!(3, screen.top());
+        assert!(screen.updated());
+
+        assert!(!screen.clamp_to(&buf));
+        assert!(screen.clamp_to(&Buffer::default()));
+        assert_eq!(0, screen.top());
+    }
+
+    #[test]
+    fn screen_draw_filler() {
+        let mut null = terminal::Null::default();
+        null.set_screen_size(3, 5);
+        let mut screen = Screen::current(&null).unwrap();
+        screen.set_filler(' ');
+
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a']);
+
+        screen.draw(&buf, &Select::default(), &mut null).unwrap();
+
+        assert_eq!(3, null.writes().len());
+        assert_eq!("a  ", null.writes()[0].2);
+        assert_eq!(" ", null.writes()[1].2);
+        assert_eq!(" ", null.writes()[2].2);
+    }
+
     #[test]
     fn screen_fit_x_right() {
         let mut null = terminal::Null::default();
@@ -510,6 +1393,22 @@ mod tests {
         assert!(screen.updated());
     }
 
+    #[test]
+    fn screen_fit_y_past_end() {
+        let mut null = terminal::Null::default();
+        null.set_screen_size(3, 4);
+        let mut screen = Screen::current(&null).unwrap();
+
+        let mut buf = Buffer::default();
+        for y in 0..screen.height() {
+            buf.insert_row(&(0, y), &['a']);
+        }
+
+        // The row after the last row is where the cursor can be.
+        assert!(screen.fit(&buf, &(0, buf.rows())));
+        assert_eq!(buf.rows(), screen.bottom());
+    }
+
     #[test]
     fn screen_fit_y_up() {
         let mut null = terminal::Null::default();
@@ -550,6 +1449,168 @@ mod tests {
         assert!(!screen.updated());
     }
 
+    #[test]
+    fn screen_keep_row() {
+        let mut null = terminal::Null::default();
+        null.set_screen_size(3, 7);
+        let mut screen = Screen::current(&null).unwrap();
+        screen.updated = false;
+        screen.top0 = 2;
+
+        let moved = screen.keep_row(&(0, 6), 3);
+
+        assert!(moved);
+        assert_eq!(3, screen.top());
+        assert!(screen.updated());
+    }
+
+    #[test]
+    fn screen_keep_row_clamp() {
+        let mut null = terminal::Null::default();
+        null.set_screen_size(3, 5);
+        let mut screen = Screen::current(&null).unwrap();
+        screen.updated = false;
+
+        let moved = screen.keep_row(&(0, 1), 4);
+
+        assert!(!moved);
+        assert_eq!(0, screen.top());
+
+        screen.keep_row(&(0, 6), 4);
+        assert_eq!(4, screen.top());
+    }
+
+    #[test]
+    fn screen_set_region() {
+        let mut null = terminal::Null::default();
+        null.set_screen_size(20, 10);
+        let mut screen = Screen::current(&null).unwrap();
+        screen.updated = false;
+
+        let region = Region {
+            x: 11,
+            y: 0,
+            width: 9,
+            height: 8,
+        };
+        assert!(screen.set_region(&region));
+        assert!(!screen.set_region(&region));
+
+        assert_eq!((11, 0), screen.origin());
+        assert_eq!(9, screen.width());
+        assert_eq!(8, screen.right());
+        assert!(screen.updated());
+
+        screen.fit(&Buffer::default(), &(12, 3));
+        assert_eq!((11 + 12 - screen.left(), 3), screen.position(&(12, 3)));
+
+        screen.resize(10, 20);
+        assert_eq!((0, 0), screen.origin());
+    }
+
+    #[test]
+    fn screen_draw_region() {
+        let mut null = terminal::Null::default();
+        null.set_screen_size(10, 6);
+        let mut screen = Screen::current(&null).unwrap();
+        screen.set_region(&Region {
+            x: 0,
+            y: 2,
+            width: 10,
+            height: 2,
+        });
+
+        let mut buf = Buffer::default();
+        buf.insert_row(&(0, 0), &['a']);
+        buf.insert_row(&(0, 1), &[]);
+        buf.insert_row(&(0, 2), &['b']);
+        screen.keep_row(&(0, 1), 0);
+
+        screen.draw(&buf, &Select::default(), &mut null).unwrap();
+
+        assert_eq!(
+            vec![(0, 2, " ".repeat(10)), (0, 3, format!("{:10}", "b"))],
+            null.writes()
+                .iter()
+                .map(|w| (w.0, w.1, w.2.clone()))
+                .collect::<Vec<(usize, usize, String)>>()
+        );
+    }
+
+    #[test]
+    fn split_regions_horizontal() {
+        let (leading, trailing) = Split::Horizontal.regions(80, 22);
+
+        assert_eq!(
+            Region {
+                x: 0,
+                y: 0,
+                width: 80,
+                height: 10
+            },
+            leading
+        );
+        assert_eq!(
+            Region {
+                x: 0,
+                y: 11,
+                width: 80,
+                height: 11
+            },
+            trailing
+        );
+    }
+
+    #[test]
+    fn split_regions_vertical() {
+        let (leading, trailing) = Split::Vertical.regions(81, 22);
+
+        assert_eq!(
+            Region {
+                x: 0,
+                y: 0,
+                width: 40,
+                height: 22
+            },
+            leading
+        );
+        assert_eq!(
+            Region {
+                x: 41,
+                y: 0,
+                width: 40,
+                height: 22
+            },
+            trailing
+        );
+    }
+
+    #[test]
+    fn split_regions_small() {
+        let (leading, trailing) = Split::Horizontal.regions(10, 2);
+        assert_eq!((0, 1), (leading.y, leading.height));
+        assert_eq!((2, 1), (trailing.y, trailing.height));
+
+        let (leading, trailing) = Split::Vertical.regions(3, 2);
+        assert_eq!((0, 1), (leading.x, leading.width));
+        assert_eq!((2, 1), (trailing.x, trailing.width));
+    }
+
+    #[test]
+    fn split_draw_separator() {
+        let mut null = terminal::Null::default();
+
+        Split::Vertical.draw_separator(5, 2, &mut null).unwrap();
+        Split::Horizontal.draw_separator(3, 4, &mut null).unwrap();
+
+        let writes = null
+            .writes()
+            .iter()
+            .map(|w| (w.0, w.1, w.2.as_str()))
+            .collect::<Vec<(usize, usize, &str)>>();
+        assert_eq!(vec![(2, 0, "|"), (2, 1, "|"), (0, 1, "---")], writes);
+    }
+
     #[test]
     fn screen_move_down() {
         let mut buf = Buffer::default();
@@ -620,6 +1681,34 @@ mod tests {
         assert!(!screen.updated());
     }
 
+    #[test]
+    fn screen_half_height() {
+        let mut null = terminal::Null::default();
+        for (height, half) in [(9, 3), (8, 3), (3, 1)] {
+            null.set_screen_size(1, height);
+            let screen = Screen::current(&null).unwrap();
+            assert_eq!(half, screen.half_height());
+        }
+    }
+
+    #[test]
+    fn screen_scroll_down_at_end() {
+        let mut buf = Buffer::default();
+        for y in 0..10 {
+            buf.insert_row(&(0, y), &['a']);
+        }
+
+        let mut null = terminal::Null::default();
+        null.set_screen_size(1, 7);
+        let mut screen = Screen::current(&null).unwrap();
+        screen.top0 = 5;
+
+        assert!(screen.scroll_down(&buf, 2));
+        assert_eq!(6, screen.top());
+        assert!(!screen.scroll_down(&buf, 2));
+        assert_eq!(6, screen.top());
+    }
+
     // -------------------------------------------------------------------------------------------
 
     #[test]
@@ -634,6 +1723,135 @@ mod tests {
         bar.draw(&mut null).unwrap();
     }
 
+    #[test]
+    fn status_bar_draw_search() {
+        let mut null = terminal::Null::default();
+        null.set_screen_size(50, 3);
+        let screen = Screen::current(&null).unwrap();
+
+        let mut bar = StatusBar::new(&screen, None);
+        bar.draw(&mut null).unwrap();
+        assert!(!bar.updated());
+
+        bar.set_search(Some("/ab 2/3".to_string()));
+        assert!(bar.updated());
+        bar.draw(&mut null).unwrap();
+
+        let line = &null.writes().last().unwrap().2;
+        assert!(line.starts_with(" \"<buffered>\"  1:1/0  100%  crlf  /ab 2/3 "));
+
+        bar.set_search(Some("/ab 2/3".to_string()));
+        assert!(!bar.updated());
+    }
+
+    #[test]
+    fn status_bar_draw_modified() {
+        let mut null = terminal::Null::default();
+        null.set_screen_size(40, 3);
+        let screen = Screen::current(&null).unwrap();
+        let mut bar = StatusBar::new(&screen, Some("a.txt"));
+        bar.draw(&mut null).unwrap();
+
+        bar.set_modified(true);
+        assert!(bar.updated());
+        bar.draw(&mut null).unwrap();
+        let line = &null.writes().last().unwrap().2;
+        assert!(line.starts_with(" \"a.txt\" [+]  1:1"), "{:?}", line);
+
+        bar.set_modified(true);
+        assert!(!bar.updated());
+
+        bar.set_modified(false);
+        assert!(bar.updated());
+        bar.draw(&mut null).unwrap();
+        let line = &null.writes().last().unwrap().2;
+        assert!(line.starts_with(" \"a.txt\"  1:1"), "{:?}", line);
+    }
+
+    #[test]
+    fn status_bar_draw_total_rows() {
+        let mut null = terminal::Null::default();
+        null.set_screen_size(32, 3);
+        let screen = Screen::current(&null).unwrap();
+        let mut bar = StatusBar::new(&screen, Some("a.txt"));
+
+        bar.set_cursor(&(11, 339));
+        bar.set_total_rows(1024);
+        assert!(bar.updated());
+        bar.draw(&mut null).unwrap();
+        let line = &null.writes().last().unwrap().2;
+        assert_eq!(" \"a.txt\"  12:340/1024  33%  crlf", line);
+
+        bar.set_total_rows(1024);
+        assert!(!bar.updated());
+
+        // the search is dropped first, then the line ending
+        bar.set_search(Some("/ab 1/2".to_string()));
+        bar.draw(&mut null).unwrap();
+        let line = &null.writes().last().unwrap().2;
+        assert_eq!(" \"a.txt\"  12:340/1024  33%  crlf", line);
+
+        bar.set_total_rows(10240000);
+        bar.draw(&mut null).unwrap();
+        let line = &null.writes().last().unwrap().2;
+        assert_eq!(" \"a.txt\"  12:340/10240000  0%   ", line);
+    }
+
+    #[test]
+    fn status_bar_draw_percent() {
+        let mut null = terminal::Null::default();
+        null.set_screen_size(40, 3);
+        let screen = Screen::current(&null).unwrap();
+        let mut bar = StatusBar::new(&screen, Some("a.txt"));
+
+        // the empty buffer and the row after the last row are at the end
+        for (y, rows, expected) in [
+            (0, 0, "1:1/0  100%"),
+            (0, 3, "1:1/3  33%"),
+            (1, 3, "1:2/3  66%"),
+            (2, 3, "1:3/3  100%"),
+            (3, 3, "1:4/3  100%"),
+        ] {
+            bar.set_cursor(&(0, y));
+            bar.set_total_rows(rows);
+            bar.draw(&mut null).unwrap();
+
+            let line = &null.writes().last().unwrap().2;
+            let expected = format!(" \"a.txt\"  {}  crlf", expected);
+            assert!(line.starts_with(&expected), "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn status_bar_draw_encoding() {
+        let mut null = terminal::Null::default();
+        null.set_screen_size(40, 3);
+        let screen = Screen::current(&null).unwrap();
+        let mut bar = StatusBar::new(&screen, None);
+
+        for (encoding, bom, expected) in [
+            (Encoding::Utf8, false, " \"<buffered>\"  1:1"),
+            (Encoding::Utf8, true, " \"<buffered>\" [UTF-8 BOM]  1:1"),
+            (
+                Encoding::Utf16Le,
+                true,
+                " \"<buffered>\" [UTF-16LE BOM]  1:1",
+            ),
+            (
+                Encoding::ShiftJis,
+                false,
+                " \"<buffered>\" [Shift_JIS]  1:1",
+            ),
+        ] {
+            bar.set_encoding(encoding);
+            bar.set_bom(bom);
+            bar.draw(&mut null).unwrap();
+
+            let line = &null.writes().last().unwrap().2;
+            assert!(line.starts_with(expected), "{:?}", line);
+        }
+    }
+
     // -------------------------------------------------------------------------------------------
 
     #[test]
@@ -646,4 +1864,33 @@ mod tests {
 
         bar.draw(&mut null).unwrap();
     }
+
+    #[test]
+    fn message_bar_draw_notice() {
+        let mut null = terminal::Null::default();
+        null.set_screen_size(20, 3);
+        let screen = Screen::current(&null).unwrap();
+        let mut bar = MessageBar::new(&screen, "menu");
+        bar.draw(&mut null).unwrap();
+
+        bar.set_notice(Row::from("saved"), Duration::from_secs(60));
+        bar.draw(&mut null).unwrap();
+        let (_, _, text, color, _) = null.writes().last().unwrap();
+        assert_eq!(("saved", Color::Yellow), (text.as_str(), *color));
+        assert!(!bar.updated());
+
+        // The message is shown again after the notice expired.
+        bar.set_notice(Row::from("saved"), Duration::ZERO);
+        bar.draw(&mut null).unwrap();
+        let (_, _, text, color, _) = null.writes().last().unwrap();
+        assert_eq!(("menu", Color::White), (text.as_str(), *color));
+        assert!(bar.notice().is_none());
+
+        // The message such as a prompt drops the notice.
+        bar.set_notice(Row::from("saved"), Duration::from_secs(60));
+        bar.set_message(Row::from("prompt"));
+        bar.draw(&mut null).unwrap();
+        let (_, _, text, _, _) = null.writes().last().unwrap();
+        assert_eq!("prompt", text);
+    }
 }
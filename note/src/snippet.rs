@@ -0,0 +1,222 @@
+use crate::buffer::{Buffer, Row};
+use std::collections::{HashMap, VecDeque};
+
+/// Template text with tab stops.
+///
+/// `$1`, `$2`, ... are tab stops in order, and `$0` is the last stop.
+/// `$$` is a literal `$`.
+#[derive(Clone, Default)]
+pub struct Snippet {
+    rows: Vec<Row>,
+    stops: Vec<(usize, usize)>,
+}
+
+impl From<&str> for Snippet {
+    fn from(value: &str) -> Self {
+        let mut rows = vec![];
+        let mut stops: Vec<(usize, (usize, usize))> = vec![];
+
+        for (y, line) in value.split('\n').enumerate() {
+            let mut column = vec![];
+            let mut chars = line.chars().peekable();
+            while let Some(ch) = chars.next() {
+                if ch != '$' {
+                    column.push(ch);
+                    continue;
+                }
+
+                let mut digits = String::new();
+                while let Some(d) = chars.next_if(|c| c.is_ascii_digit()) {
+                    digits.push(d);
+                }
+
+                match digits.parse::<usize>() {
+                    Ok(number) => {
+                        if stops.iter().all(|&(n, _)| n != number) {
+                            stops.push((number, (column.len(), y)));
+                        }
+                    }
+                    Err(_) => {
+                        column.push('$');
+                        chars.next_if_eq(&'$');
+                    }
+                }
+            }
+            rows.push(Row::from(column));
+        }
+
+        // `$0` is the last.
+        stops.sort_by_key(|&(n, _)| if n == 0 { usize::MAX } else { n });
+
+        Snippet {
+            rows,
+            stops: stops.into_iter().map(|(_, at)| at).collect(),
+        }
+    }
+}
+
+impl Snippet {
+    pub fn rows(&self) -> &[Row] {
+        &self.rows
+    }
+
+    /// Returns the tab stops relative to the start of the template.
+    pub fn stops(&self) -> &[(usize, usize)] {
+        &self.stops
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+/// Snippets by trigger name.
+#[derive(Clone, Default)]
+pub struct Snippets {
+    snippets: HashMap<String, Snippet>,
+}
+
+impl Snippets {
+    pub fn get(&self, name: &str) -> Option<&Snippet> {
+        self.snippets.get(name)
+    }
+
+    pub fn insert(&mut self, name: &str, template: &str) {
+        self.snippets
+            .insert(name.to_string(), Snippet::from(template));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snippets.is_empty()
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Snippet> {
+        self.snippets.remove(name)
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+/// Remaining tab stops of the expanded snippet.
+#[derive(Clone, Debug, Default)]
+pub struct TabStops {
+    stops: VecDeque<(usize, usize)>,
+    current: (usize, usize),
+    rows: usize,
+    len: usize,
+}
+
+impl TabStops {
+    /// Create from absolute positions of tab stops.
+    pub fn new(stops: &[(usize, usize)]) -> Self {
+        TabStops {
+            stops: stops.iter().cloned().collect(),
+            ..TabStops::default()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stops.is_empty()
+    }
+
+    /// Returns the next tab stop.
+    /// The stops are shifted by the text edited after the previous stop.
+    pub fn next(&mut self, content: &Buffer) -> Option<(usize, usize)> {
+        if self.rows != 0 {
+            let (x0, y0) = self.current;
+            let len = content.row_char_len(&self.current);
+            let rows = content.rows();
+
+            for (x, y) in self.stops.iter_mut() {
+                if *y == y0 && x0 <= *x {
+                    *x = (*x + len).saturating_sub(self.len);
+                } else if y0 < *y {
+                    *y = (*y + rows).saturating_sub(self.rows);
+                }
+            }
+        }
+
+        let next = self.stops.pop_front()?;
+        self.current = next;
+        self.rows = content.rows();
+        self.len = content.row_char_len(&next);
+        Some(next)
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snippet_from_plain() {
+        let snippet = Snippet::from("abc");
+
+        assert_eq!(1, snippet.rows().len());
+        assert_eq!("abc", snippet.rows()[0].to_string_at(0));
+        assert!(snippet.stops().is_empty());
+    }
+
+    #[test]
+    fn snippet_from_stops() {
+        let snippet = Snippet::from("fn $1($2) {\n    $0\n}");
+
+        assert_eq!(3, snippet.rows().len());
+        assert_eq!("fn () {", snippet.rows()[0].to_string_at(0));
+        assert_eq!("    ", snippet.rows()[1].to_string_at(0));
+        assert_eq!("}", snippet.rows()[2].to_string_at(0));
+        assert_eq!(&[(3, 0), (4, 0), (4, 1)], snippet.stops());
+    }
+
+    #[test]
+    fn snippet_from_stops_order() {
+        let snippet = Snippet::from("$2a$1b$2");
+
+        assert_eq!("ab", snippet.rows()[0].to_string_at(0));
+        assert_eq!(&[(1, 0), (0, 0)], snippet.stops());
+    }
+
+    #[test]
+    fn snippet_from_dollar() {
+        let snippet = Snippet::from("$$1 $a$");
+
+        assert_eq!("$1 $a$", snippet.rows()[0].to_string_at(0));
+        assert!(snippet.stops().is_empty());
+    }
+
+    #[test]
+    fn snippets_get() {
+        let mut snippets = Snippets::default();
+        snippets.insert("if", "if $1 {\n}");
+
+        assert_eq!(&[(3, 0)], snippets.get("if").unwrap().stops());
+        assert!(snippets.get("for").is_none());
+    }
+
+    #[test]
+    fn tab_stops_next() {
+        let mut content = Buffer::default();
+        content.insert_row(&(0, 0), &['(', ',', ')']);
+
+        let mut stops = TabStops::new(&[(1, 0), (2, 0)]);
+        assert_eq!(Some((1, 0)), stops.next(&content));
+
+        content.insert_char(&(1, 0), 'a');
+        content.insert_char(&(2, 0), 'b');
+        assert_eq!(Some((4, 0)), stops.next(&content));
+        assert_eq!(None, stops.next(&content));
+    }
+
+    #[test]
+    fn tab_stops_next_rows() {
+        let mut content = Buffer::default();
+        content.insert_row(&(0, 0), &['{']);
+        content.insert_row(&(0, 1), &['}']);
+
+        let mut stops = TabStops::new(&[(1, 0), (1, 1)]);
+        assert_eq!(Some((1, 0)), stops.next(&content));
+
+        content.insert_row(&(0, 1), &['a']);
+        assert_eq!(Some((1, 2)), stops.next(&content));
+    }
+}
@@ -1,40 +1,65 @@
+use crate::buffer::{char_width, Row, TAB_STOP};
 use crate::error::Error;
 use crate::key_event::{Event, KeyEvent, KeyModifier};
+#[cfg(unix)]
+use crate::unix;
+#[cfg(windows)]
 use crate::windows;
 use crate::Color;
-use std::sync::mpsc::channel;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::{Mutex, OnceLock};
 use std::thread;
 use std::time::Duration;
 
+pub const POLL_INTERVAL: Duration = Duration::from_millis(16);
+
+/// Receiver of the events read in a thread, kept by each terminal.
+type EventReceiver = OnceLock<Mutex<Receiver<Result<Event, Error>>>>;
+
 pub trait Terminal {
     fn read_event() -> Result<Event, Error>;
 
-    fn read_event_timeout() -> Result<Event, Error> {
-        let (sender, receiver) = channel();
-
-        thread::spawn(move || sender.send(Self::read_event()).unwrap());
+    /// Returns the event, or `None` if no event is read within the timeout.
+    fn poll_event(timeout: Duration) -> Result<Option<Event>, Error>;
 
+    fn read_event_timeout() -> Result<Event, Error> {
         loop {
-            if let Ok(ch) = receiver.recv_timeout(Duration::from_millis(16)) {
-                return ch;
+            if let Some(event) = Self::poll_event(POLL_INTERVAL)? {
+                return Ok(event);
             }
         }
     }
 
     fn alternate_screen_buffer(&mut self) -> Result<(), Error>;
 
+    /// Blank the cells in the rectangle, and keep the cursor position.
+    fn clear_region(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<(), Error>;
+
     fn clear_screen(&mut self) -> Result<(), Error>;
 
     fn enable_raw_mode(&mut self) -> Result<(), Error>;
 
+    /// Returns the text in the system clipboard, or `None` if it has no text.
+    fn get_clipboard(&mut self) -> Result<Option<String>, Error>;
+
     fn get_cursor_position(&self) -> Result<(usize, usize), Error>;
 
     fn get_screen_size(&self) -> Result<(usize, usize), Error>;
 
-    fn scroll_up(&self, height: usize) -> Result<(), Error>;
+    fn set_clipboard(&mut self, text: &str) -> Result<(), Error>;
 
     fn set_cursor_position(&mut self, x: usize, y: usize) -> Result<(), Error>;
 
+    fn set_cursor_visible(&mut self, visible: bool) -> Result<(), Error>;
+
     fn set_text_attribute(&mut self, x: usize, y: usize, length: usize) -> Result<(), Error>;
 
     fn write(
@@ -47,28 +72,113 @@ pub trait Terminal {
     ) -> Result<(), Error>;
 }
 
+/// Returns the event read by `read_event` in the thread started at first,
+/// or `None` if no event is read within the timeout.
+fn poll_thread(
+    receiver: &'static EventReceiver,
+    read_event: fn() -> Result<Event, Error>,
+    timeout: Duration,
+) -> Result<Option<Event>, Error> {
+    // Read events in a single thread so that events are not lost by timeout.
+    let receiver = receiver.get_or_init(|| {
+        let (sender, receiver) = channel();
+        thread::spawn(move || while sender.send(read_event()).is_ok() {});
+        Mutex::new(receiver)
+    });
+
+    match receiver.lock().unwrap().recv_timeout(timeout) {
+        Ok(event) => event.map(Some),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Returns the leading characters of the row written from the column within the width,
+/// so that no character is wrapped or cut at the right edge.
+///
+/// ```
+/// use note::terminal::fit_width;
+///
+/// assert_eq!(&['a', 'b'], fit_width(1, &['a', 'b', 'c'], 3));
+/// ```
+pub fn fit_width(x: usize, row: &[char], width: usize) -> &[char] {
+    let mut rest = width.saturating_sub(x);
+    for (i, &ch) in row.iter().enumerate() {
+        match rest.checked_sub(char_width(ch)) {
+            Some(r) => rest = r,
+            None => return &row[..i],
+        }
+    }
+    row
+}
+
 // -----------------------------------------------------------------------------------------------
 
-pub struct WindowsCon;
+/// Console restored to the state before changed when dropped.
+#[cfg(windows)]
+#[derive(Default)]
+pub struct WindowsCon {
+    state: Option<windows::ConsoleState>,
+}
+
+#[cfg(windows)]
+impl WindowsCon {
+    fn save_state(&mut self) -> Result<(), Error> {
+        if self.state.is_none() {
+            self.state = Some(windows::get_console_state()?);
+        }
+        Ok(())
+    }
+}
 
+#[cfg(windows)]
+impl Drop for WindowsCon {
+    fn drop(&mut self) {
+        if let Some(state) = self.state.take() {
+            let _ = windows::set_console_state(&state);
+        }
+    }
+}
+
+#[cfg(windows)]
 impl Terminal for WindowsCon {
     fn read_event() -> Result<Event, Error> {
         windows::read_event()
     }
 
+    fn poll_event(timeout: Duration) -> Result<Option<Event>, Error> {
+        static RECEIVER: EventReceiver = OnceLock::new();
+        poll_thread(&RECEIVER, Self::read_event, timeout)
+    }
+
     fn alternate_screen_buffer(&mut self) -> Result<(), Error> {
+        self.save_state()?;
         windows::alternate_screen_buffer()?;
         Ok(())
     }
 
+    fn clear_region(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<(), Error> {
+        windows::clear_region(x, y, width, height)
+    }
+
     fn clear_screen(&mut self) -> Result<(), Error> {
         windows::clear_screen()
     }
 
     fn enable_raw_mode(&mut self) -> Result<(), Error> {
+        self.save_state()?;
         windows::enable_raw_mode()
     }
 
+    fn get_clipboard(&mut self) -> Result<Option<String>, Error> {
+        windows::get_clipboard()
+    }
+
     fn get_cursor_position(&self) -> Result<(usize, usize), Error> {
         windows::get_cursor_position()
     }
@@ -77,14 +187,18 @@ impl Terminal for WindowsCon {
         windows::get_screen_size()
     }
 
-    fn scroll_up(&self, height: usize) -> Result<(), Error> {
-        windows::scroll_up_buffer(height)
+    fn set_clipboard(&mut self, text: &str) -> Result<(), Error> {
+        windows::set_clipboard(text)
     }
 
     fn set_cursor_position(&mut self, x: usize, y: usize) -> Result<(), Error> {
         windows::set_cursor_position(x, y)
     }
 
+    fn set_cursor_visible(&mut self, visible: bool) -> Result<(), Error> {
+        windows::set_cursor_visible(visible)
+    }
+
     fn set_text_attribute(&mut self, x: usize, y: usize, length: usize) -> Result<(), Error> {
         windows::set_text_attribute(x, y, length)
     }
@@ -103,28 +217,214 @@ impl Terminal for WindowsCon {
 
 // -----------------------------------------------------------------------------------------------
 
+/// Terminal of VT100 escape sequences restored to the state before changed when dropped.
+#[cfg(unix)]
+#[derive(Default)]
+pub struct UnixTty {
+    state: Option<unix::TtyState>,
+    cursor: (usize, usize),
+    cursor_hidden: bool,
+    cells: unix::Cells,
+}
+
+#[cfg(unix)]
+impl UnixTty {
+    fn save_state(&mut self) -> Result<(), Error> {
+        if self.state.is_none() {
+            self.state = Some(unix::get_tty_state()?);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+impl Drop for UnixTty {
+    fn drop(&mut self) {
+        if let Some(state) = self.state.take() {
+            let _ = unix::set_tty_state(&state);
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Terminal for UnixTty {
+    fn read_event() -> Result<Event, Error> {
+        unix::read_event()
+    }
+
+    fn poll_event(timeout: Duration) -> Result<Option<Event>, Error> {
+        static RECEIVER: EventReceiver = OnceLock::new();
+        poll_thread(&RECEIVER, Self::read_event, timeout)
+    }
+
+    fn alternate_screen_buffer(&mut self) -> Result<(), Error> {
+        self.save_state()?;
+        unix::alternate_screen_buffer()
+    }
+
+    fn clear_region(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<(), Error> {
+        self.cells.clear_region(x, y, width, height);
+        unix::clear_region(x, y, width, height, self.cursor)
+    }
+
+    fn clear_screen(&mut self) -> Result<(), Error> {
+        self.cells.clear(usize::MAX);
+        self.cursor = (0, 0);
+        unix::clear_screen()
+    }
+
+    fn enable_raw_mode(&mut self) -> Result<(), Error> {
+        self.save_state()?;
+        unix::enable_raw_mode()
+    }
+
+    /// The tty has no clipboard, and the text copied in the editor is pasted.
+    fn get_clipboard(&mut self) -> Result<Option<String>, Error> {
+        Ok(None)
+    }
+
+    /// Returns the position tracked by this, because the query is answered via input.
+    fn get_cursor_position(&self) -> Result<(usize, usize), Error> {
+        Ok(self.cursor)
+    }
+
+    fn get_screen_size(&self) -> Result<(usize, usize), Error> {
+        unix::get_screen_size()
+    }
+
+    fn set_clipboard(&mut self, _text: &str) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// The sequence is not written if not changed, because this is called at every refresh.
+    fn set_cursor_position(&mut self, x: usize, y: usize) -> Result<(), Error> {
+        if self.cursor == (x, y) {
+            return Ok(());
+        }
+
+        self.cursor = (x, y);
+        unix::set_cursor_position(x, y)
+    }
+
+    fn set_cursor_visible(&mut self, visible: bool) -> Result<(), Error> {
+        if self.cursor_hidden != visible {
+            return Ok(());
+        }
+
+        self.cursor_hidden = !visible;
+        unix::set_cursor_visible(visible)
+    }
+
+    fn set_text_attribute(&mut self, x: usize, y: usize, length: usize) -> Result<(), Error> {
+        let (x, cells) = self.cells.reverse(x, y, length);
+        unix::write_cells(x, y, &cells, self.cursor)
+    }
+
+    fn write(
+        &mut self,
+        x: usize,
+        y: usize,
+        row: &[char],
+        color: Color,
+        rev: bool,
+    ) -> Result<(), Error> {
+        self.cells.write(x, y, row, color);
+        // Cursor is moved to end of text as same as console.
+        self.cursor = (x + Row::from(row).width(TAB_STOP), y);
+        unix::write_tty_row(x, y, row, color, rev)
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+thread_local! {
+    static NULL_EVENTS: RefCell<VecDeque<Event>> = const { RefCell::new(VecDeque::new()) };
+}
+
 #[derive(Default)]
 pub struct Null {
     cursor: (usize, usize),
+    cursor_hidden: bool,
     screen: (usize, usize),
+    writes: Vec<(usize, usize, String, Color, bool)>,
+    regions: Vec<(usize, usize, usize, usize)>,
+    attributes: Vec<(usize, usize, usize)>,
+    clipboard: Option<String>,
 }
 
 impl Null {
+    /// Queue events to read in current thread.
+    pub fn push_events(events: &[Event]) {
+        NULL_EVENTS.with(|e| e.borrow_mut().extend(events));
+    }
+
     pub fn set_screen_size(&mut self, x: usize, y: usize) {
         self.screen = (x, y)
     }
+
+    pub fn cursor_visible(&self) -> bool {
+        !self.cursor_hidden
+    }
+
+    /// Returns the text set to the clipboard.
+    pub fn clipboard(&self) -> Option<&str> {
+        self.clipboard.as_deref()
+    }
+
+    /// Returns text attribute areas in order.
+    pub fn attributes(&self) -> &[(usize, usize, usize)] {
+        &self.attributes
+    }
+
+    /// Returns cleared regions in order.
+    pub fn regions(&self) -> &[(usize, usize, usize, usize)] {
+        &self.regions
+    }
+
+    /// Returns written texts in order.
+    pub fn writes(&self) -> &[(usize, usize, String, Color, bool)] {
+        &self.writes
+    }
 }
 
 #[allow(unused_variables)]
 impl Terminal for Null {
+    /// Returns queued event, or `Escape` if empty.
     fn read_event() -> Result<Event, Error> {
-        Ok(Event::from((KeyEvent::Char('a'), KeyModifier::None)))
+        let event = NULL_EVENTS.with(|e| e.borrow_mut().pop_front());
+        Ok(event.unwrap_or(Event::from((KeyEvent::Escape, KeyModifier::None))))
+    }
+
+    /// Returns queued event, or `None` if empty.
+    fn poll_event(timeout: Duration) -> Result<Option<Event>, Error> {
+        Ok(NULL_EVENTS.with(|e| e.borrow_mut().pop_front()))
+    }
+
+    fn read_event_timeout() -> Result<Event, Error> {
+        Self::read_event()
     }
 
     fn alternate_screen_buffer(&mut self) -> Result<(), Error> {
         Ok(())
     }
 
+    fn clear_region(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<(), Error> {
+        self.regions.push((x, y, width, height));
+        Ok(())
+    }
+
     fn clear_screen(&mut self) -> Result<(), Error> {
         Ok(())
     }
@@ -133,6 +433,10 @@ impl Terminal for Null {
         Ok(())
     }
 
+    fn get_clipboard(&mut self) -> Result<Option<String>, Error> {
+        Ok(self.clipboard.clone())
+    }
+
     fn get_cursor_position(&self) -> Result<(usize, usize), Error> {
         Ok(self.cursor)
     }
@@ -141,7 +445,8 @@ impl Terminal for Null {
         Ok(self.screen)
     }
 
-    fn scroll_up(&self, height: usize) -> Result<(), Error> {
+    fn set_clipboard(&mut self, text: &str) -> Result<(), Error> {
+        self.clipboard = Some(text.to_string());
         Ok(())
     }
 
@@ -150,7 +455,13 @@ impl Terminal for Null {
         Ok(())
     }
 
+    fn set_cursor_visible(&mut self, visible: bool) -> Result<(), Error> {
+        self.cursor_hidden = !visible;
+        Ok(())
+    }
+
     fn set_text_attribute(&mut self, x: usize, y: usize, length: usize) -> Result<(), Error> {
+        self.attributes.push((x, y, length));
         Ok(())
     }
 
@@ -162,6 +473,27 @@ impl Terminal for Null {
         color: Color,
         rev: bool,
     ) -> Result<(), Error> {
+        self.writes.push((x, y, row.iter().collect(), color, rev));
+        // Cursor is moved to end of text as same as console.
+        self.cursor = (x + Row::from(row).width(TAB_STOP), y);
         Ok(())
     }
 }
+
+// -----------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fit_width_wide() {
+        let row = ['a', 'あ', 'b'];
+
+        assert_eq!(&row, fit_width(0, &row, 4));
+        assert_eq!(&['a', 'あ'], fit_width(0, &row, 3));
+        assert_eq!(&['a'], fit_width(1, &row, 3));
+        assert!(fit_width(3, &row, 3).is_empty());
+        assert!(fit_width(5, &row, 3).is_empty());
+    }
+}
@@ -0,0 +1,610 @@
+use crate::error::Error;
+use crate::key_event::{Event, KeyEvent, KeyModifier, WindowEvent};
+use crate::Color;
+use std::io::{self, ErrorKind, Write};
+use std::iter;
+use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Once;
+use unicode_width::UnicodeWidthChar;
+
+/// Milliseconds to wait for the rest of escape sequence after `ESC`.
+const ESCAPE_TIMEOUT: i32 = 25;
+/// Milliseconds to wait for input before checking the window is resized.
+const RESIZE_INTERVAL: i32 = 100;
+
+static RESIZED: AtomicBool = AtomicBool::new(false);
+
+pub fn alternate_screen_buffer() -> Result<(), Error> {
+    write_tty("\x1b[?1049h")
+}
+
+/// Characters written on the screen, to draw them again with the reverse attribute.
+#[derive(Default)]
+pub struct Cells {
+    rows: Vec<Vec<Option<(char, Color)>>>,
+}
+
+impl Cells {
+    /// Clear the rows from the top up to the height.
+    pub fn clear(&mut self, height: usize) {
+        let height = height.min(self.rows.len());
+        self.rows[..height].iter_mut().for_each(|r| r.clear());
+    }
+
+    /// Blank the cells in the rectangle.
+    pub fn clear_region(&mut self, x: usize, y: usize, width: usize, height: usize) {
+        for row in self.rows.iter_mut().skip(y).take(height) {
+            let end = (x + width).min(row.len());
+            if x < end {
+                row[x..end].fill(Some((' ', Color::White)));
+            }
+        }
+    }
+
+    /// Returns the start of characters in the columns, and characters to draw reversed.
+    pub fn reverse(&self, x: usize, y: usize, length: usize) -> (usize, Vec<(char, Color)>) {
+        let row = match self.rows.get(y) {
+            Some(row) => row,
+            None => return (x, vec![]),
+        };
+
+        // The second column of wide character is `None`.
+        let mut start = x.min(row.len());
+        while 0 < start && start < row.len() && row[start].is_none() {
+            start -= 1;
+        }
+        let end = (x + length).min(row.len());
+        let cells = row[start..end].iter().flatten().cloned().collect();
+        (start, cells)
+    }
+
+    pub fn write(&mut self, x: usize, y: usize, row: &[char], color: Color) {
+        if self.rows.len() <= y {
+            self.rows.resize(y + 1, vec![]);
+        }
+
+        let cells = &mut self.rows[y];
+        cells.resize(x, Some((' ', Color::White)));
+        for &ch in row {
+            let width = ch.width_cjk().unwrap_or(1);
+            if 0 < width {
+                cells.push(Some((ch, color)));
+                cells.extend((1..width).map(|_| None));
+            }
+        }
+    }
+}
+
+/// Write spaces over the rectangle, and move back to the cursor.
+pub fn clear_region(
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+    cursor: (usize, usize),
+) -> Result<(), Error> {
+    let mut sequence = String::new();
+    for y in y..y + height {
+        sequence.push_str(&cursor_position(x, y));
+        sequence.push_str("\x1b[0m");
+        sequence.extend(iter::repeat(' ').take(width));
+    }
+    sequence.push_str(&cursor_position(cursor.0, cursor.1));
+    write_tty(&sequence)
+}
+
+pub fn clear_screen() -> Result<(), Error> {
+    write_tty("\x1b[0m\x1b[2J\x1b[H")
+}
+
+pub fn enable_raw_mode() -> Result<(), Error> {
+    // https://man7.org/linux/man-pages/man3/termios.3.html
+    let mut termios = get_tty_state()?.termios;
+    termios.c_iflag &= !(libc::BRKINT | libc::ICRNL | libc::INPCK | libc::ISTRIP | libc::IXON);
+    termios.c_oflag &= !libc::OPOST;
+    termios.c_cflag |= libc::CS8;
+    termios.c_lflag &= !(libc::ECHO | libc::ICANON | libc::IEXTEN | libc::ISIG);
+    termios.c_cc[libc::VMIN] = 1;
+    termios.c_cc[libc::VTIME] = 0;
+    check(unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &termios) })?;
+
+    // Disable line wrap as same as console, and report Ctrl+Shift keys by the keyboard protocol.
+    // https://sw.kovidgoyal.net/kitty/keyboard-protocol/#progressive-enhancement
+    write_tty("\x1b[?7l\x1b[>1u")
+}
+
+pub fn get_screen_size() -> Result<(usize, usize), Error> {
+    // https://man7.org/linux/man-pages/man2/TIOCGWINSZ.2const.html
+    let mut size = unsafe { mem::zeroed::<libc::winsize>() };
+    check(unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) })?;
+    Ok((size.ws_col as usize, size.ws_row as usize))
+}
+
+/// Terminal state restored at exit.
+pub struct TtyState {
+    termios: libc::termios,
+}
+
+pub fn get_tty_state() -> Result<TtyState, Error> {
+    let mut termios = unsafe { mem::zeroed::<libc::termios>() };
+    check(unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut termios) })?;
+    Ok(TtyState { termios })
+}
+
+pub fn set_tty_state(state: &TtyState) -> Result<(), Error> {
+    // Restore the attribute, the cursor, line wrap and the keyboard protocol,
+    // and leave the alternate screen.
+    write_tty("\x1b[0m\x1b[?25h\x1b[?7h\x1b[<u\x1b[?1049l")?;
+    check(unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &state.termios) })?;
+    Ok(())
+}
+
+pub fn read_event() -> Result<Event, Error> {
+    static HANDLER: Once = Once::new();
+    HANDLER.call_once(|| {
+        // https://man7.org/linux/man-pages/man7/signal.7.html
+        let handler = on_resize as extern "C" fn(libc::c_int);
+        unsafe { libc::signal(libc::SIGWINCH, handler as libc::sighandler_t) };
+    });
+
+    loop {
+        if RESIZED.swap(false, Ordering::SeqCst) {
+            return Ok(Event::from(WindowEvent::Resize));
+        }
+
+        if let Some(byte) = read_byte(RESIZE_INTERVAL)? {
+            let mut next = || read_byte(ESCAPE_TIMEOUT).ok().flatten();
+            if let Some(event) = parse_event(byte, &mut next) {
+                return Ok(event);
+            }
+        }
+    }
+}
+
+pub fn set_cursor_position(x: usize, y: usize) -> Result<(), Error> {
+    write_tty(&cursor_position(x, y))
+}
+
+pub fn set_cursor_visible(visible: bool) -> Result<(), Error> {
+    write_tty(if visible { "\x1b[?25h" } else { "\x1b[?25l" })
+}
+
+/// Write the characters reversed from the position, and move back to the cursor.
+pub fn write_cells(
+    x: usize,
+    y: usize,
+    cells: &[(char, Color)],
+    cursor: (usize, usize),
+) -> Result<(), Error> {
+    let mut sequence = cursor_position(x, y);
+    for &(ch, color) in cells {
+        sequence.push_str(&attribute(color, true));
+        sequence.push(ch);
+    }
+    sequence.push_str("\x1b[0m");
+    sequence.push_str(&cursor_position(cursor.0, cursor.1));
+    write_tty(&sequence)
+}
+
+/// Write the row from the position, and clear the rest of line.
+pub fn write_tty_row(
+    x: usize,
+    y: usize,
+    row: &[char],
+    color: Color,
+    rev: bool,
+) -> Result<(), Error> {
+    let mut sequence = cursor_position(x, y);
+    sequence.push_str("\x1b[0m\x1b[K");
+    sequence.push_str(&attribute(color, rev));
+    sequence.extend(row);
+    sequence.push_str("\x1b[0m");
+    write_tty(&sequence)
+}
+
+// -----------------------------------------------------------------------------------------------
+
+/// Returns the SGR sequence of the color.
+fn attribute(color: Color, rev: bool) -> String {
+    // https://en.wikipedia.org/wiki/ANSI_escape_code#Colors
+    let fg = match color {
+        Color::Blue => 34,
+        Color::Green => 32,
+        Color::Cyan => 36,
+        Color::Red => 31,
+        Color::Magenta => 35,
+        Color::Yellow => 33,
+        Color::White => 37,
+        Color::Gray => 90,
+    };
+    format!("\x1b[{}{}m", fg, if rev { ";7" } else { "" })
+}
+
+fn check(ret: libc::c_int) -> Result<(), Error> {
+    if ret < 0 {
+        Err(Error::from(io::Error::last_os_error()))
+    } else {
+        Ok(())
+    }
+}
+
+fn cursor_position(x: usize, y: usize) -> String {
+    format!("\x1b[{};{}H", y + 1, x + 1)
+}
+
+/// Returns the key of cursor keys and function keys by the final byte.
+fn cursor_key(byte: u8) -> Option<KeyEvent> {
+    match byte {
+        b'A' => Some(KeyEvent::ArrowUp),
+        b'B' => Some(KeyEvent::ArrowDown),
+        b'C' => Some(KeyEvent::ArrowRight),
+        b'D' => Some(KeyEvent::ArrowLeft),
+        b'F' => Some(KeyEvent::End),
+        b'H' => Some(KeyEvent::Home),
+        b'R' => Some(KeyEvent::F3),
+        _ => None,
+    }
+}
+
+/// Returns the modifier by the parameter of xterm.
+fn modifier(param: usize) -> KeyModifier {
+    // https://invisible-island.net/xterm/ctlseqs/ctlseqs.html#h3-PC-Style-Function-Keys
+    match param {
+        2 => KeyModifier::Shift,
+        3 => KeyModifier::AltLeft,
+        4 => KeyModifier::AltShift,
+        5 => KeyModifier::CtrlLeft,
+        _ => KeyModifier::None,
+    }
+}
+
+extern "C" fn on_resize(_: libc::c_int) {
+    RESIZED.store(true, Ordering::SeqCst);
+}
+
+/// Returns the event of the input bytes, or `None` if ignored.
+/// `next` returns the following byte, or `None` if not typed in a moment.
+fn parse_event(byte: u8, next: &mut impl FnMut() -> Option<u8>) -> Option<Event> {
+    let key = match byte {
+        0x1B => {
+            return match next() {
+                None => Some(Event::from((KeyEvent::Escape, KeyModifier::None))),
+                Some(b'[') => parse_csi(next),
+                Some(b'O') => cursor_key(next()?).map(|k| Event::from((k, KeyModifier::None))),
+                // Alt is sent as the prefix `ESC`.
+                Some(byte) => match parse_event(byte, next)? {
+                    Event::Key(key, KeyModifier::None) => {
+                        Some(Event::from((key, KeyModifier::AltLeft)))
+                    }
+                    event => Some(event),
+                },
+            };
+        }
+        0x09 => KeyEvent::Tab,
+        0x0D => KeyEvent::Enter,
+        0x7F => KeyEvent::BackSpace,
+        0x00 => return None,
+        0x1F => return Some(Event::from((KeyEvent::Comment, KeyModifier::CtrlLeft))), // Ctrl+'/'
+        0x01..=0x1E => {
+            let key = KeyEvent::from_control(byte).unwrap_or(KeyEvent::Char(byte as char));
+            return Some(Event::from((key, KeyModifier::CtrlLeft)));
+        }
+        _ => {
+            let len = match byte {
+                0xC0..=0xDF => 2,
+                0xE0..=0xEF => 3,
+                0xF0..=0xF7 => 4,
+                _ => 1,
+            };
+            let mut bytes = vec![byte];
+            for _ in 1..len {
+                bytes.push(next()?);
+            }
+            KeyEvent::Char(std::str::from_utf8(&bytes).ok()?.chars().next()?)
+        }
+    };
+    Some(Event::from((key, KeyModifier::None)))
+}
+
+/// Returns the event of control sequence after `ESC [`.
+fn parse_csi(next: &mut impl FnMut() -> Option<u8>) -> Option<Event> {
+    // https://invisible-island.net/xterm/ctlseqs/ctlseqs.html
+    let mut params = vec![];
+    let last = loop {
+        match next()? {
+            byte @ 0x40..=0x7E => break byte,
+            byte => params.push(byte),
+        }
+    };
+
+    let params = String::from_utf8_lossy(&params);
+    let mut params = params.split(';').map(|p| p.parse::<usize>().unwrap_or(1));
+    let first = params.next().unwrap_or(1);
+    let param = params.next().unwrap_or(1);
+    let modifier = modifier(param);
+
+    let key = match last {
+        // Ctrl+Shift+'A' reported in `CSI unicode-key-code ; modifiers u`.
+        b'u' if (first == 0x41 || first == 0x61) && param == 6 => {
+            return Some(Event::from((KeyEvent::SelectAll, KeyModifier::None)));
+        }
+        // Ctrl+Shift+'D'
+        b'u' if (first == 0x44 || first == 0x64) && param == 6 => {
+            return Some(Event::from((KeyEvent::DuplicateLine, KeyModifier::None)));
+        }
+        // Ctrl+'0'..'9' and Ctrl+Shift+'0'..'9'
+        b'u' if (0x30..=0x39).contains(&first) && param == 5 => {
+            let key = KeyEvent::JumpToMark(first - 0x30);
+            return Some(Event::from((key, KeyModifier::None)));
+        }
+        b'u' if (0x30..=0x39).contains(&first) && param == 6 => {
+            let key = KeyEvent::SetMark(first - 0x30);
+            return Some(Event::from((key, KeyModifier::None)));
+        }
+        b'u' => return unicode_key(first, param),
+        // Shift+Tab
+        b'Z' => return Some(Event::from((KeyEvent::Tab, KeyModifier::Shift))),
+        b'~' => match first {
+            1 | 7 => KeyEvent::Home,
+            3 => KeyEvent::Delete,
+            4 | 8 => KeyEvent::End,
+            5 => KeyEvent::PageUp,
+            6 => KeyEvent::PageDown,
+            13 => KeyEvent::F3,
+            _ => return None,
+        },
+        _ => cursor_key(last)?,
+    };
+    Some(Event::from((key, modifier)))
+}
+
+/// Returns the event of the other keys reported in `CSI unicode-key-code ; modifiers u`,
+/// parsed from the bytes typed without the keyboard protocol.
+fn unicode_key(code: usize, param: usize) -> Option<Event> {
+    // https://sw.kovidgoyal.net/kitty/keyboard-protocol/#disambiguate-escape-codes
+    let ch = char::from_u32(u32::try_from(code).ok()?)?;
+    let bytes = match (param, ch) {
+        // Shift is already applied to the character.
+        (1 | 2, _) => ch.to_string().into_bytes(),
+        (3, _) => {
+            return match unicode_key(code, 1)? {
+                Event::Key(key, KeyModifier::None) => {
+                    Some(Event::from((key, KeyModifier::AltLeft)))
+                }
+                event => Some(event),
+            };
+        }
+        (5, '/') => vec![0x1F],
+        (5, '@'..='~') => vec![ch.to_ascii_uppercase() as u8 & 0x1F],
+        _ => return None,
+    };
+
+    let mut rest = bytes[1..].iter().cloned();
+    parse_event(bytes[0], &mut || rest.next())
+}
+
+/// Returns the byte read from stdin, or `None` if not typed within the timeout.
+fn read_byte(timeout: i32) -> Result<Option<u8>, Error> {
+    // https://man7.org/linux/man-pages/man2/poll.2.html
+    let mut fds = libc::pollfd {
+        fd: libc::STDIN_FILENO,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let ready = unsafe { libc::poll(&mut fds, 1, timeout) };
+    if ready <= 0 {
+        let error = io::Error::last_os_error();
+        return match ready {
+            0 => Ok(None),
+            _ if error.kind() == ErrorKind::Interrupted => Ok(None),
+            _ => Err(Error::from(error)),
+        };
+    }
+
+    let mut byte = 0u8;
+    let read = unsafe { libc::read(libc::STDIN_FILENO, &mut byte as *mut u8 as *mut _, 1) };
+    match read {
+        1 => Ok(Some(byte)),
+        0 => Err(Error::from(io::Error::from(ErrorKind::UnexpectedEof))),
+        _ => {
+            let error = io::Error::last_os_error();
+            if error.kind() == ErrorKind::Interrupted {
+                Ok(None)
+            } else {
+                Err(Error::from(error))
+            }
+        }
+    }
+}
+
+fn write_tty(sequence: &str) -> Result<(), Error> {
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(sequence.as_bytes())?;
+    stdout.flush()?;
+    Ok(())
+}
+
+// -----------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(bytes: &[u8]) -> Option<Event> {
+        let mut rest = bytes[1..].iter().cloned();
+        parse_event(bytes[0], &mut || rest.next())
+    }
+
+    fn key(key: KeyEvent, modifier: KeyModifier) -> Option<Event> {
+        Some(Event::from((key, modifier)))
+    }
+
+    #[test]
+    fn parse_event_char() {
+        assert_eq!(key(KeyEvent::Char('a'), KeyModifier::None), parse(b"a"));
+        assert_eq!(
+            key(KeyEvent::Char('あ'), KeyModifier::None),
+            parse("あ".as_bytes())
+        );
+        assert_eq!(None, parse(&"あ".as_bytes()[..2]));
+    }
+
+    #[test]
+    fn parse_event_control() {
+        assert_eq!(key(KeyEvent::Find, KeyModifier::CtrlLeft), parse(b"\x06"));
+        assert_eq!(
+            key(KeyEvent::Replace, KeyModifier::CtrlLeft),
+            parse(b"\x08")
+        );
+        assert_eq!(
+            key(KeyEvent::Comment, KeyModifier::CtrlLeft),
+            parse(b"\x1f")
+        );
+        assert_eq!(
+            key(KeyEvent::MatchBracket, KeyModifier::CtrlLeft),
+            parse(b"\x1d")
+        );
+        assert_eq!(key(KeyEvent::BackSpace, KeyModifier::None), parse(b"\x7f"));
+        assert_eq!(key(KeyEvent::Enter, KeyModifier::None), parse(b"\r"));
+        assert_eq!(key(KeyEvent::Tab, KeyModifier::None), parse(b"\t"));
+        assert_eq!(key(KeyEvent::Tab, KeyModifier::Shift), parse(b"\x1b[Z"));
+        assert_eq!(
+            key(KeyEvent::Char('\n'), KeyModifier::CtrlLeft),
+            parse(b"\n")
+        );
+    }
+
+    #[test]
+    fn parse_event_escape() {
+        assert_eq!(key(KeyEvent::Escape, KeyModifier::None), parse(b"\x1b"));
+        assert_eq!(
+            key(KeyEvent::Char('r'), KeyModifier::AltLeft),
+            parse(b"\x1br")
+        );
+    }
+
+    #[test]
+    fn parse_event_cursor_keys() {
+        assert_eq!(key(KeyEvent::ArrowUp, KeyModifier::None), parse(b"\x1b[A"));
+        assert_eq!(
+            key(KeyEvent::ArrowLeft, KeyModifier::None),
+            parse(b"\x1bOD")
+        );
+        assert_eq!(key(KeyEvent::Home, KeyModifier::None), parse(b"\x1b[H"));
+        assert_eq!(key(KeyEvent::End, KeyModifier::None), parse(b"\x1b[4~"));
+        assert_eq!(key(KeyEvent::PageUp, KeyModifier::None), parse(b"\x1b[5~"));
+        assert_eq!(
+            key(KeyEvent::PageDown, KeyModifier::None),
+            parse(b"\x1b[6~")
+        );
+        assert_eq!(key(KeyEvent::Delete, KeyModifier::None), parse(b"\x1b[3~"));
+        assert_eq!(key(KeyEvent::F3, KeyModifier::None), parse(b"\x1bOR"));
+        assert_eq!(None, parse(b"\x1b[2~"));
+        assert_eq!(
+            key(KeyEvent::SelectAll, KeyModifier::None),
+            parse(b"\x1b[97;6u")
+        );
+        assert_eq!(None, parse(b"\x1b[97;7u"));
+        assert_eq!(
+            key(KeyEvent::DuplicateLine, KeyModifier::None),
+            parse(b"\x1b[100;6u")
+        );
+        assert_eq!(
+            key(KeyEvent::SetMark(3), KeyModifier::None),
+            parse(b"\x1b[51;6u")
+        );
+        assert_eq!(
+            key(KeyEvent::JumpToMark(0), KeyModifier::None),
+            parse(b"\x1b[48;5u")
+        );
+    }
+
+    #[test]
+    fn parse_event_unicode_keys() {
+        let cases = [
+            (
+                b"\x1b[27u".as_slice(),
+                key(KeyEvent::Escape, KeyModifier::None),
+            ),
+            (b"\x1b[97u", key(KeyEvent::Char('a'), KeyModifier::None)),
+            (
+                b"\x1b[12354;2u",
+                key(KeyEvent::Char('あ'), KeyModifier::None),
+            ),
+            (b"\x1b[13;2u", key(KeyEvent::Enter, KeyModifier::None)),
+            (
+                b"\x1b[114;3u",
+                key(KeyEvent::Char('r'), KeyModifier::AltLeft),
+            ),
+            (b"\x1b[97;5u", key(KeyEvent::Home, KeyModifier::CtrlLeft)),
+            (b"\x1b[115;5u", key(KeyEvent::Save, KeyModifier::CtrlLeft)),
+            (b"\x1b[47;5u", key(KeyEvent::Comment, KeyModifier::CtrlLeft)),
+            (
+                b"\x1b[93;5u",
+                key(KeyEvent::MatchBracket, KeyModifier::CtrlLeft),
+            ),
+            (b"\x1b[1114112u", None),
+        ];
+        for (bytes, expected) in cases {
+            assert_eq!(expected, parse(bytes), "{:?}", bytes);
+        }
+    }
+
+    #[test]
+    fn parse_event_modifier() {
+        assert_eq!(
+            key(KeyEvent::ArrowDown, KeyModifier::Shift),
+            parse(b"\x1b[1;2B")
+        );
+        assert_eq!(
+            key(KeyEvent::ArrowUp, KeyModifier::AltLeft),
+            parse(b"\x1b[1;3A")
+        );
+        assert_eq!(
+            key(KeyEvent::ArrowDown, KeyModifier::AltShift),
+            parse(b"\x1b[1;4B")
+        );
+        assert_eq!(key(KeyEvent::F3, KeyModifier::Shift), parse(b"\x1b[1;2R"));
+        assert_eq!(
+            key(KeyEvent::Delete, KeyModifier::CtrlLeft),
+            parse(b"\x1b[3;5~")
+        );
+    }
+
+    #[test]
+    fn cells_reverse_wide() {
+        let mut cells = Cells::default();
+        cells.write(2, 1, &['a', 'あ', 'b'], Color::Cyan);
+
+        assert_eq!((0, vec![(' ', Color::White)]), cells.reverse(0, 1, 1));
+        assert_eq!(
+            (3, vec![('あ', Color::Cyan), ('b', Color::Cyan)]),
+            cells.reverse(4, 1, 2)
+        );
+        assert_eq!((0, vec![]), cells.reverse(4, 0, 1));
+
+        cells.write(1, 1, &['c'], Color::White);
+        assert_eq!((1, vec![('c', Color::White)]), cells.reverse(1, 1, 5));
+
+        cells.clear(2);
+        assert_eq!((0, vec![]), cells.reverse(0, 1, 1));
+    }
+
+    #[test]
+    fn cells_clear_region() {
+        let mut cells = Cells::default();
+        cells.write(0, 0, &['a', 'b', 'c'], Color::Cyan);
+        cells.write(0, 1, &['d', 'e', 'f'], Color::Cyan);
+        cells.write(0, 2, &['g'], Color::Cyan);
+
+        cells.clear_region(1, 0, 5, 2);
+
+        assert_eq!(
+            (0, vec![('a', Color::Cyan), (' ', Color::White)]),
+            cells.reverse(0, 0, 2)
+        );
+        assert_eq!((2, vec![(' ', Color::White)]), cells.reverse(2, 1, 1));
+        assert_eq!((0, vec![('g', Color::Cyan)]), cells.reverse(0, 2, 1));
+    }
+}
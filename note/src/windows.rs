@@ -1,22 +1,34 @@
 use crate::error::Error;
 use crate::key_event::{Event, KeyEvent, KeyModifier, WindowEvent};
+use crate::terminal::fit_width;
 use crate::Color;
-use windows::Win32::Foundation::{GENERIC_READ, GENERIC_WRITE, HANDLE};
+use windows::Win32::Foundation::{GlobalFree, GENERIC_READ, GENERIC_WRITE, HANDLE, HGLOBAL, HWND};
 use windows::Win32::Storage::FileSystem::{FILE_SHARE_READ, FILE_SHARE_WRITE};
 use windows::Win32::System::Console::{
     CreateConsoleScreenBuffer, FillConsoleOutputAttribute, FillConsoleOutputCharacterA,
-    GetConsoleMode, GetConsoleScreenBufferInfo, GetStdHandle, ReadConsoleInputW,
-    ScrollConsoleScreenBufferA, SetConsoleActiveScreenBuffer, SetConsoleCursorPosition,
-    SetConsoleMode, SetConsoleOutputCP, SetConsoleScreenBufferSize, SetConsoleTextAttribute,
-    SetStdHandle, WriteConsoleA, WriteConsoleOutputW, CHAR_INFO, CHAR_INFO_0,
-    COMMON_LVB_LEADING_BYTE, COMMON_LVB_REVERSE_VIDEO, COMMON_LVB_TRAILING_BYTE,
-    CONSOLE_CHARACTER_ATTRIBUTES, CONSOLE_MODE, CONSOLE_SCREEN_BUFFER_INFO,
-    CONSOLE_TEXTMODE_BUFFER, COORD, ENABLE_ECHO_INPUT, ENABLE_LINE_INPUT, ENABLE_PROCESSED_INPUT,
-    ENABLE_PROCESSED_OUTPUT, ENABLE_WRAP_AT_EOL_OUTPUT, ENHANCED_KEY, INPUT_RECORD, KEY_EVENT,
-    LEFT_ALT_PRESSED, LEFT_CTRL_PRESSED, RIGHT_ALT_PRESSED, RIGHT_CTRL_PRESSED, SHIFT_PRESSED,
-    SMALL_RECT, STD_INPUT_HANDLE, STD_OUTPUT_HANDLE, WINDOW_BUFFER_SIZE_EVENT,
+    GetConsoleCursorInfo, GetConsoleMode, GetConsoleScreenBufferInfo, GetStdHandle,
+    ReadConsoleInputW, ScrollConsoleScreenBufferA, SetConsoleActiveScreenBuffer,
+    SetConsoleCursorInfo, SetConsoleCursorPosition, SetConsoleMode, SetConsoleOutputCP,
+    SetConsoleScreenBufferSize, SetConsoleTextAttribute, SetStdHandle, WriteConsoleA,
+    WriteConsoleOutputW, CHAR_INFO, CHAR_INFO_0, COMMON_LVB_LEADING_BYTE, COMMON_LVB_REVERSE_VIDEO,
+    COMMON_LVB_TRAILING_BYTE, CONSOLE_CHARACTER_ATTRIBUTES, CONSOLE_CURSOR_INFO, CONSOLE_MODE,
+    CONSOLE_SCREEN_BUFFER_INFO, CONSOLE_TEXTMODE_BUFFER, COORD, ENABLE_ECHO_INPUT,
+    ENABLE_LINE_INPUT, ENABLE_PROCESSED_INPUT, ENABLE_PROCESSED_OUTPUT, ENABLE_WRAP_AT_EOL_OUTPUT,
+    ENHANCED_KEY, INPUT_RECORD, KEY_EVENT, LEFT_ALT_PRESSED, LEFT_CTRL_PRESSED, RIGHT_ALT_PRESSED,
+    RIGHT_CTRL_PRESSED, SHIFT_PRESSED, SMALL_RECT, STD_INPUT_HANDLE, STD_OUTPUT_HANDLE,
+    WINDOW_BUFFER_SIZE_EVENT,
+};
+use windows::Win32::System::DataExchange::{
+    CloseClipboard, EmptyClipboard, GetClipboardData, IsClipboardFormatAvailable, OpenClipboard,
+    SetClipboardData,
+};
+use windows::Win32::System::Memory::{
+    GlobalAlloc, GlobalLock, GlobalSize, GlobalUnlock, GMEM_MOVEABLE,
 };
 
+// https://learn.microsoft.com/en-us/windows/win32/dataxchg/standard-clipboard-formats
+const CF_UNICODETEXT: u32 = 13;
+
 pub fn alternate_screen_buffer() -> Result<HANDLE, Error> {
     // https://learn.microsoft.com/en-us/windows/console/createconsolescreenbuffer
     let handle = unsafe {
@@ -40,6 +52,38 @@ pub fn alternate_screen_buffer() -> Result<HANDLE, Error> {
     Ok(handle)
 }
 
+/// Console state restored at exit.
+pub struct ConsoleState {
+    output: HANDLE,
+    input_mode: CONSOLE_MODE,
+    output_mode: CONSOLE_MODE,
+}
+
+pub fn get_console_state() -> Result<ConsoleState, Error> {
+    // https://learn.microsoft.com/en-us/windows/console/getconsolemode
+    let output = stdout()?;
+    let mut input_mode = CONSOLE_MODE::default();
+    unsafe { GetConsoleMode(stdin()?, &mut input_mode) }?;
+    let mut output_mode = CONSOLE_MODE::default();
+    unsafe { GetConsoleMode(output, &mut output_mode) }?;
+    Ok(ConsoleState {
+        output,
+        input_mode,
+        output_mode,
+    })
+}
+
+pub fn set_console_state(state: &ConsoleState) -> Result<(), Error> {
+    // https://learn.microsoft.com/en-us/windows/console/setconsolemode
+    unsafe { SetConsoleMode(stdin()?, state.input_mode) }?;
+    unsafe { SetConsoleMode(state.output, state.output_mode) }?;
+    // https://learn.microsoft.com/en-us/windows/console/setconsoleactivescreenbuffer
+    unsafe { SetConsoleActiveScreenBuffer(state.output) }?;
+    // https://learn.microsoft.com/en-us/windows/console/setstdhandle
+    unsafe { SetStdHandle(STD_OUTPUT_HANDLE, state.output) }?;
+    Ok(())
+}
+
 pub fn clear_screen() -> Result<(), Error> {
     // https://learn.microsoft.com/en-us/windows/console/clearing-the-screen
     let info = get_stdout_buffer_info()?;
@@ -48,6 +92,31 @@ pub fn clear_screen() -> Result<(), Error> {
     Ok(())
 }
 
+pub fn clear_region(x: usize, y: usize, width: usize, height: usize) -> Result<(), Error> {
+    // https://learn.microsoft.com/en-us/windows/console/fillconsoleoutputcharacter
+    let info = get_stdout_buffer_info()?;
+    for y in y..y + height {
+        let at = COORD {
+            X: x as i16,
+            Y: y as i16,
+        };
+        let mut written = 0;
+        unsafe {
+            FillConsoleOutputCharacterA(stdout()?, b' ' as i8, width as u32, at, &mut written)
+        }?;
+        unsafe {
+            FillConsoleOutputAttribute(
+                stdout()?,
+                info.wAttributes.0,
+                width as u32,
+                at,
+                &mut written,
+            )
+        }?;
+    }
+    Ok(())
+}
+
 pub fn enable_raw_mode() -> Result<(), Error> {
     // https://learn.microsoft.com/en-us/windows/console/high-level-console-modes
     {
@@ -71,6 +140,19 @@ pub fn enable_raw_mode() -> Result<(), Error> {
     Ok(())
 }
 
+/// Returns the text in the clipboard, or `None` if the clipboard has no text.
+pub fn get_clipboard() -> Result<Option<String>, Error> {
+    // https://learn.microsoft.com/en-us/windows/win32/dataxchg/using-the-clipboard
+    if unsafe { IsClipboardFormatAvailable(CF_UNICODETEXT) }.is_err() {
+        return Ok(None);
+    }
+
+    unsafe { OpenClipboard(HWND::default()) }?;
+    let text = read_clipboard_text();
+    unsafe { CloseClipboard() }?;
+    text
+}
+
 pub fn get_cursor_position() -> Result<(usize, usize), Error> {
     let info = get_stdout_buffer_info()?;
     Ok((
@@ -95,6 +177,7 @@ pub fn read_event() -> Result<Event, Error> {
         unsafe { ReadConsoleInputW(stdin()?, buf.as_mut_slice(), &mut num) }?;
 
         if buf[0].EventType == (WINDOW_BUFFER_SIZE_EVENT as u16) {
+            fit_buffer_to_window()?;
             return Ok(Event::from(WindowEvent::Resize));
         }
 
@@ -114,13 +197,32 @@ pub fn read_event() -> Result<Event, Error> {
             RIGHT_ALT_PRESSED => KeyModifier::AltRight,
             RIGHT_CTRL_PRESSED => KeyModifier::CtrlRight,
             SHIFT_PRESSED => KeyModifier::Shift,
+            _ if state == LEFT_ALT_PRESSED | SHIFT_PRESSED
+                || state == RIGHT_ALT_PRESSED | SHIFT_PRESSED =>
+            {
+                KeyModifier::AltShift
+            }
             _ => KeyModifier::None,
         };
 
         // https://learn.microsoft.com/en-us/windows/win32/inputdev/virtual-key-codes
         let v_key = unsafe { buf[0].Event.KeyEvent.wVirtualKeyCode };
+        let ctrl_shift =
+            state & SHIFT_PRESSED != 0 && state & (LEFT_CTRL_PRESSED | RIGHT_CTRL_PRESSED) != 0;
         match v_key {
+            0x41 if ctrl_shift => return Ok(Event::from((KeyEvent::SelectAll, modifier))), // Ctrl+Shift+'A'
+            0x44 if ctrl_shift => return Ok(Event::from((KeyEvent::DuplicateLine, modifier))), // Ctrl+Shift+'D'
+            // Ctrl+Shift+'0'..'9' and Ctrl+'0'..'9'
+            0x30..=0x39 if ctrl_shift => {
+                let key = KeyEvent::SetMark(usize::from(v_key - 0x30));
+                return Ok(Event::from((key, modifier)));
+            }
+            0x30..=0x39 if is_ctrl(modifier) => {
+                let key = KeyEvent::JumpToMark(usize::from(v_key - 0x30));
+                return Ok(Event::from((key, modifier)));
+            }
             0x08 => return Ok(Event::from((KeyEvent::BackSpace, modifier))),
+            0x09 => return Ok(Event::from((KeyEvent::Tab, modifier))),
             0x0D => return Ok(Event::from((KeyEvent::Enter, modifier))),
             0x1B => return Ok(Event::from((KeyEvent::Escape, modifier))),
             0x23 => return Ok(Event::from((KeyEvent::End, modifier))),
@@ -133,6 +235,7 @@ pub fn read_event() -> Result<Event, Error> {
             0x28 => return Ok(Event::from((KeyEvent::ArrowDown, modifier))),
             0x2E => return Ok(Event::from((KeyEvent::Delete, modifier))),
             0x72 => return Ok(Event::from((KeyEvent::F3, modifier))),
+            0xBF if is_ctrl(modifier) => return Ok(Event::from((KeyEvent::Comment, modifier))), // Ctrl+'/'
             _ => {}
         }
 
@@ -140,23 +243,8 @@ pub fn read_event() -> Result<Event, Error> {
         if let Some(ch) = char::decode_utf16([code]).next() {
             let ch = ch?;
             if ch.is_ascii_control() {
-                // https://doc.rust-lang.org/std/ascii/enum.Char.html
-                match ch as u8 {
-                    1 => return Ok(Event::from((KeyEvent::Home, modifier))), // Ctrl+'A'
-                    3 => return Ok(Event::from((KeyEvent::Copy, modifier))), // Ctrl+'C'
-                    5 => return Ok(Event::from((KeyEvent::End, modifier))),  // Ctrl+'E'
-                    6 => return Ok(Event::from((KeyEvent::Find, modifier))), // Ctrl+'F'
-                    7 => return Ok(Event::from((KeyEvent::Goto, modifier))), // Ctrl+'G'
-                    8 => return Ok(Event::from((KeyEvent::Replace, modifier))), // Ctrl+'H'
-                    11 => return Ok(Event::from((KeyEvent::DeleteRow, modifier))), // Ctrl+'K'
-                    14 => return Ok(Event::from((KeyEvent::ArrowDown, modifier))), // Ctrl+'N'
-                    16 => return Ok(Event::from((KeyEvent::ArrowUp, modifier))), // Ctrl+'P'
-                    17 => return Ok(Event::from((KeyEvent::Exit, modifier))), // Ctrl+'Q'
-                    19 => return Ok(Event::from((KeyEvent::Save, modifier))), // Ctrl+'S'
-                    22 => return Ok(Event::from((KeyEvent::Paste, modifier))), // Ctrl+'V'
-                    24 => return Ok(Event::from((KeyEvent::Cut, modifier))), // Ctrl+'X'
-                    26 => return Ok(Event::from((KeyEvent::Undo, modifier))), // Ctrl+'Z'
-                    _ => {}
+                if let Some(key) = KeyEvent::from_control(ch as u8) {
+                    return Ok(Event::from((key, modifier)));
                 }
             }
 
@@ -191,6 +279,31 @@ pub fn scroll_up_buffer(height: usize) -> Result<(), Error> {
     Ok(())
 }
 
+/// Replace the content of the clipboard with the text.
+pub fn set_clipboard(text: &str) -> Result<(), Error> {
+    // https://learn.microsoft.com/en-us/windows/win32/dataxchg/using-the-clipboard
+    let mut units = text.encode_utf16().collect::<Vec<u16>>();
+    units.push(0);
+
+    let memory = unsafe { GlobalAlloc(GMEM_MOVEABLE, units.len() * 2) }?;
+    let ptr = unsafe { GlobalLock(memory) } as *mut u16;
+    if ptr.is_null() {
+        let _ = unsafe { GlobalFree(memory) };
+        return Err(Error::from(windows::core::Error::from_win32()));
+    }
+    unsafe { std::ptr::copy_nonoverlapping(units.as_ptr(), ptr, units.len()) };
+    // The result is error even if succeeded when the memory is unlocked.
+    let _ = unsafe { GlobalUnlock(memory) };
+
+    let (set, closed) = write_clipboard(memory);
+    if !set {
+        // The memory is owned by the system only if set.
+        let _ = unsafe { GlobalFree(memory) };
+    }
+
+    closed
+}
+
 pub fn set_cursor_position(x: usize, y: usize) -> Result<(), Error> {
     // https://learn.microsoft.com/en-us/windows/console/setconsolecursorposition
     let pos = COORD {
@@ -201,6 +314,15 @@ pub fn set_cursor_position(x: usize, y: usize) -> Result<(), Error> {
     Ok(())
 }
 
+pub fn set_cursor_visible(visible: bool) -> Result<(), Error> {
+    // https://learn.microsoft.com/en-us/windows/console/setconsolecursorinfo
+    let mut info = CONSOLE_CURSOR_INFO::default();
+    unsafe { GetConsoleCursorInfo(stdout()?, &mut info) }?;
+    info.bVisible = visible.into();
+    unsafe { SetConsoleCursorInfo(stdout()?, &info) }?;
+    Ok(())
+}
+
 pub fn set_text_attribute(x: usize, y: usize, length: usize) -> Result<(), Error> {
     // https://learn.microsoft.com/en-us/windows/console/fillconsoleoutputattribute
     let info = get_stdout_buffer_info()?;
@@ -234,7 +356,9 @@ pub fn write_console(
     unsafe { SetConsoleTextAttribute(stdout()?, attr) }?;
 
     // https://learn.microsoft.com/en-us/windows/console/fillconsoleoutputcharacter
-    let width = (info.srWindow.Right as u32) - x as u32;
+    let writable = writable_width(&info);
+    let row = fit_width(x, row, writable);
+    let width = writable.saturating_sub(x) as u32;
     let spece_at = COORD {
         X: x as i16,
         Y: y as i16,
@@ -262,6 +386,34 @@ fn control_key(c: u8) -> u8 {
     c & 0x1F
 }
 
+fn is_ctrl(modifier: KeyModifier) -> bool {
+    modifier == KeyModifier::CtrlLeft || modifier == KeyModifier::CtrlRight
+}
+
+/// Resize the screen buffer to the window, so that rows are not wrapped at the buffer width.
+/// Manual test: shrink the window and widen it again on a file with long rows,
+/// and the rows under the cursor keep being drawn without garbage in the next row.
+fn fit_buffer_to_window() -> Result<(), Error> {
+    // https://learn.microsoft.com/en-us/windows/console/setconsolescreenbuffersize
+    let info = get_stdout_buffer_info()?;
+    let size = COORD {
+        X: info.srWindow.Right - info.srWindow.Left + 1,
+        Y: info.dwSize.Y,
+    };
+    if size.X != info.dwSize.X {
+        // Fails if the buffer would be smaller than the window, which is kept as is.
+        let _ = unsafe { SetConsoleScreenBufferSize(stdout()?, size) };
+    }
+    Ok(())
+}
+
+/// Returns the columns written without wrapping, which is the window or the buffer narrower.
+fn writable_width(info: &CONSOLE_SCREEN_BUFFER_INFO) -> usize {
+    let window = (info.srWindow.Right + 1).max(0) as usize;
+    let buffer = info.dwSize.X.max(0) as usize;
+    window.min(buffer)
+}
+
 fn get_stdout_buffer_info() -> Result<CONSOLE_SCREEN_BUFFER_INFO, Error> {
     // https://learn.microsoft.com/en-us/windows/console/getconsolescreenbufferinfo
     let mut info = CONSOLE_SCREEN_BUFFER_INFO::default();
@@ -269,6 +421,22 @@ fn get_stdout_buffer_info() -> Result<CONSOLE_SCREEN_BUFFER_INFO, Error> {
     Ok(info)
 }
 
+fn read_clipboard_text() -> Result<Option<String>, Error> {
+    let handle = unsafe { GetClipboardData(CF_UNICODETEXT) }?;
+    let memory = HGLOBAL(handle.0);
+    let ptr = unsafe { GlobalLock(memory) } as *const u16;
+    if ptr.is_null() {
+        return Ok(None);
+    }
+
+    let len = unsafe { GlobalSize(memory) } / 2;
+    let units = unsafe { std::slice::from_raw_parts(ptr, len) };
+    let end = units.iter().position(|&u| u == 0).unwrap_or(len);
+    let text = String::from_utf16_lossy(&units[..end]);
+    let _ = unsafe { GlobalUnlock(memory) };
+    Ok(Some(text))
+}
+
 fn stdin() -> Result<HANDLE, Error> {
     // https://learn.microsoft.com/en-us/windows/console/getstdhandle
     let handle = unsafe { GetStdHandle(STD_INPUT_HANDLE) }?;
@@ -281,6 +449,22 @@ fn stdout() -> Result<HANDLE, Error> {
     Ok(handle)
 }
 
+/// Returns whether the memory is set, and then owned by the system even if closing fails,
+/// and the result of setting and closing the clipboard.
+fn write_clipboard(memory: HGLOBAL) -> (bool, Result<(), Error>) {
+    if let Err(e) = unsafe { OpenClipboard(HWND::default()) } {
+        return (false, Err(Error::from(e)));
+    }
+
+    let set = unsafe { EmptyClipboard() }
+        .and_then(|_| unsafe { SetClipboardData(CF_UNICODETEXT, HANDLE(memory.0)) });
+    let closed = unsafe { CloseClipboard() };
+    match set {
+        Ok(_) => (true, closed.map_err(Error::from)),
+        Err(e) => (false, Err(Error::from(e))),
+    }
+}
+
 #[allow(dead_code)]
 fn write_console_legacy(_x: usize, y: usize, row: &[char], rev: bool) -> Result<(), Error> {
     let info = get_stdout_buffer_info()?;